@@ -1,13 +1,15 @@
 use clap::{App, Arg};
-use regex::Regex;
 use std::error::Error;
-use threecast::predict::predict_two;
+use threecast::predict::{nowcast, probability_of_exceedance, time_to_rain};
 
 use threecast::geomath::get_distance_between_points;
-use threecast::net::{get_data_by_station, get_data_file_listing, get_station_statuses};
+use threecast::net::{find_nearest_online_station, get_station_statuses, get_two_most_recent_data};
 use threecast::parse::parse_dpr;
-use threecast::stations::{find_nearest_stations, STATIONS};
-use threecast::util::find_pixel_by_lat_long;
+use threecast::stations::STATIONS;
+use threecast::util::{find_pixel_by_lat_long, precip_category, read_input};
+
+/// Radius in pixels of the neighborhood used to estimate exceedance probabilities
+const EXCEEDANCE_RADIUS: usize = 4;
 
 fn main() -> Result<(), Box<dyn Error>> {
     let matches = App::new("threecast")
@@ -28,7 +30,7 @@ fn main() -> Result<(), Box<dyn Error>> {
                 .short("f")
                 .long("file")
                 .value_name("FILE")
-                .help("Path to a NEXRAD Level III Product 176 data file")
+                .help("Path or URL of a NEXRAD Level III Product 176 data file, optionally gzipped, or - for stdin")
                 .takes_value(true)
                 .conflicts_with("station")
                 .number_of_values(2),
@@ -53,6 +55,33 @@ fn main() -> Result<(), Box<dyn Error>> {
                 .required(true)
                 .allow_hyphen_values(true),
         )
+        .arg(
+            Arg::with_name("exceedance")
+                .short("e")
+                .long("exceedance")
+                .value_name("THRESHOLD")
+                .help("Also show the probability that the rate exceeds THRESHOLD in/hr")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("rain-threshold")
+                .short("r")
+                .long("rain-threshold")
+                .value_name("THRESHOLD")
+                .help("Rate in in/hr above which it counts as raining")
+                .takes_value(true)
+                .default_value("0.0"),
+        )
+        .arg(
+            Arg::with_name("output")
+                .short("o")
+                .long("output")
+                .value_name("FORMAT")
+                .help("Output format; home-assistant prints JSON for a REST or command line sensor")
+                .takes_value(true)
+                .possible_values(&["text", "home-assistant"])
+                .default_value("text"),
+        )
         .arg(Arg::with_name("verbose").short("v").long("verbose"))
         .get_matches();
 
@@ -65,6 +94,19 @@ fn main() -> Result<(), Box<dyn Error>> {
         Err(_) => return Err("Failed to parse longitude".into()),
     };
 
+    let exceedance_threshold = match matches.value_of("exceedance") {
+        None => None,
+        Some(t) => match t.parse::<f32>() {
+            Ok(t) => Some(t),
+            Err(_) => return Err("Failed to parse exceedance threshold".into()),
+        },
+    };
+
+    let rain_threshold = match matches.value_of("rain-threshold").unwrap().parse::<f32>() {
+        Ok(t) => t,
+        Err(_) => return Err("Failed to parse rain threshold".into()),
+    };
+
     if latitude >= 90. || latitude <= -90. {
         return Err(format!("Latitude must be between -90 and 90 (got {})", latitude).into());
     }
@@ -74,7 +116,7 @@ fn main() -> Result<(), Box<dyn Error>> {
 
     let input = if matches.is_present("file") {
         let files: Vec<&str> = matches.values_of("file").unwrap().collect();
-        (std::fs::read(files[0])?, std::fs::read(files[1])?)
+        (read_input(files[0])?, read_input(files[1])?)
     } else {
         let station_code = if matches.is_present("station") {
             let station_code = matches.value_of("station").unwrap().to_lowercase();
@@ -87,101 +129,85 @@ fn main() -> Result<(), Box<dyn Error>> {
             }
             station_code
         } else {
-            let nearby_stations = match find_nearest_stations(latitude, longitude) {
-                Some(s) => s,
-                None => {
-                    return Err(String::from(
-                        "Given location is not within range of any radar stations",
-                    )
-                    .into())
-                }
-            };
-            let station_statuses = get_station_statuses()?;
-            let mut nearest_station = None;
-            for station in nearby_stations {
-                if station_statuses.iter().find(|s| s.0 == station).unwrap().1 {
-                    nearest_station = Some(station.to_lowercase());
-                    break;
-                }
-            }
-            if nearest_station.is_none() {
-                return Err(String::from(
-                    "All radar stations within range of this location are offline",
-                )
-                .into());
-            }
-            nearest_station.unwrap()
+            find_nearest_online_station(latitude, longitude)?
         };
-        let file_listing = get_data_file_listing(&station_code)?;
-        // parse the file listing and determine the number of the second-most recent file
-        let re = Regex::new(
-            r#"sn\.(0\d{3}|last)</a></td><td align="right">(\d{2}-\w{3}-\d{4} \d{2}:\d{2})"#,
-        )
-        .unwrap();
-        let mut files: Vec<(chrono::NaiveDateTime, String)> = re
-            .captures_iter(&file_listing)
-            .map(|cap| {
-                (
-                    chrono::NaiveDateTime::parse_from_str(&cap[2], "%d-%b-%Y %H:%M").unwrap(),
-                    cap[1].to_string(),
-                )
-            })
-            .collect();
-        files.sort_by(|a, b| b.0.cmp(&a.0));
-        let second_to_last_index = files[2].1.as_str();
-        let sn_last = get_data_by_station(&station_code.to_lowercase(), "last")?;
-        let sn_second_to_last =
-            get_data_by_station(&station_code.to_lowercase(), second_to_last_index)?;
-        (sn_second_to_last, sn_last)
+        get_two_most_recent_data(&station_code)?
     };
 
     let dpr_second_last = parse_dpr(input.0)?;
     let dpr_last = parse_dpr(input.1)?;
-    let precip_second_last = dpr_second_last.sample_radials_to_equirectangular(256, 256);
-    let precip_last = dpr_last.sample_radials_to_equirectangular(256, 256);
-    let coords = {
-        if matches.is_present("file") {
-            let distance_from_station = get_distance_between_points(
-                (latitude, longitude),
-                (dpr_last.latitude, dpr_last.longitude),
-            );
-            if distance_from_station > 230. {
-                return Err(format!(
-                    "Supplied file contains data for station {}, but supplied point is outside coverage area ({} km away)",
-                    dpr_last.station_code,
-                    distance_from_station.round()).into());
-            }
+    if matches.is_present("file") {
+        let distance_from_station = get_distance_between_points(
+            (latitude, longitude),
+            (dpr_last.latitude, dpr_last.longitude),
+        );
+        if distance_from_station > 230. {
+            return Err(format!(
+                "Supplied file contains data for station {}, but supplied point is outside coverage area ({} km away)",
+                dpr_last.station_code,
+                distance_from_station.round()).into());
         }
-        find_pixel_by_lat_long(&precip_last, latitude, longitude)?
-    };
+    }
+
+    let predictions = nowcast(&dpr_second_last, &dpr_last);
+    let coords = find_pixel_by_lat_long(&predictions[0], latitude, longitude)?;
+    let probabilities = exceedance_threshold
+        .map(|threshold| probability_of_exceedance(&predictions, threshold, EXCEEDANCE_RADIUS));
+    let minutes_to_rain = time_to_rain(&predictions, latitude, longitude, rain_threshold)?;
+
+    if matches.value_of("output") == Some("home-assistant") {
+        let forecast: Vec<serde_json::Value> = predictions
+            .iter()
+            .enumerate()
+            .map(|(idx, prediction)| {
+                let rate = prediction[coords.0][coords.1].1;
+                serde_json::json!({
+                    "minutes": idx * 5,
+                    "rate": rate,
+                    "category": precip_category(rate),
+                })
+            })
+            .collect();
+        let rate = predictions[0][coords.0][coords.1].1;
+        println!(
+            "{}",
+            serde_json::json!({
+                "rate": rate,
+                "unit_of_measurement": "in/h",
+                "category": precip_category(rate),
+                "minutes_to_rain": minutes_to_rain,
+                "forecast": forecast,
+                "station": dpr_last.station_code,
+                "capture_time": dpr_last.capture_time.format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+            })
+        );
+        return Ok(());
+    }
 
-    let delta_t_image = (dpr_last.capture_time - dpr_second_last.capture_time).num_seconds() as u16;
-    let delta_t_now = (chrono::Utc::now().timestamp() - dpr_last.capture_time.timestamp()) as u16;
-    for (idx, prediction) in predict_two(
-        [&precip_second_last, &precip_last],
-        delta_t_image,
-        delta_t_now,
-    )
-    .iter()
-    .enumerate()
-    {
+    for (idx, prediction) in predictions.iter().enumerate() {
         let precip_at_coords = prediction[coords.0][coords.1].1;
         match idx {
             0 => print!(" right now: "),
             _ => print!("in {: >2} mins: ", idx * 5),
         };
-        println!(
+        print!(
             "{:.3} in/hr ({})",
             precip_at_coords,
-            match precip_at_coords {
-                p if p == 0. => "none",
-                p if p < 0.098 => "light",
-                p if p < 0.35 => "moderate",
-                p if p < 2. => "heavy",
-                p if p >= 2. => "violent",
-                _ => unreachable!(),
-            }
+            precip_category(precip_at_coords)
         );
+        if let (Some(probabilities), Some(threshold)) = (&probabilities, exceedance_threshold) {
+            print!(
+                ", {:.0}% chance of exceeding {} in/hr",
+                probabilities[idx][coords.0][coords.1].1 * 100.,
+                threshold
+            );
+        }
+        println!();
+    }
+    match minutes_to_rain {
+        Some(0) => println!("it's raining now"),
+        Some(minutes) => println!("rain expected in {} mins", minutes),
+        None => println!("no rain expected in the next hour"),
     }
 
     Ok(())
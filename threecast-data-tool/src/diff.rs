@@ -0,0 +1,66 @@
+//! Structural comparison of two data files for `tcdt diff`
+
+use std::collections::HashMap;
+
+use threecast::compare::Tolerances;
+use threecast::parse::{parse_dpr, PrecipRate};
+
+use crate::dump::{header_field_names, header_values};
+
+/// Compare the raw header fields of two files
+fn diff_headers(a: &HashMap<&str, i64>, b: &HashMap<&str, i64>) -> Vec<String> {
+    header_field_names()
+        .filter_map(|name| match (a.get(name), b.get(name)) {
+            (Some(x), Some(y)) if x != y => Some(format!("header {}: {} != {}", name, x, y)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Compare the decoded metadata and radials of two scans exactly
+pub fn diff_scans(a: &PrecipRate, b: &PrecipRate) -> Vec<String> {
+    a.differences(b, &Tolerances::exact())
+}
+
+/// Compare two data files field by field and return a line for each
+/// difference
+pub fn diff(a: Vec<u8>, b: Vec<u8>) -> Result<Vec<String>, String> {
+    let mut differences = diff_headers(&header_values(&a)?, &header_values(&b)?);
+    differences.append(&mut diff_scans(&parse_dpr(a)?, &parse_dpr(b)?));
+    Ok(differences)
+}
+
+#[test]
+fn test_diff_scans() {
+    use threecast::parse::{OperationalMode, Radial};
+    let scan = |rates: Vec<f32>| PrecipRate {
+        station_code: String::from("KGYX"),
+        capture_time: chrono::NaiveDateTime::from_timestamp(0, 0),
+        scan_number: 1,
+        latitude: 43.891,
+        longitude: -70.256,
+        operational_mode: OperationalMode::Precipitation,
+        precip_detected: true,
+        bin_size: 0.25,
+        range_to_first_bin: 0.,
+        times: Default::default(),
+        metadata: None,
+        extra_components: Vec::new(),
+        alphanumeric: Default::default(),
+        radials: vec![Radial {
+            azimuth: 0.5,
+            elevation: 0.5,
+            width: 1.,
+            precip_rates: rates,
+            interpolated: false,
+        }],
+    };
+    assert!(diff_scans(&scan(vec![0., 0.1]), &scan(vec![0., 0.1])).is_empty());
+    assert_eq!(
+        diff_scans(&scan(vec![0., 0.25, 0.5]), &scan(vec![0., 0.5])),
+        vec![
+            "radial 0 bins: 3 != 2",
+            "radial 0 rates: 1 bins differ starting at bin 1, by up to 0.25 in/hr"
+        ]
+    );
+}
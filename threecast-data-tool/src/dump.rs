@@ -0,0 +1,339 @@
+//! Annotated structural dumps of data files for `tcdt dump`
+
+use std::collections::HashMap;
+use std::error::Error;
+
+use threecast::parse::{
+    parse_dpr_header, parse_dpr_with_report, text_header_length, PrecipRate, Radial, ScanHeader,
+};
+use threecast::util::read_input;
+
+/// How to decode a header field
+enum Kind {
+    Text(usize),
+    I8,
+    I16,
+    I32,
+    /// A latitude or longitude in thousandths of a degree
+    Degrees,
+    /// Bytes that the parser doesn't decode
+    Skipped(usize),
+}
+
+impl Kind {
+    fn size(&self) -> usize {
+        match self {
+            Kind::Text(n) | Kind::Skipped(n) => *n,
+            Kind::I8 => 1,
+            Kind::I16 => 2,
+            Kind::I32 | Kind::Degrees => 4,
+        }
+    }
+}
+
+/// Decode a numeric field, or `None` for text and skipped fields
+fn decode(kind: &Kind, bytes: &[u8]) -> Option<i64> {
+    match kind {
+        Kind::I8 => Some(bytes[0] as i8 as i64),
+        Kind::I16 => Some(i16::from_be_bytes([bytes[0], bytes[1]]) as i64),
+        Kind::I32 | Kind::Degrees => Some(i32::from_be_bytes(bytes.try_into().unwrap()) as i64),
+        Kind::Text(_) | Kind::Skipped(_) => None,
+    }
+}
+
+/// Decode every numeric field of the message header and the product
+/// description block, keyed by field name. Latitude and longitude are left
+/// in thousandths of a degree.
+pub(crate) fn header_values(data: &[u8]) -> Result<HashMap<&'static str, i64>, String> {
+    let mut values = HashMap::new();
+    // the text header can be longer than usual, which moves everything
+    // after it
+    let mut offset = text_header_length(data)?;
+    for (name, kind) in MESSAGE_HEADER_FIELDS
+        .iter()
+        .chain(PRODUCT_DESCRIPTION_FIELDS)
+    {
+        let bytes = match data.get(offset..offset + kind.size()) {
+            Some(b) => b,
+            None => return Err(format!("File ends inside field '{}'", name)),
+        };
+        if let Some(value) = decode(kind, bytes) {
+            values.insert(*name, value);
+        }
+        offset += kind.size();
+    }
+    Ok(values)
+}
+
+/// Names of the fields in [`header_values`], in file order
+pub(crate) fn header_field_names() -> impl Iterator<Item = &'static str> {
+    MESSAGE_HEADER_FIELDS
+        .iter()
+        .chain(PRODUCT_DESCRIPTION_FIELDS)
+        .map(|(name, _)| *name)
+}
+
+/// Fields of the message header (Figure 3-3)
+const MESSAGE_HEADER_FIELDS: &[(&str, Kind)] = &[
+    ("message code", Kind::I16),
+    ("message date", Kind::I16),
+    ("message time", Kind::I32),
+    ("message length", Kind::I32),
+    ("source ID", Kind::I16),
+    ("destination ID", Kind::I16),
+    ("number of blocks", Kind::I16),
+];
+
+/// Fields of the product description block (Figure 3-6)
+const PRODUCT_DESCRIPTION_FIELDS: &[(&str, Kind)] = &[
+    ("block divider", Kind::I16),
+    ("latitude", Kind::Degrees),
+    ("longitude", Kind::Degrees),
+    ("height", Kind::I16),
+    ("product code", Kind::I16),
+    ("operational mode", Kind::I16),
+    ("volume coverage pattern", Kind::I16),
+    ("sequence number", Kind::I16),
+    ("volume scan number", Kind::I16),
+    ("volume scan date", Kind::I16),
+    ("volume scan time", Kind::I32),
+    ("generation date", Kind::I16),
+    ("generation time", Kind::I32),
+    ("product dependent 1", Kind::I16),
+    ("product dependent 2", Kind::I16),
+    ("elevation number", Kind::I16),
+    ("product dependent 3", Kind::I16),
+    ("data levels", Kind::Skipped(32)),
+    ("product dependent 4", Kind::I16),
+    ("product dependent 5", Kind::I16),
+    ("product dependent 6", Kind::I16),
+    ("product dependent 7", Kind::I16),
+    ("compression method", Kind::I16),
+    ("uncompressed size", Kind::I32),
+    ("version", Kind::I8),
+    ("spot blank", Kind::I8),
+    ("symbology offset", Kind::I32),
+    ("graphic offset", Kind::I32),
+    ("tabular offset", Kind::I32),
+];
+
+/// A field as the parser decoded it, or `None` for fields that it doesn't
+/// use, which are shown as they are in the file
+fn decoded_value(header: &ScanHeader, name: &str) -> Option<String> {
+    let metadata = &header.metadata;
+    let date = |time: Option<chrono::NaiveDateTime>| time.map(|t| t.format("%Y-%m-%d").to_string());
+    let time =
+        |time: Option<chrono::NaiveDateTime>| time.map(|t| t.format("%H:%M:%SZ").to_string());
+    let parameter = |n: usize| Some(metadata.parameters[n - 1].to_string());
+    match name {
+        "message date" => date(header.times.message),
+        "message time" => time(header.times.message),
+        "latitude" => Some(format!("{:.3}", metadata.latitude)),
+        "longitude" => Some(format!("{:.3}", metadata.longitude)),
+        "height" => Some(format!("{} ft", metadata.height)),
+        "product code" => Some(metadata.product_code.to_string()),
+        "operational mode" => Some(format!("{:?}", metadata.operational_mode)),
+        "volume coverage pattern" => Some(metadata.volume_coverage_pattern.to_string()),
+        "sequence number" => Some(metadata.sequence_number.to_string()),
+        "volume scan number" => Some(metadata.volume_scan_number.to_string()),
+        "volume scan date" => date(metadata.volume_start),
+        "volume scan time" => time(metadata.volume_start),
+        "generation date" => date(metadata.generation_time),
+        "generation time" => time(metadata.generation_time),
+        "product dependent 1" => parameter(1),
+        "product dependent 2" => parameter(2),
+        "elevation number" => Some(metadata.elevation_number.to_string()),
+        "product dependent 3" => Some(format!(
+            "{} (precip detected: {})",
+            metadata.parameters[2],
+            metadata.precip_detected()
+        )),
+        "product dependent 4" => Some(format!(
+            "{} (max rate: {:.3} in/hr)",
+            metadata.parameters[3], header.max_rate
+        )),
+        "product dependent 5" => parameter(5),
+        "product dependent 6" => parameter(6),
+        "product dependent 7" => parameter(7),
+        "compression method" => Some(metadata.compression_method().to_string()),
+        "uncompressed size" => Some(metadata.uncompressed_size().to_string()),
+        "version" => Some(metadata.version.to_string()),
+        "spot blank" => Some(metadata.spot_blank.to_string()),
+        "symbology offset" => Some(metadata.symbology_offset.to_string()),
+        "graphic offset" => Some(metadata.graphic_offset.to_string()),
+        "tabular offset" => Some(metadata.tabular_offset.to_string()),
+        _ => None,
+    }
+}
+
+/// Print one field per line as offset, raw bytes, name, and value. Values
+/// come from `header` where the parser decodes them, and straight from the
+/// bytes otherwise. Long fields only show their first eight bytes.
+fn dump_fields(
+    data: &[u8],
+    start: usize,
+    fields: &[(&str, Kind)],
+    header: Option<&ScanHeader>,
+) -> Result<usize, String> {
+    let mut offset = start;
+    for (name, kind) in fields {
+        let bytes = match data.get(offset..offset + kind.size()) {
+            Some(b) => b,
+            None => return Err(format!("File ends inside field '{}'", name)),
+        };
+        let decoded = header.and_then(|h| decoded_value(h, name));
+        let value = decoded.unwrap_or_else(|| match (kind, decode(kind, bytes)) {
+            (Kind::Text(_), _) => format!("{:?}", String::from_utf8_lossy(bytes)),
+            (Kind::Degrees, Some(v)) => format!("{:.3}", v as f32 / 1000.),
+            (_, Some(v)) => v.to_string(),
+            (_, None) => String::from("(skipped)"),
+        });
+        let hex: Vec<String> = bytes.iter().take(8).map(|b| format!("{:02x}", b)).collect();
+        let ellipsis = if bytes.len() > 8 { " .." } else { "" };
+        println!(
+            "  {:#07x}  {:<26}  {:<24} {}",
+            offset,
+            hex.join(" ") + ellipsis,
+            name,
+            value
+        );
+        offset += kind.size();
+    }
+    Ok(offset)
+}
+
+fn dump_radial(label: &str, index: usize, radial: &Radial) {
+    let max_rate = radial.precip_rates.iter().cloned().fold(0., f32::max);
+    println!(
+        "  {} radial #{}: azimuth {:.2}°, elevation {:.2}°, width {:.2}°, {} bins, max rate {:.3} in/hr",
+        label,
+        index,
+        radial.azimuth,
+        radial.elevation,
+        radial.width,
+        radial.precip_rates.len(),
+        max_rate
+    );
+}
+
+fn dump_symbology(dpr: &PrecipRate) {
+    println!("  station code        {}", dpr.station_code);
+    println!(
+        "  capture time        {}",
+        dpr.capture_time.format("%Y-%m-%dT%H:%M:%SZ")
+    );
+    let times = [
+        ("elevation start", dpr.times.elevation_start),
+        ("generation time", dpr.times.generation),
+        ("message time", dpr.times.message),
+    ];
+    for (name, time) in times {
+        if let Some(time) = time {
+            println!("  {:<18}  {}", name, time.format("%Y-%m-%dT%H:%M:%SZ"));
+        }
+    }
+    if let Some(delay) = dpr.generation_delay() {
+        println!("  generation delay    {} s", delay.num_seconds());
+    }
+    println!("  scan number         {}", dpr.scan_number);
+    println!("  range to first bin  {} km", dpr.range_to_first_bin);
+    println!("  bin size            {} km", dpr.bin_size);
+    println!("  radials             {}", dpr.radials.len());
+    if let Some(first) = dpr.radials.first() {
+        dump_radial("first", 0, first);
+    }
+    if dpr.radials.len() > 1 {
+        let last = dpr.radials.len() - 1;
+        dump_radial("last", last, &dpr.radials[last]);
+    }
+}
+
+/// Print an annotated dump of the data file at `input`, which can be
+/// anything [`read_input`] accepts. The headers are dumped field by field
+/// straight from the file, with the values the parser decodes from them.
+/// If the headers don't parse, the raw values are shown instead, so this
+/// still shows something useful for broken files. With `preview`, the scan
+/// is drawn in the terminal at the end.
+pub fn dump(input: &str, preview: bool) -> Result<(), Box<dyn Error>> {
+    let data = read_input(input)?;
+    println!("{} ({} bytes)", input, data.len());
+    let header = match parse_dpr_header(&data) {
+        Ok(h) => Some(h),
+        Err(e) => {
+            println!("headers failed to parse, showing raw values: {}", e);
+            None
+        }
+    };
+    println!("text header");
+    let length = text_header_length(&data)?;
+    dump_fields(&data, 0, &[("text header", Kind::Text(length))], None)?;
+    if let Some(header) = header.as_ref() {
+        println!("  station code is {}", header.station_code);
+    }
+    println!("message header");
+    let offset = dump_fields(&data, length, MESSAGE_HEADER_FIELDS, header.as_ref())?;
+    println!("product description block");
+    dump_fields(&data, offset, PRODUCT_DESCRIPTION_FIELDS, header.as_ref())?;
+    let (dpr, report) = parse_dpr_with_report(data)?;
+    println!("sections");
+    for section in report.sections.iter() {
+        println!(
+            "  {:#07x}  {:<24} {:>8} bytes  {:>8.3} ms",
+            section.offset,
+            section.name,
+            section.length,
+            section.duration.as_secs_f64() * 1000.
+        );
+    }
+    println!(
+        "  decompressed symbology is {} bytes, {} left over after the last radial",
+        report.decompressed_size, report.trailing_bytes
+    );
+    println!("product symbology block");
+    dump_symbology(&dpr);
+    if preview {
+        print!("{}", crate::preview::render_terminal_fit(&dpr));
+    }
+    Ok(())
+}
+
+#[test]
+fn test_field_layout() {
+    let size = |fields: &[(&str, Kind)]| fields.iter().map(|(_, k)| k.size()).sum::<usize>();
+    assert_eq!(size(MESSAGE_HEADER_FIELDS), 18);
+    assert_eq!(size(PRODUCT_DESCRIPTION_FIELDS), 102);
+}
+
+#[test]
+fn test_layout_matches_parser() {
+    let data = threecast::synthetic::SyntheticDpr::new("KGYX")
+        .with_geometry(4, 8, 0.25)
+        .with_background(0.5)
+        .to_bytes();
+    let header = parse_dpr_header(&data).unwrap();
+    let values = header_values(&data).unwrap();
+    assert_eq!(
+        values["product dependent 3"],
+        header.metadata.parameters[2] as i64
+    );
+    assert_eq!(
+        values["compression method"],
+        header.metadata.compression_method() as i64
+    );
+    assert_eq!(
+        values["uncompressed size"],
+        header.metadata.uncompressed_size() as i64
+    );
+    assert_eq!(
+        decoded_value(&header, "volume scan time"),
+        Some(header.capture_time.format("%H:%M:%SZ").to_string())
+    );
+
+    // a longer text header from LDM moves everything else along
+    let mut longer = data.clone();
+    longer.splice(
+        ..30,
+        b"\x01\r\r\n042 \r\r\nSDUS51 KGYX 010000\r\r\nDPRGYX\r\r\n".to_vec(),
+    );
+    assert_eq!(header_values(&longer).unwrap(), values);
+}
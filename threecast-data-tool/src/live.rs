@@ -0,0 +1,258 @@
+//! Keep the latest scan for a location converted on disk for `tcdt live`
+//!
+//! Each new scan is written into `history/` as GeoJSON, a PNG for map image
+//! overlays, and a JSON file with the bounds of the PNG. `latest.geojson`,
+//! `latest.png`, and `latest.json` in the output directory always point at
+//! the newest of them, and only the newest `history` scans are kept.
+
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+use threecast::filter::MinRate;
+use threecast::output::{write_scan_filtered, GeoJsonSink, WriterOptions};
+use threecast::parse::{parse_dpr, PrecipRate};
+use threecast::schedule::ScanCadence;
+use threecast::util::precip_category;
+
+use crate::preview::CATEGORY_RGB;
+
+/// Which station to follow
+pub enum Target {
+    Station(String),
+    /// The nearest online station to a latitude and longitude, which is
+    /// looked up again before every fetch so that outages are ridden out
+    Location(f32, f32),
+}
+
+pub struct LiveOptions {
+    pub target: Target,
+    pub output_dir: PathBuf,
+    /// Number of scans to keep in `history/`
+    pub history: usize,
+    /// Width and height of the PNG in pixels
+    pub size: usize,
+    /// Bins below this rate in in/hr are left out of the GeoJSON
+    pub min_rate: f32,
+    pub writer_options: WriterOptions,
+}
+
+/// Fetch and convert new scans forever
+pub fn live(options: LiveOptions) -> Result<(), Box<dyn Error>> {
+    let history_dir = options.output_dir.join("history");
+    std::fs::create_dir_all(&history_dir)?;
+    let mut cadence = ScanCadence::new();
+    let mut last_capture_time = None;
+    loop {
+        let station = match &options.target {
+            Target::Station(station) => Ok(station.clone()),
+            Target::Location(lat, lon) => threecast::net::find_nearest_online_station(*lat, *lon),
+        };
+        let result = station.and_then(|station| {
+            let dpr = parse_dpr(threecast::net::get_data_by_station(&station, "last")?)?;
+            Ok((station, dpr))
+        });
+        match result {
+            Ok((station, dpr)) => {
+                cadence.observe(dpr.capture_time, &dpr.operational_mode);
+                let key = (station.clone(), dpr.capture_time);
+                if last_capture_time.as_ref() != Some(&key) {
+                    match update(&options, &history_dir, &dpr) {
+                        Ok(stem) => println!("[{}] wrote {}", station, stem),
+                        Err(e) => println!("[{}] failed to write scan: {}", station, e),
+                    }
+                    last_capture_time = Some(key);
+                }
+            }
+            Err(e) => println!("failed to get data: {}", e),
+        }
+        let now = chrono::Utc::now().naive_utc();
+        std::thread::sleep(cadence.next_fetch_delay(now));
+    }
+}
+
+/// Write one scan into the history, point the `latest` links at it, and
+/// prune old scans. Returns the file stem of the new scan.
+fn update(
+    options: &LiveOptions,
+    history_dir: &Path,
+    dpr: &PrecipRate,
+) -> Result<String, Box<dyn Error>> {
+    // names start with the time so that they sort in scan order even when
+    // the nearest station changes
+    let stem = format!(
+        "{}_{}",
+        dpr.capture_time.format("%Y%m%dT%H%M%SZ"),
+        dpr.station_code.to_uppercase()
+    );
+
+    let mut geojson = Vec::new();
+    let mut sink = GeoJsonSink::new(&mut geojson).with_options(options.writer_options);
+    write_scan_filtered(dpr, &mut sink, &MinRate(options.min_rate))?;
+    drop(sink);
+    let (png, bounds) = render_png(dpr, options.size)?;
+    let metadata = serde_json::json!({
+        "station": dpr.station_code.to_uppercase(),
+        "captureTime": dpr.capture_time.format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+        "bounds": bounds,
+        "geojson": format!("history/{}.geojson", stem),
+        "png": format!("history/{}.png", stem),
+    });
+    for (extension, data) in [
+        ("geojson", geojson),
+        ("png", png),
+        ("json", metadata.to_string().into_bytes()),
+    ] {
+        let name = format!("{}.{}", stem, extension);
+        write_atomically(&history_dir.join(&name), &data)?;
+        link_atomically(
+            &Path::new("history").join(&name),
+            &options.output_dir.join(format!("latest.{}", extension)),
+        )?;
+    }
+    prune_history(history_dir, options.history)?;
+    Ok(stem)
+}
+
+/// `[[south, west], [north, east]]` in degrees, which is what Leaflet's
+/// `imageOverlay` wants
+type Bounds = [[f32; 2]; 2];
+
+/// Render the scan as a square PNG with transparent pixels where there's no
+/// precipitation, colored like the preview. Also returns the bounds of the
+/// image.
+fn render_png(dpr: &PrecipRate, size: usize) -> Result<(Vec<u8>, Bounds), Box<dyn Error>> {
+    let size = size.max(2);
+    let grid = dpr.sample_radials_to_equirectangular(size, size);
+    let mut image = image::RgbaImage::new(size as u32, size as u32);
+    for (y, row) in grid.iter().enumerate() {
+        for (x, (_, rate)) in row.iter().enumerate() {
+            if let Some((_, [r, g, b])) = CATEGORY_RGB
+                .iter()
+                .find(|(category, _)| *category == precip_category(*rate))
+            {
+                image.put_pixel(x as u32, y as u32, image::Rgba([*r, *g, *b, 255]));
+            }
+        }
+    }
+    // the grid holds pixel centers, so pad by half a pixel on each side
+    let coord = |y: usize, x: usize| {
+        let [lat, lon] = grid[y][x].0;
+        (lat as f32 / 10000., lon as f32 / 10000.)
+    };
+    let (north, west) = coord(0, 0);
+    let (south, east) = coord(size - 1, size - 1);
+    let half_height = (north - south) / (size - 1) as f32 / 2.;
+    let half_width = (east - west) / (size - 1) as f32 / 2.;
+    let mut png = Vec::new();
+    image::DynamicImage::ImageRgba8(image).write_to(&mut png, image::ImageOutputFormat::Png)?;
+    Ok((
+        png,
+        [
+            [south - half_height, west - half_width],
+            [north + half_height, east + half_width],
+        ],
+    ))
+}
+
+/// Write `data` to a temporary file next to `path` and rename it into place,
+/// so that readers never see a partly written file
+fn write_atomically(path: &Path, data: &[u8]) -> Result<(), Box<dyn Error>> {
+    let temporary = path.with_extension("tmp");
+    std::fs::write(&temporary, data)?;
+    std::fs::rename(&temporary, path)?;
+    Ok(())
+}
+
+/// Make `link` point at `target`, which is relative to the link's
+/// directory, replacing any existing link in one step. Without symlinks,
+/// the target is copied instead.
+fn link_atomically(target: &Path, link: &Path) -> Result<(), Box<dyn Error>> {
+    let temporary = link.with_extension("tmp-link");
+    let _ = std::fs::remove_file(&temporary);
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(target, &temporary)?;
+    #[cfg(not(unix))]
+    std::fs::copy(
+        link.parent().unwrap_or(Path::new(".")).join(target),
+        &temporary,
+    )?;
+    std::fs::rename(&temporary, link)?;
+    Ok(())
+}
+
+/// Delete all but the newest `keep` scans from the history, going by file
+/// names
+fn prune_history(history_dir: &Path, keep: usize) -> Result<(), Box<dyn Error>> {
+    let mut stems: Vec<String> = std::fs::read_dir(history_dir)?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let path = entry.path();
+            match path.extension().and_then(|e| e.to_str()) {
+                Some("json") => Some(path.file_stem()?.to_string_lossy().into_owned()),
+                _ => None,
+            }
+        })
+        .collect();
+    stems.sort();
+    let excess = stems.len().saturating_sub(keep.max(1));
+    for stem in stems.iter().take(excess) {
+        for extension in ["geojson", "png", "json"] {
+            let path = history_dir.join(format!("{}.{}", stem, extension));
+            if path.exists() {
+                std::fs::remove_file(path)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_update() {
+    use threecast::parse::{OperationalMode, Radial};
+    let output_dir = std::env::temp_dir().join(format!("tcdt-live-test-{}", std::process::id()));
+    let history_dir = output_dir.join("history");
+    std::fs::create_dir_all(&history_dir).unwrap();
+    let options = LiveOptions {
+        target: Target::Station(String::from("kgyx")),
+        output_dir: output_dir.clone(),
+        history: 2,
+        size: 16,
+        min_rate: 0.001,
+        writer_options: WriterOptions::default(),
+    };
+    let scan = |minutes: i64| PrecipRate {
+        station_code: String::from("kgyx"),
+        capture_time: chrono::NaiveDateTime::from_timestamp(minutes * 60, 0),
+        scan_number: 1,
+        latitude: 43.891,
+        longitude: -70.256,
+        operational_mode: OperationalMode::Precipitation,
+        precip_detected: true,
+        bin_size: 1.,
+        range_to_first_bin: 0.,
+        times: Default::default(),
+        metadata: None,
+        extra_components: Vec::new(),
+        alphanumeric: Default::default(),
+        radials: vec![Radial {
+            azimuth: 0.5,
+            elevation: 0.5,
+            width: 1.,
+            precip_rates: vec![0., 0.2, 0.],
+            interpolated: false,
+        }],
+    };
+    for minutes in [0, 5, 10] {
+        update(&options, &history_dir, &scan(minutes)).unwrap();
+    }
+    let metadata: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(output_dir.join("latest.json")).unwrap()).unwrap();
+    assert_eq!(metadata["png"], "history/19700101T001000Z_KGYX.png");
+    let geojson = std::fs::read_to_string(output_dir.join("latest.geojson")).unwrap();
+    assert_eq!(geojson.matches("\"Feature\"").count(), 1);
+    assert!(std::fs::read(output_dir.join("latest.png"))
+        .unwrap()
+        .starts_with(b"\x89PNG"));
+    assert_eq!(std::fs::read_dir(&history_dir).unwrap().count(), 6);
+    std::fs::remove_dir_all(output_dir).unwrap();
+}
@@ -1,96 +1,354 @@
+mod diff;
+mod dump;
+mod live;
+mod pipeline;
+mod preview;
+mod stats;
+mod validate;
+mod zonal;
+
 use clap::{App, Arg, SubCommand};
+use std::collections::HashMap;
 use std::error::Error;
+use std::sync::Arc;
+use std::time::Instant;
+use threecast::metrics::Metrics;
+use threecast::mqtt::{default_client_id, MqttClient};
 use threecast::parse::PrecipRate;
+use threecast::schedule::ScanCadence;
 use threecast::stations::STATIONS;
+use threecast::util::{read_input, read_inputs};
+use threecast::watch::{Alert, AlertTracker, Watch, WatchArea};
 
-fn compute_precip_fraction(dpr: &PrecipRate) -> f32 {
-    let mut rainy_bins = 0.0;
-    let mut total_bins = 0.0;
-    for radial in dpr.radials.iter() {
-        for bin in radial.precip_rates.iter() {
-            if bin > &0.0 {
-                rainy_bins += 1.0;
-            }
-            total_bins += 1.0;
-        }
-    }
-    rainy_bins / total_bins
-}
-
-fn collect_data(station: &str, target_precip_fraction: f32) {
-    let sleep_duration_sec = 180;
+/// Fetch new scans from `station` as they're published, save the ones with
+/// enough precipitation, and run each new scan through `pipelines`
+fn collect_data(
+    station: &str,
+    target_precip_fraction: f32,
+    metrics: &Metrics,
+    pipelines: &[pipeline::Pipeline],
+) {
+    let mut cadence = ScanCadence::new();
     let mut first_run = true;
     let mut last_scan_number = -1; // scan numbers are between 1 and 80, inclusive
     loop {
         if !first_run {
-            // sleep for a random-ish amount of time
-            // without this, the threads tend to collect into time clusters
-            let random_extra_seconds = (chrono::offset::Utc::now().timestamp_nanos() % 30) as u64;
-            println!(
-                "[{}] sleeping for {} seconds",
-                station,
-                sleep_duration_sec + random_extra_seconds
-            );
-            std::thread::sleep(std::time::Duration::from_secs(180 + random_extra_seconds));
+            // wait until shortly after the next scan should be published
+            let delay = cadence.next_fetch_delay(chrono::Utc::now().naive_utc());
+            println!("[{}] sleeping for {} seconds", station, delay.as_secs());
+            std::thread::sleep(delay);
         }
         first_run = false;
+        let fetch_start = Instant::now();
         let dpr_data = match threecast::net::get_data_by_station(station, "last") {
             Ok(d) => {
+                metrics.record_fetch(station, fetch_start.elapsed(), true);
                 println!("[{}] got data", station);
                 d
             }
             Err(e) => {
+                metrics.record_fetch(station, fetch_start.elapsed(), false);
                 println!("[{}] failed to get data: {}", station, e);
                 continue;
             }
         };
+        let parse_start = Instant::now();
+        // the headers are enough to tell whether the scan is new, which
+        // saves decompressing scans that have already been seen
+        let header = match threecast::parse::parse_dpr_header(&dpr_data) {
+            Ok(h) => h,
+            Err(e) => {
+                metrics.record_parse_failure(station);
+                println!("[{}] failed to parse data: {}", station, e);
+                continue;
+            }
+        };
+        cadence.observe(header.capture_time, &header.operational_mode);
+        if header.scan_number == last_scan_number {
+            println!("[{}] data file is old", station);
+            continue;
+        }
         let dpr = match threecast::parse::parse_dpr(dpr_data.clone()) {
             Ok(d) => {
                 println!("[{}] parsed data", station);
                 d
             }
             Err(e) => {
+                metrics.record_parse_failure(station);
                 println!("[{}] failed to parse data: {}", station, e);
                 continue;
             }
         };
-        if dpr.scan_number != last_scan_number {
-            println!("[{}] data file is new", station);
-            last_scan_number = dpr.scan_number;
-            let precip_fraction = compute_precip_fraction(&dpr);
-            if precip_fraction >= target_precip_fraction {
-                println!(
-                    "[{}] data file exceeds precipitation threshold ({:.4} >= {:.4})",
-                    station, precip_fraction, target_precip_fraction
-                );
-                let write_result = std::fs::write(
-                    format!(
-                        "./{}-{}-{:0>2}.nexrad", // TODO: use path from CLI arg
-                        station.to_uppercase(),
-                        dpr.capture_time.format("%Y-%m-%dT%H:%M:%SZ"),
-                        dpr.scan_number
-                    ),
-                    dpr_data,
-                );
-                if let Err(e) = write_result {
-                    println!("[{}] failed to write data file to disk: {}", station, e);
-                } else {
-                    println!("[{}] wrote data file to disk", station);
-                }
+        println!("[{}] data file is new", station);
+        metrics.record_scan(station, dpr.capture_time, parse_start.elapsed());
+        last_scan_number = dpr.scan_number;
+        for pipeline in pipelines {
+            if let Err(e) = pipeline.process(dpr_data.clone(), dpr.clone()) {
+                println!("[{}] pipeline failed: {}", station, e);
+            }
+        }
+        let precip_fraction = dpr.precip_fraction();
+        if precip_fraction >= target_precip_fraction {
+            println!(
+                "[{}] data file exceeds precipitation threshold ({:.4} >= {:.4})",
+                station, precip_fraction, target_precip_fraction
+            );
+            let write_result = std::fs::write(
+                format!(
+                    "./{}-{}-{:0>2}.nexrad", // TODO: use path from CLI arg
+                    station.to_uppercase(),
+                    dpr.capture_time.format("%Y-%m-%dT%H:%M:%SZ"),
+                    dpr.scan_number
+                ),
+                dpr_data,
+            );
+            if let Err(e) = write_result {
+                println!("[{}] failed to write data file to disk: {}", station, e);
             } else {
-                println!(
-                    "[{}] data file does not exceed preciptation threshold ({:.4} < {:.4})",
-                    station, precip_fraction, target_precip_fraction
-                );
+                println!("[{}] wrote data file to disk", station);
             }
         } else {
-            println!("[{}] data file is old", station);
+            println!(
+                "[{}] data file does not exceed preciptation threshold ({:.4} < {:.4})",
+                station, precip_fraction, target_precip_fraction
+            );
+        }
+    }
+}
+
+/// Parse a watch given on the command line as `NAME=LAT,LON`, or as
+/// `NAME=LAT,LON;LAT,LON;...` for polygons
+fn parse_watch(spec: &str, threshold: f32) -> Result<Watch, Box<dyn Error>> {
+    let (name, coords) = match spec.split_once('=') {
+        Some(s) => s,
+        None => return Err(format!("Watch '{}' is missing a name", spec).into()),
+    };
+    let vertices = coords
+        .split(';')
+        .map(|pair| {
+            let parsed = pair
+                .split(',')
+                .map(|c| {
+                    c.trim().parse::<f32>().map_err(|e| {
+                        format!("Invalid number '{}' in watch '{}': {}", c.trim(), name, e)
+                    })
+                })
+                .collect::<Result<Vec<f32>, String>>()?;
+            match parsed[..] {
+                [lat, lon] => Ok((lat, lon)),
+                _ => Err(format!(
+                    "Failed to parse coordinate '{}' of watch '{}'",
+                    pair, name
+                )),
+            }
+        })
+        .collect::<Result<Vec<(f32, f32)>, String>>()?;
+    let area = match vertices[..] {
+        [(lat, lon)] => WatchArea::Point(lat, lon),
+        _ if vertices.len() >= 3 => WatchArea::Polygon(vertices),
+        _ => return Err(format!("Watch '{}' must be a point or a polygon", name).into()),
+    };
+    Ok(Watch {
+        name: name.to_string(),
+        area,
+        threshold,
+    })
+}
+
+fn alert_payload(station: &str, alert: &Alert) -> serde_json::Value {
+    serde_json::json!({
+        "watch": alert.watch,
+        "event": alert.kind.as_str(),
+        "minutes": alert.minutes,
+        "peak_rate": alert.peak_rate,
+        "station": station.to_uppercase(),
+        "time": chrono::Utc::now().to_rfc3339(),
+    })
+}
+
+fn post_alert(client: &reqwest::blocking::Client, webhook: &str, payload: &serde_json::Value) {
+    let watch = payload["watch"].as_str().unwrap_or_default();
+    match client
+        .post(webhook)
+        .header("Content-Type", "application/json")
+        .body(payload.to_string())
+        .send()
+    {
+        Ok(r) if r.status().is_success() => println!("[{}] sent alert to {}", watch, webhook),
+        Ok(r) => println!(
+            "[{}] webhook {} responded with {}",
+            watch,
+            webhook,
+            r.status()
+        ),
+        Err(e) => println!("[{}] failed to send alert to {}: {}", watch, webhook, e),
+    }
+}
+
+/// Connection details for publishing to an MQTT broker
+struct MqttConfig {
+    broker: String,
+    prefix: String,
+    client_id: String,
+}
+
+/// Publish the scan summary, the nowcasts for each watch, and any alerts for
+/// one station to the broker
+fn publish_mqtt(
+    config: &MqttConfig,
+    scan: &PrecipRate,
+    nowcasts: &[(&Watch, Vec<f32>)],
+    alerts: &[serde_json::Value],
+) -> Result<(), Box<dyn Error>> {
+    let mut client = MqttClient::connect(&config.broker, &config.client_id)?;
+    client.publish(
+        &format!(
+            "{}/{}/scan",
+            config.prefix,
+            scan.station_code.to_uppercase()
+        ),
+        serde_json::to_string(&scan.summary())?.as_bytes(),
+        true,
+    )?;
+    for (watch, rates) in nowcasts {
+        let nowcast = serde_json::json!({
+            "minutes": (0..rates.len()).map(|i| i * 5).collect::<Vec<usize>>(),
+            "rates": rates,
+        });
+        client.publish(
+            &format!("{}/watch/{}/nowcast", config.prefix, watch.name),
+            nowcast.to_string().as_bytes(),
+            true,
+        )?;
+    }
+    for alert in alerts {
+        client.publish(
+            &format!(
+                "{}/watch/{}/alert",
+                config.prefix,
+                alert["watch"].as_str().unwrap_or_default()
+            ),
+            alert.to_string().as_bytes(),
+            false,
+        )?;
+    }
+    Ok(())
+}
+
+fn notify(
+    watches: Vec<Watch>,
+    webhooks: Vec<String>,
+    mqtt: Option<MqttConfig>,
+    cooldown: u64,
+    interval: u64,
+    metrics: &Metrics,
+) {
+    let client = reqwest::blocking::Client::new();
+    let mut tracker = AlertTracker::new(std::time::Duration::from_secs(cooldown));
+    loop {
+        // group watches by station so that each station is only fetched once
+        let mut by_station: HashMap<String, Vec<&Watch>> = HashMap::new();
+        for watch in watches.iter() {
+            let (lat, lon) = watch.area.center();
+            match threecast::net::find_nearest_online_station(lat, lon) {
+                Ok(station) => by_station.entry(station).or_default().push(watch),
+                Err(e) => println!("[{}] no station available: {}", watch.name, e),
+            }
+        }
+        for (station, watches) in by_station {
+            let fetch_start = Instant::now();
+            let (older, newer) = match threecast::net::get_two_most_recent_data(&station) {
+                Ok(d) => {
+                    metrics.record_fetch(&station, fetch_start.elapsed(), true);
+                    d
+                }
+                Err(e) => {
+                    metrics.record_fetch(&station, fetch_start.elapsed(), false);
+                    println!("[{}] failed to get data: {}", station, e);
+                    continue;
+                }
+            };
+            let conversion_start = Instant::now();
+            let (older, newer) = match (
+                threecast::parse::parse_dpr(older),
+                threecast::parse::parse_dpr(newer),
+            ) {
+                (Ok(older), Ok(newer)) => (older, newer),
+                (Err(e), _) | (_, Err(e)) => {
+                    metrics.record_parse_failure(&station);
+                    println!("[{}] failed to parse data: {}", station, e);
+                    continue;
+                }
+            };
+            let predictions = threecast::predict::nowcast(&older, &newer);
+            metrics.record_scan(&station, newer.capture_time, conversion_start.elapsed());
+            let mut nowcasts = Vec::new();
+            let mut alerts = Vec::new();
+            for watch in watches {
+                match watch.peak_rates(&predictions) {
+                    Ok(rates) => nowcasts.push((watch, rates)),
+                    Err(e) => println!("[{}] failed to evaluate watch: {}", watch.name, e),
+                }
+                match tracker.update(watch, &predictions) {
+                    Ok(Some(alert)) => alerts.push(alert_payload(&station, &alert)),
+                    Ok(None) => (),
+                    Err(e) => println!("[{}] failed to evaluate watch: {}", watch.name, e),
+                }
+            }
+            for alert in alerts.iter() {
+                for webhook in webhooks.iter() {
+                    post_alert(&client, webhook, alert);
+                }
+            }
+            if let Some(config) = &mqtt {
+                match publish_mqtt(config, &newer, &nowcasts, &alerts) {
+                    Ok(_) => println!("[{}] published to MQTT broker", station),
+                    Err(e) => println!("[{}] failed to publish to MQTT broker: {}", station, e),
+                }
+            }
+        }
+        std::thread::sleep(std::time::Duration::from_secs(interval));
+    }
+}
+
+/// Read and parse every data file in `inputs`, expanding tar archives and
+/// printing errors for files that don't parse rather than stopping
+fn read_scans<'a>(
+    inputs: impl Iterator<Item = &'a str>,
+) -> Result<Vec<PrecipRate>, Box<dyn Error>> {
+    let mut scans = Vec::new();
+    for input in inputs {
+        for (file, data) in read_inputs(input)? {
+            match threecast::parse::parse_dpr(data) {
+                Ok(dpr) => scans.push(dpr),
+                Err(e) => eprintln!("[{}] {}", file, e),
+            }
         }
     }
+    Ok(scans)
+}
+
+/// Parse a `SOUTH,WEST,NORTH,EAST` extent in degrees
+fn parse_bbox(bbox: &str) -> Result<threecast::filter::BoundingBox, Box<dyn Error>> {
+    let edges: Vec<f32> = match bbox.split(',').map(|e| e.trim().parse()).collect() {
+        Ok(edges) => edges,
+        Err(_) => return Err("Failed to parse bounding box".into()),
+    };
+    match edges[..] {
+        [south, west, north, east] if south < north && west < east => {
+            Ok(threecast::filter::BoundingBox {
+                south,
+                west,
+                north,
+                east,
+            })
+        }
+        _ => Err("Bounding box must be SOUTH,WEST,NORTH,EAST".into()),
+    }
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
-    let matches = App::new("threecast-data-tool")
+    let app = App::new("threecast-data-tool")
         .version("0.1.0")
         .author("Bradley Gannon <bradley@bradleygannon.com>")
         .about("Makes it easier to gather DPR data and test prediction methods")
@@ -124,6 +382,22 @@ fn main() -> Result<(), Box<dyn Error>> {
                         .help("Directory to collect data in")
                         .takes_value(true)
                         .required(true),
+                )
+                .arg(
+                    Arg::with_name("metrics")
+                        .long("metrics")
+                        .value_name("ADDRESS")
+                        .help("Serve Prometheus metrics at /metrics on ADDRESS (e.g. 0.0.0.0:9176)")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("pipeline")
+                        .long("pipeline")
+                        .value_name("FILE")
+                        .help("Run each new scan through the filters, transforms, and sinks in this pipeline file (may be repeated; its source is ignored)")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1),
                 ),
         )
         .subcommand(
@@ -138,7 +412,717 @@ fn main() -> Result<(), Box<dyn Error>> {
                         .takes_value(true),
                 ),
         )
-        .get_matches();
+        .subcommand(
+            SubCommand::with_name("run")
+                .about("run a pipeline described in a TOML file")
+                .arg(
+                    Arg::with_name("pipeline")
+                        .value_name("PIPELINE")
+                        .help("Path to the pipeline file")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("live")
+                .about("keep the latest scan for a station or location converted on disk")
+                .arg(
+                    Arg::with_name("station")
+                        .short("s")
+                        .long("station")
+                        .value_name("STATION")
+                        .help("Four-letter station code, e.g. KGYX")
+                        .takes_value(true)
+                        .required_unless_all(&["latitude", "longitude"])
+                        .conflicts_with_all(&["latitude", "longitude"]),
+                )
+                .arg(
+                    Arg::with_name("latitude")
+                        .short("y")
+                        .long("lat")
+                        .value_name("LATITUDE")
+                        .help("Follow the nearest online station to this latitude")
+                        .takes_value(true)
+                        .requires("longitude")
+                        .allow_hyphen_values(true),
+                )
+                .arg(
+                    Arg::with_name("longitude")
+                        .short("x")
+                        .long("lon")
+                        .value_name("LONGITUDE")
+                        .help("Follow the nearest online station to this longitude")
+                        .takes_value(true)
+                        .requires("latitude")
+                        .allow_hyphen_values(true),
+                )
+                .arg(
+                    Arg::with_name("output-dir")
+                        .short("o")
+                        .long("output-dir")
+                        .value_name("DIR")
+                        .help("Directory for latest.geojson, latest.png, latest.json, and history/")
+                        .takes_value(true)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("history")
+                        .long("history")
+                        .value_name("SCANS")
+                        .help("Number of scans to keep in history/")
+                        .takes_value(true)
+                        .default_value("24"),
+                )
+                .arg(
+                    Arg::with_name("size")
+                        .long("size")
+                        .value_name("PIXELS")
+                        .help("Width and height of the PNG")
+                        .takes_value(true)
+                        .default_value("512"),
+                )
+                .arg(
+                    Arg::with_name("min-rate")
+                        .long("min-rate")
+                        .value_name("RATE")
+                        .help("Leave bins below this rate in in/hr out of the GeoJSON")
+                        .takes_value(true)
+                        .default_value("0.001"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("dump")
+                .about("print an annotated structural dump of a data file")
+                .arg(
+                    Arg::with_name("file")
+                        .value_name("FILE")
+                        .help("Path or URL of the data file, optionally gzipped, or - for stdin")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("preview")
+                        .long("preview")
+                        .help("Also draw the scan in the terminal"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("preview")
+                .about("write an HTML map of a data file and open it in a browser")
+                .arg(
+                    Arg::with_name("file")
+                        .value_name("FILE")
+                        .help("Path or URL of the data file, optionally gzipped, or - for stdin")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .short("o")
+                        .long("output")
+                        .value_name("PATH")
+                        .help("Where to write the HTML; defaults to the temporary directory")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("no-open")
+                        .long("no-open")
+                        .help("Don't open the preview in a browser"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("validate")
+                .about("check data files against the ICD and exit nonzero if any have errors")
+                .arg(
+                    Arg::with_name("files")
+                        .value_name("FILE")
+                        .help("Paths or URLs of the data files, optionally gzipped, or tar archives of them")
+                        .multiple(true)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("strict")
+                        .long("strict")
+                        .help("Treat warnings as errors"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("diff")
+                .about("compare two data files field by field and exit nonzero if they differ")
+                .arg(
+                    Arg::with_name("a")
+                        .value_name("A")
+                        .help("Path or URL of the first data file, optionally gzipped")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("b")
+                        .value_name("B")
+                        .help("Path or URL of the second data file, optionally gzipped")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("zonal")
+                .about("compute per-zone rate statistics for data files")
+                .arg(
+                    Arg::with_name("zones")
+                        .long("zones")
+                        .value_name("GEOJSON")
+                        .help("GeoJSON FeatureCollection of Polygon or MultiPolygon zones")
+                        .takes_value(true)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("name-property")
+                        .long("name-property")
+                        .value_name("PROPERTY")
+                        .help("Feature property to name zones by")
+                        .takes_value(true)
+                        .default_value("name"),
+                )
+                .arg(
+                    Arg::with_name("threshold")
+                        .short("t")
+                        .long("threshold")
+                        .value_name("RATE")
+                        .help("Rate in in/hr for the fraction of each zone above it")
+                        .takes_value(true)
+                        .default_value("0.0"),
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .short("f")
+                        .long("format")
+                        .value_name("FORMAT")
+                        .takes_value(true)
+                        .possible_values(&["csv", "geojson"])
+                        .default_value("csv"),
+                )
+                .arg(
+                    Arg::with_name("files")
+                        .value_name("FILE")
+                        .help("Paths or URLs of the data files, optionally gzipped, or tar archives of them")
+                        .multiple(true)
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("stats")
+                .about("summarize many data files, one row per scan or period")
+                .arg(
+                    Arg::with_name("aggregate")
+                        .short("a")
+                        .long("aggregate")
+                        .value_name("PERIOD")
+                        .help("Combine scans from each station by hour or day")
+                        .takes_value(true)
+                        .possible_values(&["none", "hour", "day"])
+                        .default_value("none"),
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .short("f")
+                        .long("format")
+                        .value_name("FORMAT")
+                        .takes_value(true)
+                        .possible_values(&["csv", "json"])
+                        .default_value("csv"),
+                )
+                .arg(
+                    Arg::with_name("files")
+                        .value_name("FILE")
+                        .help("Paths or URLs of the data files, optionally gzipped, or tar archives of them")
+                        .multiple(true)
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("to-kml")
+                .about("convert data files to one KML or KMZ document that animates in Google Earth")
+                .arg(
+                    Arg::with_name("output")
+                        .short("o")
+                        .long("output")
+                        .value_name("PATH")
+                        .help("Where to write the document; a .kmz extension makes a KMZ archive")
+                        .takes_value(true)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("min-rate")
+                        .long("min-rate")
+                        .value_name("RATE")
+                        .help("Leave out bins below this rate in in/hr")
+                        .takes_value(true)
+                        .default_value("0.001"),
+                )
+                .arg(
+                    Arg::with_name("files")
+                        .value_name("FILE")
+                        .help("Paths or URLs of the data files, optionally gzipped, or tar archives of them")
+                        .multiple(true)
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("to-csv")
+                .about("convert data files to one table with a row per bin, for pandas, R, and spreadsheets")
+                .arg(
+                    Arg::with_name("output")
+                        .short("o")
+                        .long("output")
+                        .value_name("PATH")
+                        .help("Where to write the table instead of standard output")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("min-rate")
+                        .long("min-rate")
+                        .value_name("RATE")
+                        .help("Leave out bins below this rate in in/hr")
+                        .takes_value(true)
+                        .default_value("0.001"),
+                )
+                .arg(
+                    Arg::with_name("tsv")
+                        .long("tsv")
+                        .help("Separate fields with tabs instead of commas"),
+                )
+                .arg(
+                    Arg::with_name("files")
+                        .value_name("FILE")
+                        .help("Paths or URLs of the data files, optionally gzipped, or tar archives of them")
+                        .multiple(true)
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("to-geotiff")
+                .about("burn the rates of a data file into a single-band GeoTIFF")
+                .arg(
+                    Arg::with_name("file")
+                        .value_name("FILE")
+                        .help("Path or URL of the data file, optionally gzipped, or - for stdin")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .short("o")
+                        .long("output")
+                        .value_name("PATH")
+                        .help("Where to write the GeoTIFF")
+                        .takes_value(true)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("resolution")
+                        .short("r")
+                        .long("resolution")
+                        .value_name("DEGREES")
+                        .help("Width and height of a pixel")
+                        .takes_value(true)
+                        .default_value("0.005"),
+                )
+                .arg(
+                    Arg::with_name("bbox")
+                        .long("bbox")
+                        .value_name("SOUTH,WEST,NORTH,EAST")
+                        .help("Extent of the raster in degrees; defaults to the whole coverage area")
+                        .takes_value(true)
+                        .allow_hyphen_values(true),
+                )
+                .arg(
+                    Arg::with_name("cog")
+                        .long("cog")
+                        .help("Write a tiled Cloud Optimized GeoTIFF with overviews"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("to-hdf5")
+                .about("convert a data file to HDF5 with the original polar grid")
+                .arg(
+                    Arg::with_name("file")
+                        .value_name("FILE")
+                        .help("Path or URL of the data file, optionally gzipped, or - for stdin")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .short("o")
+                        .long("output")
+                        .value_name("PATH")
+                        .help("Where to write the HDF5 file")
+                        .takes_value(true)
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("to-grib2")
+                .about("rasterize a data file into a GRIB2 precipitation rate message")
+                .arg(
+                    Arg::with_name("file")
+                        .value_name("FILE")
+                        .help("Path or URL of the data file, optionally gzipped, or - for stdin")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .short("o")
+                        .long("output")
+                        .value_name("PATH")
+                        .help("Where to write the GRIB2 file")
+                        .takes_value(true)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("resolution")
+                        .short("r")
+                        .long("resolution")
+                        .value_name("DEGREES")
+                        .help("Spacing of the grid points")
+                        .takes_value(true)
+                        .default_value("0.005"),
+                )
+                .arg(
+                    Arg::with_name("bbox")
+                        .long("bbox")
+                        .value_name("SOUTH,WEST,NORTH,EAST")
+                        .help("Extent of the grid in degrees; defaults to the whole coverage area")
+                        .takes_value(true)
+                        .allow_hyphen_values(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("render")
+                .about("draw a data file as a PNG for previews and web map overlays")
+                .arg(
+                    Arg::with_name("file")
+                        .value_name("FILE")
+                        .help("Path or URL of the data file, optionally gzipped, or - for stdin")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .short("o")
+                        .long("output")
+                        .value_name("PATH")
+                        .help("Where to write the PNG")
+                        .takes_value(true)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("resolution")
+                        .short("r")
+                        .long("resolution")
+                        .value_name("DEGREES")
+                        .help("Width and height of a pixel")
+                        .takes_value(true)
+                        .default_value("0.005"),
+                )
+                .arg(
+                    Arg::with_name("bbox")
+                        .long("bbox")
+                        .value_name("SOUTH,WEST,NORTH,EAST")
+                        .help("Extent of the image in degrees; defaults to the whole coverage area")
+                        .takes_value(true)
+                        .allow_hyphen_values(true),
+                )
+                .arg(
+                    Arg::with_name("colormap")
+                        .short("c")
+                        .long("colormap")
+                        .value_name("COLORMAP")
+                        .help("nws, categories, or steps like 0.01:04e9e7,0.1:01c501,0.5:fdf802")
+                        .takes_value(true)
+                        .default_value("nws"),
+                )
+                .arg(
+                    Arg::with_name("world-file")
+                        .long("world-file")
+                        .help("Also write a .pgw world file next to the PNG"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("to-mvt")
+                .about("cut a data file into Mapbox Vector Tiles for web maps")
+                .arg(
+                    Arg::with_name("file")
+                        .value_name("FILE")
+                        .help("Path or URL of the data file, optionally gzipped, or - for stdin")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .short("o")
+                        .long("output")
+                        .value_name("DIRECTORY")
+                        .help("Where to write the tiles as {z}/{x}/{y}.pbf")
+                        .takes_value(true)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("min-zoom")
+                        .long("min-zoom")
+                        .value_name("ZOOM")
+                        .help("Lowest zoom level to make tiles for")
+                        .takes_value(true)
+                        .default_value("4"),
+                )
+                .arg(
+                    Arg::with_name("max-zoom")
+                        .long("max-zoom")
+                        .value_name("ZOOM")
+                        .help("Highest zoom level to make tiles for")
+                        .takes_value(true)
+                        .default_value("10"),
+                )
+                .arg(
+                    Arg::with_name("min-rate")
+                        .long("min-rate")
+                        .value_name("RATE")
+                        .help("Leave out bins below this rate in in/hr")
+                        .takes_value(true)
+                        .default_value("0.001"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("to-gml")
+                .about("convert a data file to a GML 3.2 feature collection for OGC toolchains")
+                .arg(
+                    Arg::with_name("file")
+                        .value_name("FILE")
+                        .help("Path or URL of the data file, optionally gzipped, or - for stdin")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .short("o")
+                        .long("output")
+                        .value_name("PATH")
+                        .help("Where to write the document instead of standard output")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("min-rate")
+                        .long("min-rate")
+                        .value_name("RATE")
+                        .help("Leave out bins below this rate in in/hr")
+                        .takes_value(true)
+                        .default_value("0.001"),
+                )
+                .arg(
+                    Arg::with_name("lat-lon")
+                        .long("lat-lon")
+                        .help("Put latitude first and use EPSG:4326 instead of CRS84"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("to-shapefile")
+                .about("convert a data file to a shapefile with a .prj for desktop GIS")
+                .arg(
+                    Arg::with_name("file")
+                        .value_name("FILE")
+                        .help("Path or URL of the data file, optionally gzipped, or - for stdin")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .short("o")
+                        .long("output")
+                        .value_name("PATH")
+                        .help(
+                            "Where to write the .shp, with the other files next to it, \
+                             or - for a zip archive on standard output",
+                        )
+                        .takes_value(true)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("min-rate")
+                        .long("min-rate")
+                        .value_name("RATE")
+                        .help("Leave out bins below this rate in in/hr")
+                        .takes_value(true)
+                        .default_value("0.001"),
+                )
+                .arg(
+                    Arg::with_name("prj")
+                        .long("prj")
+                        .value_name("PATH")
+                        .help("Copy the WKT in this file to the .prj instead of writing WGS84")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("fields")
+                        .long("fields")
+                        .value_name("FIELDS")
+                        .help(
+                            "Attributes to add after the rate, from station, capture-time, \
+                             azimuth, bin-index, range, and category, or all",
+                        )
+                        .takes_value(true)
+                        .use_delimiter(true),
+                )
+                .arg(
+                    Arg::with_name("zip")
+                        .long("zip")
+                        .help("Bundle the files into one zip archive at the output path"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("info")
+                .about("print the metadata of data files without decompressing them")
+                .arg(
+                    Arg::with_name("files")
+                        .value_name("FILE")
+                        .help("Paths or URLs of the data files, optionally gzipped, or tar archives of them")
+                        .multiple(true)
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("to-geojsonseq")
+                .about("stream the bins of data files as GeoJSON text sequences, one feature per line")
+                .arg(
+                    Arg::with_name("output")
+                        .short("o")
+                        .long("output")
+                        .value_name("PATH")
+                        .help("Where to write the features instead of standard output")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("min-rate")
+                        .long("min-rate")
+                        .value_name("RATE")
+                        .help("Leave out bins below this rate in in/hr")
+                        .takes_value(true)
+                        .default_value("0.001"),
+                )
+                .arg(
+                    Arg::with_name("no-rs")
+                        .long("no-rs")
+                        .help("Leave out the record separator before each feature, for plain newline-delimited GeoJSON"),
+                )
+                .arg(
+                    Arg::with_name("files")
+                        .value_name("FILE")
+                        .help("Paths or URLs of the data files, optionally gzipped, or tar archives of them")
+                        .multiple(true)
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("notify")
+                .about("watch locations and send webhook alerts when rain is coming")
+                .arg(
+                    Arg::with_name("watch")
+                        .short("w")
+                        .long("watch")
+                        .value_name("WATCH")
+                        .help("NAME=LAT,LON for a point or NAME=LAT,LON;LAT,LON;... for a polygon")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .required(true)
+                        .allow_hyphen_values(true),
+                )
+                .arg(
+                    Arg::with_name("webhook")
+                        .short("u")
+                        .long("webhook")
+                        .value_name("URL")
+                        .help("URL to POST JSON alerts to")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .required_unless("mqtt"),
+                )
+                .arg(
+                    Arg::with_name("mqtt")
+                        .short("m")
+                        .long("mqtt")
+                        .value_name("BROKER")
+                        .help("MQTT broker to publish scans, nowcasts, and alerts to (e.g. localhost:1883)")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("mqtt-prefix")
+                        .long("mqtt-prefix")
+                        .value_name("PREFIX")
+                        .help("Prefix for MQTT topics")
+                        .takes_value(true)
+                        .default_value("threecast"),
+                )
+                .arg(
+                    Arg::with_name("mqtt-client-id")
+                        .long("mqtt-client-id")
+                        .value_name("ID")
+                        .help("Client ID to connect to the MQTT broker with, which must be unique among its clients [default: threecast-<hostname>-<pid>]")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("threshold")
+                        .short("t")
+                        .long("threshold")
+                        .value_name("THRESHOLD")
+                        .help("Rate in in/hr that triggers an exceedance alert")
+                        .takes_value(true)
+                        .default_value("0.35"),
+                )
+                .arg(
+                    Arg::with_name("cooldown")
+                        .short("c")
+                        .long("cooldown")
+                        .value_name("MINUTES")
+                        .help("Minimum time between repeated alerts of the same kind")
+                        .takes_value(true)
+                        .default_value("60"),
+                )
+                .arg(
+                    Arg::with_name("interval")
+                        .short("i")
+                        .long("interval")
+                        .value_name("SECONDS")
+                        .help("Time between checks")
+                        .takes_value(true)
+                        .default_value("180"),
+                )
+                .arg(
+                    Arg::with_name("metrics")
+                        .long("metrics")
+                        .value_name("ADDRESS")
+                        .help("Serve Prometheus metrics at /metrics on ADDRESS (e.g. 0.0.0.0:9176)")
+                        .takes_value(true),
+                ),
+        );
+    #[cfg(feature = "geoparquet")]
+    let app = app.subcommand(
+        SubCommand::with_name("to-geoparquet")
+            .about("convert data files to one GeoParquet file with a row per bin")
+            .arg(
+                Arg::with_name("output")
+                    .short("o")
+                    .long("output")
+                    .value_name("PATH")
+                    .help("Where to write the GeoParquet file")
+                    .takes_value(true)
+                    .required(true),
+            )
+            .arg(
+                Arg::with_name("min-rate")
+                    .long("min-rate")
+                    .value_name("RATE")
+                    .help("Leave out bins below this rate in in/hr")
+                    .takes_value(true),
+            )
+            .arg(
+                Arg::with_name("files")
+                    .value_name("FILE")
+                    .help("Paths or URLs of the data files, optionally gzipped, or tar archives of them")
+                    .multiple(true)
+                    .required(true),
+            ),
+    );
+    let matches = app.get_matches();
 
     if let Some(matches) = matches.subcommand_matches("collect") {
         // collect data for each station independently
@@ -172,17 +1156,471 @@ fn main() -> Result<(), Box<dyn Error>> {
             );
         }
 
+        let metrics = Arc::new(Metrics::new());
+        if let Some(address) = matches.value_of("metrics") {
+            metrics.serve(address)?;
+        }
+
+        let pipelines = Arc::new(
+            matches
+                .values_of("pipeline")
+                .into_iter()
+                .flatten()
+                .map(|path| pipeline::Pipeline::from_file(std::path::Path::new(path)))
+                .collect::<Result<Vec<_>, _>>()?,
+        );
+
         for station in stations {
+            let metrics = Arc::clone(&metrics);
+            let pipelines = Arc::clone(&pipelines);
             std::thread::spawn(move || {
-                collect_data(&station, precip_threshold);
+                collect_data(&station, precip_threshold, &metrics, &pipelines);
             });
             std::thread::sleep(std::time::Duration::from_secs(1));
         }
         loop {
             std::thread::sleep(std::time::Duration::from_secs(999));
         }
+    } else if let Some(matches) = matches.subcommand_matches("run") {
+        let path = std::path::Path::new(matches.value_of("pipeline").unwrap());
+        pipeline::Pipeline::from_file(path)?.run()?;
+    } else if let Some(matches) = matches.subcommand_matches("live") {
+        let target = match matches.value_of("station") {
+            Some(station) => {
+                let station = station.to_lowercase();
+                if !STATIONS.iter().any(|s| s.code == station) {
+                    return Err(format!("'{}' is not a valid station code", station).into());
+                }
+                live::Target::Station(station)
+            }
+            None => {
+                let parse = |name: &str| match matches.value_of(name).unwrap().parse::<f32>() {
+                    Ok(v) => Ok(v),
+                    Err(_) => Err(format!("Failed to parse {}", name)),
+                };
+                live::Target::Location(parse("latitude")?, parse("longitude")?)
+            }
+        };
+        let history = match matches.value_of("history").unwrap().parse::<usize>() {
+            Ok(h) => h,
+            Err(_) => return Err("Failed to parse history length".into()),
+        };
+        let size = match matches.value_of("size").unwrap().parse::<usize>() {
+            Ok(s) => s,
+            Err(_) => return Err("Failed to parse image size".into()),
+        };
+        let min_rate = match matches.value_of("min-rate").unwrap().parse::<f32>() {
+            Ok(r) => r,
+            Err(_) => return Err("Failed to parse minimum rate".into()),
+        };
+        live::live(live::LiveOptions {
+            target,
+            output_dir: std::path::PathBuf::from(matches.value_of("output-dir").unwrap()),
+            history,
+            size,
+            min_rate,
+            writer_options: Default::default(),
+        })?;
+    } else if let Some(matches) = matches.subcommand_matches("dump") {
+        dump::dump(
+            matches.value_of("file").unwrap(),
+            matches.is_present("preview"),
+        )?;
+    } else if let Some(matches) = matches.subcommand_matches("preview") {
+        let path = preview::preview(
+            matches.value_of("file").unwrap(),
+            matches.value_of("output").map(std::path::Path::new),
+            !matches.is_present("no-open"),
+        )?;
+        println!("{}", path.display());
+    } else if let Some(matches) = matches.subcommand_matches("validate") {
+        let fail_at = if matches.is_present("strict") {
+            validate::Severity::Warning
+        } else {
+            validate::Severity::Error
+        };
+        let mut failed = 0;
+        let mut files = Vec::new();
+        for input in matches.values_of("files").unwrap() {
+            files.extend(read_inputs(input)?);
+        }
+        for (file, data) in files {
+            let violations = validate::validate(data);
+            if violations.iter().any(|v| v.severity >= fail_at) {
+                failed += 1;
+            }
+            for violation in violations {
+                println!("{}: {}: {}", file, violation.severity, violation.message);
+            }
+        }
+        if failed > 0 {
+            return Err(format!("{} file(s) failed validation", failed).into());
+        }
+    } else if let Some(matches) = matches.subcommand_matches("diff") {
+        let differences = diff::diff(
+            read_input(matches.value_of("a").unwrap())?,
+            read_input(matches.value_of("b").unwrap())?,
+        )?;
+        for difference in differences.iter() {
+            println!("{}", difference);
+        }
+        if !differences.is_empty() {
+            return Err(format!("Found {} difference(s)", differences.len()).into());
+        }
+    } else if let Some(matches) = matches.subcommand_matches("zonal") {
+        let threshold = match matches.value_of("threshold").unwrap().parse::<f32>() {
+            Ok(t) => t,
+            Err(_) => return Err("Failed to parse threshold".into()),
+        };
+        let zones = threecast::zonal::zones_from_geojson(
+            &std::fs::read_to_string(matches.value_of("zones").unwrap())?,
+            matches.value_of("name-property").unwrap(),
+        )?;
+        let scans = read_scans(matches.values_of("files").unwrap())?;
+        let stdout = std::io::stdout();
+        let writer = std::io::BufWriter::new(stdout.lock());
+        match matches.value_of("format") {
+            Some("geojson") => zonal::write_geojson(writer, &scans, &zones, threshold)?,
+            _ => zonal::write_csv(writer, &scans, &zones, threshold)?,
+        }
+    } else if let Some(matches) = matches.subcommand_matches("stats") {
+        let mut summaries = Vec::new();
+        for input in matches.values_of("files").unwrap() {
+            for (file, data) in read_inputs(input)? {
+                match threecast::parse::parse_dpr(data) {
+                    Ok(dpr) => summaries.push(dpr.summary()),
+                    Err(e) => eprintln!("[{}] {}", file, e),
+                }
+            }
+        }
+        let rows = stats::aggregate(
+            &summaries,
+            stats::Aggregate::from_name(matches.value_of("aggregate").unwrap()).unwrap(),
+        );
+        let stdout = std::io::stdout();
+        let writer = std::io::BufWriter::new(stdout.lock());
+        match matches.value_of("format") {
+            Some("json") => stats::write_json(writer, &rows)?,
+            _ => stats::write_csv(writer, &rows)?,
+        }
+    } else if let Some(matches) = matches.subcommand_matches("to-kml") {
+        let min_rate = match matches.value_of("min-rate").unwrap().parse::<f32>() {
+            Ok(r) => r,
+            Err(_) => return Err("Failed to parse minimum rate".into()),
+        };
+        let mut scans = read_scans(matches.values_of("files").unwrap())?;
+        scans.sort_by_key(|scan| scan.capture_time);
+        let mut sink = threecast::kml::KmlSink::new(Vec::new());
+        for scan in scans.iter() {
+            threecast::output::write_scan_filtered(
+                scan,
+                &mut sink,
+                &threecast::filter::MinRate(min_rate),
+            )?;
+        }
+        let kml = sink.finish_document()?;
+        let path = std::path::Path::new(matches.value_of("output").unwrap());
+        if path
+            .extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case("kmz"))
+        {
+            threecast::kml::write_kmz(std::fs::File::create(path)?, &kml)?;
+        } else {
+            std::fs::write(path, kml)?;
+        }
+    } else if let Some(matches) = matches.subcommand_matches("to-csv") {
+        let min_rate = match matches.value_of("min-rate").unwrap().parse::<f32>() {
+            Ok(r) => r,
+            Err(_) => return Err("Failed to parse minimum rate".into()),
+        };
+        let writer: Box<dyn std::io::Write> = match matches.value_of("output") {
+            Some(path) => Box::new(std::fs::File::create(path)?),
+            None => Box::new(std::io::stdout()),
+        };
+        let mut sink = threecast::output::CsvSink::new(std::io::BufWriter::new(writer));
+        if matches.is_present("tsv") {
+            sink = sink.tab_separated();
+        }
+        for input in matches.values_of("files").unwrap() {
+            for (file, data) in read_inputs(input)? {
+                match threecast::parse::parse_dpr(data) {
+                    Ok(dpr) => threecast::output::write_scan_filtered(
+                        &dpr,
+                        &mut sink,
+                        &threecast::filter::MinRate(min_rate),
+                    )?,
+                    Err(e) => eprintln!("[{}] {}", file, e),
+                }
+            }
+        }
+    } else if let Some(matches) = matches.subcommand_matches("to-geotiff") {
+        let resolution = match matches.value_of("resolution").unwrap().parse::<f32>() {
+            Ok(r) if r > 0. => r,
+            _ => return Err("Resolution must be a positive number of degrees".into()),
+        };
+        let dpr = threecast::parse::parse_dpr(read_input(matches.value_of("file").unwrap())?)?;
+        let extent = match matches.value_of("bbox") {
+            Some(bbox) => parse_bbox(bbox)?,
+            None => dpr.coverage_extent(),
+        };
+        let raster = dpr.rasterize(resolution, &extent);
+        let file = std::fs::File::create(matches.value_of("output").unwrap())?;
+        let writer = std::io::BufWriter::new(file);
+        if matches.is_present("cog") {
+            threecast::geotiff::write_cog(&raster, writer)?;
+        } else {
+            threecast::geotiff::write_geotiff(&raster, writer)?;
+        }
+    } else if let Some(matches) = matches.subcommand_matches("to-hdf5") {
+        let dpr = threecast::parse::parse_dpr(read_input(matches.value_of("file").unwrap())?)?;
+        let file = std::fs::File::create(matches.value_of("output").unwrap())?;
+        threecast::hdf5::write_hdf5(&dpr, std::io::BufWriter::new(file))?;
+    } else if let Some(matches) = matches.subcommand_matches("to-grib2") {
+        let resolution = match matches.value_of("resolution").unwrap().parse::<f32>() {
+            Ok(r) if r > 0. => r,
+            _ => return Err("Resolution must be a positive number of degrees".into()),
+        };
+        let dpr = threecast::parse::parse_dpr(read_input(matches.value_of("file").unwrap())?)?;
+        let extent = match matches.value_of("bbox") {
+            Some(bbox) => parse_bbox(bbox)?,
+            None => dpr.coverage_extent(),
+        };
+        let raster = dpr.rasterize(resolution, &extent);
+        let file = std::fs::File::create(matches.value_of("output").unwrap())?;
+        threecast::grib2::write_grib2(&dpr, &raster, std::io::BufWriter::new(file))?;
+    } else if let Some(matches) = matches.subcommand_matches("render") {
+        let resolution = match matches.value_of("resolution").unwrap().parse::<f32>() {
+            Ok(r) if r > 0. => r,
+            _ => return Err("Resolution must be a positive number of degrees".into()),
+        };
+        let colormap = threecast::render::Colormap::parse(matches.value_of("colormap").unwrap())?;
+        let dpr = threecast::parse::parse_dpr(read_input(matches.value_of("file").unwrap())?)?;
+        let extent = match matches.value_of("bbox") {
+            Some(bbox) => parse_bbox(bbox)?,
+            None => dpr.coverage_extent(),
+        };
+        let raster = dpr.rasterize(resolution, &extent);
+        let path = std::path::Path::new(matches.value_of("output").unwrap());
+        std::fs::write(path, threecast::render::render_png(&raster, &colormap)?)?;
+        if matches.is_present("world-file") {
+            std::fs::write(
+                path.with_extension("pgw"),
+                threecast::render::world_file(&raster),
+            )?;
+        }
+    } else if let Some(matches) = matches.subcommand_matches("to-mvt") {
+        let zoom = |name: &str| match matches.value_of(name).unwrap().parse::<u8>() {
+            Ok(z) if z <= 24 => Ok(z),
+            _ => Err(format!("Failed to parse {}", name)),
+        };
+        let (min_zoom, max_zoom) = (zoom("min-zoom")?, zoom("max-zoom")?);
+        if min_zoom > max_zoom {
+            return Err("Minimum zoom is above maximum zoom".into());
+        }
+        let min_rate = match matches.value_of("min-rate").unwrap().parse::<f32>() {
+            Ok(r) => r,
+            Err(_) => return Err("Failed to parse minimum rate".into()),
+        };
+        let dpr = threecast::parse::parse_dpr(read_input(matches.value_of("file").unwrap())?)?;
+        let directory = std::path::Path::new(matches.value_of("output").unwrap());
+        let tiles = dpr.vector_tiles(&threecast::filter::MinRate(min_rate), min_zoom..=max_zoom);
+        for (tile, data) in tiles {
+            let path = directory.join(tile.z.to_string()).join(tile.x.to_string());
+            std::fs::create_dir_all(&path)?;
+            std::fs::write(path.join(format!("{}.pbf", tile.y)), data)?;
+        }
+    } else if let Some(matches) = matches.subcommand_matches("to-gml") {
+        let min_rate = match matches.value_of("min-rate").unwrap().parse::<f32>() {
+            Ok(r) => r,
+            Err(_) => return Err("Failed to parse minimum rate".into()),
+        };
+        let dpr = threecast::parse::parse_dpr(read_input(matches.value_of("file").unwrap())?)?;
+        let writer: Box<dyn std::io::Write> = match matches.value_of("output") {
+            Some(path) => Box::new(std::fs::File::create(path)?),
+            None => Box::new(std::io::stdout()),
+        };
+        let mut options = threecast::output::WriterOptions::default();
+        if matches.is_present("lat-lon") {
+            options.axis_order = threecast::output::AxisOrder::LatLon;
+        }
+        let mut sink =
+            threecast::gml::GmlSink::new(std::io::BufWriter::new(writer)).with_options(options);
+        threecast::output::write_scan_filtered(
+            &dpr,
+            &mut sink,
+            &threecast::filter::MinRate(min_rate),
+        )?;
+    } else if let Some(matches) = matches.subcommand_matches("to-shapefile") {
+        let min_rate = match matches.value_of("min-rate").unwrap().parse::<f32>() {
+            Ok(r) => r,
+            Err(_) => return Err("Failed to parse minimum rate".into()),
+        };
+        let mut fields = Vec::new();
+        for name in matches.values_of("fields").into_iter().flatten() {
+            match (name, threecast::shapefile::DbfField::from_name(name)) {
+                ("all", _) => fields.extend(threecast::shapefile::DbfField::OPTIONAL),
+                (_, Some(field)) => fields.push(field),
+                (_, None) => return Err(format!("Unknown shapefile field {}", name).into()),
+            }
+        }
+        let dpr = threecast::parse::parse_dpr(read_input(matches.value_of("file").unwrap())?)?;
+        let mut sink = threecast::shapefile::ShapefileSink::new().with_fields(&fields);
+        if let Some(path) = matches.value_of("prj") {
+            sink = sink.with_prj(&std::fs::read_to_string(path)?);
+        }
+        threecast::output::write_scan_filtered(
+            &dpr,
+            &mut sink,
+            &threecast::filter::MinRate(min_rate),
+        )?;
+        let output = matches.value_of("output").unwrap();
+        if output == "-" {
+            // zip needs to seek, so build the archive before writing it out
+            let name = format!(
+                "{}-{}",
+                dpr.station_code.to_uppercase(),
+                dpr.capture_time.format("%Y%m%dT%H%M%SZ")
+            );
+            let zip = sink.write_zip(std::io::Cursor::new(Vec::new()), &name)?;
+            std::io::Write::write_all(&mut std::io::stdout(), &zip.into_inner())?;
+        } else if matches.is_present("zip") {
+            let path = std::path::Path::new(output);
+            let name = path.file_stem().unwrap_or_default().to_string_lossy();
+            sink.write_zip(std::fs::File::create(path)?, &name)?;
+        } else {
+            sink.save(std::path::Path::new(output))?;
+        }
+    } else if let Some(matches) = matches.subcommand_matches("info") {
+        println!("file\tstation\tcapture time\tscan\tmode\tprecip detected\tmax rate");
+        for input in matches.values_of("files").unwrap() {
+            for (file, data) in read_inputs(input)? {
+                match threecast::parse::parse_dpr_header(&data) {
+                    Ok(header) => println!(
+                        "{}\t{}\t{}\t{}\t{:?}\t{}\t{}",
+                        file,
+                        header.station_code,
+                        header.capture_time.format("%Y-%m-%dT%H:%M:%SZ"),
+                        header.scan_number,
+                        header.operational_mode,
+                        header.precip_detected,
+                        header.max_rate
+                    ),
+                    Err(e) => eprintln!("[{}] {}", file, e),
+                }
+            }
+        }
+    } else if let Some(matches) = matches.subcommand_matches("to-geojsonseq") {
+        let min_rate = match matches.value_of("min-rate").unwrap().parse::<f32>() {
+            Ok(r) => r,
+            Err(_) => return Err("Failed to parse minimum rate".into()),
+        };
+        let writer: Box<dyn std::io::Write> = match matches.value_of("output") {
+            Some(path) => Box::new(std::fs::File::create(path)?),
+            None => Box::new(std::io::stdout()),
+        };
+        let mut sink = threecast::output::GeoJsonSeqSink::new(std::io::BufWriter::new(writer));
+        if matches.is_present("no-rs") {
+            sink = sink.without_record_separators();
+        }
+        // features go out as soon as each file is parsed, so nothing but the
+        // current scan is ever held in memory
+        for input in matches.values_of("files").unwrap() {
+            for (file, data) in read_inputs(input)? {
+                match threecast::parse::parse_dpr(data) {
+                    Ok(dpr) => threecast::output::write_scan_filtered(
+                        &dpr,
+                        &mut sink,
+                        &threecast::filter::MinRate(min_rate),
+                    )?,
+                    Err(e) => eprintln!("[{}] {}", file, e),
+                }
+            }
+        }
+    } else if let Some(matches) = matches.subcommand_matches("to-geoparquet") {
+        #[cfg(feature = "geoparquet")]
+        {
+            let filter: Box<dyn threecast::filter::BinFilter> = match matches.value_of("min-rate") {
+                Some(rate) => match rate.parse::<f32>() {
+                    Ok(r) => Box::new(threecast::filter::MinRate(r)),
+                    Err(_) => return Err("Failed to parse minimum rate".into()),
+                },
+                None => Box::new(|_: &threecast::output::BinRef| true),
+            };
+            let file = std::fs::File::create(matches.value_of("output").unwrap())?;
+            let mut sink =
+                threecast::geoparquet::GeoParquetSink::new(std::io::BufWriter::new(file))?;
+            // convert one file at a time so that big archives don't have to
+            // fit in memory
+            for input in matches.values_of("files").unwrap() {
+                for (file, data) in read_inputs(input)? {
+                    match threecast::parse::parse_dpr(data) {
+                        Ok(dpr) => threecast::output::write_scan_filtered(
+                            &dpr,
+                            &mut sink,
+                            filter.as_ref(),
+                        )?,
+                        Err(e) => eprintln!("[{}] {}", file, e),
+                    }
+                }
+            }
+            sink.finish_file()?;
+        }
+        #[cfg(not(feature = "geoparquet"))]
+        let _ = matches;
+    } else if let Some(matches) = matches.subcommand_matches("notify") {
+        let threshold = match matches.value_of("threshold").unwrap().parse::<f32>() {
+            Ok(t) => t,
+            Err(_) => return Err("Failed to parse threshold".into()),
+        };
+        let cooldown = match matches.value_of("cooldown").unwrap().parse::<u64>() {
+            Ok(c) => c * 60,
+            Err(_) => return Err("Failed to parse cooldown".into()),
+        };
+        let interval = match matches.value_of("interval").unwrap().parse::<u64>() {
+            Ok(i) => i,
+            Err(_) => return Err("Failed to parse interval".into()),
+        };
+        let watches = matches
+            .values_of("watch")
+            .unwrap()
+            .map(|w| parse_watch(w, threshold))
+            .collect::<Result<Vec<Watch>, _>>()?;
+        let webhooks = matches
+            .values_of("webhook")
+            .map(|w| w.map(|w| w.to_string()).collect())
+            .unwrap_or_default();
+        let mqtt = matches.value_of("mqtt").map(|broker| MqttConfig {
+            broker: broker.to_string(),
+            prefix: matches.value_of("mqtt-prefix").unwrap().to_string(),
+            client_id: matches
+                .value_of("mqtt-client-id")
+                .map_or_else(default_client_id, String::from),
+        });
+        let metrics = Arc::new(Metrics::new());
+        if let Some(address) = matches.value_of("metrics") {
+            metrics.serve(address)?;
+        }
+        notify(watches, webhooks, mqtt, cooldown, interval, &metrics);
     } else if let Some(_matches) = matches.subcommand_matches("test") {
         unimplemented!();
     }
     Ok(())
 }
+
+#[test]
+fn test_parse_watch() {
+    let watch = parse_watch("home=43.9, -70.3", 0.1).unwrap();
+    assert_eq!(watch.name, "home");
+    assert!(matches!(watch.area, WatchArea::Point(lat, lon) if lat == 43.9 && lon == -70.3));
+    let watch = parse_watch("field=43,-70;44,-70;44,-71", 0.1).unwrap();
+    assert!(matches!(watch.area, WatchArea::Polygon(ref v) if v.len() == 3));
+    // a bad token is reported rather than dropped
+    let error = parse_watch("home=43.9,-70.3x", 0.1).unwrap_err();
+    assert!(error
+        .to_string()
+        .starts_with("Invalid number '-70.3x' in watch 'home'"));
+    let error = parse_watch("home=43.9,,-70.3", 0.1).unwrap_err();
+    assert!(error
+        .to_string()
+        .starts_with("Invalid number '' in watch 'home'"));
+}
@@ -0,0 +1,751 @@
+//! Declarative pipelines for `tcdt run`
+//!
+//! A pipeline file is written in TOML and describes where scans come from,
+//! which ones to keep, how to transform them, and where to send the results.
+//! YAML isn't supported. Results can go to local files, S3, MQTT, a webhook,
+//! or, with the `gdal-cli` feature, PostGIS. For example:
+//!
+//! ```toml
+//! [source]
+//! stations = ["KGYX", "KBOX"]
+//! watch = true
+//!
+//! [filter]
+//! min_precip_fraction = 0.05
+//! min_rate = 0.01
+//!
+//! [[transform]]
+//! type = "max-range"
+//! range = 150.0
+//!
+//! [[transform]]
+//! type = "resample"
+//! width = 256
+//! height = 256
+//!
+//! [[sink]]
+//! type = "grid-csv"
+//! directory = "./grids"
+//!
+//! [[sink]]
+//! type = "geojson"
+//! directory = "./bins"
+//! split = "category"
+//!
+//! [[sink]]
+//! type = "s3"
+//! prefix = "s3://my-bucket/raw"
+//!
+//! [[sink]]
+//! type = "mqtt"
+//! broker = "localhost:1883"
+//! client_id = "threecast-pipeline"
+//! ```
+
+use serde::Deserialize;
+use std::error::Error;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+use threecast::filter::{BinFilter, BoundingBox, DropMissing, MinRate, MissingData};
+use threecast::gml::GmlSink;
+use threecast::mqtt::{default_client_id, MqttClient};
+use threecast::output::{
+    default_properties, properties_with_beam_height, write_scan_capped, write_scan_filtered,
+    GeoJsonSink, OutputSink, PolarCsvSink, Split, SplitSink, Truncation, WriterOptions,
+};
+use threecast::parse::{parse_dpr, parse_dpr_many, GridData, PrecipRate};
+use threecast::schedule::ScanCadence;
+use threecast::util::{read_inputs, NamedInput};
+
+/// Number of local files to read and parse at a time
+const FILE_BATCH_SIZE: usize = 64;
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Pipeline {
+    source: Source,
+    #[serde(default)]
+    filter: Filter,
+    #[serde(default)]
+    transform: Vec<Transform>,
+    sink: Vec<Sink>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Source {
+    /// Stations to fetch the latest scan from
+    #[serde(default)]
+    stations: Vec<String>,
+    /// Local data files to read, which may be gzipped or tar archives of
+    /// data files
+    #[serde(default)]
+    files: Vec<PathBuf>,
+    /// Keep fetching new scans from the stations as they're published
+    #[serde(default)]
+    watch: bool,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct Filter {
+    /// Skip scans where less than this fraction of bins has nonzero precip
+    min_precip_fraction: Option<f32>,
+    /// Skip scans where the radar didn't detect precipitation
+    #[serde(default)]
+    precip_detected: bool,
+    /// Only write bins with at least this rate to the geojson and polar-csv
+    /// sinks
+    min_rate: Option<f32>,
+    /// Only write bins inside `[south, west, north, east]` to the geojson and
+    /// polar-csv sinks
+    bbox: Option<[f32; 4]>,
+    /// Whether to drop, zero-fill, or flag bins without data
+    #[serde(default)]
+    missing: MissingData,
+    /// Write at most this many bins per scan to the geojson and polar-csv
+    /// sinks, keeping the highest rates
+    max_features: Option<usize>,
+}
+
+impl Filter {
+    /// Write the bins that pass the filters through `sink`, capped at
+    /// `max_features` if it's set
+    fn write_bins(
+        &self,
+        dpr: &PrecipRate,
+        sink: &mut dyn OutputSink,
+    ) -> Result<Option<Truncation>, Box<dyn Error>> {
+        match self.max_features {
+            Some(max) => write_scan_capped(dpr, sink, &self.bin_filters(), max),
+            None => write_scan_filtered(dpr, sink, &self.bin_filters()).map(|_| None),
+        }
+    }
+
+    fn bin_filters(&self) -> Vec<Box<dyn BinFilter>> {
+        let mut filters: Vec<Box<dyn BinFilter>> = Vec::new();
+        if self.missing == MissingData::Drop {
+            filters.push(Box::new(DropMissing));
+        }
+        if let Some(rate) = self.min_rate {
+            filters.push(Box::new(MinRate(rate)));
+        }
+        if let Some([south, west, north, east]) = self.bbox {
+            filters.push(Box::new(BoundingBox {
+                south,
+                west,
+                north,
+                east,
+            }));
+        }
+        filters
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case", deny_unknown_fields)]
+enum Transform {
+    /// Drop bins farther than `range` kilometers from the station
+    MaxRange { range: f32 },
+    /// Set rates below `rate` in/hr to zero
+    MinRate { rate: f32 },
+    /// Sample the scan onto an equirectangular grid for the grid sinks
+    Resample { width: usize, height: usize },
+    /// Sample the scan onto the HRAP grid out to `radius` kilometers for the
+    /// grid sinks
+    ResampleHrap {
+        #[serde(default = "default_hrap_radius")]
+        radius: f32,
+    },
+    /// Replace each rate with the median of its neighbors within `radius`
+    /// bins and radials
+    Median { radius: usize },
+    /// Smooth the rates with a Gaussian kernel that has a standard deviation
+    /// of `sigma` bins and radials
+    Gaussian { sigma: f32 },
+    /// Fill runs of up to `max_radials` radials that are absent or have no
+    /// data by interpolating between their neighbors. This always happens
+    /// before the other transforms and before missing bins are zeroed.
+    FillGaps {
+        #[serde(default = "default_max_fill_radials")]
+        max_radials: usize,
+    },
+}
+
+fn default_hrap_radius() -> f32 {
+    230.
+}
+
+fn default_max_fill_radials() -> usize {
+    2
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case", deny_unknown_fields)]
+enum Sink {
+    /// Copy the original data file into `directory`
+    Raw { directory: PathBuf },
+    /// Append a JSON summary line for each scan to `path`
+    Summary { path: PathBuf },
+    /// Write the resampled grid as CSV into `directory`
+    GridCsv { directory: PathBuf },
+    /// Write the resampled grid as CF NetCDF into `directory`
+    Netcdf { directory: PathBuf },
+    /// Write the polar data of each scan as HDF5 into `directory`
+    Hdf5 { directory: PathBuf },
+    /// Append the resampled grid of each scan as a time slice of the Zarr
+    /// store at `path`
+    Zarr { path: PathBuf },
+    /// Write every bin as a GeoJSON polygon into `directory`, optionally
+    /// with the height of the beam above the ground
+    Geojson {
+        directory: PathBuf,
+        #[serde(default)]
+        beam_height: bool,
+        /// Write one file per intensity class or per chunk of bins, plus an
+        /// index of the parts
+        split: Option<Split>,
+        /// Axis order and CRS tagging for consumers that need them
+        #[serde(default)]
+        options: WriterOptions,
+    },
+    /// Write every bin as a GML 3.2 polygon into `directory`, with the
+    /// same properties and options as the GeoJSON sink
+    Gml {
+        directory: PathBuf,
+        #[serde(default)]
+        beam_height: bool,
+        split: Option<Split>,
+        #[serde(default)]
+        options: WriterOptions,
+    },
+    /// Write every bin as an `azimuth_deg,range_km,rate` CSV row into
+    /// `directory`
+    PolarCsv {
+        directory: PathBuf,
+        split: Option<Split>,
+    },
+    /// Write every bin as a FlatGeobuf polygon into `directory`
+    #[cfg(feature = "fgb")]
+    Flatgeobuf {
+        directory: PathBuf,
+        split: Option<Split>,
+    },
+    /// Convert the bins, or the resampled grid if `raster` is set, to any
+    /// format GDAL has a driver for, into `directory` with `extension`. This
+    /// runs the GDAL command-line tools, which must be installed.
+    #[cfg(feature = "gdal-cli")]
+    Gdal {
+        directory: PathBuf,
+        driver: String,
+        extension: String,
+        #[serde(default)]
+        raster: bool,
+    },
+    /// Append the filtered bins of each scan to `table` in a PostGIS
+    /// database, given as a libpq connection string. This runs `ogr2ogr`,
+    /// which must be installed with its PostgreSQL driver.
+    #[cfg(feature = "gdal-cli")]
+    Postgis { connection: String, table: String },
+    /// Upload the original data file under an `s3://bucket/prefix`, signed
+    /// with the AWS credentials in the environment
+    S3 { prefix: String },
+    /// Publish a summary of each scan to an MQTT broker
+    Mqtt {
+        broker: String,
+        #[serde(default = "default_mqtt_prefix")]
+        prefix: String,
+        /// Defaults to one with the host name and process ID, so that
+        /// pipelines don't knock each other off the broker
+        #[serde(default = "default_client_id")]
+        client_id: String,
+    },
+    /// POST a summary of each scan to a URL
+    Webhook { url: String },
+}
+
+fn default_mqtt_prefix() -> String {
+    String::from("threecast")
+}
+
+/// A scan as it moves through the pipeline
+struct Scan {
+    data: Vec<u8>,
+    dpr: PrecipRate,
+    grid: Option<GridData>,
+}
+
+impl Pipeline {
+    pub fn from_file(path: &Path) -> Result<Self, Box<dyn Error>> {
+        if matches!(
+            path.extension().and_then(|e| e.to_str()),
+            Some("yaml" | "yml")
+        ) {
+            return Err(format!(
+                "Pipeline '{}' looks like YAML, but pipelines are written in TOML",
+                path.display()
+            )
+            .into());
+        }
+        let pipeline: Pipeline = toml::from_str(&std::fs::read_to_string(path)?)?;
+        if pipeline.source.stations.is_empty() && pipeline.source.files.is_empty() {
+            return Err("Pipeline source must have at least one station or file".into());
+        }
+        if pipeline.sink.is_empty() {
+            return Err("Pipeline must have at least one sink".into());
+        }
+        Ok(pipeline)
+    }
+
+    /// Parse and process a batch of named data files, printing errors
+    /// rather than stopping
+    fn process_batch(&self, files: Vec<NamedInput>) {
+        let (names, data): (Vec<String>, Vec<Vec<u8>>) = files.into_iter().unzip();
+        let parsed = parse_dpr_many(data.clone());
+        for ((name, data), dpr) in names.iter().zip(data).zip(parsed) {
+            let result = match dpr {
+                Ok(dpr) => self.process(data, dpr),
+                Err(e) => Err(e.into()),
+            };
+            if let Err(e) = result {
+                println!("[{}] {}", name, e);
+            }
+        }
+    }
+
+    /// Run the pipeline on every source once, or forever if the source is
+    /// watching stations
+    pub fn run(self) -> Result<(), Box<dyn Error>> {
+        // parse local files in batches so that large archives don't have to
+        // fit in memory all at once. Tar archives are expanded into their
+        // members, so one archive may fill several batches.
+        let mut pending = Vec::new();
+        for file in self.source.files.iter() {
+            pending.extend(read_inputs(&file.to_string_lossy())?);
+            while pending.len() >= FILE_BATCH_SIZE {
+                let rest = pending.split_off(FILE_BATCH_SIZE);
+                self.process_batch(std::mem::replace(&mut pending, rest));
+            }
+        }
+        self.process_batch(pending);
+        let mut cadences: Vec<ScanCadence> = self
+            .source
+            .stations
+            .iter()
+            .map(|_| ScanCadence::new())
+            .collect();
+        let mut last_capture_times = vec![None; self.source.stations.len()];
+        loop {
+            for (idx, station) in self.source.stations.iter().enumerate() {
+                match threecast::net::get_data_by_station(station, "last")
+                    .and_then(|data| Ok((data.clone(), parse_dpr(data)?)))
+                {
+                    Ok((data, dpr)) => {
+                        cadences[idx].observe(dpr.capture_time, &dpr.operational_mode);
+                        if last_capture_times[idx] == Some(dpr.capture_time) {
+                            continue;
+                        }
+                        last_capture_times[idx] = Some(dpr.capture_time);
+                        if let Err(e) = self.process(data, dpr) {
+                            println!("[{}] {}", station, e);
+                        }
+                    }
+                    Err(e) => println!("[{}] failed to get data: {}", station, e),
+                }
+            }
+            if !self.source.watch || self.source.stations.is_empty() {
+                return Ok(());
+            }
+            let now = chrono::Utc::now().naive_utc();
+            let delay = cadences
+                .iter()
+                .map(|c| c.next_fetch_delay(now))
+                .min()
+                .unwrap_or_default();
+            std::thread::sleep(delay);
+        }
+    }
+
+    /// Run one scan through the filters, transforms, and sinks of this
+    /// pipeline
+    pub fn process(&self, data: Vec<u8>, dpr: PrecipRate) -> Result<(), Box<dyn Error>> {
+        if self.filter.precip_detected && !dpr.precip_detected {
+            return Ok(());
+        }
+        if let Some(min) = self.filter.min_precip_fraction {
+            if dpr.precip_fraction() < min {
+                return Ok(());
+            }
+        }
+        let mut dpr = dpr;
+        for transform in self.transform.iter() {
+            if let Transform::FillGaps { max_radials } = transform {
+                dpr = dpr.fill_radial_gaps(*max_radials);
+            }
+        }
+        let mut scan = Scan {
+            data,
+            dpr,
+            grid: None,
+        };
+        if self.filter.missing == MissingData::Zero {
+            scan.dpr.fill_missing(0.);
+        }
+        for transform in self.transform.iter() {
+            match transform {
+                Transform::MaxRange { range } => scan.dpr.crop_range(*range),
+                Transform::MinRate { rate } => scan.dpr.clip_below(*rate),
+                Transform::Resample { width, height } => {
+                    scan.grid = Some(scan.dpr.sample_radials_to_equirectangular(*height, *width))
+                }
+                Transform::ResampleHrap { radius } => {
+                    scan.grid = Some(scan.dpr.sample_radials_to_hrap(*radius).1)
+                }
+                Transform::Median { radius } => scan.dpr.median_filter(*radius),
+                Transform::Gaussian { sigma } => scan.dpr.gaussian_smooth(*sigma),
+                Transform::FillGaps { .. } => {}
+            }
+        }
+        for sink in self.sink.iter() {
+            write_to_sink(sink, &scan, &self.filter)?;
+        }
+        Ok(())
+    }
+}
+
+/// Write the filtered bins of a scan through the sinks that `make_sink`
+/// makes, into `directory/{stem}.{extension}`. If `split` is set, write one
+/// `directory/{stem}-{part}.{extension}` per part instead, plus a
+/// `{stem}.index.json` manifest of the parts and their extents so that
+/// consumers can page in only the parts they need.
+fn write_bin_files<S, F>(
+    scan: &Scan,
+    filter: &Filter,
+    directory: &Path,
+    extension: &'static str,
+    split: Option<Split>,
+    make_sink: F,
+) -> Result<(), Box<dyn Error>>
+where
+    S: OutputSink + 'static,
+    F: Fn(BufWriter<File>) -> S + 'static,
+{
+    let stem = file_stem(&scan.dpr);
+    let split = match split {
+        Some(split) => split,
+        None => {
+            let file = File::create(directory.join(format!("{}.{}", stem, extension)))?;
+            filter.write_bins(&scan.dpr, &mut make_sink(BufWriter::new(file)))?;
+            return Ok(());
+        }
+    };
+    let part_file = move |stem: &str, part: &str| format!("{}-{}.{}", stem, part, extension);
+    let mut sink = {
+        let (directory, stem) = (directory.to_path_buf(), stem.clone());
+        SplitSink::new(split, move |part| {
+            let file = File::create(directory.join(part_file(&stem, part)))?;
+            Ok(Box::new(make_sink(BufWriter::new(file))) as Box<dyn OutputSink>)
+        })
+    };
+    let truncation = filter.write_bins(&scan.dpr, &mut sink)?;
+    let parts: Vec<serde_json::Value> = sink
+        .summaries()
+        .iter()
+        .map(|part| {
+            serde_json::json!({
+                "file": part_file(&stem, &part.name),
+                "name": part.name,
+                "bins": part.bins,
+                "bbox": part.bbox,
+            })
+        })
+        .collect();
+    std::fs::write(
+        directory.join(format!("{}.index.json", stem)),
+        serde_json::to_string_pretty(&serde_json::json!({
+            "parts": parts,
+            "truncated": truncation,
+        }))?,
+    )?;
+    Ok(())
+}
+
+/// File name stem shared by all file sinks, e.g. `KGYX-2022-01-01T00:00:00Z-01`
+fn file_stem(dpr: &PrecipRate) -> String {
+    format!(
+        "{}-{}-{:0>2}",
+        dpr.station_code.to_uppercase(),
+        dpr.capture_time.format("%Y-%m-%dT%H:%M:%SZ"),
+        dpr.scan_number
+    )
+}
+
+fn write_to_sink(sink: &Sink, scan: &Scan, filter: &Filter) -> Result<(), Box<dyn Error>> {
+    match sink {
+        Sink::Raw { directory } => {
+            std::fs::write(
+                directory.join(format!("{}.nexrad", file_stem(&scan.dpr))),
+                &scan.data,
+            )?;
+        }
+        Sink::Summary { path } => {
+            use std::io::Write;
+            let mut file = std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)?;
+            writeln!(file, "{}", serde_json::to_string(&scan.dpr.summary())?)?;
+        }
+        Sink::GridCsv { directory } => {
+            let grid = match &scan.grid {
+                Some(g) => g,
+                None => return Err("The grid-csv sink needs a resample transform".into()),
+            };
+            let mut csv = String::from("latitude,longitude,precip_rate\n");
+            for (coords, rate) in grid.iter().flatten() {
+                csv.push_str(&format!(
+                    "{},{},{}\n",
+                    coords[0] as f32 / 10000.,
+                    coords[1] as f32 / 10000.,
+                    rate
+                ));
+            }
+            std::fs::write(directory.join(format!("{}.csv", file_stem(&scan.dpr))), csv)?;
+        }
+        Sink::Netcdf { directory } => {
+            let grid = match &scan.grid {
+                Some(g) => g,
+                None => return Err("The netcdf sink needs a resample transform".into()),
+            };
+            let file = File::create(directory.join(format!("{}.nc", file_stem(&scan.dpr))))?;
+            threecast::netcdf::write_netcdf(&scan.dpr, grid, BufWriter::new(file))?;
+        }
+        Sink::Hdf5 { directory } => {
+            let file = File::create(directory.join(format!("{}.h5", file_stem(&scan.dpr))))?;
+            threecast::hdf5::write_hdf5(&scan.dpr, BufWriter::new(file))?;
+        }
+        Sink::Zarr { path } => {
+            let grid = match &scan.grid {
+                Some(g) => g,
+                None => return Err("The zarr sink needs a resample transform".into()),
+            };
+            threecast::zarr::append_zarr(&scan.dpr, grid, path)?;
+        }
+        Sink::Geojson {
+            directory,
+            beam_height,
+            split,
+            options,
+        } => {
+            let (beam_height, options) = (*beam_height, *options);
+            write_bin_files(scan, filter, directory, "geojson", *split, move |writer| {
+                GeoJsonSink::with_properties(
+                    writer,
+                    if beam_height {
+                        properties_with_beam_height
+                    } else {
+                        default_properties
+                    },
+                )
+                .with_options(options)
+            })?;
+        }
+        Sink::Gml {
+            directory,
+            beam_height,
+            split,
+            options,
+        } => {
+            let (beam_height, options) = (*beam_height, *options);
+            write_bin_files(scan, filter, directory, "gml", *split, move |writer| {
+                GmlSink::with_properties(
+                    writer,
+                    if beam_height {
+                        properties_with_beam_height
+                    } else {
+                        default_properties
+                    },
+                )
+                .with_options(options)
+            })?;
+        }
+        Sink::PolarCsv { directory, split } => {
+            write_bin_files(
+                scan,
+                filter,
+                directory,
+                "polar.csv",
+                *split,
+                PolarCsvSink::new,
+            )?;
+        }
+        #[cfg(feature = "fgb")]
+        Sink::Flatgeobuf { directory, split } => {
+            write_bin_files(
+                scan,
+                filter,
+                directory,
+                "fgb",
+                *split,
+                threecast::fgb::FlatGeobufSink::new,
+            )?;
+        }
+        #[cfg(feature = "gdal-cli")]
+        Sink::Gdal {
+            directory,
+            driver,
+            extension,
+            raster,
+        } => {
+            let path = directory.join(format!("{}.{}", file_stem(&scan.dpr), extension));
+            if *raster {
+                let grid = match &scan.grid {
+                    Some(g) => g,
+                    None => return Err("A raster gdal sink needs a resample transform".into()),
+                };
+                threecast::gdal_cli::write_raster_with_gdal(grid, &path, driver)?;
+            } else {
+                threecast::gdal_cli::write_vector_with_gdal(
+                    &scan.dpr,
+                    &filter.bin_filters(),
+                    &path,
+                    driver,
+                )?;
+            }
+        }
+        #[cfg(feature = "gdal-cli")]
+        Sink::Postgis { connection, table } => {
+            threecast::gdal_cli::append_to_postgis(
+                &scan.dpr,
+                &filter.bin_filters(),
+                connection,
+                table,
+            )?;
+        }
+        Sink::S3 { prefix } => {
+            threecast::s3::put_object(
+                &format!(
+                    "{}/{}.nexrad",
+                    prefix.trim_end_matches('/'),
+                    file_stem(&scan.dpr)
+                ),
+                scan.data.clone(),
+            )?;
+        }
+        Sink::Mqtt {
+            broker,
+            prefix,
+            client_id,
+        } => {
+            let mut client = MqttClient::connect(broker, client_id)?;
+            client.publish(
+                &format!("{}/{}/scan", prefix, scan.dpr.station_code.to_uppercase()),
+                serde_json::to_string(&scan.dpr.summary())?.as_bytes(),
+                true,
+            )?;
+        }
+        Sink::Webhook { url } => {
+            reqwest::blocking::Client::new()
+                .post(url)
+                .header("Content-Type", "application/json")
+                .body(serde_json::to_string(&scan.dpr.summary())?)
+                .send()?
+                .error_for_status()?;
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_parse_pipeline() {
+    let pipeline: Pipeline = toml::from_str(
+        r#"
+        [source]
+        stations = ["KGYX"]
+
+        [[transform]]
+        type = "resample"
+        width = 64
+        height = 64
+
+        [[sink]]
+        type = "grid-csv"
+        directory = "."
+
+        [[sink]]
+        type = "geojson"
+        directory = "."
+        split = "category"
+
+        [[sink]]
+        type = "polar-csv"
+        directory = "."
+        split = { chunks = 1000 }
+
+        [[sink]]
+        type = "netcdf"
+        directory = "."
+
+        [[sink]]
+        type = "gml"
+        directory = "."
+        options = { axis_order = "lat-lon" }
+
+        [[sink]]
+        type = "mqtt"
+        broker = "localhost:1883"
+
+        [[sink]]
+        type = "s3"
+        prefix = "s3://bucket/raw/"
+        "#,
+    )
+    .unwrap();
+    assert_eq!(pipeline.source.stations, vec!["KGYX"]);
+    assert!(!pipeline.source.watch);
+    assert!(matches!(
+        pipeline.transform[0],
+        Transform::Resample {
+            width: 64,
+            height: 64
+        }
+    ));
+    assert!(matches!(pipeline.sink[0], Sink::GridCsv { .. }));
+    assert!(matches!(
+        pipeline.sink[1],
+        Sink::Geojson {
+            split: Some(Split::Category),
+            ..
+        }
+    ));
+    assert!(matches!(
+        pipeline.sink[2],
+        Sink::PolarCsv {
+            split: Some(Split::Chunks(1000)),
+            ..
+        }
+    ));
+    assert!(matches!(pipeline.sink[3], Sink::Netcdf { .. }));
+    assert!(matches!(
+        pipeline.sink[4],
+        Sink::Gml {
+            options: WriterOptions {
+                axis_order: threecast::output::AxisOrder::LatLon,
+                ..
+            },
+            ..
+        }
+    ));
+    match &pipeline.sink[5] {
+        Sink::Mqtt { client_id, .. } => assert_eq!(*client_id, default_client_id()),
+        _ => panic!("expected an MQTT sink"),
+    }
+    assert!(matches!(&pipeline.sink[6], Sink::S3 { prefix } if prefix == "s3://bucket/raw/"));
+    match Pipeline::from_file(Path::new("pipeline.yaml")) {
+        Err(e) => assert!(e.to_string().contains("pipelines are written in TOML")),
+        Ok(_) => panic!("expected YAML to be refused"),
+    }
+    assert!(toml::from_str::<Pipeline>("[source]\nstations = []\nbogus = 1\n").is_err());
+}
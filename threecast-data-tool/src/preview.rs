@@ -0,0 +1,244 @@
+//! Map previews of data files, in a browser for `tcdt preview` and in the
+//! terminal for `tcdt dump --preview`
+
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+use threecast::filter::BinFilter;
+use threecast::output::{write_scan_filtered, BinRef, GeoJsonSink};
+use threecast::parse::{parse_dpr, PrecipRate};
+use threecast::util::{precip_category, read_input};
+
+/// Leaflet is loaded from a CDN, since inlining it would make every preview
+/// much bigger than the data it shows
+const LEAFLET_URL: &str = "https://unpkg.com/leaflet@1.9.4/dist/leaflet";
+
+/// Fill colors by [`precip_category`], roughly following the usual radar
+/// color scale
+const CATEGORY_COLORS: &[(&str, &str)] = &[
+    ("light", "#04e9e7"),
+    ("moderate", "#01c501"),
+    ("heavy", "#fdf802"),
+    ("violent", "#fd0000"),
+];
+
+/// Terminal colors by [`precip_category`] as 24-bit RGB, matching
+/// [`CATEGORY_COLORS`]
+pub(crate) const CATEGORY_RGB: &[(&str, [u8; 3])] = &[
+    ("light", [4, 233, 231]),
+    ("moderate", [1, 197, 1]),
+    ("heavy", [253, 248, 2]),
+    ("violent", [253, 0, 0]),
+];
+
+/// Characters by [`precip_category`] for terminals without color
+const CATEGORY_CHARS: &[(&str, char)] = &[
+    ("none", ' '),
+    ("light", '.'),
+    ("moderate", '+'),
+    ("heavy", '#'),
+    ("violent", '@'),
+];
+
+/// ANSI escape that sets the foreground (`layer` 38) or background (48)
+/// color for a rate, or resets it if there's no precipitation
+fn ansi_color(layer: u8, rate: f32) -> String {
+    match CATEGORY_RGB
+        .iter()
+        .find(|(category, _)| *category == precip_category(rate))
+    {
+        Some((_, [r, g, b])) => format!("\x1b[{};2;{};{};{}m", layer, r, g, b),
+        None => format!("\x1b[{}m", layer + 1),
+    }
+}
+
+/// Render the scan as `columns` by `rows` characters, centered on the
+/// station. With `color`, each character is an upper half block colored
+/// with ANSI escapes so that it shows two square pixels; otherwise each
+/// character shows one pixel with a character per category.
+pub fn render_terminal(dpr: &PrecipRate, columns: usize, rows: usize, color: bool) -> String {
+    // the scan area is square, and character cells are about twice as tall
+    // as they are wide
+    let size = columns.min(rows * 2).max(2);
+    let (width, height) = (size, if color { size } else { size / 2 });
+    let grid = dpr.sample_radials_to_equirectangular(height, width);
+    let mut output = String::new();
+    if color {
+        for pair in grid.chunks(2) {
+            for (x, (_, top)) in pair[0].iter().enumerate() {
+                let bottom = pair.get(1).map_or(0., |row| row[x].1);
+                output.push_str(&ansi_color(38, *top));
+                output.push_str(&ansi_color(48, bottom));
+                output.push('\u{2580}');
+            }
+            output.push_str("\x1b[0m\n");
+        }
+    } else {
+        for row in grid.iter() {
+            for (_, rate) in row.iter() {
+                let category = precip_category(*rate);
+                output.push(
+                    CATEGORY_CHARS
+                        .iter()
+                        .find(|(c, _)| *c == category)
+                        .map_or('?', |(_, ch)| *ch),
+                );
+            }
+            output.push('\n');
+        }
+    }
+    output
+}
+
+/// Render the scan to fit the current terminal, leaving a couple of lines
+/// for the prompt. Color is used unless `NO_COLOR` is set or stdout isn't a
+/// terminal.
+pub fn render_terminal_fit(dpr: &PrecipRate) -> String {
+    let (columns, rows, tty) = match terminal_size::terminal_size() {
+        Some((terminal_size::Width(w), terminal_size::Height(h))) => (w as usize, h as usize, true),
+        None => (80, 24, false),
+    };
+    let color = tty && std::env::var_os("NO_COLOR").is_none();
+    render_terminal(dpr, columns, rows.saturating_sub(2), color)
+}
+
+/// Build a standalone HTML page that shows every bin with precipitation on
+/// a map, colored by category, over an outline of the radar's range
+fn render_html(dpr: &PrecipRate) -> Result<String, Box<dyn Error>> {
+    let mut sink = GeoJsonSink::with_properties(Vec::new(), |bin: &BinRef| {
+        let mut properties = serde_json::Map::new();
+        properties.insert(String::from("rate"), bin.rate().into());
+        properties.insert(String::from("category"), precip_category(bin.rate()).into());
+        properties
+    });
+    let filter = |bin: &BinRef| bin.rate() > 0.;
+    write_scan_filtered(dpr, &mut sink, &filter as &dyn BinFilter)?;
+    let geojson = String::from_utf8(sink.into_inner())?;
+    let colors: serde_json::Map<String, serde_json::Value> = CATEGORY_COLORS
+        .iter()
+        .map(|(category, color)| (String::from(*category), (*color).into()))
+        .collect();
+    let max_range = dpr.range_to_first_bin
+        + dpr.bin_size
+            * dpr
+                .radials
+                .iter()
+                .map(|r| r.precip_rates.len())
+                .max()
+                .unwrap_or(0) as f32;
+    let title = format!(
+        "{} {}",
+        dpr.station_code.to_uppercase(),
+        dpr.capture_time.format("%Y-%m-%dT%H:%M:%SZ")
+    );
+    Ok(format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>{title}</title>
+<link rel="stylesheet" href="{leaflet}.css">
+<script src="{leaflet}.js"></script>
+<style>html, body, #map {{ height: 100%; margin: 0; }}</style>
+</head>
+<body>
+<div id="map"></div>
+<script>
+const map = L.map("map").setView([{latitude}, {longitude}], 7);
+L.tileLayer("https://tile.openstreetmap.org/{{z}}/{{x}}/{{y}}.png", {{
+  attribution: "&copy; OpenStreetMap contributors",
+}}).addTo(map);
+L.circle([{latitude}, {longitude}], {{ radius: {range_m}, fill: false, weight: 1 }}).addTo(map);
+const colors = {colors};
+L.geoJSON({geojson}, {{
+  style: (feature) => ({{
+    stroke: false,
+    fillColor: colors[feature.properties.category],
+    fillOpacity: 0.7,
+  }}),
+  onEachFeature: (feature, layer) =>
+    layer.bindTooltip(feature.properties.rate.toFixed(3) + " in/hr"),
+}}).addTo(map);
+L.control.attribution({{ prefix: "{title}" }}).addTo(map);
+</script>
+</body>
+</html>
+"#,
+        title = title,
+        leaflet = LEAFLET_URL,
+        latitude = dpr.latitude,
+        longitude = dpr.longitude,
+        range_m = max_range * 1000.,
+        colors = serde_json::Value::Object(colors),
+        geojson = geojson,
+    ))
+}
+
+/// Open `path` with the desktop's default handler
+fn open_in_browser(path: &Path) -> Result<(), Box<dyn Error>> {
+    let mut command = if cfg!(target_os = "macos") {
+        std::process::Command::new("open")
+    } else if cfg!(target_os = "windows") {
+        let mut command = std::process::Command::new("cmd");
+        command.args(["/C", "start", ""]);
+        command
+    } else {
+        std::process::Command::new("xdg-open")
+    };
+    command.arg(path).spawn()?;
+    Ok(())
+}
+
+/// Write a map preview of the data file at `input` to `output`, or next to
+/// the system's temporary files if it's not given, and optionally open it
+/// in a browser. Returns where the preview was written.
+pub fn preview(input: &str, output: Option<&Path>, open: bool) -> Result<PathBuf, Box<dyn Error>> {
+    let dpr = parse_dpr(read_input(input)?)?;
+    let path = match output {
+        Some(path) => path.to_path_buf(),
+        None => std::env::temp_dir().join(format!(
+            "{}-{}.html",
+            dpr.station_code.to_uppercase(),
+            dpr.capture_time.format("%Y%m%dT%H%M%SZ")
+        )),
+    };
+    std::fs::write(&path, render_html(&dpr)?)?;
+    if open {
+        open_in_browser(&path)?;
+    }
+    Ok(path)
+}
+
+#[test]
+fn test_render_html() {
+    use threecast::parse::{OperationalMode, Radial};
+    let dpr = PrecipRate {
+        station_code: String::from("kgyx"),
+        capture_time: chrono::NaiveDateTime::from_timestamp(0, 0),
+        scan_number: 1,
+        latitude: 43.891,
+        longitude: -70.256,
+        operational_mode: OperationalMode::Precipitation,
+        precip_detected: true,
+        bin_size: 0.25,
+        range_to_first_bin: 0.,
+        times: Default::default(),
+        metadata: None,
+        extra_components: Vec::new(),
+        alphanumeric: Default::default(),
+        radials: vec![Radial {
+            azimuth: 0.5,
+            elevation: 0.5,
+            width: 1.,
+            precip_rates: vec![0., 0.2, 0.],
+            interpolated: false,
+        }],
+    };
+    let html = render_html(&dpr).unwrap();
+    assert!(html.contains("<title>KGYX 1970-01-01T00:00:00Z</title>"));
+    assert_eq!(html.matches(r#""category":"moderate""#).count(), 1);
+    assert!(!html.contains(r#""category":"none""#));
+    let text = render_terminal(&dpr, 20, 5, false);
+    assert_eq!(text.lines().count(), 5);
+    assert!(text.lines().all(|line| line.chars().count() == 10));
+}
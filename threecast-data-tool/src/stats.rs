@@ -0,0 +1,151 @@
+//! Batch summaries for `tcdt stats`
+
+use chrono::{NaiveDateTime, Timelike};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::io::Write;
+
+use threecast::summary::ScanSummary;
+
+const TIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%SZ";
+
+/// How to group scans into rows
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Aggregate {
+    /// One row per scan
+    None,
+    /// One row per station and clock hour
+    Hour,
+    /// One row per station and UTC day
+    Day,
+}
+
+impl Aggregate {
+    pub fn from_name(name: &str) -> Option<Aggregate> {
+        match name {
+            "none" => Some(Aggregate::None),
+            "hour" => Some(Aggregate::Hour),
+            "day" => Some(Aggregate::Day),
+            _ => None,
+        }
+    }
+
+    /// Start of the period that `time` falls in
+    fn period(&self, time: NaiveDateTime) -> NaiveDateTime {
+        match self {
+            Aggregate::None => time,
+            Aggregate::Hour => time.date().and_hms(time.hour(), 0, 0),
+            Aggregate::Day => time.date().and_hms(0, 0, 0),
+        }
+    }
+}
+
+/// Summary of one or more scans from a station starting at `time`
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct StatsRow {
+    pub station: String,
+    #[serde(serialize_with = "serialize_time")]
+    pub time: NaiveDateTime,
+    pub scans: usize,
+    /// Highest rate of any bin in in/hr
+    pub max_rate: f32,
+    /// Mean over the scans of the fraction of bins with nonzero precip
+    pub precip_fraction: f32,
+    /// Mean over the scans of the rainy area in square kilometers
+    pub rainy_area: f32,
+    /// Largest rainy area of any of the scans in square kilometers
+    pub max_rainy_area: f32,
+}
+
+fn serialize_time<S: serde::Serializer>(time: &NaiveDateTime, s: S) -> Result<S::Ok, S::Error> {
+    s.serialize_str(&time.format(TIME_FORMAT).to_string())
+}
+
+/// Group summaries into rows sorted by station and time
+pub fn aggregate(summaries: &[ScanSummary], by: Aggregate) -> Vec<StatsRow> {
+    let mut groups: BTreeMap<(String, NaiveDateTime), Vec<&ScanSummary>> = BTreeMap::new();
+    for summary in summaries {
+        groups
+            .entry((
+                summary.station.to_uppercase(),
+                by.period(summary.capture_time),
+            ))
+            .or_default()
+            .push(summary);
+    }
+    groups
+        .into_iter()
+        .map(|((station, time), group)| {
+            let count = group.len() as f32;
+            StatsRow {
+                station,
+                time,
+                scans: group.len(),
+                max_rate: group.iter().map(|s| s.max_rate).fold(0., f32::max),
+                precip_fraction: group.iter().map(|s| s.precip_fraction).sum::<f32>() / count,
+                rainy_area: group.iter().map(|s| s.rainy_area).sum::<f32>() / count,
+                max_rainy_area: group.iter().map(|s| s.rainy_area).fold(0., f32::max),
+            }
+        })
+        .collect()
+}
+
+pub fn write_csv<W: Write>(mut writer: W, rows: &[StatsRow]) -> Result<(), Box<dyn Error>> {
+    writeln!(
+        writer,
+        "station,time,scans,max_rate,precip_fraction,rainy_area_km2,max_rainy_area_km2"
+    )?;
+    for row in rows {
+        writeln!(
+            writer,
+            "{},{},{},{},{},{},{}",
+            row.station,
+            row.time.format(TIME_FORMAT),
+            row.scans,
+            row.max_rate,
+            row.precip_fraction,
+            row.rainy_area,
+            row.max_rainy_area
+        )?;
+    }
+    Ok(())
+}
+
+/// Write one JSON object per line
+pub fn write_json<W: Write>(mut writer: W, rows: &[StatsRow]) -> Result<(), Box<dyn Error>> {
+    for row in rows {
+        serde_json::to_writer(&mut writer, row)?;
+        writeln!(writer)?;
+    }
+    Ok(())
+}
+
+#[test]
+fn test_aggregate() {
+    let summary = |minute: u32, max_rate: f32, rainy_area: f32| ScanSummary {
+        station: String::from("kgyx"),
+        capture_time: chrono::NaiveDate::from_ymd(2022, 1, 1).and_hms(12, minute, 0),
+        generation_time: None,
+        scan_number: 1,
+        operational_mode: threecast::parse::OperationalMode::Precipitation,
+        precip_detected: true,
+        max_rate,
+        radials: 360,
+        bins: 360 * 115,
+        precip_fraction: rainy_area / 1000.,
+        rainy_area,
+        bbox: [0.; 4],
+    };
+    let summaries = [summary(54, 0.5, 100.), summary(59, 0.25, 300.)];
+    assert_eq!(aggregate(&summaries, Aggregate::None).len(), 2);
+    let rows = aggregate(&summaries, Aggregate::Hour);
+    assert_eq!(rows.len(), 1);
+    assert_eq!((rows[0].scans, rows[0].max_rate), (2, 0.5));
+    assert_eq!((rows[0].rainy_area, rows[0].max_rainy_area), (200., 300.));
+    let mut output = Vec::new();
+    write_csv(&mut output, &rows).unwrap();
+    assert_eq!(
+        String::from_utf8(output).unwrap().lines().nth(1).unwrap(),
+        "KGYX,2022-01-01T12:00:00Z,2,0.5,0.2,200,300"
+    );
+}
@@ -0,0 +1,330 @@
+//! Conformance checks for `tcdt validate`
+
+use std::fmt;
+
+use threecast::parse::{parse_dpr_with, text_header_length, ParseOptions, DPR_PRODUCT_CODE};
+
+use crate::dump::header_values;
+
+/// Volume coverage patterns in use on the WSR-88D network
+const KNOWN_VCPS: &[i64] = &[12, 31, 32, 35, 112, 121, 212, 215];
+/// Offset of the symbology block in halfwords from the start of the message
+/// header, since it follows right after the product description block
+const SYMBOLOGY_OFFSET: i64 = (18 + 102) / 2;
+const SECONDS_PER_DAY: i64 = 86400;
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Severity::Info => write!(f, "info"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+pub struct Violation {
+    pub severity: Severity,
+    pub message: String,
+}
+
+/// Collects violations while checking a file
+#[derive(Default)]
+struct Checker {
+    violations: Vec<Violation>,
+}
+
+impl Checker {
+    fn report(&mut self, severity: Severity, message: String) {
+        self.violations.push(Violation { severity, message });
+    }
+
+    /// Report `field` if its value isn't in `range`
+    fn check_range(
+        &mut self,
+        severity: Severity,
+        field: &str,
+        value: i64,
+        range: std::ops::RangeInclusive<i64>,
+    ) {
+        if !range.contains(&value) {
+            self.report(
+                severity,
+                format!(
+                    "{} is {}, expected {} to {}",
+                    field,
+                    value,
+                    range.start(),
+                    range.end()
+                ),
+            );
+        }
+    }
+
+    fn check_equal(&mut self, severity: Severity, field: &str, value: i64, expected: i64) {
+        if value != expected {
+            self.report(
+                severity,
+                format!("{} is {}, expected {}", field, value, expected),
+            );
+        }
+    }
+}
+
+/// Check the headers and product description block field by field
+fn check_headers(checker: &mut Checker, data: &[u8]) -> Result<(), String> {
+    use Severity::*;
+    let values = header_values(data)?;
+    let field = |name| values[name];
+    checker.check_equal(
+        Error,
+        "message code",
+        field("message code"),
+        DPR_PRODUCT_CODE as i64,
+    );
+    checker.check_range(Warning, "message date", field("message date"), 1..=65535);
+    checker.check_range(
+        Error,
+        "message time",
+        field("message time"),
+        0..=SECONDS_PER_DAY - 1,
+    );
+    checker.check_equal(
+        Error,
+        "message length",
+        field("message length"),
+        // the text header isn't counted in the message length
+        (data.len() - text_header_length(data)?) as i64,
+    );
+    checker.check_equal(Warning, "number of blocks", field("number of blocks"), 3);
+    checker.check_equal(Error, "block divider", field("block divider"), -1);
+    checker.check_range(Error, "latitude", field("latitude"), -90000..=90000);
+    checker.check_range(Error, "longitude", field("longitude"), -180000..=180000);
+    checker.check_range(Warning, "height", field("height"), -100..=11000);
+    checker.check_equal(
+        Error,
+        "product code",
+        field("product code"),
+        DPR_PRODUCT_CODE as i64,
+    );
+    checker.check_range(Error, "operational mode", field("operational mode"), 0..=2);
+    let vcp = field("volume coverage pattern");
+    if !KNOWN_VCPS.contains(&vcp) {
+        checker.report(
+            Warning,
+            format!("volume coverage pattern {} is not a known VCP", vcp),
+        );
+    }
+    checker.check_range(
+        Error,
+        "sequence number",
+        field("sequence number"),
+        0..=32767,
+    );
+    checker.check_range(
+        Warning,
+        "volume scan number",
+        field("volume scan number"),
+        1..=80,
+    );
+    checker.check_range(
+        Error,
+        "volume scan date",
+        field("volume scan date"),
+        1..=65535,
+    );
+    checker.check_range(
+        Error,
+        "volume scan time",
+        field("volume scan time"),
+        0..=SECONDS_PER_DAY - 1,
+    );
+    checker.check_range(
+        Error,
+        "generation date",
+        field("generation date"),
+        1..=65535,
+    );
+    checker.check_range(
+        Error,
+        "generation time",
+        field("generation time"),
+        0..=SECONDS_PER_DAY - 1,
+    );
+    if (field("generation date"), field("generation time"))
+        < (field("volume scan date"), field("volume scan time"))
+    {
+        checker.report(
+            Warning,
+            String::from("product was generated before its volume scan"),
+        );
+    }
+    checker.check_range(
+        Warning,
+        "elevation number",
+        field("elevation number"),
+        0..=20,
+    );
+    // the flag is the high byte of parameter 3
+    checker.check_range(
+        Warning,
+        "precip detected",
+        field("product dependent 3") >> 8,
+        0..=1,
+    );
+    match field("compression method") {
+        0 => checker.report(Info, String::from("symbology block is not compressed")),
+        1 => {} // bzip2
+        method => checker.report(
+            Error,
+            format!(
+                "compression method is {}, expected 0 (none) or 1 (bzip2)",
+                method
+            ),
+        ),
+    }
+    checker.check_range(
+        Error,
+        "uncompressed size",
+        field("uncompressed size"),
+        1..=i32::MAX as i64,
+    );
+    checker.check_equal(
+        Warning,
+        "symbology offset",
+        field("symbology offset"),
+        SYMBOLOGY_OFFSET,
+    );
+    checker.check_equal(Info, "graphic offset", field("graphic offset"), 0);
+    checker.check_equal(Info, "tabular offset", field("tabular offset"), 0);
+    Ok(())
+}
+
+/// Check the decoded symbology block, mostly for radial coverage
+fn check_symbology(checker: &mut Checker, data: Vec<u8>, uncompressed_size: i64) {
+    use Severity::*;
+    // let values outside the spec through so that every one of them gets
+    // reported, instead of only the first
+    let options = ParseOptions {
+        lenient: true,
+        ..Default::default()
+    };
+    let (dpr, report) = match parse_dpr_with(data, &options) {
+        Ok(p) => p,
+        Err(e) => return checker.report(Error, format!("failed to parse: {}", e)),
+    };
+    for warning in report.warnings.iter() {
+        checker.report(Error, warning.to_lowercase());
+    }
+    checker.check_equal(
+        Error,
+        "decompressed symbology size",
+        report.decompressed_size as i64,
+        uncompressed_size,
+    );
+    if report.trailing_bytes > 0 {
+        checker.report(
+            Warning,
+            format!(
+                "{} bytes left over after the last radial",
+                report.trailing_bytes
+            ),
+        );
+    }
+    if dpr.radials.is_empty() {
+        return checker.report(Error, String::from("scan has no radials"));
+    }
+    checker.check_equal(Info, "number of radials", dpr.radials.len() as i64, 360);
+    let num_bins = dpr.radials[0].precip_rates.len();
+    for (idx, radial) in dpr.radials.iter().enumerate() {
+        if radial.precip_rates.len() != num_bins {
+            checker.report(
+                Warning,
+                format!(
+                    "radial {} has {} bins, but radial 0 has {}",
+                    idx,
+                    radial.precip_rates.len(),
+                    num_bins
+                ),
+            );
+        }
+    }
+    let diagnostics = dpr.radial_diagnostics();
+    for gap in diagnostics.gaps.iter() {
+        checker.report(
+            Warning,
+            format!("no radial covers {:.2}° to {:.2}°", gap.start, gap.end),
+        );
+    }
+    for overlap in diagnostics.overlaps.iter() {
+        checker.report(
+            Warning,
+            format!(
+                "more than one radial covers {:.2}° to {:.2}°",
+                overlap.start, overlap.end
+            ),
+        );
+    }
+    for idx in diagnostics.out_of_order.iter() {
+        checker.report(Info, format!("radial {} is out of order by azimuth", idx));
+    }
+    if !diagnostics.has_consistent_widths() {
+        checker.report(
+            Info,
+            format!(
+                "radial widths vary from {}° to {}°",
+                diagnostics.min_width, diagnostics.max_width
+            ),
+        );
+    }
+}
+
+/// Check a data file against the ranges and layout in the ICD and return
+/// everything that doesn't conform, most severe first
+pub fn validate(data: Vec<u8>) -> Vec<Violation> {
+    let mut checker = Checker::default();
+    match check_headers(&mut checker, &data) {
+        Ok(()) => {
+            let uncompressed_size = header_values(&data).unwrap()["uncompressed size"];
+            check_symbology(&mut checker, data, uncompressed_size);
+        }
+        Err(e) => checker.report(Severity::Error, e),
+    }
+    checker
+        .violations
+        .sort_by_key(|v| std::cmp::Reverse(v.severity));
+    checker.violations
+}
+
+#[test]
+fn test_validate_truncated() {
+    let violations = validate(vec![0; 40]);
+    assert_eq!(violations.len(), 1);
+    assert_eq!(violations[0].severity, Severity::Error);
+}
+
+#[test]
+fn test_validate_compression_method() {
+    // synthetic scans are only compressed with the libbz2 feature, and
+    // either way is fine
+    let mut data = threecast::synthetic::SyntheticDpr::new("KGYX")
+        .with_geometry(360, 8, 0.25)
+        .to_bytes();
+    assert!(!validate(data.clone())
+        .iter()
+        .any(|v| v.message.starts_with("compression method")));
+    // parameter 8, the last halfword before the uncompressed size
+    let offset = 30 + 18 + 82;
+    data[offset..offset + 2].copy_from_slice(&2i16.to_be_bytes());
+    let expected = "compression method is 2, expected 0 (none) or 1 (bzip2)";
+    assert!(validate(data)
+        .iter()
+        .any(|v| v.severity == Severity::Error && v.message == expected));
+}
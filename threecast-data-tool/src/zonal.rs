@@ -0,0 +1,119 @@
+//! Zonal statistics for `tcdt zonal`
+
+use std::error::Error;
+use std::io::Write;
+
+use threecast::parse::PrecipRate;
+use threecast::zonal::Zone;
+
+/// Write one CSV row per scan and zone
+pub fn write_csv<W: Write>(
+    mut writer: W,
+    scans: &[PrecipRate],
+    zones: &[Zone],
+    threshold: f32,
+) -> Result<(), Box<dyn Error>> {
+    writeln!(
+        writer,
+        "station,capture_time,zone,bins,mean,max,fraction_above"
+    )?;
+    for scan in scans {
+        for stats in scan.zonal_stats(zones, threshold) {
+            writeln!(
+                writer,
+                "{},{},\"{}\",{},{},{},{}",
+                scan.station_code.to_uppercase(),
+                scan.capture_time.format("%Y-%m-%dT%H:%M:%SZ"),
+                stats.zone.replace('"', "\"\""),
+                stats.bins,
+                stats.mean,
+                stats.max,
+                stats.fraction_above
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Write a FeatureCollection with a copy of each zone's feature per scan,
+/// with the statistics added to its properties
+pub fn write_geojson<W: Write>(
+    writer: W,
+    scans: &[PrecipRate],
+    zones: &[Zone],
+    threshold: f32,
+) -> Result<(), Box<dyn Error>> {
+    let mut features = Vec::new();
+    for scan in scans {
+        for (zone, stats) in zones.iter().zip(scan.zonal_stats(zones, threshold)) {
+            let mut feature = zone.feature.clone().unwrap_or_default();
+            if !feature["properties"].is_object() {
+                feature["properties"] = serde_json::json!({});
+            }
+            let properties = feature["properties"].as_object_mut().unwrap();
+            properties.insert(
+                String::from("station"),
+                scan.station_code.to_uppercase().into(),
+            );
+            properties.insert(
+                String::from("captureTime"),
+                scan.capture_time
+                    .format("%Y-%m-%dT%H:%M:%SZ")
+                    .to_string()
+                    .into(),
+            );
+            properties.insert(String::from("bins"), stats.bins.into());
+            properties.insert(String::from("meanRate"), stats.mean.into());
+            properties.insert(String::from("maxRate"), stats.max.into());
+            properties.insert(String::from("fractionAbove"), stats.fraction_above.into());
+            features.push(feature);
+        }
+    }
+    serde_json::to_writer(
+        writer,
+        &serde_json::json!({"type": "FeatureCollection", "features": features}),
+    )?;
+    Ok(())
+}
+
+#[test]
+fn test_write_csv() {
+    let mut scan = threecast::parse::PrecipRate {
+        station_code: String::from("kgyx"),
+        capture_time: chrono::NaiveDateTime::from_timestamp(0, 0),
+        scan_number: 1,
+        latitude: 43.891,
+        longitude: -70.256,
+        operational_mode: threecast::parse::OperationalMode::Precipitation,
+        precip_detected: true,
+        bin_size: 1.,
+        range_to_first_bin: 0.,
+        times: Default::default(),
+        metadata: None,
+        extra_components: Vec::new(),
+        alphanumeric: Default::default(),
+        radials: Vec::new(),
+    };
+    scan.radials.push(threecast::parse::Radial {
+        azimuth: 90.,
+        elevation: 0.5,
+        width: 1.,
+        precip_rates: vec![0.5],
+        interpolated: false,
+    });
+    let zone = Zone::new(
+        "a \"quoted\" zone",
+        &[vec![vec![
+            (43., -71.),
+            (45., -71.),
+            (45., -69.),
+            (43., -69.),
+        ]]],
+    );
+    let mut output = Vec::new();
+    write_csv(&mut output, &[scan], &[zone], 0.1).unwrap();
+    assert_eq!(
+        String::from_utf8(output).unwrap().lines().nth(1).unwrap(),
+        "KGYX,1970-01-01T00:00:00Z,\"a \"\"quoted\"\" zone\",1,0.5,0.5,1"
+    );
+}
@@ -0,0 +1,221 @@
+//! The graphic (Figure 3-13) and tabular (Figure 3-15) alphanumeric blocks
+//! that can follow the symbology block, which hold pages of text like
+//! adaptation data and summaries
+//!
+//! In compressed products these blocks are compressed along with the
+//! symbology block, so they're parsed from whatever is left after it.
+
+/// Text pages from the alphanumeric blocks of a product, each page a list
+/// of lines
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AlphanumericBlocks {
+    pub graphic: Vec<Vec<String>>,
+    pub tabular: Vec<Vec<String>>,
+}
+
+impl AlphanumericBlocks {
+    pub fn is_empty(&self) -> bool {
+        self.graphic.is_empty() && self.tabular.is_empty()
+    }
+}
+
+const GRAPHIC_BLOCK_ID: i16 = 2;
+const TABULAR_BLOCK_ID: i16 = 3;
+
+/// Length of the copy of the message header and product description at
+/// the start of the tabular block
+const TABULAR_HEADERS_SIZE: usize = 18 + 102;
+
+/// Reads big-endian numbers from a slice, failing instead of panicking at
+/// the end
+struct Cursor<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn bytes(&mut self, n: usize) -> Result<&'a [u8], String> {
+        match self.data.get(self.offset..self.offset + n) {
+            Some(bytes) => {
+                self.offset += n;
+                Ok(bytes)
+            }
+            None => Err(format!(
+                "Unexpected end of file: wanted {} bytes, but only {} are left",
+                n,
+                self.data.len().saturating_sub(self.offset)
+            )),
+        }
+    }
+
+    fn i16(&mut self) -> Result<i16, String> {
+        let bytes = self.bytes(2)?;
+        Ok(i16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        let bytes = self.bytes(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn text(&mut self, n: usize) -> Result<String, String> {
+        Ok(String::from_utf8_lossy(self.bytes(n)?)
+            .trim_end()
+            .to_string())
+    }
+}
+
+/// Text packets (codes 1 and 8) in each page of a graphic block, skipping
+/// any other packets
+fn graphic_pages(cursor: &mut Cursor) -> Result<Vec<Vec<String>>, String> {
+    let num_pages = cursor.i16()?.max(0);
+    let mut pages = Vec::new();
+    for _ in 0..num_pages {
+        let _page_number = cursor.i16()?;
+        let page_end = cursor.offset + cursor.i16()?.max(0) as usize;
+        let mut lines = Vec::new();
+        while cursor.offset < page_end {
+            let code = cursor.i16()?;
+            let length = cursor.i16()?.max(0) as usize;
+            let mut packet = Cursor {
+                data: cursor.bytes(length)?,
+                offset: 0,
+            };
+            match code {
+                1 => {
+                    packet.bytes(4)?; // position
+                    lines.push(packet.text(length.saturating_sub(4))?);
+                }
+                8 => {
+                    packet.bytes(6)?; // color and position
+                    lines.push(packet.text(length.saturating_sub(6))?);
+                }
+                _ => (),
+            }
+        }
+        pages.push(lines);
+    }
+    Ok(pages)
+}
+
+/// Lines of each page of a tabular block, after its copy of the headers
+fn tabular_pages(cursor: &mut Cursor) -> Result<Vec<Vec<String>>, String> {
+    cursor.bytes(TABULAR_HEADERS_SIZE)?;
+    let divider = cursor.i16()?;
+    if divider != -1 {
+        return Err(format!("Tabular block divider is {}, expected -1", divider));
+    }
+    let num_pages = cursor.i16()?.max(0);
+    let mut pages = Vec::new();
+    for _ in 0..num_pages {
+        let mut lines = Vec::new();
+        // each page ends with -1 where the next line's length would be
+        loop {
+            match cursor.i16()? {
+                -1 => break,
+                length => lines.push(cursor.text(length.max(0) as usize)?),
+            }
+        }
+        pages.push(lines);
+    }
+    Ok(pages)
+}
+
+/// Parse the alphanumeric blocks at the start of `data`, and return them
+/// along with how many bytes they took up. Parsing stops at the first thing
+/// that isn't an alphanumeric block.
+pub(crate) fn parse_alphanumeric(data: &[u8]) -> Result<(AlphanumericBlocks, usize), String> {
+    let mut blocks = AlphanumericBlocks::default();
+    let mut offset = 0;
+    while let Some(header) = data.get(offset..offset + 8) {
+        let divider = i16::from_be_bytes([header[0], header[1]]);
+        let block_id = i16::from_be_bytes([header[2], header[3]]);
+        if divider != -1 || !matches!(block_id, GRAPHIC_BLOCK_ID | TABULAR_BLOCK_ID) {
+            break;
+        }
+        let mut cursor = Cursor { data, offset };
+        cursor.bytes(4)?;
+        let length = cursor.u32()? as usize;
+        if length < 8 || offset + length > data.len() {
+            return Err(format!(
+                "Alphanumeric block {} has a bad length of {} bytes",
+                block_id, length
+            ));
+        }
+        let mut cursor = Cursor {
+            data: &data[..offset + length],
+            offset: offset + 8,
+        };
+        let (name, pages) = match block_id {
+            GRAPHIC_BLOCK_ID => ("graphic", graphic_pages(&mut cursor)),
+            _ => ("tabular", tabular_pages(&mut cursor)),
+        };
+        let pages = pages.map_err(|e| {
+            format!(
+                "{} alphanumeric block at byte {} after the symbology block: {}",
+                name, offset, e
+            )
+        })?;
+        match block_id {
+            GRAPHIC_BLOCK_ID => blocks.graphic.extend(pages),
+            _ => blocks.tabular.extend(pages),
+        }
+        offset += length;
+    }
+    Ok((blocks, offset))
+}
+
+/// A graphic block with one page of two text packets, then a tabular block
+/// with two pages
+#[cfg(test)]
+pub(crate) fn alphanumeric_bytes() -> Vec<u8> {
+    let mut packets = Vec::new();
+    for (code, text) in [(1i16, "STORM TOTAL"), (8, "MAX 1.25 IN/HR")] {
+        let prefix = if code == 8 { 6 } else { 4 };
+        packets.extend(code.to_be_bytes());
+        packets.extend(((prefix + text.len()) as i16).to_be_bytes());
+        packets.extend(vec![0; prefix]);
+        packets.extend(text.as_bytes());
+    }
+    let mut graphic = (-1i16).to_be_bytes().to_vec();
+    graphic.extend(GRAPHIC_BLOCK_ID.to_be_bytes());
+    graphic.extend((10 + 4 + packets.len() as u32).to_be_bytes());
+    graphic.extend(1i16.to_be_bytes());
+    graphic.extend(1i16.to_be_bytes());
+    graphic.extend((packets.len() as i16).to_be_bytes());
+    graphic.extend(packets);
+
+    let mut tabular = (-1i16).to_be_bytes().to_vec();
+    tabular.extend(TABULAR_BLOCK_ID.to_be_bytes());
+    tabular.extend([0; 4]);
+    tabular.extend([0; TABULAR_HEADERS_SIZE]);
+    tabular.extend((-1i16).to_be_bytes());
+    tabular.extend(2i16.to_be_bytes());
+    for page in [&["ADAPTATION DATA", "MIN REFL 20 DBZ  "][..], &["PAGE 2"]] {
+        for line in page {
+            tabular.extend((line.len() as i16).to_be_bytes());
+            tabular.extend(line.as_bytes());
+        }
+        tabular.extend((-1i16).to_be_bytes());
+    }
+    let length = (tabular.len() as u32).to_be_bytes();
+    tabular[4..8].copy_from_slice(&length);
+
+    graphic.extend(tabular);
+    graphic
+}
+
+#[test]
+fn test_parse_alphanumeric() {
+    let mut data = alphanumeric_bytes();
+    data.extend([0, 0, 0]);
+    let (blocks, consumed) = parse_alphanumeric(&data).unwrap();
+    assert_eq!(consumed, data.len() - 3);
+    assert_eq!(blocks.graphic, [["STORM TOTAL", "MAX 1.25 IN/HR"]]);
+    assert_eq!(blocks.tabular.len(), 2);
+    assert_eq!(blocks.tabular[0], ["ADAPTATION DATA", "MIN REFL 20 DBZ"]);
+    assert_eq!(blocks.tabular[1], ["PAGE 2"]);
+
+    assert_eq!(parse_alphanumeric(&[1, 2, 3]).unwrap().1, 0);
+    assert!(parse_alphanumeric(&data[..data.len() - 10]).is_err());
+}
@@ -0,0 +1,96 @@
+use crate::parse::GridData;
+use crate::predict::{find_best_offset, shift};
+
+/// Move `previous` along the estimated motion vector between it and `current`
+/// so that the two grids line up in time
+#[allow(clippy::ptr_arg)]
+fn advect(previous: &GridData, current: &GridData) -> GridData {
+    let offset = find_best_offset(previous, current);
+    shift(previous, offset.1, offset.0)
+}
+
+/// Fuse a new scan with the previous one by exponential weighting. The previous
+/// grid is first advected to line up with the current one, then each output
+/// cell is `weight * current + (1 - weight) * previous`. A `weight` of 1 returns
+/// the current grid unchanged, and smaller values suppress more scan-to-scan
+/// flicker at the cost of reacting more slowly to new precipitation.
+#[allow(clippy::ptr_arg)]
+pub fn blend_exponential(previous: &GridData, current: &GridData, weight: f32) -> GridData {
+    let advected = advect(previous, current);
+    current
+        .iter()
+        .zip(advected.iter())
+        .map(|(current_row, previous_row)| {
+            current_row
+                .iter()
+                .zip(previous_row.iter())
+                .map(|(c, p)| (c.0, weight * c.1 + (1. - weight) * p.1))
+                .collect()
+        })
+        .collect()
+}
+
+/// A simple Kalman filter that treats every grid cell as an independent
+/// random walk observed through noisy scans. Between updates, the running
+/// state is advected along the estimated motion vector before being fused with
+/// the new scan. Since the noise parameters are the same for every cell, the
+/// variance and gain are shared across the whole grid.
+pub struct KalmanBlender {
+    state: Option<GridData>,
+    variance: f32,
+    process_noise: f32,
+    measurement_noise: f32,
+}
+
+impl KalmanBlender {
+    /// `process_noise` is the expected variance of real changes in the rate
+    /// between scans, and `measurement_noise` is the expected variance of the
+    /// scan-to-scan noise. Both are in (in/hr)^2.
+    pub fn new(process_noise: f32, measurement_noise: f32) -> Self {
+        KalmanBlender {
+            state: None,
+            variance: measurement_noise,
+            process_noise,
+            measurement_noise,
+        }
+    }
+
+    /// Fuse a new scan into the running state and return the blended grid.
+    /// The first scan passes through unchanged.
+    #[allow(clippy::ptr_arg)]
+    pub fn update(&mut self, scan: &GridData) -> &GridData {
+        let blended = match &self.state {
+            None => scan.clone(),
+            Some(previous) => {
+                let predicted_variance = self.variance + self.process_noise;
+                let gain = predicted_variance / (predicted_variance + self.measurement_noise);
+                self.variance = (1. - gain) * predicted_variance;
+                blend_exponential(previous, scan, gain)
+            }
+        };
+        self.state.insert(blended)
+    }
+
+    /// The current blended state, if any scans have been seen yet
+    pub fn state(&self) -> Option<&GridData> {
+        self.state.as_ref()
+    }
+}
+
+#[test]
+fn test_blend_exponential_stationary() {
+    let previous: GridData = vec![
+        vec![([0, 0], 0.), ([0, 0], 0.), ([0, 0], 0.)],
+        vec![([0, 0], 0.), ([0, 0], 2.), ([0, 0], 0.)],
+        vec![([0, 0], 0.), ([0, 0], 0.), ([0, 0], 0.)],
+    ];
+    let current: GridData = vec![
+        vec![([0, 0], 0.), ([0, 0], 0.), ([0, 0], 0.)],
+        vec![([0, 0], 0.), ([0, 0], 1.), ([0, 0], 0.)],
+        vec![([0, 0], 0.), ([0, 0], 0.), ([0, 0], 0.)],
+    ];
+    let blended = blend_exponential(&previous, &current, 0.5);
+    assert_eq!(blended.len(), 3);
+    assert_eq!(blended[1][1].1, 1.5);
+    assert_eq!(blended[0][0].1, 0.);
+}
@@ -0,0 +1,187 @@
+//! Approximate comparison of scans, for round-trip tests and for checking
+//! that changes to the geometry or resampling code don't move things more
+//! than expected
+
+use crate::parse::{PrecipRate, Radial};
+
+/// How far apart two values can be and still count as equal
+#[derive(Debug, Clone, Copy)]
+pub struct Tolerances {
+    /// Precipitation rates in in/hr
+    pub rate: f32,
+    /// Azimuths, elevations, and widths in degrees
+    pub angle: f32,
+    /// Bin sizes and ranges in kilometers
+    pub distance: f32,
+    /// Station latitude and longitude in degrees
+    pub coordinate: f32,
+}
+
+impl Tolerances {
+    /// Tolerances of zero, so that only identical scans are equal
+    pub fn exact() -> Self {
+        Tolerances {
+            rate: 0.,
+            angle: 0.,
+            distance: 0.,
+            coordinate: 0.,
+        }
+    }
+}
+
+impl Default for Tolerances {
+    /// The resolution that data files store each value at
+    fn default() -> Self {
+        Tolerances {
+            rate: 0.001,
+            angle: crate::coverage::TOLERANCE,
+            distance: 0.001,
+            coordinate: 0.001,
+        }
+    }
+}
+
+/// True if `a` and `b` differ by no more than `tolerance`. Unlike a plain
+/// comparison, two `NaN`s are equal.
+fn within(a: f32, b: f32, tolerance: f32) -> bool {
+    (a - b).abs() <= tolerance || (a.is_nan() && b.is_nan())
+}
+
+/// Compare one pair of radials, summarizing the rate arrays instead of
+/// listing every bin
+fn radial_differences(idx: usize, a: &Radial, b: &Radial, tolerances: &Tolerances) -> Vec<String> {
+    let mut differences = Vec::new();
+    for (name, x, y) in [
+        ("azimuth", a.azimuth, b.azimuth),
+        ("elevation", a.elevation, b.elevation),
+        ("width", a.width, b.width),
+    ] {
+        if !within(x, y, tolerances.angle) {
+            differences.push(format!("radial {} {}: {} != {}", idx, name, x, y));
+        }
+    }
+    if a.precip_rates.len() != b.precip_rates.len() {
+        differences.push(format!(
+            "radial {} bins: {} != {}",
+            idx,
+            a.precip_rates.len(),
+            b.precip_rates.len()
+        ));
+    }
+    let (mut changed, mut max_difference, mut first_changed) = (0, 0f32, None);
+    for (bin, (x, y)) in a.precip_rates.iter().zip(b.precip_rates.iter()).enumerate() {
+        if !within(*x, *y, tolerances.rate) {
+            changed += 1;
+            max_difference = max_difference.max((x - y).abs());
+            first_changed.get_or_insert(bin);
+        }
+    }
+    if let Some(first) = first_changed {
+        differences.push(format!(
+            "radial {} rates: {} bins differ starting at bin {}, by up to {} in/hr",
+            idx, changed, first, max_difference
+        ));
+    }
+    differences
+}
+
+impl PrecipRate {
+    /// Compare the metadata and radials of two scans and return a line for
+    /// each difference beyond `tolerances`
+    pub fn differences(&self, other: &PrecipRate, tolerances: &Tolerances) -> Vec<String> {
+        let mut differences = Vec::new();
+        for (name, x, y) in [
+            (
+                "station code",
+                self.station_code.clone(),
+                other.station_code.clone(),
+            ),
+            (
+                "capture time",
+                self.capture_time.to_string(),
+                other.capture_time.to_string(),
+            ),
+            (
+                "scan number",
+                self.scan_number.to_string(),
+                other.scan_number.to_string(),
+            ),
+            (
+                "operational mode",
+                format!("{:?}", self.operational_mode),
+                format!("{:?}", other.operational_mode),
+            ),
+            (
+                "precip detected",
+                self.precip_detected.to_string(),
+                other.precip_detected.to_string(),
+            ),
+        ] {
+            if x != y {
+                differences.push(format!("{}: {} != {}", name, x, y));
+            }
+        }
+        for (name, x, y, tolerance) in [
+            (
+                "latitude",
+                self.latitude,
+                other.latitude,
+                tolerances.coordinate,
+            ),
+            (
+                "longitude",
+                self.longitude,
+                other.longitude,
+                tolerances.coordinate,
+            ),
+            (
+                "bin size",
+                self.bin_size,
+                other.bin_size,
+                tolerances.distance,
+            ),
+            (
+                "range to first bin",
+                self.range_to_first_bin,
+                other.range_to_first_bin,
+                tolerances.distance,
+            ),
+        ] {
+            if !within(x, y, tolerance) {
+                differences.push(format!("{}: {} != {}", name, x, y));
+            }
+        }
+        if self.radials.len() != other.radials.len() {
+            differences.push(format!(
+                "radials: {} != {}",
+                self.radials.len(),
+                other.radials.len()
+            ));
+        }
+        for (idx, (x, y)) in self.radials.iter().zip(other.radials.iter()).enumerate() {
+            differences.append(&mut radial_differences(idx, x, y, tolerances));
+        }
+        differences
+    }
+
+    /// True if the scans match within `tolerances`. Use
+    /// [`PrecipRate::differences`] to find out what doesn't match.
+    pub fn approx_eq(&self, other: &PrecipRate, tolerances: &Tolerances) -> bool {
+        self.differences(other, tolerances).is_empty()
+    }
+}
+
+#[test]
+fn test_approx_eq() {
+    let a = crate::coverage::scan_with_azimuths(&[0.5, 1.5]);
+    let mut b = crate::coverage::scan_with_azimuths(&[0.5, 1.5]);
+    b.radials[1].azimuth += 0.005;
+    b.radials[0].precip_rates[0] += 0.0005;
+    assert!(a.approx_eq(&b, &Tolerances::default()));
+    assert!(!a.approx_eq(&b, &Tolerances::exact()));
+    b.radials[0].precip_rates[0] = 0.25;
+    assert_eq!(
+        a.differences(&b, &Tolerances::default()),
+        vec!["radial 0 rates: 1 bins differ starting at bin 0, by up to 0.25 in/hr"]
+    );
+}
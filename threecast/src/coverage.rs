@@ -0,0 +1,250 @@
+use crate::geomath::get_point_bearing_distance;
+use crate::parse::PrecipRate;
+
+/// Differences in degrees smaller than this are treated as rounding error
+pub(crate) const TOLERANCE: f32 = 0.01;
+
+/// Vertices in each circle of [`PrecipRate::coverage_polygon`], one per
+/// degree
+const COVERAGE_POINTS: usize = 360;
+
+/// A span of azimuths, in degrees clockwise from due north. `end` may be
+/// greater than 360 when the span crosses north.
+#[derive(Debug, PartialEq)]
+pub struct AzimuthSpan {
+    pub start: f32,
+    pub end: f32,
+}
+
+/// How well the radials of a scan cover the full circle
+#[derive(Debug)]
+pub struct RadialDiagnostics {
+    /// Spans that no radial covers
+    pub gaps: Vec<AzimuthSpan>,
+    /// Spans that more than one radial covers
+    pub overlaps: Vec<AzimuthSpan>,
+    /// Indices of radials whose azimuth is lower than the one before, not
+    /// counting one wrap past north
+    pub out_of_order: Vec<usize>,
+    pub min_width: f32,
+    pub max_width: f32,
+}
+
+impl RadialDiagnostics {
+    /// True if the radials are in order and cover 0–360° exactly once with
+    /// a consistent width
+    pub fn is_clean(&self) -> bool {
+        self.gaps.is_empty()
+            && self.overlaps.is_empty()
+            && self.out_of_order.is_empty()
+            && self.has_consistent_widths()
+    }
+
+    pub fn has_consistent_widths(&self) -> bool {
+        self.max_width - self.min_width <= TOLERANCE
+    }
+
+    /// Total degrees not covered by any radial
+    pub fn gap_degrees(&self) -> f32 {
+        self.gaps.iter().map(|g| g.end - g.start).sum()
+    }
+}
+
+impl PrecipRate {
+    /// Check whether the radials cover the full circle without gaps or
+    /// overlaps, are ordered by azimuth, and have consistent widths
+    pub fn radial_diagnostics(&self) -> RadialDiagnostics {
+        let mut out_of_order = Vec::new();
+        let mut wrapped = false;
+        for idx in 1..self.radials.len() {
+            if self.radials[idx].azimuth < self.radials[idx - 1].azimuth {
+                // scans can start at any azimuth, so allow one wrap past north
+                if wrapped || self.radials[idx - 1].azimuth - self.radials[idx].azimuth < 180. {
+                    out_of_order.push(idx);
+                } else {
+                    wrapped = true;
+                }
+            }
+        }
+
+        let mut spans: Vec<AzimuthSpan> = self
+            .radials
+            .iter()
+            .map(|r| {
+                let start = (r.azimuth - r.width / 2.).rem_euclid(360.);
+                AzimuthSpan {
+                    start,
+                    end: start + r.width,
+                }
+            })
+            .collect();
+        spans.sort_by(|a, b| a.start.partial_cmp(&b.start).unwrap());
+        let (mut gaps, mut overlaps) = (Vec::new(), Vec::new());
+        match spans.first() {
+            None => gaps.push(AzimuthSpan {
+                start: 0.,
+                end: 360.,
+            }),
+            Some(first) => {
+                // walk around the circle, comparing each span to the one that
+                // ends farthest clockwise so far, and finally to the first
+                // span again on its way back around
+                let wrap = AzimuthSpan {
+                    start: first.start + 360.,
+                    end: first.end + 360.,
+                };
+                let mut reach = first.end;
+                for span in spans.iter().skip(1).chain(std::iter::once(&wrap)) {
+                    if span.start - reach > TOLERANCE {
+                        gaps.push(AzimuthSpan {
+                            start: reach,
+                            end: span.start,
+                        });
+                    } else if reach - span.start > TOLERANCE {
+                        overlaps.push(AzimuthSpan {
+                            start: span.start,
+                            end: reach.min(span.end),
+                        });
+                    }
+                    reach = reach.max(span.end);
+                }
+            }
+        }
+
+        let widths = self.radials.iter().map(|r| r.width);
+        RadialDiagnostics {
+            gaps,
+            overlaps,
+            out_of_order,
+            min_width: widths.clone().fold(f32::INFINITY, f32::min),
+            max_width: widths.fold(f32::NEG_INFINITY, f32::max),
+        }
+    }
+
+    /// Distance in kilometers from the station to the far edge of the last
+    /// bin of the longest radial
+    pub fn max_range(&self) -> f32 {
+        let bins = self
+            .radials
+            .iter()
+            .map(|r| r.precip_rates.len())
+            .max()
+            .unwrap_or(0);
+        self.range_to_first_bin + self.bin_size * bins as f32
+    }
+
+    /// The area the scan covers, as closed rings of (latitude, longitude)
+    /// coordinates in degrees: a circle out to [`max_range`], going
+    /// counterclockwise like [`BinRef::polygon`], then a clockwise hole out
+    /// to `range_to_first_bin` if the first bin doesn't start at the
+    /// station. This is the whole circle even if some radials are missing.
+    ///
+    /// [`max_range`]: Self::max_range
+    /// [`BinRef::polygon`]: crate::output::BinRef::polygon
+    pub fn coverage_polygon(&self) -> Vec<Vec<(f32, f32)>> {
+        let station = (self.latitude, self.longitude);
+        let circle = |range: f32| -> Vec<(f32, f32)> {
+            let mut ring: Vec<(f32, f32)> = (0..COVERAGE_POINTS)
+                .map(|idx| {
+                    let bearing = 360. * idx as f32 / COVERAGE_POINTS as f32;
+                    get_point_bearing_distance(station, bearing, range)
+                })
+                .collect();
+            ring.push(ring[0]);
+            ring
+        };
+        // bearings go clockwise, so the outer ring is the one to reverse
+        let mut outer = circle(self.max_range());
+        outer.reverse();
+        let mut rings = vec![outer];
+        if self.range_to_first_bin > 0. {
+            rings.push(circle(self.range_to_first_bin));
+        }
+        rings
+    }
+}
+
+#[cfg(test)]
+pub(crate) fn scan_with_azimuths(azimuths: &[f32]) -> PrecipRate {
+    PrecipRate {
+        station_code: String::from("KGYX"),
+        capture_time: chrono::NaiveDateTime::from_timestamp(0, 0),
+        scan_number: 1,
+        latitude: 43.891,
+        longitude: -70.256,
+        operational_mode: crate::parse::OperationalMode::Precipitation,
+        precip_detected: true,
+        bin_size: 0.25,
+        range_to_first_bin: 0.,
+        times: Default::default(),
+        metadata: None,
+        extra_components: Vec::new(),
+        alphanumeric: Default::default(),
+        radials: azimuths
+            .iter()
+            .map(|&azimuth| crate::parse::Radial {
+                azimuth,
+                elevation: 0.5,
+                width: 1.,
+                precip_rates: vec![0.],
+                interpolated: false,
+            })
+            .collect(),
+    }
+}
+
+#[test]
+fn test_coverage_polygon() {
+    let mut scan = scan_with_azimuths(&[0.5, 1.5]);
+    scan.radials[0].precip_rates = vec![0.; 920];
+    assert_eq!(scan.max_range(), 230.);
+    let rings = scan.coverage_polygon();
+    assert_eq!(rings.len(), 1);
+    assert_eq!(rings[0].len(), COVERAGE_POINTS + 1);
+    assert_eq!(rings[0].first(), rings[0].last());
+    let station = (scan.latitude, scan.longitude);
+    for &point in rings[0].iter() {
+        let distance = crate::geomath::get_distance_between_points(station, point);
+        assert!((distance - 230.).abs() < 1.);
+    }
+    // north, then west, so counterclockwise
+    assert!(rings[0][0].0 > station.0);
+    assert!(rings[0][90].1 < station.1);
+
+    scan.range_to_first_bin = 2.;
+    let rings = scan.coverage_polygon();
+    assert_eq!(scan.max_range(), 232.);
+    assert_eq!(rings.len(), 2);
+    // the hole goes the other way around
+    assert!(rings[1][90].1 > station.1);
+    let distance = crate::geomath::get_distance_between_points(station, rings[1][0]);
+    assert!((distance - 2.).abs() < 0.01);
+}
+
+#[test]
+fn test_radial_diagnostics() {
+    // a full scan that starts at 180 and wraps past north
+    let full: Vec<f32> = (0..360).map(|a| (a as f32 + 180.5) % 360.).collect();
+    assert!(scan_with_azimuths(&full).radial_diagnostics().is_clean());
+
+    let mut broken: Vec<f32> = (0..360).map(|a| a as f32 + 0.5).collect();
+    broken.retain(|&a| !(10. ..15.).contains(&a));
+    broken.swap(100, 101);
+    broken.push(20.5);
+    let diagnostics = scan_with_azimuths(&broken).radial_diagnostics();
+    assert_eq!(
+        diagnostics.gaps,
+        vec![AzimuthSpan {
+            start: 10.,
+            end: 15.
+        }]
+    );
+    assert_eq!(
+        diagnostics.overlaps,
+        vec![AzimuthSpan {
+            start: 20.,
+            end: 21.
+        }]
+    );
+    assert_eq!(diagnostics.out_of_order, vec![101]);
+}
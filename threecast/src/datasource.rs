@@ -0,0 +1,98 @@
+//! A [geozero](https://docs.rs/geozero) datasource for scans, so that any
+//! geozero sink, like its GeoJSON, WKB, PostGIS, or GDAL writers, can take
+//! the bins without a writer of its own here
+
+use geozero::error::Result;
+use geozero::{ColumnValue, FeatureProcessor, GeozeroDatasource};
+
+use crate::output::BinRef;
+use crate::parse::PrecipRate;
+
+/// Send one bin to `processor` as a polygon feature with the same
+/// properties as [`crate::output::default_properties`]. Bins without data
+/// have no `precipRate` property.
+fn process_bin<P: FeatureProcessor>(bin: &BinRef, idx: u64, processor: &mut P) -> Result<()> {
+    processor.feature_begin(idx)?;
+    processor.properties_begin()?;
+    let mut column = 0;
+    if !bin.is_missing() {
+        processor.property(column, "precipRate", &ColumnValue::Float(bin.rate()))?;
+        column += 1;
+    }
+    if bin.is_interpolated() {
+        processor.property(column, "interpolated", &ColumnValue::Bool(true))?;
+    }
+    processor.properties_end()?;
+
+    processor.geometry_begin()?;
+    let ring = bin.polygon();
+    processor.polygon_begin(true, 1, 0)?;
+    processor.linestring_begin(false, ring.len(), 0)?;
+    for (idx, (latitude, longitude)) in ring.into_iter().enumerate() {
+        processor.xy(longitude as f64, latitude as f64, idx)?;
+    }
+    processor.linestring_end(false, 0)?;
+    processor.polygon_end(true, 0)?;
+    processor.geometry_end()?;
+    processor.feature_end(idx)
+}
+
+/// Every bin as a polygon feature in longitude/latitude, in a dataset named
+/// after the station
+impl GeozeroDatasource for PrecipRate {
+    fn process<P: FeatureProcessor>(&mut self, processor: &mut P) -> Result<()> {
+        processor.dataset_begin(Some(&self.station_code.to_uppercase()))?;
+        for (idx, bin) in self.bins().enumerate() {
+            process_bin(&bin, idx as u64, processor)?;
+        }
+        processor.dataset_end()
+    }
+}
+
+#[test]
+fn test_geozero_datasource() {
+    use geozero::{GeomProcessor, PropertyProcessor};
+
+    #[derive(Default)]
+    struct Recorder {
+        dataset: Option<String>,
+        features: u64,
+        rates: Vec<f32>,
+        points: Vec<(f64, f64)>,
+    }
+    impl GeomProcessor for Recorder {
+        fn xy(&mut self, x: f64, y: f64, _idx: usize) -> Result<()> {
+            self.points.push((x, y));
+            Ok(())
+        }
+    }
+    impl PropertyProcessor for Recorder {
+        fn property(&mut self, _idx: usize, name: &str, value: &ColumnValue) -> Result<bool> {
+            if let ("precipRate", ColumnValue::Float(rate)) = (name, value) {
+                self.rates.push(*rate);
+            }
+            Ok(false)
+        }
+    }
+    impl FeatureProcessor for Recorder {
+        fn dataset_begin(&mut self, name: Option<&str>) -> Result<()> {
+            self.dataset = name.map(String::from);
+            Ok(())
+        }
+        fn feature_end(&mut self, _idx: u64) -> Result<()> {
+            self.features += 1;
+            Ok(())
+        }
+    }
+
+    let mut scan = crate::coverage::scan_with_azimuths(&[0.5, 1.5]);
+    scan.radials[0].precip_rates = vec![0.25, f32::NAN];
+    let mut recorder = Recorder::default();
+    scan.process(&mut recorder).unwrap();
+    assert_eq!(recorder.dataset.as_deref(), Some("KGYX"));
+    assert_eq!(recorder.features, 3);
+    assert_eq!(recorder.rates, vec![0.25, 0.]);
+    assert_eq!(recorder.points.len(), 15);
+    let (latitude, longitude) = scan.bins().next().unwrap().polygon()[0];
+    assert_eq!(recorder.points[0], (longitude as f64, latitude as f64));
+}
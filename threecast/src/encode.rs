@@ -0,0 +1,393 @@
+//! Writing scans back out as Level III digital precipitation rate products,
+//! for round-trip testing of the parser and for making test files, like
+//! fixtures with the data cut down or scans made up from scratch
+//!
+//! Fields that a [`PrecipRate`] doesn't hold, like the volume coverage
+//! pattern, come from its [`ProductMetadata`] if it has one and are zero
+//! otherwise. The symbology block is compressed with bzip2 if the `libbz2`
+//! feature is enabled, since the pure-Rust bzip2 crate can only decompress,
+//! and is left uncompressed otherwise.
+
+use chrono::NaiveDateTime;
+
+use crate::alphanumeric::AlphanumericBlocks;
+use crate::parse::{
+    OperationalMode, PrecipRate, ProductMetadata, RadarType, Radial, FIRST_TDWR_SOURCE_ID,
+};
+
+const PRODUCT_CODE: i16 = 176;
+
+/// Bin value that marks missing data, as in the parser
+const MISSING_DATA_CODE: u16 = u16::MAX;
+
+/// Offset of the symbology block from the start of the message, in
+/// halfwords, right after the message header and product description
+const SYMBOLOGY_OFFSET: i32 = (18 + 102) / 2;
+
+/// Split a time into days since 1969-12-31 and seconds after midnight, or
+/// zeros for `None`
+fn julian(time: Option<NaiveDateTime>) -> (i16, i32) {
+    match time {
+        Some(t) => (
+            (t.timestamp().div_euclid(86400) + 1) as i16,
+            t.timestamp().rem_euclid(86400) as i32,
+        ),
+        None => (0, 0),
+    }
+}
+
+/// Append an XDR string, padded to a multiple of four bytes
+fn xdr_string(data: &mut Vec<u8>, s: &str) {
+    data.extend((s.len() as u32).to_be_bytes());
+    data.extend(s.as_bytes());
+    data.resize(data.len() + (4 - s.len() % 4) % 4, 0);
+}
+
+/// The WMO and AWIPS headers, which are the only place the station code is
+/// kept. TDWR products are sent by a WSR-88D office, which is made up
+/// here from the site.
+fn text_header(scan: &PrecipRate) -> Vec<u8> {
+    let station = scan.station_code.to_uppercase();
+    let site = station.get(1..).unwrap_or("");
+    let office = match scan.radar_type() {
+        RadarType::Wsr88d => station.clone(),
+        RadarType::Tdwr => format!("K{}", site),
+    };
+    let mut header = format!(
+        "SDUS51 {:4.4} {}\r\r\nDPR{:3.3}\r\r\n",
+        office,
+        scan.capture_time.format("%d%H%M"),
+        site
+    )
+    .into_bytes();
+    header.resize(30, b' ');
+    header
+}
+
+/// The product description block (Figure 3-6) for a symbology block of
+/// `uncompressed_size` bytes compressed with `compression`
+fn product_description(
+    scan: &PrecipRate,
+    metadata: &ProductMetadata,
+    compression: i16,
+    uncompressed_size: u32,
+) -> Vec<u8> {
+    let mut parameters = metadata.parameters;
+    parameters[2] = (scan.precip_detected as i16) << 8 | (parameters[2] & 0xff);
+    parameters[7] = compression;
+    parameters[8] = (uncompressed_size >> 16) as u16 as i16;
+    parameters[9] = uncompressed_size as u16 as i16;
+    let (volume_date, volume_time) = julian(scan.times.volume_start.or(Some(scan.capture_time)));
+    let (generation_date, generation_time) = julian(scan.times.generation);
+
+    let mut block = (-1i16).to_be_bytes().to_vec();
+    block.extend(((scan.latitude * 1000.).round() as i32).to_be_bytes());
+    block.extend(((scan.longitude * 1000.).round() as i32).to_be_bytes());
+    block.extend(metadata.height.to_be_bytes());
+    block.extend(PRODUCT_CODE.to_be_bytes());
+    let mode: i16 = match scan.operational_mode {
+        OperationalMode::Maintenance => 0,
+        OperationalMode::CleanAir => 1,
+        OperationalMode::Precipitation => 2,
+    };
+    block.extend(mode.to_be_bytes());
+    block.extend(metadata.volume_coverage_pattern.to_be_bytes());
+    block.extend(metadata.sequence_number.to_be_bytes());
+    block.extend((scan.scan_number as i16).to_be_bytes());
+    block.extend(volume_date.to_be_bytes());
+    block.extend(volume_time.to_be_bytes());
+    block.extend(generation_date.to_be_bytes());
+    block.extend(generation_time.to_be_bytes());
+    block.extend(parameters[0].to_be_bytes());
+    block.extend(parameters[1].to_be_bytes());
+    block.extend(metadata.elevation_number.to_be_bytes());
+    block.extend(parameters[2].to_be_bytes());
+    for threshold in metadata.thresholds {
+        block.extend(threshold.to_be_bytes());
+    }
+    for parameter in parameters[3..].iter() {
+        block.extend(parameter.to_be_bytes());
+    }
+    block.extend([metadata.version, metadata.spot_blank as u8]);
+    block.extend(SYMBOLOGY_OFFSET.to_be_bytes());
+    block.extend([0; 8]); // no graphic or tabular blocks
+    block
+}
+
+/// The symbology block holding one generic radial packet (Figure 3-15c) in
+/// the layout of the digital precipitation rate product (Figures E-1 to
+/// E-4)
+fn product_symbology(scan: &PrecipRate, metadata: &ProductMetadata) -> Vec<u8> {
+    let time = |t: Option<NaiveDateTime>| t.map_or(0, |t| t.timestamp() as u32);
+    let mut block = vec![0; 24];
+    xdr_string(&mut block, "DPR");
+    xdr_string(&mut block, "Digital Precipitation Rate");
+    block.extend((PRODUCT_CODE as i32).to_be_bytes());
+    block.extend([0; 4]);
+    block.extend(time(scan.times.generation).to_be_bytes());
+    xdr_string(&mut block, &scan.station_code.to_uppercase());
+    block.extend(scan.latitude.to_be_bytes());
+    block.extend(scan.longitude.to_be_bytes());
+    block.extend((metadata.height as f32).to_be_bytes());
+    block.extend(time(Some(scan.capture_time)).to_be_bytes());
+    block.extend(time(scan.times.elevation_start).to_be_bytes());
+    block.extend(metadata.elevation_angle().to_be_bytes());
+    block.extend(scan.scan_number.to_be_bytes());
+    // the rest of the product description is in the product description
+    // block, and there are no parameters
+    block.extend([0; 28]);
+
+    let first = (
+        "",
+        scan.bin_size,
+        scan.range_to_first_bin,
+        &[][..],
+        &scan.radials,
+    );
+    let extra = scan.extra_components.iter().map(|c| {
+        (
+            c.description.as_str(),
+            c.bin_size,
+            c.range_to_first_bin,
+            c.parameters.as_slice(),
+            &c.radials,
+        )
+    });
+    let components: Vec<_> = std::iter::once(first).chain(extra).collect();
+    block.extend((components.len() as i32).to_be_bytes());
+    for (description, bin_size, range_to_first_bin, parameters, radials) in components {
+        // pointer, then radial component type
+        block.extend([0; 4]);
+        block.extend(1i32.to_be_bytes());
+        radial_component(
+            &mut block,
+            description,
+            bin_size,
+            range_to_first_bin,
+            parameters,
+            radials,
+        );
+    }
+
+    // fill in the block, layer, and packet headers now that the length is
+    // known
+    let length = block.len() as u32;
+    block[..2].copy_from_slice(&(-1i16).to_be_bytes());
+    block[2..4].copy_from_slice(&1i16.to_be_bytes());
+    block[4..8].copy_from_slice(&length.to_be_bytes());
+    block[8..10].copy_from_slice(&1i16.to_be_bytes());
+    block[10..12].copy_from_slice(&(-1i16).to_be_bytes());
+    block[12..16].copy_from_slice(&(length - 16).to_be_bytes());
+    block[16..18].copy_from_slice(&28i16.to_be_bytes());
+    block[20..24].copy_from_slice(&(length - 24).to_be_bytes());
+    block
+}
+
+/// Append a radial component (Figure E-3)
+fn radial_component(
+    block: &mut Vec<u8>,
+    description: &str,
+    bin_size: f32,
+    range_to_first_bin: f32,
+    parameters: &[(String, String)],
+    radials: &[Radial],
+) {
+    xdr_string(block, description);
+    block.extend((bin_size * 1000.).to_be_bytes());
+    block.extend((range_to_first_bin * 1000.).to_be_bytes());
+    block.extend((parameters.len() as i32).to_be_bytes());
+    for (name, value) in parameters {
+        // pointer to the next parameter
+        block.extend([0; 4]);
+        xdr_string(block, name);
+        xdr_string(block, value);
+    }
+    if parameters.is_empty() {
+        block.extend([0; 4]);
+    }
+    block.extend((radials.len() as i32).to_be_bytes());
+    for radial in radials {
+        block.extend(radial.azimuth.to_be_bytes());
+        block.extend(radial.elevation.to_be_bytes());
+        block.extend(radial.width.to_be_bytes());
+        block.extend((radial.precip_rates.len() as i32).to_be_bytes());
+        xdr_string(block, "");
+        block.extend((radial.precip_rates.len() as i32).to_be_bytes());
+        for rate in radial.precip_rates.iter() {
+            let code = match rate.is_nan() {
+                true => MISSING_DATA_CODE,
+                false => (rate * 1000.)
+                    .round()
+                    .clamp(0., (MISSING_DATA_CODE - 1) as f32) as u16,
+            };
+            block.extend([0, 0]);
+            block.extend(code.to_be_bytes());
+        }
+    }
+}
+
+/// The graphic and tabular alphanumeric blocks, each left out if it has no
+/// pages. Graphic lines become text packets at the origin, and the copy of
+/// the headers in the tabular block is left blank.
+fn alphanumeric_blocks(blocks: &AlphanumericBlocks) -> Vec<u8> {
+    let mut data = Vec::new();
+    if !blocks.graphic.is_empty() {
+        let mut block = (-1i16).to_be_bytes().to_vec();
+        block.extend(2i16.to_be_bytes());
+        block.extend([0; 4]);
+        block.extend((blocks.graphic.len() as i16).to_be_bytes());
+        for (idx, page) in blocks.graphic.iter().enumerate() {
+            let mut packets = Vec::new();
+            for line in page {
+                packets.extend(1i16.to_be_bytes());
+                packets.extend(((line.len() + 4) as i16).to_be_bytes());
+                packets.extend([0; 4]);
+                packets.extend(line.as_bytes());
+            }
+            block.extend((idx as i16 + 1).to_be_bytes());
+            block.extend((packets.len() as i16).to_be_bytes());
+            block.extend(packets);
+        }
+        let length = (block.len() as u32).to_be_bytes();
+        block[4..8].copy_from_slice(&length);
+        data.extend(block);
+    }
+    if !blocks.tabular.is_empty() {
+        let mut block = (-1i16).to_be_bytes().to_vec();
+        block.extend(3i16.to_be_bytes());
+        block.extend([0; 4]);
+        block.extend([0; 18 + 102]);
+        block.extend((-1i16).to_be_bytes());
+        block.extend((blocks.tabular.len() as i16).to_be_bytes());
+        for page in blocks.tabular.iter() {
+            for line in page {
+                block.extend((line.len() as i16).to_be_bytes());
+                block.extend(line.as_bytes());
+            }
+            block.extend((-1i16).to_be_bytes());
+        }
+        let length = (block.len() as u32).to_be_bytes();
+        block[4..8].copy_from_slice(&length);
+        data.extend(block);
+    }
+    data
+}
+
+/// Compress the symbology block if possible, and return it along with the
+/// compression method
+#[cfg(feature = "libbz2")]
+fn compress(symbology: Vec<u8>) -> (Vec<u8>, i16) {
+    use std::io::Write;
+    let mut encoder = bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::best());
+    encoder
+        .write_all(&symbology)
+        .expect("Writing to a Vec can't fail");
+    (encoder.finish().expect("Writing to a Vec can't fail"), 1)
+}
+
+#[cfg(not(feature = "libbz2"))]
+fn compress(symbology: Vec<u8>) -> (Vec<u8>, i16) {
+    (symbology, 0)
+}
+
+/// Serialize `scan` as a digital precipitation rate product that
+/// [`crate::parse::parse_dpr`] reads back as the same scan. Rates are
+/// rounded to the nearest thousandth of an inch per hour, like in the
+/// original files.
+pub fn write_dpr(scan: &PrecipRate) -> Vec<u8> {
+    let metadata = scan.metadata.clone().unwrap_or(ProductMetadata {
+        latitude: scan.latitude,
+        longitude: scan.longitude,
+        height: 0,
+        product_code: PRODUCT_CODE,
+        operational_mode: scan.operational_mode.clone(),
+        volume_coverage_pattern: 0,
+        sequence_number: 0,
+        volume_scan_number: scan.scan_number as i16,
+        volume_start: Some(scan.capture_time),
+        generation_time: scan.times.generation,
+        elevation_number: 0,
+        parameters: [0; 10],
+        thresholds: [0; 16],
+        version: 0,
+        spot_blank: false,
+        symbology_offset: SYMBOLOGY_OFFSET,
+        graphic_offset: 0,
+        tabular_offset: 0,
+    });
+    let mut symbology = product_symbology(scan, &metadata);
+    symbology.extend(alphanumeric_blocks(&scan.alphanumeric));
+    let uncompressed_size = symbology.len() as u32;
+    let (symbology, compression) = compress(symbology);
+    let description = product_description(scan, &metadata, compression, uncompressed_size);
+
+    let length = (18 + description.len() + symbology.len()) as u32;
+    let (message_date, message_time) = julian(scan.times.message);
+    let mut data = text_header(scan);
+    data.extend(PRODUCT_CODE.to_be_bytes());
+    data.extend(message_date.to_be_bytes());
+    data.extend(message_time.to_be_bytes());
+    data.extend(length.to_be_bytes());
+    let source_id = match scan.radar_type() {
+        RadarType::Wsr88d => 0,
+        RadarType::Tdwr => FIRST_TDWR_SOURCE_ID,
+    };
+    data.extend(source_id.to_be_bytes());
+    data.extend([0; 2]); // destination ID
+    data.extend(3i16.to_be_bytes()); // header, description, and symbology
+    data.extend(description);
+    data.extend(symbology);
+    data
+}
+
+#[test]
+fn test_write_dpr_round_trip() {
+    use crate::parse::{parse_dpr, parse_dpr_with, ParseOptions};
+
+    let original = parse_dpr(crate::parse::dpr_bytes(&[
+        vec![0, 250, MISSING_DATA_CODE],
+        vec![500, 1],
+    ]))
+    .unwrap();
+    let data = write_dpr(&original);
+    let strict = ParseOptions {
+        strict: true,
+        ..Default::default()
+    };
+    let (scan, _) = parse_dpr_with(data.clone(), &strict).unwrap();
+    assert_eq!(scan.station_code, original.station_code);
+    assert_eq!(scan.capture_time, original.capture_time);
+    assert_eq!(scan.scan_number, original.scan_number);
+    assert_eq!((scan.latitude, scan.longitude), (43.891, -70.256));
+    assert_eq!(scan.operational_mode, original.operational_mode);
+    assert_eq!(scan.precip_detected, original.precip_detected);
+    assert_eq!(scan.bin_size, original.bin_size);
+    assert_eq!(scan.times, original.times);
+    assert_eq!(scan.radials.len(), 2);
+    for (radial, original) in scan.radials.iter().zip(original.radials.iter()) {
+        assert_eq!(radial.azimuth, original.azimuth);
+        assert_eq!(radial.width, original.width);
+        assert_eq!(
+            format!("{:?}", radial.precip_rates),
+            format!("{:?}", original.precip_rates)
+        );
+    }
+    let (metadata, original) = (scan.metadata.unwrap(), original.metadata.unwrap());
+    assert_eq!(metadata.thresholds, original.thresholds);
+    assert_eq!(metadata.parameters[..7], original.parameters[..7]);
+
+    // TDWR station codes survive the trip too
+    let mut tdwr = parse_dpr(data.clone()).unwrap();
+    tdwr.station_code = String::from("TBOS");
+    assert_eq!(parse_dpr(write_dpr(&tdwr)).unwrap().station_code, "TBOS");
+
+    // writing the parsed scan again gives the same bytes
+    assert_eq!(write_dpr(&parse_dpr(data.clone()).unwrap()), data);
+
+    // and so does a scan without metadata, apart from what only the
+    // metadata holds
+    let mut scan = parse_dpr(data).unwrap();
+    scan.metadata = None;
+    let scan = parse_dpr(write_dpr(&scan)).unwrap();
+    assert_eq!(scan.radials[1].precip_rates, [0.5, 0.001]);
+}
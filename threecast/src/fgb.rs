@@ -0,0 +1,115 @@
+//! [FlatGeobuf](https://flatgeobuf.org/) output, which web map clients can
+//! read straight from a static file server thanks to its spatial index
+
+use std::error::Error;
+use std::io::Write;
+
+use flatgeobuf::geozero::{ColumnValue, GeomProcessor, GeozeroGeometry, PropertyProcessor};
+use flatgeobuf::{ColumnType, FgbCrs, FgbWriter, FgbWriterOptions, GeometryType};
+
+use crate::output::{BinRef, OutputSink};
+use crate::parse::PrecipRate;
+
+/// Writes bins as polygon features of a FlatGeobuf file with a
+/// `precipRate` column, which is null for bins without data. The index
+/// sorts features spatially, so all of a scan's features are buffered in a
+/// temporary file until [`OutputSink::finish`].
+pub struct FlatGeobufSink<W: Write> {
+    writer: W,
+    fgb: Option<FgbWriter<'static>>,
+}
+
+impl<W: Write> FlatGeobufSink<W> {
+    pub fn new(writer: W) -> Self {
+        FlatGeobufSink { writer, fgb: None }
+    }
+
+    /// Give back the underlying writer
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// The outline of a bin as longitude/latitude pairs
+struct BinPolygon(Vec<(f32, f32)>);
+
+impl GeozeroGeometry for BinPolygon {
+    fn process_geom<P: GeomProcessor>(
+        &self,
+        processor: &mut P,
+    ) -> flatgeobuf::geozero::error::Result<()> {
+        processor.polygon_begin(true, 1, 0)?;
+        processor.linestring_begin(false, self.0.len(), 0)?;
+        for (idx, (latitude, longitude)) in self.0.iter().enumerate() {
+            processor.xy(*longitude as f64, *latitude as f64, idx)?;
+        }
+        processor.linestring_end(false, 0)?;
+        processor.polygon_end(true, 0)
+    }
+}
+
+impl<W: Write> OutputSink for FlatGeobufSink<W> {
+    fn begin(&mut self, scan: &PrecipRate) -> Result<(), Box<dyn Error>> {
+        let mut fgb = FgbWriter::create_with_options(
+            &scan.station_code.to_uppercase(),
+            GeometryType::Polygon,
+            FgbWriterOptions {
+                crs: FgbCrs {
+                    code: 4326,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        )?;
+        fgb.add_column("precipRate", ColumnType::Float, |_, column| {
+            column.nullable = true;
+        });
+        self.fgb = Some(fgb);
+        Ok(())
+    }
+
+    fn write_bin(&mut self, bin: &BinRef) -> Result<(), Box<dyn Error>> {
+        let fgb = match self.fgb.as_mut() {
+            Some(fgb) => fgb,
+            None => return Err("FlatGeobufSink got a bin before a scan".into()),
+        };
+        let rate = bin.rate();
+        let mut result = Ok(false);
+        fgb.add_feature_geom(BinPolygon(bin.polygon()), |feature| {
+            if !rate.is_nan() {
+                result = feature.property(0, "precipRate", &ColumnValue::Float(rate));
+            }
+        })?;
+        result?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Box<dyn Error>> {
+        if let Some(fgb) = self.fgb.take() {
+            fgb.write(&mut self.writer)?;
+        }
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+#[test]
+fn test_flatgeobuf_sink() {
+    use flatgeobuf::{FallibleStreamingIterator, FeatureProperties, FgbReader};
+    let mut scan = crate::coverage::scan_with_azimuths(&[0.5, 1.5]);
+    scan.radials[0].precip_rates = vec![0.25, f32::NAN];
+    let mut sink = FlatGeobufSink::new(Vec::new());
+    crate::output::write_scan(&scan, &mut sink).unwrap();
+    let data = sink.into_inner();
+    let mut reader = FgbReader::open(std::io::Cursor::new(data))
+        .unwrap()
+        .select_all()
+        .unwrap();
+    assert_eq!(reader.header().features_count(), 3);
+    let mut rates = Vec::new();
+    while let Some(feature) = reader.next().unwrap() {
+        rates.push(feature.property::<f32>("precipRate").ok());
+    }
+    rates.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    assert_eq!(rates, vec![None, Some(0.), Some(0.25)]);
+}
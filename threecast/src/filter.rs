@@ -0,0 +1,120 @@
+use crate::output::BinRef;
+use crate::parse::coord_as_i64;
+use crate::util::precip_category;
+use crate::watch::contains;
+
+/// Decides which bins to keep when iterating over or writing out a scan.
+/// Closures of the form `Fn(&BinRef) -> bool` are filters too, and a `Vec` of
+/// boxed filters keeps only the bins that pass all of them.
+pub trait BinFilter {
+    fn keep(&self, bin: &BinRef) -> bool;
+}
+
+impl<F: Fn(&BinRef) -> bool> BinFilter for F {
+    fn keep(&self, bin: &BinRef) -> bool {
+        self(bin)
+    }
+}
+
+impl BinFilter for Vec<Box<dyn BinFilter>> {
+    fn keep(&self, bin: &BinRef) -> bool {
+        self.iter().all(|f| f.keep(bin))
+    }
+}
+
+/// Keep bins with a rate of at least this many in/hr
+pub struct MinRate(pub f32);
+
+impl BinFilter for MinRate {
+    fn keep(&self, bin: &BinRef) -> bool {
+        bin.rate() >= self.0
+    }
+}
+
+/// Keep bins whose centers are inside a latitude/longitude box
+pub struct BoundingBox {
+    pub south: f32,
+    pub west: f32,
+    pub north: f32,
+    pub east: f32,
+}
+
+impl BinFilter for BoundingBox {
+    fn keep(&self, bin: &BinRef) -> bool {
+        let (lat, lon) = bin.center();
+        lat >= self.south && lat <= self.north && lon >= self.west && lon <= self.east
+    }
+}
+
+/// Keep bins that lie entirely between `min` and `max` kilometers from the
+/// station
+pub struct RangeLimits {
+    pub min: f32,
+    pub max: f32,
+}
+
+impl BinFilter for RangeLimits {
+    fn keep(&self, bin: &BinRef) -> bool {
+        let (near, far) = bin.range();
+        near >= self.min && far <= self.max
+    }
+}
+
+/// Keep bins in any of the given intensity categories, as named by
+/// [`precip_category`]
+pub struct Categories(pub Vec<&'static str>);
+
+impl BinFilter for Categories {
+    fn keep(&self, bin: &BinRef) -> bool {
+        self.0.contains(&precip_category(bin.rate()))
+    }
+}
+
+/// Keep bins whose centers are inside a closed ring of (latitude,
+/// longitude) vertices in degrees
+pub struct InPolygon {
+    ring: Vec<[i64; 2]>,
+}
+
+impl InPolygon {
+    pub fn new(vertices: &[(f32, f32)]) -> Self {
+        InPolygon {
+            ring: vertices
+                .iter()
+                .map(|v| [coord_as_i64(v.0), coord_as_i64(v.1)])
+                .collect(),
+        }
+    }
+}
+
+impl BinFilter for InPolygon {
+    fn keep(&self, bin: &BinRef) -> bool {
+        let (latitude, longitude) = bin.center();
+        contains(
+            &self.ring,
+            [coord_as_i64(latitude), coord_as_i64(longitude)],
+        )
+    }
+}
+
+/// What to do with bins that have no data when writing out a scan
+#[derive(Debug, Clone, Copy, PartialEq, Default, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MissingData {
+    /// Leave them out
+    Drop,
+    /// Write them with a rate of zero, like bins without precipitation
+    Zero,
+    /// Write them with a `NaN` rate, which becomes `null` in JSON
+    #[default]
+    Flag,
+}
+
+/// Keep bins that have data
+pub struct DropMissing;
+
+impl BinFilter for DropMissing {
+    fn keep(&self, bin: &BinRef) -> bool {
+        !bin.is_missing()
+    }
+}
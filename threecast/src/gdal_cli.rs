@@ -0,0 +1,294 @@
+//! An escape hatch to GDAL's drivers for formats without a native writer,
+//! behind the `gdal-cli` feature. This wraps the GDAL command-line tools
+//! rather than linking to libgdal through the `gdal` crate, so that the
+//! feature doesn't need the GDAL headers and libraries at build time. Output
+//! is written in a native format first and then converted with the
+//! `ogr2ogr` and `gdal_translate` tools, which must be on the `PATH` when
+//! it runs. [`PrecipRate::to_ogr_layer`] keeps a scan in memory as GeoJSON
+//! and streams it to `ogr2ogr` when it's translated, so no temporary file is
+//! needed.
+//!
+//! There is no libgdal-backed feature alongside this one. Anything that needs
+//! a real GDAL dataset, or a driver that can't be fed through the command-line
+//! tools, is out of scope.
+
+use std::error::Error;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::filter::BinFilter;
+use crate::output::{default_properties, write_scan, write_scan_filtered, GeoJsonSink};
+use crate::parse::{GridData, PrecipRate};
+
+/// Written for cells without data, although every cell of a sampled grid has
+/// a value
+const NODATA: f32 = -9999.;
+
+/// Numbers temporary files so that concurrent conversions don't collide
+static TEMP_FILES: AtomicUsize = AtomicUsize::new(0);
+
+fn temp_path(extension: &str) -> PathBuf {
+    std::env::temp_dir().join(format!(
+        "threecast-{}-{}.{}",
+        std::process::id(),
+        TEMP_FILES.fetch_add(1, Ordering::Relaxed),
+        extension
+    ))
+}
+
+/// Run a GDAL tool, turning a missing tool or a nonzero exit into an error
+fn run(tool: &str, args: &[&std::ffi::OsStr]) -> Result<(), Box<dyn Error>> {
+    run_with_input(tool, args, &[])
+}
+
+/// Run a GDAL tool with `input` on its standard input
+fn run_with_input(
+    tool: &str,
+    args: &[&std::ffi::OsStr],
+    input: &[u8],
+) -> Result<(), Box<dyn Error>> {
+    let spawned = Command::new(tool)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn();
+    let mut child = match spawned {
+        Ok(c) => c,
+        Err(e) => return Err(format!("Failed to run {}; is GDAL installed? ({})", tool, e).into()),
+    };
+    // the tool may exit without reading everything, which is reported below
+    if let Some(mut stdin) = child.stdin.take() {
+        let _ = stdin.write_all(input);
+    }
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Err(format!(
+            "{} failed: {}",
+            tool,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// Write the bins of `scan` that pass `filter` as polygons with a
+/// `precipRate` field to `path`, using the OGR vector driver named `driver`,
+/// e.g. `"DXF"` or `"GPKG"`
+pub fn write_vector_with_gdal(
+    scan: &PrecipRate,
+    filter: &dyn BinFilter,
+    path: &Path,
+    driver: &str,
+) -> Result<(), Box<dyn Error>> {
+    let temp = temp_path("geojson");
+    let result = (|| {
+        let file = std::io::BufWriter::new(std::fs::File::create(&temp)?);
+        write_scan_filtered(scan, &mut GeoJsonSink::new(file), filter)?;
+        run(
+            "ogr2ogr",
+            &["-f".as_ref(), driver.as_ref(), path.as_ref(), temp.as_ref()],
+        )
+    })();
+    let _ = std::fs::remove_file(&temp);
+    result
+}
+
+/// A scan held in memory as the GeoJSON for a single OGR vector layer of bin
+/// polygons with a `precipRate` field, ready to be piped to `ogr2ogr` and
+/// written with any OGR driver. This isn't a libgdal dataset, so the layer
+/// can't be edited through the OGR API.
+pub struct OgrLayer {
+    layer: String,
+    geojson: Vec<u8>,
+}
+
+impl OgrLayer {
+    /// The name of the layer, which is the station code
+    pub fn layer_name(&self) -> &str {
+        &self.layer
+    }
+
+    /// The layer as the GeoJSON that OGR reads
+    pub fn as_geojson(&self) -> &[u8] {
+        &self.geojson
+    }
+
+    /// Write the layer to `path` with the OGR vector driver named `driver`,
+    /// e.g. `"FileGDB"` or `"DXF"`
+    pub fn translate(&self, path: &Path, driver: &str) -> Result<(), Box<dyn Error>> {
+        run_with_input(
+            "ogr2ogr",
+            &[
+                "-f".as_ref(),
+                driver.as_ref(),
+                "-nln".as_ref(),
+                self.layer.as_ref(),
+                path.as_ref(),
+                "/vsistdin/".as_ref(),
+            ],
+            &self.geojson,
+        )
+    }
+}
+
+impl PrecipRate {
+    /// Every bin of the scan as an in-memory layer for `ogr2ogr`
+    pub fn to_ogr_layer(&self) -> Result<OgrLayer, Box<dyn Error>> {
+        let mut geojson = Vec::new();
+        write_scan(self, &mut GeoJsonSink::new(&mut geojson))?;
+        Ok(OgrLayer {
+            layer: self.station_code.to_uppercase(),
+            geojson,
+        })
+    }
+}
+
+/// The bins of `scan` that pass `filter` as GeoJSON for a table that holds
+/// many scans, so each feature also carries the station code and capture
+/// time
+fn scan_rows_geojson(scan: &PrecipRate, filter: &dyn BinFilter) -> Result<Vec<u8>, Box<dyn Error>> {
+    let station = scan.station_code.to_uppercase();
+    let capture_time = scan.capture_time.format("%Y-%m-%dT%H:%M:%SZ").to_string();
+    let mut geojson = Vec::new();
+    let mut sink = GeoJsonSink::with_properties(&mut geojson, move |bin| {
+        let mut properties = default_properties(bin);
+        properties.insert(String::from("station"), station.clone().into());
+        properties.insert(String::from("captureTime"), capture_time.clone().into());
+        properties
+    });
+    write_scan_filtered(scan, &mut sink, filter)?;
+    drop(sink);
+    Ok(geojson)
+}
+
+/// Append the bins of `scan` that pass `filter` to `table` in the PostGIS
+/// database at `connection`, a libpq connection string like
+/// `"host=localhost dbname=radar"`. `ogr2ogr` creates the table for the
+/// first scan, and rows have `station` and `captureTime` columns alongside
+/// `precipRate`.
+pub fn append_to_postgis(
+    scan: &PrecipRate,
+    filter: &dyn BinFilter,
+    connection: &str,
+    table: &str,
+) -> Result<(), Box<dyn Error>> {
+    run_with_input(
+        "ogr2ogr",
+        &[
+            "-f".as_ref(),
+            "PostgreSQL".as_ref(),
+            "-append".as_ref(),
+            "-nln".as_ref(),
+            table.as_ref(),
+            format!("PG:{}", connection).as_ref(),
+            "/vsistdin/".as_ref(),
+        ],
+        &scan_rows_geojson(scan, filter)?,
+    )
+}
+
+/// Write `grid` to `path` in WGS84 with the GDAL raster driver named
+/// `driver`, e.g. `"GTiff"` or `"HFA"`. The grid must be regularly spaced
+/// in latitude and longitude, like the equirectangular grids.
+#[allow(clippy::ptr_arg)]
+pub fn write_raster_with_gdal(
+    grid: &GridData,
+    path: &Path,
+    driver: &str,
+) -> Result<(), Box<dyn Error>> {
+    let temp = temp_path("asc");
+    let result = (|| {
+        write_ascii_grid(grid, std::io::BufWriter::new(std::fs::File::create(&temp)?))?;
+        run(
+            "gdal_translate",
+            &[
+                "-of".as_ref(),
+                driver.as_ref(),
+                "-a_srs".as_ref(),
+                "EPSG:4326".as_ref(),
+                temp.as_ref(),
+                path.as_ref(),
+            ],
+        )
+    })();
+    let _ = std::fs::remove_file(&temp);
+    result
+}
+
+/// Write `grid` as an Esri ASCII grid, with the spacing taken from its
+/// corner cells
+#[allow(clippy::ptr_arg)]
+pub fn write_ascii_grid<W: Write>(grid: &GridData, mut writer: W) -> Result<(), Box<dyn Error>> {
+    let (rows, columns) = (grid.len(), grid.first().map_or(0, |row| row.len()));
+    if rows < 2 || columns < 2 {
+        return Err("Grid must be at least 2 by 2 to work out its spacing".into());
+    }
+    let degrees = |coord: i64| coord as f64 / 10000.;
+    let west = degrees(grid[0][0].0[1]);
+    let north = degrees(grid[0][0].0[0]);
+    let dx = (degrees(grid[0][columns - 1].0[1]) - west) / (columns - 1) as f64;
+    let dy = (north - degrees(grid[rows - 1][0].0[0])) / (rows - 1) as f64;
+    writeln!(writer, "ncols {}", columns)?;
+    writeln!(writer, "nrows {}", rows)?;
+    writeln!(writer, "xllcorner {}", west - dx / 2.)?;
+    writeln!(writer, "yllcorner {}", north - dy * (rows as f64 - 0.5))?;
+    writeln!(writer, "dx {}", dx)?;
+    writeln!(writer, "dy {}", dy)?;
+    writeln!(writer, "NODATA_value {}", NODATA)?;
+    for row in grid.iter() {
+        let values: Vec<String> = row
+            .iter()
+            .map(|(_, rate)| match rate.is_nan() {
+                true => NODATA.to_string(),
+                false => rate.to_string(),
+            })
+            .collect();
+        writeln!(writer, "{}", values.join(" "))?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+#[test]
+fn test_write_ascii_grid() {
+    let grid: GridData = vec![
+        vec![([440000, -700000], 0.), ([440000, -695000], 0.5)],
+        vec![([435000, -700000], f32::NAN), ([435000, -695000], 0.)],
+    ];
+    let mut output = Vec::new();
+    write_ascii_grid(&grid, &mut output).unwrap();
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        "ncols 2\nnrows 2\nxllcorner -70.25\nyllcorner 43.25\ndx 0.5\ndy 0.5\n\
+         NODATA_value -9999\n0 0.5\n-9999 0\n"
+    );
+}
+
+#[test]
+fn test_to_ogr_layer() {
+    let scan = crate::coverage::scan_with_azimuths(&[0.5, 1.5]);
+    let layer = scan.to_ogr_layer().unwrap();
+    assert_eq!(layer.layer_name(), "KGYX");
+    let geojson: serde_json::Value = serde_json::from_slice(layer.as_geojson()).unwrap();
+    assert_eq!(geojson["type"], "FeatureCollection");
+    assert_eq!(geojson["features"].as_array().unwrap().len(), 2);
+}
+
+#[test]
+fn test_scan_rows_geojson() {
+    let scan = crate::coverage::scan_with_azimuths(&[0.5, 1.5]);
+    let geojson = scan_rows_geojson(&scan, &|_: &crate::output::BinRef| true).unwrap();
+    let geojson: serde_json::Value = serde_json::from_slice(&geojson).unwrap();
+    let features = geojson["features"].as_array().unwrap();
+    assert_eq!(features.len(), 2);
+    assert_eq!(features[0]["properties"]["station"], "KGYX");
+    assert_eq!(
+        features[0]["properties"]["captureTime"],
+        scan.capture_time.format("%Y-%m-%dT%H:%M:%SZ").to_string()
+    );
+    assert_eq!(features[0]["properties"]["precipRate"], 0.);
+}
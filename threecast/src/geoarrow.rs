@@ -0,0 +1,157 @@
+//! [Apache Arrow](https://arrow.apache.org/) export with
+//! [GeoArrow](https://geoarrow.org/) geometry, for handing scans to
+//! DataFusion, Polars, and other Arrow-based tools without copying
+//!
+//! The `geometry` column uses the native `geoarrow.polygon` encoding: a list
+//! of rings, each a list of `{x, y}` vertices with x = longitude and
+//! y = latitude in degrees.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use arrow::array::{
+    Array, ArrayData, ArrayRef, Float32Array, Float64Array, ListArray, StructArray,
+};
+use arrow::buffer::Buffer;
+use arrow::datatypes::{DataType, Field, Schema};
+use arrow::error::ArrowError;
+use arrow::record_batch::RecordBatch;
+
+use crate::filter::BinFilter;
+use crate::output::BinRef;
+use crate::parse::PrecipRate;
+
+impl PrecipRate {
+    /// Every bin as a row with `geometry`, `precip_rate` in in/hr (null
+    /// for bins without data), `azimuth` in degrees, and `range_km` to the
+    /// center of the bin
+    pub fn to_arrow(&self) -> Result<RecordBatch, ArrowError> {
+        self.to_arrow_filtered(&|_: &BinRef| true)
+    }
+
+    /// Like [`PrecipRate::to_arrow`], but only with the bins that pass
+    /// `filter`
+    pub fn to_arrow_filtered(&self, filter: &dyn BinFilter) -> Result<RecordBatch, ArrowError> {
+        let (mut x, mut y) = (Vec::new(), Vec::new());
+        let mut ring_offsets = vec![0i32];
+        let mut polygon_offsets = vec![0i32];
+        let (mut rates, mut azimuths, mut ranges) = (Vec::new(), Vec::new(), Vec::new());
+        for bin in self.bins_filtered(filter) {
+            for (latitude, longitude) in bin.polygon() {
+                x.push(longitude as f64);
+                y.push(latitude as f64);
+            }
+            ring_offsets.push(x.len() as i32);
+            polygon_offsets.push(ring_offsets.len() as i32 - 1);
+            rates.push(if bin.is_missing() {
+                None
+            } else {
+                Some(bin.rate())
+            });
+            azimuths.push(bin.azimuth());
+            let (near, far) = bin.range();
+            ranges.push((near + far) / 2.);
+        }
+
+        let vertex_fields = vec![
+            Field::new("x", DataType::Float64, false),
+            Field::new("y", DataType::Float64, false),
+        ];
+        let vertices = StructArray::from(vec![
+            (
+                vertex_fields[0].clone(),
+                Arc::new(Float64Array::from(x)) as ArrayRef,
+            ),
+            (
+                vertex_fields[1].clone(),
+                Arc::new(Float64Array::from(y)) as ArrayRef,
+            ),
+        ]);
+        let ring_type = DataType::List(Box::new(Field::new(
+            "vertices",
+            DataType::Struct(vertex_fields),
+            false,
+        )));
+        let rings = list_array(ring_type.clone(), &ring_offsets, vertices.data().clone())?;
+        let polygon_type = DataType::List(Box::new(Field::new("rings", ring_type, false)));
+        let polygons = list_array(polygon_type.clone(), &polygon_offsets, rings.data().clone())?;
+
+        let geometry =
+            Field::new("geometry", polygon_type, false).with_metadata(Some(BTreeMap::from([(
+                String::from("ARROW:extension:name"),
+                String::from("geoarrow.polygon"),
+            )])));
+        let schema = Schema::new(vec![
+            geometry,
+            Field::new("precip_rate", DataType::Float32, true),
+            Field::new("azimuth", DataType::Float32, false),
+            Field::new("range_km", DataType::Float32, false),
+        ]);
+        RecordBatch::try_new(
+            Arc::new(schema),
+            vec![
+                Arc::new(polygons),
+                Arc::new(Float32Array::from(rates)),
+                Arc::new(Float32Array::from(azimuths)),
+                Arc::new(Float32Array::from(ranges)),
+            ],
+        )
+    }
+}
+
+/// A list array of `data_type` whose `offsets` index into `child`
+fn list_array(
+    data_type: DataType,
+    offsets: &[i32],
+    child: ArrayData,
+) -> Result<ListArray, ArrowError> {
+    let data = ArrayData::builder(data_type)
+        .len(offsets.len() - 1)
+        .add_buffer(Buffer::from_slice_ref(&offsets))
+        .add_child_data(child)
+        .build()?;
+    Ok(ListArray::from(data))
+}
+
+#[test]
+fn test_to_arrow() {
+    let mut scan = crate::coverage::scan_with_azimuths(&[0.5, 1.5]);
+    scan.radials[0].precip_rates = vec![0.25, f32::NAN];
+    let batch = scan.to_arrow().unwrap();
+    assert_eq!(batch.num_rows(), 3);
+    let schema = batch.schema();
+    assert_eq!(
+        schema.field(0).metadata().unwrap()["ARROW:extension:name"],
+        "geoarrow.polygon"
+    );
+    let rates = batch
+        .column(1)
+        .as_any()
+        .downcast_ref::<Float32Array>()
+        .unwrap();
+    assert_eq!(rates.value(0), 0.25);
+    assert!(rates.is_null(1));
+    let polygons = batch
+        .column(0)
+        .as_any()
+        .downcast_ref::<ListArray>()
+        .unwrap();
+    let rings = polygons.value(2);
+    let ring = rings.as_any().downcast_ref::<ListArray>().unwrap().value(0);
+    let vertices = ring.as_any().downcast_ref::<StructArray>().unwrap();
+    assert_eq!(vertices.len(), 5);
+    let x = vertices
+        .column(0)
+        .as_any()
+        .downcast_ref::<Float64Array>()
+        .unwrap();
+    assert_eq!(
+        x.value(0),
+        scan.bins().nth(2).unwrap().polygon()[0].1 as f64
+    );
+
+    let batch = scan
+        .to_arrow_filtered(&crate::filter::MinRate(0.1))
+        .unwrap();
+    assert_eq!(batch.num_rows(), 1);
+}
@@ -1,15 +1,41 @@
 const EARTH_RADIUS_KM: f32 = 6371.;
 
+/// WGS 84 ellipsoid
+const WGS84_SEMI_MAJOR_AXIS_M: f64 = 6378137.;
+const WGS84_FLATTENING: f64 = 1. / 298.257223563;
+
 /// Given a starting coordinate, a bearing, and a distance, compute the
 /// destination coordinates. Coordinates are (latitude, longitude) in degrees,
 /// bearing is in degrees clockwise from due north, and distance is in
 /// kilometers. Should be accurate within 0.0005 degrees, but probably better.
 ///
+/// This treats the earth as a sphere. Build with the `accurate` feature to
+/// use [`get_point_bearing_distance_accurate`] instead, which is slower but
+/// follows the ellipsoid.
+///
 /// Math copied from [here](http://www.movable-type.co.uk/scripts/latlong.html#dest-point).
 pub fn get_point_bearing_distance(
     start_point: (f32, f32),
     bearing: f32,
     distance: f32,
+) -> (f32, f32) {
+    if cfg!(feature = "accurate") {
+        let (lat, lon) = get_point_bearing_distance_accurate(
+            (start_point.0 as f64, start_point.1 as f64),
+            bearing as f64,
+            distance as f64,
+        );
+        return (lat as f32, lon as f32);
+    }
+    spherical_point_bearing_distance(start_point, bearing, distance)
+}
+
+/// The spherical math behind [`get_point_bearing_distance`], whether or not
+/// the `accurate` feature is on
+fn spherical_point_bearing_distance(
+    start_point: (f32, f32),
+    bearing: f32,
+    distance: f32,
 ) -> (f32, f32) {
     let (start_lat, start_lon) = (start_point.0.to_radians(), start_point.1.to_radians());
     let bearing_radians = bearing.to_radians();
@@ -23,6 +49,108 @@ pub fn get_point_bearing_distance(
     (final_lat.to_degrees(), final_lon.to_degrees())
 }
 
+/// Like [`get_point_bearing_distance`], but in `f64` throughout. In `f32`,
+/// the corners of bins a couple hundred kilometers from the radar jitter
+/// by a few meters, which shows up as slivers between neighboring bins.
+/// This is as fast as the `f32` version on most hardware, but output that
+/// keeps the extra digits is twice as big.
+pub fn get_point_bearing_distance_f64(
+    start_point: (f64, f64),
+    bearing: f64,
+    distance: f64,
+) -> (f64, f64) {
+    if cfg!(feature = "accurate") {
+        return get_point_bearing_distance_accurate(start_point, bearing, distance);
+    }
+    spherical_point_bearing_distance_f64(start_point, bearing, distance)
+}
+
+/// The spherical math behind [`get_point_bearing_distance_f64`], whether or
+/// not the `accurate` feature is on
+fn spherical_point_bearing_distance_f64(
+    start_point: (f64, f64),
+    bearing: f64,
+    distance: f64,
+) -> (f64, f64) {
+    let (start_lat, start_lon) = (start_point.0.to_radians(), start_point.1.to_radians());
+    let bearing_radians = bearing.to_radians();
+    let delta = distance / EARTH_RADIUS_KM as f64;
+    let final_lat = (start_lat.sin() * delta.cos()
+        + start_lat.cos() * delta.sin() * bearing_radians.cos())
+    .asin();
+    let final_lon = start_lon
+        + (bearing_radians.sin() * delta.sin() * start_lat.cos())
+            .atan2(delta.cos() - start_lat.sin() * final_lat.sin());
+    (final_lat.to_degrees(), final_lon.to_degrees())
+}
+
+/// Like [`get_point_bearing_distance`], but solved on the WGS 84 ellipsoid
+/// in `f64` with Vincenty's direct formula, which is accurate to well under a
+/// millimeter
+///
+/// Math from T. Vincenty, "[Direct and Inverse Solutions of Geodesics on the
+/// Ellipsoid with Application of Nested Equations](https://www.ngs.noaa.gov/PUBS_LIB/inverse.pdf)",
+/// Survey Review, 1975.
+pub fn get_point_bearing_distance_accurate(
+    start_point: (f64, f64),
+    bearing: f64,
+    distance: f64,
+) -> (f64, f64) {
+    let a = WGS84_SEMI_MAJOR_AXIS_M;
+    let f = WGS84_FLATTENING;
+    let b = a * (1. - f);
+    let distance = distance * 1000.;
+    let (sin_alpha1, cos_alpha1) = bearing.to_radians().sin_cos();
+    // reduced latitude of the start point
+    let tan_u1 = (1. - f) * start_point.0.to_radians().tan();
+    let cos_u1 = 1. / (1. + tan_u1 * tan_u1).sqrt();
+    let sin_u1 = tan_u1 * cos_u1;
+    let sigma1 = tan_u1.atan2(cos_alpha1);
+    let sin_alpha = cos_u1 * sin_alpha1;
+    let cos_sq_alpha = 1. - sin_alpha * sin_alpha;
+    let u_sq = cos_sq_alpha * (a * a - b * b) / (b * b);
+    let big_a = 1. + u_sq / 16384. * (4096. + u_sq * (-768. + u_sq * (320. - 175. * u_sq)));
+    let big_b = u_sq / 1024. * (256. + u_sq * (-128. + u_sq * (74. - 47. * u_sq)));
+
+    // iterate until the angular distance on the auxiliary sphere converges
+    let mut sigma = distance / (b * big_a);
+    let (mut sin_sigma, mut cos_sigma, mut cos_2sigma_m);
+    loop {
+        cos_2sigma_m = (2. * sigma1 + sigma).cos();
+        sin_sigma = sigma.sin();
+        cos_sigma = sigma.cos();
+        let delta_sigma = big_b
+            * sin_sigma
+            * (cos_2sigma_m
+                + big_b / 4.
+                    * (cos_sigma * (-1. + 2. * cos_2sigma_m * cos_2sigma_m)
+                        - big_b / 6.
+                            * cos_2sigma_m
+                            * (-3. + 4. * sin_sigma * sin_sigma)
+                            * (-3. + 4. * cos_2sigma_m * cos_2sigma_m)));
+        let previous = sigma;
+        sigma = distance / (b * big_a) + delta_sigma;
+        if (sigma - previous).abs() < 1e-12 {
+            break;
+        }
+    }
+
+    let x = sin_u1 * sin_sigma - cos_u1 * cos_sigma * cos_alpha1;
+    let lat2 = (sin_u1 * cos_sigma + cos_u1 * sin_sigma * cos_alpha1)
+        .atan2((1. - f) * (sin_alpha * sin_alpha + x * x).sqrt());
+    let lambda =
+        (sin_sigma * sin_alpha1).atan2(cos_u1 * cos_sigma - sin_u1 * sin_sigma * cos_alpha1);
+    let c = f / 16. * cos_sq_alpha * (4. + f * (4. - 3. * cos_sq_alpha));
+    let l = lambda
+        - (1. - c)
+            * f
+            * sin_alpha
+            * (sigma
+                + c * sin_sigma
+                    * (cos_2sigma_m + c * cos_sigma * (-1. + 2. * cos_2sigma_m * cos_2sigma_m)));
+    (lat2.to_degrees(), start_point.1 + l.to_degrees())
+}
+
 /// Given a pair of coordinates, compute the distance between the coordinates.
 /// Coordinates are (latitude, longitude) in degrees and distance is in
 /// kilometers.
@@ -36,23 +164,76 @@ pub fn get_distance_between_points(start_point: (f32, f32), end_point: (f32, f32
     EARTH_RADIUS_KM * 2. * haversine.sqrt().atan2((1. - haversine).sqrt())
 }
 
+/// Given a pair of coordinates, compute the initial bearing from the first
+/// to the second in degrees clockwise from due north, in `[0, 360)`.
+/// Coordinates are (latitude, longitude) in degrees.
+///
+/// Math copied from [here](http://www.movable-type.co.uk/scripts/latlong.html#bearing).
+pub fn get_bearing_between_points(start_point: (f32, f32), end_point: (f32, f32)) -> f32 {
+    let (start_lat, start_lon) = (start_point.0.to_radians(), start_point.1.to_radians());
+    let (end_lat, end_lon) = (end_point.0.to_radians(), end_point.1.to_radians());
+    let y = (end_lon - start_lon).sin() * end_lat.cos();
+    let x = start_lat.cos() * end_lat.sin()
+        - start_lat.sin() * end_lat.cos() * (end_lon - start_lon).cos();
+    y.atan2(x).to_degrees().rem_euclid(360.)
+}
+
+/// Given the slant range to a point along the beam in kilometers and the
+/// elevation angle of the beam in degrees, compute the height of the beam
+/// centerline above the antenna in kilometers. Refraction is modeled with the
+/// usual 4/3 effective earth radius.
+///
+/// Math from Doviak and Zrnić, _Doppler Radar and Weather Observations_,
+/// equation 2.28b.
+pub fn get_beam_height(range: f32, elevation: f32) -> f32 {
+    let effective_radius = EARTH_RADIUS_KM * 4. / 3.;
+    (range.powi(2)
+        + effective_radius.powi(2)
+        + 2. * range * effective_radius * elevation.to_radians().sin())
+    .sqrt()
+        - effective_radius
+}
+
 #[cfg(test)]
 fn is_equal_within_error(test_value: f32, true_value: f32, error: f32) -> bool {
     test_value >= true_value - error && test_value <= true_value + error
 }
 
 #[test]
+#[allow(clippy::excessive_precision)]
 fn test_get_point_bearing_distance() {
     // https://xkcd.com/2170
     let error = 0.0005;
-    let (lat, lon) = get_point_bearing_distance((53.320556, -1.729722), 96.021666667, 124.8);
+    let (lat, lon) = spherical_point_bearing_distance((53.320556, -1.729722), 96.021666667, 124.8);
     assert!(is_equal_within_error(lat, 53.188333, error));
     assert!(is_equal_within_error(lon, 0.133333, error));
-    let (lat, lon) = get_point_bearing_distance((81.9289182, -126.645662), 38.848430, 198.5);
+    let (lat, lon) = spherical_point_bearing_distance((81.9289182, -126.645662), 38.848430, 198.5);
     assert!(is_equal_within_error(lat, 83.226667, error));
     assert!(is_equal_within_error(lon, -117.109167, error));
 }
 
+#[test]
+#[allow(clippy::excessive_precision)]
+fn test_get_point_bearing_distance_f64() {
+    let (lat, lon) =
+        spherical_point_bearing_distance_f64((53.320556, -1.729722), 96.021666667, 124.8);
+    assert!((lat - 53.188333).abs() < 0.0005);
+    assert!((lon - 0.133333).abs() < 0.0005);
+
+    // agrees with the f32 version to within its precision
+    let start = (43.8913, -70.2565);
+    for bearing in (0..360).step_by(30) {
+        let fast = spherical_point_bearing_distance(start, bearing as f32, 230.);
+        let precise = spherical_point_bearing_distance_f64(
+            (start.0 as f64, start.1 as f64),
+            bearing as f64,
+            230.,
+        );
+        assert!((fast.0 as f64 - precise.0).abs() < 1e-4);
+        assert!((fast.1 as f64 - precise.1).abs() < 1e-4);
+    }
+}
+
 #[test]
 fn test_get_distance_between_points() {
     let error = 0.1;
@@ -61,3 +242,157 @@ fn test_get_distance_between_points() {
     let distance = get_distance_between_points((32.1515, 1.5073), (33.2410, 1.7384));
     assert!(is_equal_within_error(distance, 123.1, error));
 }
+
+#[test]
+#[allow(clippy::excessive_precision)]
+fn test_get_bearing_between_points() {
+    let error = 0.01;
+    let bearing = get_bearing_between_points((53.320556, -1.729722), (53.188333, 0.133333));
+    assert!(is_equal_within_error(bearing, 96.02, error));
+    let bearing = get_bearing_between_points((43.8913, -70.2565), (43.5, -70.2565));
+    assert!(is_equal_within_error(bearing, 180., error));
+    let bearing = get_bearing_between_points((43.8913, -70.2565), (44.5, -70.3));
+    assert!(bearing > 355. && bearing < 360.);
+}
+
+#[test]
+fn test_get_beam_height() {
+    // a flat beam still rises because the earth curves away beneath it
+    assert!(is_equal_within_error(
+        get_beam_height(100., 0.),
+        0.589,
+        0.001
+    ));
+    assert!(is_equal_within_error(
+        get_beam_height(100., 0.5),
+        1.461,
+        0.001
+    ));
+}
+
+#[test]
+fn test_get_point_bearing_distance_accurate() {
+    // Flinders Peak to Buninyong, the worked example from Vincenty's paper as
+    // republished by Geoscience Australia
+    let dms = |d: f64, m: f64, s: f64| d.signum() * (d.abs() + m / 60. + s / 3600.);
+    let (lat, lon) = get_point_bearing_distance_accurate(
+        (dms(-37., 57., 3.7203), dms(144., 25., 29.5244)),
+        dms(306., 52., 5.37),
+        54.972271,
+    );
+    assert!((lat - dms(-37., 39., 10.1561)).abs() < 1e-7);
+    assert!((lon - dms(143., 55., 35.3839)).abs() < 1e-7);
+
+    // the spherical approximation should stay within half a percent of the
+    // range over the whole coverage area of a station
+    let start = (43.8913, -70.2565);
+    for bearing in (0..360).step_by(15) {
+        for distance in [1., 50., 150., 250., 460.] {
+            let fast = get_point_bearing_distance(start, bearing as f32, distance);
+            let (lat, lon) = get_point_bearing_distance_accurate(
+                (start.0 as f64, start.1 as f64),
+                bearing as f64,
+                distance as f64,
+            );
+            let error = get_distance_between_points(fast, (lat as f32, lon as f32));
+            assert!(error <= distance * 0.005 + 0.01);
+        }
+    }
+}
+
+/// Solutions of the direct problem on WGS 84 from KGYX, eight compass bearings
+/// out to the edge of coverage, as (bearing, distance in km, latitude,
+/// longitude). Solved by integrating the geodesic equations (Karney,
+/// "[Algorithms for geodesics](https://doi.org/10.1007/s00190-012-0578-z)",
+/// 2013, eqs. 7-8) numerically at 30 significant digits, the same quantities
+/// that GeographicLib's series expand.
+#[cfg(test)]
+#[allow(clippy::excessive_precision)]
+const GEODESIC_REFERENCE: [(f64, f64, f64, f64); 48] = [
+    (0., 0., 43.8913, -70.2565),
+    (0., 1., 43.90030007317, -70.2565),
+    (0., 50., 44.3412862226, -70.2565),
+    (0., 150., 45.24115188968, -70.2565),
+    (0., 250., 46.14087519842, -70.2565),
+    (0., 460., 48.02983155479, -70.2565),
+    (45., 0., 43.8913, -70.2565),
+    (45., 1., 43.89766367537, -70.24769900128),
+    (45., 50., 44.20863879584, -69.81414143691),
+    (45., 150., 44.83803174798, -68.91505895506),
+    (45., 250., 45.46012269375, -67.996302045),
+    (45., 460., 46.74131151622, -66.00006157899),
+    (90., 0., 43.8913, -70.2565),
+    (90., 1., 43.89129932234, -70.24405483368),
+    (90., 50., 43.88960588611, -69.63425343787),
+    (90., 150., 43.87605530969, -68.39004240426),
+    (90., 250., 43.84896659711, -67.14667659892),
+    (90., 460., 43.74813912469, -64.54084447998),
+    (135., 0., 43.8913, -70.2565),
+    (135., 1., 43.88493563986, -70.24770087539),
+    (135., 50., 43.57224920307, -69.81882676137),
+    (135., 150., 42.92915552149, -68.95723031115),
+    (135., 250., 42.27963794324, -68.11346368245),
+    (135., 460., 40.89592077387, -66.39695929304),
+    (180., 0., 43.8913, -70.2565),
+    (180., 1., 43.8822999126, -70.2565),
+    (180., 50., 43.44127819975, -70.2565),
+    (180., 150., 42.54112796424, -70.2565),
+    (180., 250., 41.64083579973, -70.2565),
+    (180., 460., 39.74976233347, -70.2565),
+    (225., 0., 43.8913, -70.2565),
+    (225., 1., 43.88493563986, -70.26529912461),
+    (225., 50., 43.57224920307, -70.69417323863),
+    (225., 150., 42.92915552149, -71.55576968885),
+    (225., 250., 42.27963794324, -72.39953631755),
+    (225., 460., 40.89592077387, -74.11604070696),
+    (270., 0., 43.8913, -70.2565),
+    (270., 1., 43.89129932234, -70.26894516632),
+    (270., 50., 43.88960588611, -70.87874656213),
+    (270., 150., 43.87605530969, -72.12295759574),
+    (270., 250., 43.84896659711, -73.36632340108),
+    (270., 460., 43.74813912469, -75.97215552002),
+    (315., 0., 43.8913, -70.2565),
+    (315., 1., 43.89766367537, -70.26530099872),
+    (315., 50., 44.20863879584, -70.69885856309),
+    (315., 150., 44.83803174798, -71.59794104494),
+    (315., 250., 45.46012269375, -72.516697955),
+    (315., 460., 46.74131151622, -74.51293842101),
+];
+
+#[test]
+fn test_geodesic_reference() {
+    let start = (43.8913, -70.2565);
+    for &(bearing, distance, lat, lon) in GEODESIC_REFERENCE.iter() {
+        let accurate = get_point_bearing_distance_accurate(start, bearing, distance);
+        assert!((accurate.0 - lat).abs() < 1e-10, "{} {}", bearing, distance);
+        assert!((accurate.1 - lon).abs() < 1e-10, "{} {}", bearing, distance);
+
+        let spherical = spherical_point_bearing_distance_f64(start, bearing, distance);
+        let spherical_f32 = spherical_point_bearing_distance(
+            (start.0 as f32, start.1 as f32),
+            bearing as f32,
+            distance as f32,
+        );
+        let spherical_error = get_distance_between_points(
+            (spherical.0 as f32, spherical.1 as f32),
+            (lat as f32, lon as f32),
+        );
+        let spherical_f32_error =
+            get_distance_between_points(spherical_f32, (lat as f32, lon as f32));
+        // flattening puts the sphere off by at most 0.27% of the range here,
+        // with a few meters on top from haversine in f32
+        let tolerance = distance as f32 * 0.003 + 0.005;
+        assert!(spherical_error <= tolerance, "{} {}", bearing, distance);
+        assert!(spherical_f32_error <= tolerance, "{} {}", bearing, distance);
+
+        // the public functions follow whichever path the build selects
+        let expected = match cfg!(feature = "accurate") {
+            true => accurate,
+            false => spherical,
+        };
+        assert_eq!(
+            get_point_bearing_distance_f64(start, bearing, distance),
+            expected
+        );
+    }
+}
@@ -0,0 +1,176 @@
+//! [GeoParquet](https://geoparquet.org/) output for DuckDB, Spark, and
+//! other columnar analytics tools
+//!
+//! Every bin is a row with its outline as a WKB `geometry` plus
+//! `precip_rate`, `azimuth`, `capture_time`, and `station` columns. Each
+//! scan is written as its own row group, so many scans can share one file.
+
+use std::error::Error;
+use std::io::Write;
+use std::sync::Arc;
+
+use parquet::basic::Compression;
+use parquet::data_type::{ByteArray, ByteArrayType, DataType, FloatType, Int64Type};
+use parquet::file::properties::WriterProperties;
+use parquet::file::writer::{SerializedFileWriter, SerializedRowGroupWriter};
+use parquet::format::KeyValue;
+use parquet::schema::parser::parse_message_type;
+
+use crate::output::{BinRef, OutputSink};
+use crate::parse::PrecipRate;
+
+const SCHEMA: &str = "
+message bins {
+    required binary geometry;
+    optional float precip_rate;
+    required float azimuth;
+    required int64 capture_time (TIMESTAMP(MILLIS, true));
+    required binary station (STRING);
+}";
+
+/// Columns of the scan being written
+#[derive(Default)]
+struct Columns {
+    geometry: Vec<ByteArray>,
+    /// Rates of the bins with data, and whether each bin has data
+    precip_rate: Vec<f32>,
+    has_rate: Vec<i16>,
+    azimuth: Vec<f32>,
+    capture_time: i64,
+    station: ByteArray,
+}
+
+/// Writes bins as rows of a GeoParquet file. Call
+/// [`GeoParquetSink::finish_file`] after the last scan to write the footer.
+pub struct GeoParquetSink<W: Write + Send> {
+    writer: SerializedFileWriter<W>,
+    columns: Columns,
+}
+
+impl<W: Write + Send> GeoParquetSink<W> {
+    pub fn new(writer: W) -> Result<Self, Box<dyn Error>> {
+        // no crs member means OGC:CRS84, which is what the bins use
+        let geo = serde_json::json!({
+            "version": "1.1.0",
+            "primary_column": "geometry",
+            "columns": {
+                "geometry": {"encoding": "WKB", "geometry_types": ["Polygon"]},
+            },
+        });
+        let properties = WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .set_key_value_metadata(Some(vec![KeyValue::new(
+                String::from("geo"),
+                geo.to_string(),
+            )]))
+            .build();
+        Ok(GeoParquetSink {
+            writer: SerializedFileWriter::new(
+                writer,
+                Arc::new(parse_message_type(SCHEMA)?),
+                Arc::new(properties),
+            )?,
+            columns: Columns::default(),
+        })
+    }
+
+    /// Write the file footer and give back the underlying writer
+    pub fn finish_file(self) -> Result<W, Box<dyn Error>> {
+        Ok(self.writer.into_inner()?)
+    }
+}
+
+impl<W: Write + Send> OutputSink for GeoParquetSink<W> {
+    fn begin(&mut self, scan: &PrecipRate) -> Result<(), Box<dyn Error>> {
+        self.columns = Columns {
+            capture_time: scan.capture_time.timestamp_millis(),
+            station: ByteArray::from(scan.station_code.to_uppercase().as_str()),
+            ..Default::default()
+        };
+        Ok(())
+    }
+
+    fn write_bin(&mut self, bin: &BinRef) -> Result<(), Box<dyn Error>> {
+        self.columns.geometry.push(ByteArray::from(bin.wkb()));
+        if bin.is_missing() {
+            self.columns.has_rate.push(0);
+        } else {
+            self.columns.has_rate.push(1);
+            self.columns.precip_rate.push(bin.rate());
+        }
+        self.columns.azimuth.push(bin.azimuth());
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Box<dyn Error>> {
+        let columns = std::mem::take(&mut self.columns);
+        let rows = columns.geometry.len();
+        let mut row_group = self.writer.next_row_group()?;
+        write_column::<ByteArrayType, W>(&mut row_group, &columns.geometry, None)?;
+        write_column::<FloatType, W>(
+            &mut row_group,
+            &columns.precip_rate,
+            Some(&columns.has_rate),
+        )?;
+        write_column::<FloatType, W>(&mut row_group, &columns.azimuth, None)?;
+        write_column::<Int64Type, W>(&mut row_group, &vec![columns.capture_time; rows], None)?;
+        write_column::<ByteArrayType, W>(&mut row_group, &vec![columns.station; rows], None)?;
+        row_group.close()?;
+        Ok(())
+    }
+}
+
+/// Write the next column of `row_group`, with `definition_levels` for
+/// optional columns
+fn write_column<T: DataType, W: Write + Send>(
+    row_group: &mut SerializedRowGroupWriter<W>,
+    values: &[T::T],
+    definition_levels: Option<&[i16]>,
+) -> Result<(), Box<dyn Error>> {
+    let mut column = match row_group.next_column()? {
+        Some(column) => column,
+        None => return Err("Ran out of GeoParquet columns".into()),
+    };
+    column
+        .typed::<T>()
+        .write_batch(values, definition_levels, None)?;
+    column.close()?;
+    Ok(())
+}
+
+#[test]
+fn test_geoparquet_sink() {
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+    let mut scan = crate::coverage::scan_with_azimuths(&[0.5, 1.5]);
+    scan.radials[0].precip_rates = vec![0.25, f32::NAN];
+    let mut sink = GeoParquetSink::new(Vec::new()).unwrap();
+    crate::output::write_scan(&scan, &mut sink).unwrap();
+    crate::output::write_scan(&scan, &mut sink).unwrap();
+    let path = std::env::temp_dir().join(format!("threecast-test-{}.parquet", std::process::id()));
+    std::fs::write(&path, sink.finish_file().unwrap()).unwrap();
+
+    let reader = SerializedFileReader::new(std::fs::File::open(&path).unwrap()).unwrap();
+    let metadata = reader.metadata();
+    assert_eq!(metadata.num_row_groups(), 2);
+    assert_eq!(metadata.file_metadata().num_rows(), 6);
+    let geo = metadata
+        .file_metadata()
+        .key_value_metadata()
+        .unwrap()
+        .iter()
+        .find(|kv| kv.key == "geo")
+        .and_then(|kv| kv.value.clone())
+        .unwrap();
+    let geo: serde_json::Value = serde_json::from_str(&geo).unwrap();
+    assert_eq!(geo["columns"]["geometry"]["encoding"], "WKB");
+    let rows: Vec<String> = reader
+        .get_row_iter(None)
+        .unwrap()
+        .take(3)
+        .map(|row| row.to_string())
+        .collect();
+    assert!(rows[0].contains("precip_rate: 0.25"));
+    assert!(rows[1].contains("precip_rate: null"));
+    assert!(rows[2].contains("station: \"KGYX\""));
+    std::fs::remove_file(path).unwrap();
+}
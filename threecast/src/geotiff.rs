@@ -0,0 +1,420 @@
+//! [GeoTIFF](https://www.ogc.org/standards/geotiff/) output of rasters
+//!
+//! The TIFF is written by hand as little-endian 32-bit floats, tagged with
+//! EPSG:4326 and `NaN` as the nodata value so that GDAL and QGIS pick
+//! everything up. [`write_geotiff`] writes a plain single-strip image, and
+//! [`write_cog`] writes a [Cloud Optimized GeoTIFF](https://cogeo.org/)
+//! that web clients can read piece by piece straight from object storage.
+
+use std::error::Error;
+use std::io::Write;
+
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+
+use crate::raster::Raster;
+
+const SHORT: u16 = 3;
+const LONG: u16 = 4;
+const DOUBLE: u16 = 12;
+const ASCII: u16 = 2;
+
+/// One field of an image file directory, with its value already encoded
+pub(crate) struct Entry {
+    tag: u16,
+    field_type: u16,
+    count: u32,
+    value: Vec<u8>,
+}
+
+impl Entry {
+    pub(crate) fn shorts(tag: u16, values: &[u16]) -> Self {
+        Entry {
+            tag,
+            field_type: SHORT,
+            count: values.len() as u32,
+            value: values.iter().flat_map(|v| v.to_le_bytes()).collect(),
+        }
+    }
+
+    pub(crate) fn longs(tag: u16, values: &[u32]) -> Self {
+        Entry {
+            tag,
+            field_type: LONG,
+            count: values.len() as u32,
+            value: values.iter().flat_map(|v| v.to_le_bytes()).collect(),
+        }
+    }
+
+    pub(crate) fn doubles(tag: u16, values: &[f64]) -> Self {
+        Entry {
+            tag,
+            field_type: DOUBLE,
+            count: values.len() as u32,
+            value: values.iter().flat_map(|v| v.to_le_bytes()).collect(),
+        }
+    }
+
+    pub(crate) fn ascii(tag: u16, text: &str) -> Self {
+        let mut value = text.as_bytes().to_vec();
+        value.push(0);
+        Entry {
+            tag,
+            field_type: ASCII,
+            count: value.len() as u32,
+            value,
+        }
+    }
+}
+
+/// Bytes taken by an image file directory with `entries`, including the
+/// values too big to fit in the entries themselves
+pub(crate) fn ifd_len(entries: &[Entry]) -> usize {
+    2 + 12 * entries.len()
+        + 4
+        + entries
+            .iter()
+            .filter(|e| e.value.len() > 4)
+            .map(|e| (e.value.len() + 1) & !1)
+            .sum::<usize>()
+}
+
+/// Encode an image file directory that will be written at `offset`, with
+/// its big values right after it, pointing at the next directory at
+/// `next_ifd` or zero for the last one
+pub(crate) fn encode_ifd(mut entries: Vec<Entry>, offset: usize, next_ifd: u32) -> Vec<u8> {
+    entries.sort_by_key(|e| e.tag);
+    let mut extra_offset = offset + 2 + 12 * entries.len() + 4;
+    let mut ifd = Vec::new();
+    let mut extra = Vec::new();
+    ifd.extend((entries.len() as u16).to_le_bytes());
+    for entry in entries.iter() {
+        ifd.extend(entry.tag.to_le_bytes());
+        ifd.extend(entry.field_type.to_le_bytes());
+        ifd.extend(entry.count.to_le_bytes());
+        if entry.value.len() <= 4 {
+            let mut value = entry.value.clone();
+            value.resize(4, 0);
+            ifd.extend(value);
+        } else {
+            ifd.extend((extra_offset as u32).to_le_bytes());
+            extra.extend(&entry.value);
+            // values have to start on word boundaries
+            if entry.value.len() % 2 == 1 {
+                extra.push(0);
+            }
+            extra_offset += (entry.value.len() + 1) & !1;
+        }
+    }
+    ifd.extend(next_ifd.to_le_bytes());
+    ifd.extend(extra);
+    ifd
+}
+
+/// The tags that place `raster` on the earth in WGS84 and mark `NaN` as
+/// nodata
+pub(crate) fn geo_entries(raster: &Raster) -> Vec<Entry> {
+    vec![
+        // ModelPixelScaleTag
+        Entry::doubles(33550, &[raster.resolution, raster.resolution, 0.]),
+        // ModelTiepointTag: the top left corner of the top left pixel
+        Entry::doubles(33922, &[0., 0., 0., raster.west, raster.north, 0.]),
+        // GeoKeyDirectoryTag, version 1.1.0 with three keys
+        Entry::shorts(
+            34735,
+            &[
+                1, 1, 0, 3, // header
+                1024, 0, 1, 2, // GTModelTypeGeoKey: geographic
+                1025, 0, 1, 1, // GTRasterTypeGeoKey: pixels are areas
+                2048, 0, 1, 4326, // GeographicTypeGeoKey: WGS84
+            ],
+        ),
+        // GDAL_NODATA
+        Entry::ascii(42113, "nan"),
+    ]
+}
+
+/// The tags that every single-band float image needs
+pub(crate) fn image_entries(width: usize, height: usize) -> Vec<Entry> {
+    vec![
+        Entry::longs(256, &[width as u32]),
+        Entry::longs(257, &[height as u32]),
+        // BitsPerSample
+        Entry::shorts(258, &[32]),
+        // Photometric: BlackIsZero
+        Entry::shorts(262, &[1]),
+        // SamplesPerPixel
+        Entry::shorts(277, &[1]),
+        // PlanarConfiguration: chunky
+        Entry::shorts(284, &[1]),
+        // SampleFormat: IEEE float
+        Entry::shorts(339, &[3]),
+    ]
+}
+
+/// Write `raster` as a single-band GeoTIFF
+pub fn write_geotiff<W: Write>(raster: &Raster, mut writer: W) -> Result<(), Box<dyn Error>> {
+    let data: Vec<u8> = raster.data.iter().flat_map(|v| v.to_le_bytes()).collect();
+    if data.len() > u32::MAX as usize / 2 {
+        return Err("Raster is too big for a classic TIFF".into());
+    }
+    let mut entries = image_entries(raster.width, raster.height);
+    entries.extend(geo_entries(raster));
+    entries.extend([
+        // Compression: none
+        Entry::shorts(259, &[1]),
+        // StripOffsets, filled in below
+        Entry::longs(273, &[0]),
+        // RowsPerStrip
+        Entry::longs(278, &[raster.height as u32]),
+        // StripByteCounts
+        Entry::longs(279, &[data.len() as u32]),
+    ]);
+    let data_offset = 8 + ifd_len(&entries);
+    for entry in entries.iter_mut().filter(|e| e.tag == 273) {
+        *entry = Entry::longs(273, &[data_offset as u32]);
+    }
+
+    writer.write_all(b"II\x2a\x00")?;
+    writer.write_all(&8u32.to_le_bytes())?;
+    writer.write_all(&encode_ifd(entries, 8, 0))?;
+    writer.write_all(&data)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Width and height of the tiles in a COG
+const TILE_SIZE: usize = 256;
+
+/// Split `raster` into deflated tiles, row by row, padding the edge tiles
+/// with `NaN`
+fn encode_tiles(raster: &Raster) -> Result<Vec<Vec<u8>>, Box<dyn Error>> {
+    let (across, down) = (
+        raster.width.div_ceil(TILE_SIZE),
+        raster.height.div_ceil(TILE_SIZE),
+    );
+    let mut tiles = Vec::with_capacity(across * down);
+    for tile_y in 0..down {
+        for tile_x in 0..across {
+            let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+            for y in tile_y * TILE_SIZE..(tile_y + 1) * TILE_SIZE {
+                for x in tile_x * TILE_SIZE..(tile_x + 1) * TILE_SIZE {
+                    let value = match x < raster.width && y < raster.height {
+                        true => raster.get(x, y),
+                        false => f32::NAN,
+                    };
+                    encoder.write_all(&value.to_le_bytes())?;
+                }
+            }
+            tiles.push(encoder.finish()?);
+        }
+    }
+    Ok(tiles)
+}
+
+/// Write `raster` as a Cloud Optimized GeoTIFF: deflated 256x256 tiles,
+/// plus overviews at half the resolution of the level before until the
+/// whole raster fits in one tile. All of the directories come first and the
+/// tiles follow from the smallest overview to the full resolution, so a
+/// client can get the layout with one small range request.
+pub fn write_cog<W: Write>(raster: &Raster, mut writer: W) -> Result<(), Box<dyn Error>> {
+    let mut levels = vec![raster.clone()];
+    while let Some(last) = levels.last().filter(|l| l.width.max(l.height) > TILE_SIZE) {
+        levels.push(last.halved());
+    }
+    let tiles = levels
+        .iter()
+        .map(encode_tiles)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let level_entries = |level: usize, offsets: &[u32]| {
+        let image = &levels[level];
+        let counts: Vec<u32> = tiles[level].iter().map(|t| t.len() as u32).collect();
+        let mut entries = image_entries(image.width, image.height);
+        entries.extend([
+            // Compression: Adobe deflate
+            Entry::shorts(259, &[8]),
+            Entry::shorts(322, &[TILE_SIZE as u16]),
+            Entry::shorts(323, &[TILE_SIZE as u16]),
+            Entry::longs(324, offsets),
+            Entry::longs(325, &counts),
+        ]);
+        if level == 0 {
+            entries.extend(geo_entries(image));
+        } else {
+            // NewSubfileType: reduced resolution version
+            entries.push(Entry::longs(254, &[1]));
+            entries.push(Entry::ascii(42113, "nan"));
+        }
+        entries
+    };
+
+    // the directories' sizes don't depend on the offsets in them, so lay
+    // them out with placeholders first
+    let mut ifd_offsets = Vec::new();
+    let mut offset = 8;
+    for (level, level_tiles) in tiles.iter().enumerate() {
+        ifd_offsets.push(offset);
+        offset += ifd_len(&level_entries(level, &vec![0; level_tiles.len()]));
+    }
+    let mut tile_offsets = vec![Vec::new(); levels.len()];
+    for (level, level_tiles) in tiles.iter().enumerate().rev() {
+        for tile in level_tiles {
+            tile_offsets[level].push(offset as u32);
+            offset += tile.len();
+        }
+    }
+    if offset > u32::MAX as usize {
+        return Err("Raster is too big for a classic TIFF".into());
+    }
+
+    writer.write_all(b"II\x2a\x00")?;
+    writer.write_all(&8u32.to_le_bytes())?;
+    for level in 0..levels.len() {
+        let next_ifd = ifd_offsets.get(level + 1).map_or(0, |&o| o as u32);
+        let entries = level_entries(level, &tile_offsets[level]);
+        writer.write_all(&encode_ifd(entries, ifd_offsets[level], next_ifd))?;
+    }
+    for level_tiles in tiles.iter().rev() {
+        for tile in level_tiles {
+            writer.write_all(tile)?;
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+#[test]
+fn test_write_geotiff() {
+    let raster = Raster {
+        west: -71.,
+        north: 44.,
+        resolution: 0.5,
+        width: 3,
+        height: 2,
+        data: vec![0., 0.25, f32::NAN, 0.5, 0., 1.],
+    };
+    let mut output = Vec::new();
+    write_geotiff(&raster, &mut output).unwrap();
+    let u16_at = |at: usize| u16::from_le_bytes([output[at], output[at + 1]]);
+    let u32_at = |at: usize| u32::from_le_bytes(output[at..at + 4].try_into().unwrap());
+    assert_eq!(&output[..4], b"II\x2a\x00");
+    let ifd = u32_at(4) as usize;
+    let count = u16_at(ifd) as usize;
+    let entry = |tag: u16| {
+        (0..count)
+            .map(|i| ifd + 2 + 12 * i)
+            .find(|&at| u16_at(at) == tag)
+            .unwrap()
+    };
+    // tags must be sorted
+    let tags: Vec<u16> = (0..count).map(|i| u16_at(ifd + 2 + 12 * i)).collect();
+    let mut sorted = tags.clone();
+    sorted.sort();
+    assert_eq!(tags, sorted);
+    assert_eq!(u32_at(entry(256) + 8), 3);
+    assert_eq!(u32_at(entry(257) + 8), 2);
+    let tiepoint = u32_at(entry(33922) + 8) as usize;
+    let west = f64::from_le_bytes(output[tiepoint + 24..tiepoint + 32].try_into().unwrap());
+    assert_eq!(west, -71.);
+    let geokeys = u32_at(entry(34735) + 8) as usize;
+    assert_eq!(u16_at(geokeys + 30), 4326);
+    let strip = u32_at(entry(273) + 8) as usize;
+    assert_eq!(strip + 24, output.len());
+    let values: Vec<f32> = output[strip..]
+        .chunks(4)
+        .map(|b| f32::from_le_bytes(b.try_into().unwrap()))
+        .collect();
+    assert_eq!(values[..2], [0., 0.25]);
+    assert!(values[2].is_nan());
+}
+
+#[test]
+fn test_write_cog() {
+    use std::io::Read;
+    let (width, height) = (600, 300);
+    let raster = Raster {
+        west: -71.,
+        north: 44.,
+        resolution: 0.01,
+        width,
+        height,
+        data: (0..width * height)
+            .map(|i| match i % 7 {
+                0 => f32::NAN,
+                n => n as f32 / 10.,
+            })
+            .collect(),
+    };
+    let mut output = Vec::new();
+    write_cog(&raster, &mut output).unwrap();
+    let u16_at = |at: usize| u16::from_le_bytes([output[at], output[at + 1]]);
+    let u32_at = |at: usize| u32::from_le_bytes(output[at..at + 4].try_into().unwrap());
+    // the value of `tag` in the directory at `ifd`, or its offset if it
+    // doesn't fit
+    let field = |ifd: usize, tag: u16| {
+        (0..u16_at(ifd) as usize)
+            .map(|i| ifd + 2 + 12 * i)
+            .find(|&at| u16_at(at) == tag)
+            .map(|at| match u16_at(at + 2) {
+                3 if u32_at(at + 4) == 1 => u16_at(at + 8) as u32,
+                _ => u32_at(at + 8),
+            })
+    };
+
+    // 600x300, 300x150, and 150x75
+    let mut ifds = vec![u32_at(4) as usize];
+    while let Some(&ifd) = ifds.last() {
+        let count = u16_at(ifd) as usize;
+        match u32_at(ifd + 2 + 12 * count) {
+            0 => break,
+            next => ifds.push(next as usize),
+        }
+    }
+    assert_eq!(ifds.len(), 3);
+    assert_eq!(field(ifds[0], 256), Some(600));
+    assert_eq!(field(ifds[2], 257), Some(75));
+    assert_eq!(field(ifds[0], 254), None);
+    assert_eq!(field(ifds[1], 254), Some(1));
+    assert!(field(ifds[0], 34735).is_some());
+    assert_eq!(field(ifds[0], 322), Some(256));
+
+    // the LONG values of `tag`, which are only stored elsewhere if there's
+    // more than one
+    let longs = |ifd: usize, tag: u16| {
+        let at = (0..u16_at(ifd) as usize)
+            .map(|i| ifd + 2 + 12 * i)
+            .find(|&at| u16_at(at) == tag)
+            .unwrap();
+        match u32_at(at + 4) as usize {
+            1 => vec![u32_at(at + 8) as usize],
+            n => {
+                let start = u32_at(at + 8) as usize;
+                (0..n).map(|i| u32_at(start + 4 * i) as usize).collect()
+            }
+        }
+    };
+    assert_eq!(longs(ifds[0], 324).len(), 6);
+    assert_eq!(longs(ifds[1], 324).len(), 2);
+    assert_eq!(longs(ifds[2], 324).len(), 1);
+
+    // the smallest overview's tile comes first, right after the directories
+    let first_tile = |ifd: usize| longs(ifd, 324)[0];
+    assert!(first_tile(ifds[2]) < first_tile(ifds[1]));
+    assert!(first_tile(ifds[1]) < first_tile(ifds[0]));
+    assert!(ifds.iter().all(|&ifd| ifd < first_tile(ifds[2])));
+
+    // the second tile across at full resolution starts at x = 256
+    let (start, len) = (longs(ifds[0], 324)[1], longs(ifds[0], 325)[1]);
+    let mut tile = Vec::new();
+    flate2::read::ZlibDecoder::new(&output[start..start + len])
+        .read_to_end(&mut tile)
+        .unwrap();
+    assert_eq!(tile.len(), TILE_SIZE * TILE_SIZE * 4);
+    let value = |x: usize, y: usize| {
+        let at = (y * TILE_SIZE + x) * 4;
+        f32::from_le_bytes(tile[at..at + 4].try_into().unwrap())
+    };
+    assert_eq!(value(1, 1), raster.get(257, 1));
+    assert_eq!(value(10, 20), raster.get(266, 20));
+}
@@ -0,0 +1,181 @@
+//! [GML 3.2](https://www.ogc.org/standards/gml/) output for OGC toolchains
+//! that only take GML or WFS payloads
+//!
+//! Each scan becomes a WFS 2.0 `FeatureCollection` of `tc:Bin` features,
+//! with the same properties as the GeoJSON output as child elements and the
+//! polygon in `tc:geometry`.
+
+use std::error::Error;
+use std::io::Write;
+
+use crate::filter::BinFilter;
+use crate::kml::escape;
+use crate::output::{
+    converted_properties, default_properties, BinRef, ConvertOptions, OutputSink, PropertyMapper,
+    WriterOptions,
+};
+use crate::parse::PrecipRate;
+
+/// Namespace of the `tc` feature type and property elements
+pub const NAMESPACE: &str = "https://github.com/bmgxyz/threecast";
+
+/// Writes bins as polygon features of a GML 3.2 document wrapped in a WFS
+/// 2.0 `FeatureCollection`. Since the collection has to say how many
+/// features it holds up front, each scan's features are held in memory
+/// until [`OutputSink::finish`].
+///
+/// Property names become element names, so custom mappers must use names
+/// that are valid in XML.
+pub struct GmlSink<W: Write> {
+    writer: W,
+    properties: PropertyMapper,
+    options: WriterOptions,
+    convert: ConvertOptions,
+    members: Vec<u8>,
+    count: usize,
+    time_stamp: String,
+}
+
+impl<W: Write> GmlSink<W> {
+    pub fn new(writer: W) -> Self {
+        Self::with_properties(writer, default_properties)
+    }
+
+    /// Use `mapper` to build each feature's properties instead of the
+    /// default single `precipRate` property
+    pub fn with_properties<F>(writer: W, mapper: F) -> Self
+    where
+        F: Fn(&BinRef) -> serde_json::Map<String, serde_json::Value> + 'static,
+    {
+        GmlSink {
+            writer,
+            properties: Box::new(mapper),
+            options: WriterOptions::default(),
+            convert: ConvertOptions::default(),
+            members: Vec::new(),
+            count: 0,
+            time_stamp: String::new(),
+        }
+    }
+
+    /// Use the axis order from `options`. GML always names the CRS with
+    /// `srsName`, so `legacy_crs` is ignored.
+    pub fn with_options(mut self, options: WriterOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Use `convert` to pick the bins, their outlines, and the units of
+    /// the `precipRate` property
+    pub fn with_convert_options(mut self, convert: ConvertOptions) -> Self {
+        self.convert = convert;
+        self
+    }
+
+    /// Give back the underlying writer
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// The text of a property element, or `None` to leave the element out
+fn property_text(value: &serde_json::Value) -> Option<String> {
+    match value {
+        serde_json::Value::Null => None,
+        serde_json::Value::String(s) => Some(escape(s)),
+        // numbers and booleans are already valid XML Schema literals, and
+        // anything nested is left as JSON
+        _ => Some(escape(&value.to_string())),
+    }
+}
+
+impl<W: Write> OutputSink for GmlSink<W> {
+    fn begin(&mut self, scan: &PrecipRate) -> Result<(), Box<dyn Error>> {
+        self.members.clear();
+        self.count = 0;
+        self.time_stamp = scan.capture_time.format("%Y-%m-%dT%H:%M:%SZ").to_string();
+        Ok(())
+    }
+
+    fn write_bin(&mut self, bin: &BinRef) -> Result<(), Box<dyn Error>> {
+        if !self.convert.keep(bin) {
+            return Ok(());
+        }
+        let (radial, index) = bin.indices();
+        let id = format!("bin.{}.{}", radial, index);
+        write!(self.members, "<wfs:member><tc:Bin gml:id=\"{}\">", id)?;
+        for (name, value) in converted_properties(bin, &self.properties, &self.convert) {
+            if let Some(text) = property_text(&value) {
+                write!(self.members, "<tc:{0}>{1}</tc:{0}>", name, text)?;
+            }
+        }
+        let axis_order = self.options.axis_order;
+        let positions: Vec<String> = match self.convert.double_precision() {
+            true => bin
+                .outline_f64(self.convert.arc_points())
+                .into_iter()
+                .map(|c| axis_order.arrange(c))
+                .map(|[a, b]| format!("{} {}", a, b))
+                .collect(),
+            false => bin
+                .outline(self.convert.arc_points())
+                .into_iter()
+                .map(|c| axis_order.arrange(c))
+                .map(|[a, b]| format!("{} {}", a, b))
+                .collect(),
+        };
+        writeln!(
+            self.members,
+            "<tc:geometry><gml:Polygon gml:id=\"{}.geometry\" srsName=\"{}\" srsDimension=\"2\">\
+             <gml:exterior><gml:LinearRing><gml:posList>{}</gml:posList></gml:LinearRing>\
+             </gml:exterior></gml:Polygon></tc:geometry></tc:Bin></wfs:member>",
+            id,
+            self.options.axis_order.crs_urn(),
+            positions.join(" ")
+        )?;
+        self.count += 1;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Box<dyn Error>> {
+        writeln!(
+            self.writer,
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <wfs:FeatureCollection xmlns:wfs=\"http://www.opengis.net/wfs/2.0\" \
+             xmlns:gml=\"http://www.opengis.net/gml/3.2\" xmlns:tc=\"{}\" \
+             timeStamp=\"{}\" numberMatched=\"{2}\" numberReturned=\"{2}\">",
+            NAMESPACE, self.time_stamp, self.count
+        )?;
+        self.writer.write_all(&self.members)?;
+        self.writer.write_all(b"</wfs:FeatureCollection>\n")?;
+        self.writer.flush()?;
+        self.members.clear();
+        Ok(())
+    }
+}
+
+#[test]
+fn test_gml_sink() {
+    let mut scan = crate::coverage::scan_with_azimuths(&[0.5, 1.5]);
+    scan.radials[0].precip_rates = vec![0.25, f32::NAN];
+    let mut sink = GmlSink::with_properties(Vec::new(), crate::output::properties_with_beam_height)
+        .with_options(crate::output::WriterOptions {
+            axis_order: crate::output::AxisOrder::LatLon,
+            legacy_crs: false,
+        });
+    crate::output::write_scan(&scan, &mut sink).unwrap();
+    let gml = String::from_utf8(sink.into_inner()).unwrap();
+    assert!(gml.contains("timeStamp=\"1970-01-01T00:00:00Z\""));
+    assert!(gml.contains("numberMatched=\"3\" numberReturned=\"3\""));
+    assert_eq!(gml.matches("<wfs:member>").count(), 3);
+    assert!(gml.contains("<tc:Bin gml:id=\"bin.0.0\"><tc:beamHeight>"));
+    assert!(gml.contains("<tc:precipRate>0.25</tc:precipRate>"));
+    assert!(gml.contains("srsName=\"urn:ogc:def:crs:EPSG::4326\""));
+    // NaN has no JSON number, so the missing bin has no rate at all
+    assert_eq!(gml.matches("<tc:precipRate>").count(), 2);
+    // latitude first
+    let (latitude, longitude) = scan.bins().next().unwrap().polygon()[0];
+    let start = format!("<gml:posList>{} {} ", latitude, longitude);
+    assert!(gml.contains(&start));
+    assert!(gml.ends_with("</wfs:FeatureCollection>\n"));
+}
@@ -0,0 +1,247 @@
+//! [GRIB2](https://library.wmo.int/idurl/4/35625) output of rasterized
+//! scans, for meteorological tools like WRF post-processing, wgrib2, and
+//! the Unidata stack
+//!
+//! The rates go out as instantaneous precipitation rate (PRATE, discipline
+//! 0, category 1, parameter 7) in kg m-2 s-1 on a regular latitude/longitude
+//! grid, with simple packing and a bitmap for pixels without data.
+
+use std::error::Error;
+use std::io::Write;
+
+use chrono::{Datelike, Timelike};
+
+use crate::parse::PrecipRate;
+use crate::raster::Raster;
+
+/// kg m-2 s-1, which is mm/s, per in/hr
+const RATE_SCALE: f32 = 25.4 / 3600.;
+
+/// Values are kept to 10^-DECIMAL_SCALE kg m-2 s-1, which is well under a
+/// thousandth of an in/hr
+const DECIMAL_SCALE: i16 = 7;
+
+/// Degrees to the GRIB2 unit of 10^-6 degrees
+fn micro(degrees: f64) -> i32 {
+    (degrees * 1e6).round() as i32
+}
+
+/// GRIB2 stores negative numbers with a sign bit instead of two's
+/// complement
+fn signed32(value: i32) -> [u8; 4] {
+    match value < 0 {
+        true => (value.unsigned_abs() | 0x8000_0000).to_be_bytes(),
+        false => (value as u32).to_be_bytes(),
+    }
+}
+
+pub(crate) fn signed16(value: i16) -> [u8; 2] {
+    match value < 0 {
+        true => (value.unsigned_abs() | 0x8000).to_be_bytes(),
+        false => (value as u16).to_be_bytes(),
+    }
+}
+
+/// A section with its length and number in front of `body`
+pub(crate) fn section(number: u8, body: &[u8]) -> Vec<u8> {
+    let mut section = ((body.len() + 5) as u32).to_be_bytes().to_vec();
+    section.push(number);
+    section.extend(body);
+    section
+}
+
+/// Pack `values` into big-endian fields of `bits` bits each
+fn pack_bits(values: impl Iterator<Item = u32>, bits: u8) -> Vec<u8> {
+    let mut packed = Vec::new();
+    let (mut buffer, mut filled) = (0u64, 0);
+    for value in values {
+        buffer = buffer << bits | value as u64;
+        filled += bits as u32;
+        while filled >= 8 {
+            filled -= 8;
+            packed.push((buffer >> filled) as u8);
+        }
+    }
+    if filled > 0 {
+        packed.push((buffer << (8 - filled)) as u8);
+    }
+    packed
+}
+
+/// Write `raster`, which was made from `scan`, as a single GRIB2 message
+pub fn write_grib2<W: Write>(
+    scan: &PrecipRate,
+    raster: &Raster,
+    mut writer: W,
+) -> Result<(), Box<dyn Error>> {
+    let points = raster.width * raster.height;
+    let time = scan.capture_time;
+
+    // identification: no originating center, observation time, operational
+    // processed radar observations
+    let mut identification = vec![0xff, 0xff, 0, 0, 2, 0, 3];
+    identification.extend((time.year() as u16).to_be_bytes());
+    identification.extend([
+        time.month() as u8,
+        time.day() as u8,
+        time.hour() as u8,
+        time.minute() as u8,
+        time.second() as u8,
+        0,
+        7,
+    ]);
+
+    // grid definition template 3.0, on the WGS84 ellipsoid, with the first
+    // point in the northwest corner and rows running west to east
+    let (first_latitude, first_longitude) = raster.pixel_center(0, 0);
+    let (last_latitude, last_longitude) = raster.pixel_center(raster.width - 1, raster.height - 1);
+    let longitude = |degrees: f64| (micro(degrees.rem_euclid(360.)) as u32).to_be_bytes();
+    let mut grid = vec![0];
+    grid.extend((points as u32).to_be_bytes());
+    grid.extend([0, 0, 0, 0]); // no list of points, template 0
+    grid.push(5);
+    grid.extend([0; 15]); // no custom earth radius or axes
+    grid.extend((raster.width as u32).to_be_bytes());
+    grid.extend((raster.height as u32).to_be_bytes());
+    grid.extend([0, 0, 0, 0, 0xff, 0xff, 0xff, 0xff]); // units of 10^-6 degrees
+    grid.extend(signed32(micro(first_latitude)));
+    grid.extend(longitude(first_longitude));
+    grid.push(0x30); // increments given
+    grid.extend(signed32(micro(last_latitude)));
+    grid.extend(longitude(last_longitude));
+    grid.extend((micro(raster.resolution) as u32).to_be_bytes());
+    grid.extend((micro(raster.resolution) as u32).to_be_bytes());
+    grid.push(0);
+
+    // product definition template 4.0: precipitation rate observed at the
+    // surface at the scan time
+    let mut product = vec![0, 0, 0, 0, 1, 7, 8, 0xff, 0xff, 0, 0, 0, 1];
+    product.extend([0; 4]); // no forecast time
+    product.extend([1, 0, 0, 0, 0, 0]); // ground or water surface
+    product.extend([0xff; 6]); // no second surface
+
+    let scaled: Vec<Option<i64>> = raster
+        .data
+        .iter()
+        .map(|rate| match rate.is_nan() {
+            true => None,
+            false => {
+                let value = *rate as f64 * RATE_SCALE as f64;
+                Some((value * 10f64.powi(DECIMAL_SCALE as i32)).round() as i64)
+            }
+        })
+        .collect();
+    let present: Vec<i64> = scaled.iter().flatten().copied().collect();
+    let reference = present.iter().min().copied().unwrap_or(0);
+    let range = present.iter().max().map_or(0, |max| max - reference);
+    let bits = (64 - range.leading_zeros()) as u8;
+
+    // data representation template 5.0: simple packing of decimal-scaled
+    // integers
+    let mut representation = (present.len() as u32).to_be_bytes().to_vec();
+    representation.extend(0u16.to_be_bytes());
+    representation.extend((reference as f32).to_be_bytes());
+    representation.extend(signed16(0));
+    representation.extend(signed16(DECIMAL_SCALE));
+    representation.extend([bits, 0]);
+
+    let mut bitmap = vec![0];
+    bitmap.extend(pack_bits(
+        scaled.iter().map(|value| value.is_some() as u32),
+        1,
+    ));
+    let data = match bits {
+        0 => Vec::new(),
+        _ => pack_bits(present.iter().map(|value| (value - reference) as u32), bits),
+    };
+
+    let sections = [
+        section(1, &identification),
+        section(3, &grid),
+        section(4, &product),
+        section(5, &representation),
+        section(6, &bitmap),
+        section(7, &data),
+    ];
+    let len = 16 + sections.iter().map(|s| s.len()).sum::<usize>() + 4;
+    // indicator: meteorological products, edition 2
+    writer.write_all(b"GRIB\0\0\0\x02")?;
+    writer.write_all(&(len as u64).to_be_bytes())?;
+    for section in sections.iter() {
+        writer.write_all(section)?;
+    }
+    writer.write_all(b"7777")?;
+    writer.flush()?;
+    Ok(())
+}
+
+#[test]
+fn test_write_grib2() {
+    let scan = crate::coverage::scan_with_azimuths(&[0.5]);
+    let raster = Raster {
+        west: -71.,
+        north: 44.,
+        resolution: 0.5,
+        width: 3,
+        height: 2,
+        data: vec![0., 0.5, f32::NAN, 1., f32::NAN, 0.25],
+    };
+    let mut grib = Vec::new();
+    write_grib2(&scan, &raster, &mut grib).unwrap();
+    assert!(grib.starts_with(b"GRIB"));
+    assert!(grib.ends_with(b"7777"));
+    assert_eq!(grib[7], 2);
+    assert_eq!(
+        u64::from_be_bytes(grib[8..16].try_into().unwrap()) as usize,
+        grib.len()
+    );
+
+    // walk the sections by their lengths
+    let mut sections = std::collections::BTreeMap::new();
+    let mut offset = 16;
+    while offset < grib.len() - 4 {
+        let len = u32::from_be_bytes(grib[offset..offset + 4].try_into().unwrap()) as usize;
+        sections.insert(grib[offset + 4], &grib[offset..offset + len]);
+        offset += len;
+    }
+    assert_eq!(offset, grib.len() - 4);
+    assert_eq!(
+        sections.keys().copied().collect::<Vec<u8>>(),
+        [1, 3, 4, 5, 6, 7]
+    );
+    assert_eq!(sections[&1].len(), 21);
+    assert_eq!(sections[&3].len(), 72);
+    assert_eq!(sections[&4].len(), 34);
+    assert_eq!(sections[&5].len(), 21);
+
+    let int = |bytes: &[u8]| u32::from_be_bytes(bytes.try_into().unwrap());
+    let grid = sections[&3];
+    assert_eq!((int(&grid[30..34]), int(&grid[34..38])), (3, 2));
+    // first point is the center of the northwest pixel
+    assert_eq!(int(&grid[46..50]), 43_750_000);
+    assert_eq!(int(&grid[50..54]), 289_250_000);
+    // and the last is south of it, so the latitude is still positive
+    assert_eq!(int(&grid[55..59]), 43_250_000);
+    assert_eq!(int(&grid[63..67]), 500_000);
+    assert_eq!(&sections[&4][9..11], &[1, 7]);
+
+    // unpack the values again
+    let representation = sections[&5];
+    assert_eq!(int(&representation[5..9]), 4);
+    let reference = f32::from_be_bytes(representation[11..15].try_into().unwrap());
+    let bits = representation[19] as usize;
+    assert_eq!(sections[&6][6], 0b1101_0100);
+    let data = &sections[&7][5..];
+    let unpacked: Vec<f32> = (0..4)
+        .map(|i| {
+            let mut value = 0;
+            for bit in i * bits..(i + 1) * bits {
+                value = value << 1 | (data[bit / 8] >> (7 - bit % 8) & 1) as u32;
+            }
+            (reference + value as f32) / 1e7 / RATE_SCALE
+        })
+        .collect();
+    for (unpacked, rate) in unpacked.iter().zip([0., 0.5, 1., 0.25]) {
+        assert!((unpacked - rate).abs() < 1e-4);
+    }
+}
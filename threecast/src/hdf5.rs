@@ -0,0 +1,437 @@
+//! [HDF5](https://docs.hdfgroup.org/hdf5/develop/_f_m_t3.html) output of
+//! the untouched polar data, for radar researchers who want the original
+//! azimuth by bin grid rather than polygons or resampled rasters
+//!
+//! The file has a `precip_rate` dataset of shape (radials, bins) along with
+//! `azimuth`, `elevation`, and `range` coordinates, and the scan's metadata
+//! as attributes of the root group. It's written by hand in the oldest
+//! version of the format, which every HDF5 reader understands, so the HDF5
+//! C library isn't needed.
+
+use std::error::Error;
+use std::io::Write;
+
+use crate::parse::PrecipRate;
+
+const SIGNATURE: &[u8] = b"\x89HDF\r\n\x1a\n";
+
+/// Size of the superblock, with 8-byte offsets and lengths
+const SUPERBLOCK_LEN: usize = 96;
+
+/// K values for the root group's B-tree and symbol table node. Readers
+/// expect nodes to take up their full size even when they're mostly empty.
+const LEAF_K: usize = 4;
+const INTERNAL_K: usize = 16;
+const BTREE_LEN: usize = 24 + 2 * INTERNAL_K * 8 + (2 * INTERNAL_K + 1) * 8;
+const SYMBOL_NODE_LEN: usize = 8 + 2 * LEAF_K * 40;
+
+const UNDEFINED: u64 = u64::MAX;
+
+const DATASPACE: u16 = 0x01;
+const DATATYPE: u16 = 0x03;
+const FILL_VALUE: u16 = 0x05;
+const LAYOUT: u16 = 0x08;
+const ATTRIBUTE: u16 = 0x0c;
+const SYMBOL_TABLE: u16 = 0x11;
+
+/// An attribute value
+enum Value {
+    Text(String),
+    Float(f64),
+    Int(i64),
+}
+
+impl Value {
+    fn datatype(&self) -> Vec<u8> {
+        match self {
+            // null terminated ASCII
+            Value::Text(s) => datatype(3, [0, 0, 0], s.len() as u32 + 1, &[]),
+            Value::Float(_) => float_type(8),
+            // signed little endian
+            Value::Int(_) => datatype(0, [0x08, 0, 0], 8, &[0, 0, 64, 0]),
+        }
+    }
+
+    fn data(&self) -> Vec<u8> {
+        match self {
+            Value::Text(s) => {
+                let mut data = s.as_bytes().to_vec();
+                data.push(0);
+                data
+            }
+            Value::Float(f) => f.to_le_bytes().to_vec(),
+            Value::Int(i) => i.to_le_bytes().to_vec(),
+        }
+    }
+}
+
+fn text(s: &str) -> Value {
+    Value::Text(String::from(s))
+}
+
+/// A datatype message of `class` with its bit field, size, and properties
+fn datatype(class: u8, bits: [u8; 3], size: u32, properties: &[u8]) -> Vec<u8> {
+    let mut datatype = vec![1 << 4 | class];
+    datatype.extend(bits);
+    datatype.extend(size.to_le_bytes());
+    datatype.extend(properties);
+    datatype
+}
+
+/// Little-endian IEEE 754 floats of 4 or 8 bytes
+fn float_type(size: u32) -> Vec<u8> {
+    let (exponent_location, exponent_size, mantissa_size, bias) = match size {
+        4 => (23u8, 8u8, 23u8, 127u32),
+        _ => (52, 11, 52, 1023),
+    };
+    let mut properties = vec![0, 0];
+    properties.extend((size as u16 * 8).to_le_bytes());
+    properties.extend([exponent_location, exponent_size, 0, mantissa_size]);
+    properties.extend(bias.to_le_bytes());
+    // implied leading mantissa bit, sign in the top bit
+    datatype(1, [0x20, size as u8 * 8 - 1, 0], size, &properties)
+}
+
+/// A simple dataspace with `dims`, or a scalar if there are none
+fn dataspace(dims: &[u64]) -> Vec<u8> {
+    let mut dataspace = vec![1, dims.len() as u8, 0, 0, 0, 0, 0, 0];
+    for dim in dims {
+        dataspace.extend(dim.to_le_bytes());
+    }
+    dataspace
+}
+
+fn pad(buffer: &mut Vec<u8>) {
+    while !buffer.len().is_multiple_of(8) {
+        buffer.push(0);
+    }
+}
+
+fn padded(bytes: &[u8]) -> Vec<u8> {
+    let mut bytes = bytes.to_vec();
+    pad(&mut bytes);
+    bytes
+}
+
+fn message(kind: u16, data: &[u8]) -> Vec<u8> {
+    let data = padded(data);
+    let mut message = kind.to_le_bytes().to_vec();
+    message.extend((data.len() as u16).to_le_bytes());
+    message.extend([0; 4]);
+    message.extend(data);
+    message
+}
+
+fn attribute(name: &str, value: &Value) -> Vec<u8> {
+    let mut name = name.as_bytes().to_vec();
+    name.push(0);
+    let (datatype, dataspace) = (value.datatype(), dataspace(&[]));
+    let mut attribute = vec![1, 0];
+    attribute.extend((name.len() as u16).to_le_bytes());
+    attribute.extend((datatype.len() as u16).to_le_bytes());
+    attribute.extend((dataspace.len() as u16).to_le_bytes());
+    attribute.extend(padded(&name));
+    attribute.extend(padded(&datatype));
+    attribute.extend(padded(&dataspace));
+    attribute.extend(value.data());
+    message(ATTRIBUTE, &attribute)
+}
+
+fn object_header(messages: &[Vec<u8>]) -> Vec<u8> {
+    let len: usize = messages.iter().map(|m| m.len()).sum();
+    let mut header = vec![1, 0];
+    header.extend((messages.len() as u16).to_le_bytes());
+    header.extend(1u32.to_le_bytes()); // reference count
+    header.extend((len as u32).to_le_bytes());
+    header.extend([0; 4]); // messages start 8-byte aligned
+    for message in messages {
+        header.extend(message);
+    }
+    header
+}
+
+/// A symbol table entry for a link whose name is at `name` in the local
+/// heap
+fn symbol_entry(name: u64, header: u64, scratch: Option<(u64, u64)>) -> Vec<u8> {
+    let mut entry = name.to_le_bytes().to_vec();
+    entry.extend(header.to_le_bytes());
+    match scratch {
+        Some((btree, heap)) => {
+            entry.extend(1u32.to_le_bytes());
+            entry.extend([0; 4]);
+            entry.extend(btree.to_le_bytes());
+            entry.extend(heap.to_le_bytes());
+        }
+        None => entry.extend([0; 24]),
+    }
+    entry
+}
+
+struct Dataset {
+    name: &'static str,
+    dims: Vec<u64>,
+    /// Little-endian 32-bit floats
+    data: Vec<f32>,
+    attributes: Vec<(&'static str, Value)>,
+}
+
+impl Dataset {
+    fn header(&self, address: u64) -> Vec<u8> {
+        let mut layout = vec![3, 1]; // contiguous
+        layout.extend(address.to_le_bytes());
+        layout.extend((self.data.len() as u64 * 4).to_le_bytes());
+        let mut messages = vec![
+            message(DATASPACE, &dataspace(&self.dims)),
+            message(DATATYPE, &float_type(4)),
+            // allocated early, no fill value
+            message(FILL_VALUE, &[2, 1, 2, 0]),
+            message(LAYOUT, &layout),
+        ];
+        for (name, value) in self.attributes.iter() {
+            messages.push(attribute(name, value));
+        }
+        object_header(&messages)
+    }
+}
+
+/// Write the polar data of `scan` as an HDF5 file. Radials with fewer bins
+/// than the longest one are padded with `NaN`.
+pub fn write_hdf5<W: Write>(scan: &PrecipRate, mut writer: W) -> Result<(), Box<dyn Error>> {
+    let radials = scan.radials.len();
+    let bins = scan
+        .radials
+        .iter()
+        .map(|r| r.precip_rates.len())
+        .max()
+        .unwrap_or(0);
+    let mut rates = Vec::with_capacity(radials * bins);
+    for radial in scan.radials.iter() {
+        rates.extend(radial.precip_rates.iter());
+        rates.extend(std::iter::repeat_n(
+            f32::NAN,
+            bins - radial.precip_rates.len(),
+        ));
+    }
+    // sorted by name, as the symbol table node requires
+    let datasets = [
+        Dataset {
+            name: "azimuth",
+            dims: vec![radials as u64],
+            data: scan.radials.iter().map(|r| r.azimuth).collect(),
+            attributes: vec![
+                ("long_name", text("azimuth of the center of the radial")),
+                ("units", text("degrees")),
+            ],
+        },
+        Dataset {
+            name: "elevation",
+            dims: vec![radials as u64],
+            data: scan.radials.iter().map(|r| r.elevation).collect(),
+            attributes: vec![
+                ("long_name", text("elevation angle of the beam")),
+                ("units", text("degrees")),
+            ],
+        },
+        Dataset {
+            name: "precip_rate",
+            dims: vec![radials as u64, bins as u64],
+            data: rates,
+            attributes: vec![
+                ("long_name", text("digital precipitation rate")),
+                ("units", text("in/hr")),
+                ("dimensions", text("azimuth range")),
+            ],
+        },
+        Dataset {
+            name: "range",
+            dims: vec![bins as u64],
+            data: (0..bins)
+                .map(|b| scan.range_to_first_bin + scan.bin_size * (b as f32 + 0.5))
+                .collect(),
+            attributes: vec![
+                ("long_name", text("distance to the center of the bin")),
+                ("units", text("km")),
+            ],
+        },
+    ];
+    let root_attributes = [
+        ("station", Value::Text(scan.station_code.to_uppercase())),
+        (
+            "capture_time",
+            Value::Text(scan.capture_time.format("%Y-%m-%dT%H:%M:%SZ").to_string()),
+        ),
+        ("latitude", Value::Float(scan.latitude as f64)),
+        ("longitude", Value::Float(scan.longitude as f64)),
+        ("bin_size", Value::Float(scan.bin_size as f64)),
+        (
+            "range_to_first_bin",
+            Value::Float(scan.range_to_first_bin as f64),
+        ),
+        ("scan_number", Value::Int(scan.scan_number as i64)),
+        (
+            "operational_mode",
+            text(&format!("{:?}", scan.operational_mode)),
+        ),
+    ];
+
+    // names in the local heap, after the empty string that it must start
+    // with
+    let mut heap_data = vec![0; 8];
+    let mut name_offsets = Vec::new();
+    for dataset in datasets.iter() {
+        name_offsets.push(heap_data.len() as u64);
+        heap_data.extend(dataset.name.as_bytes());
+        heap_data.push(0);
+        pad(&mut heap_data);
+    }
+
+    // everything but the raw data has a size that doesn't depend on where
+    // things go, so lay the file out first
+    let root_header = |btree: u64, heap: u64| {
+        let mut symbol_table = btree.to_le_bytes().to_vec();
+        symbol_table.extend(heap.to_le_bytes());
+        let mut messages = vec![message(SYMBOL_TABLE, &symbol_table)];
+        for (name, value) in root_attributes.iter() {
+            messages.push(attribute(name, value));
+        }
+        object_header(&messages)
+    };
+    let root_address = SUPERBLOCK_LEN as u64;
+    let heap_address = root_address + root_header(0, 0).len() as u64;
+    let btree_address = heap_address + 32 + heap_data.len() as u64;
+    let node_address = btree_address + BTREE_LEN as u64;
+    let mut header_addresses = Vec::new();
+    let mut offset = node_address + SYMBOL_NODE_LEN as u64;
+    for dataset in datasets.iter() {
+        header_addresses.push(offset);
+        offset += dataset.header(0).len() as u64;
+    }
+    let mut data_addresses = Vec::new();
+    for dataset in datasets.iter() {
+        data_addresses.push(offset);
+        offset += dataset.data.len() as u64 * 4;
+    }
+    let end = offset;
+
+    let mut file = SIGNATURE.to_vec();
+    file.extend([0, 0, 0, 0, 0, 8, 8, 0]);
+    file.extend((LEAF_K as u16).to_le_bytes());
+    file.extend((INTERNAL_K as u16).to_le_bytes());
+    file.extend([0; 4]);
+    file.extend(0u64.to_le_bytes());
+    file.extend(UNDEFINED.to_le_bytes());
+    file.extend(end.to_le_bytes());
+    file.extend(UNDEFINED.to_le_bytes());
+    file.extend(symbol_entry(
+        0,
+        root_address,
+        Some((btree_address, heap_address)),
+    ));
+
+    file.extend(root_header(btree_address, heap_address));
+
+    file.extend(b"HEAP\0\0\0\0");
+    file.extend((heap_data.len() as u64).to_le_bytes());
+    // readers take an offset of 1 to mean there are no free blocks
+    file.extend(1u64.to_le_bytes());
+    file.extend((heap_address + 32).to_le_bytes());
+    file.extend(&heap_data);
+
+    // one leaf with every dataset, keyed by the empty name and the last one
+    let mut btree = b"TREE\0\0".to_vec();
+    btree.extend(1u16.to_le_bytes());
+    btree.extend(UNDEFINED.to_le_bytes());
+    btree.extend(UNDEFINED.to_le_bytes());
+    btree.extend(0u64.to_le_bytes());
+    btree.extend(node_address.to_le_bytes());
+    btree.extend(name_offsets.last().unwrap().to_le_bytes());
+    btree.resize(BTREE_LEN, 0);
+    file.extend(btree);
+
+    let mut node = b"SNOD\x01\0".to_vec();
+    node.extend((datasets.len() as u16).to_le_bytes());
+    for (name, header) in name_offsets.iter().zip(header_addresses.iter()) {
+        node.extend(symbol_entry(*name, *header, None));
+    }
+    node.resize(SYMBOL_NODE_LEN, 0);
+    file.extend(node);
+
+    for (dataset, address) in datasets.iter().zip(data_addresses) {
+        file.extend(dataset.header(address));
+    }
+    writer.write_all(&file)?;
+    for dataset in datasets.iter() {
+        let data: Vec<u8> = dataset.data.iter().flat_map(|v| v.to_le_bytes()).collect();
+        writer.write_all(&data)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+#[test]
+fn test_write_hdf5() {
+    let mut scan = crate::coverage::scan_with_azimuths(&[0.5, 1.5]);
+    scan.radials[0].precip_rates = vec![0.25, f32::NAN, 1.];
+    let mut file = Vec::new();
+    write_hdf5(&scan, &mut file).unwrap();
+
+    let u64_at = |at: usize| u64::from_le_bytes(file[at..at + 8].try_into().unwrap());
+    let u16_at = |at: usize| u16::from_le_bytes([file[at], file[at + 1]]);
+    assert!(file.starts_with(SIGNATURE));
+    assert_eq!(u64_at(40) as usize, file.len());
+
+    // follow the root group's B-tree to its symbol table node
+    let (btree, heap) = (u64_at(80) as usize, u64_at(88) as usize);
+    assert_eq!(&file[btree..btree + 4], b"TREE");
+    assert_eq!(&file[heap..heap + 4], b"HEAP");
+    let heap_data = u64_at(heap + 24) as usize;
+    let node = u64_at(btree + 32) as usize;
+    assert_eq!(&file[node..node + 4], b"SNOD");
+    let names: Vec<(String, usize)> = (0..u16_at(node + 6) as usize)
+        .map(|i| {
+            let entry = node + 8 + i * 40;
+            let name = &file[heap_data + u64_at(entry) as usize..];
+            let name = &name[..name.iter().position(|b| *b == 0).unwrap()];
+            (
+                String::from_utf8(name.to_vec()).unwrap(),
+                u64_at(entry + 8) as usize,
+            )
+        })
+        .collect();
+    let keys: Vec<&str> = names.iter().map(|(name, _)| name.as_str()).collect();
+    assert_eq!(keys, ["azimuth", "elevation", "precip_rate", "range"]);
+
+    // walk the messages of precip_rate's object header
+    let header = names[2].1;
+    assert_eq!(file[header], 1);
+    let mut at = header + 16;
+    let (mut dims, mut data) = (Vec::new(), 0..0);
+    for _ in 0..u16_at(header + 2) {
+        let (kind, len) = (u16_at(at), u16_at(at + 2) as usize);
+        let body = at + 8;
+        match kind {
+            DATASPACE => {
+                dims = (0..file[body + 1] as usize)
+                    .map(|d| u64_at(body + 8 + d * 8))
+                    .collect()
+            }
+            LAYOUT => {
+                let start = u64_at(body + 2) as usize;
+                data = start..start + u64_at(body + 10) as usize;
+            }
+            _ => (),
+        }
+        at = body + len;
+    }
+    assert_eq!(dims, [2, 3]);
+    let rates: Vec<f32> = file[data]
+        .chunks(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect();
+    assert_eq!(rates[0], 0.25);
+    assert!(rates[1].is_nan());
+    assert_eq!(rates[2], 1.);
+    // the second radial is padded out to the longest one
+    assert_eq!(rates[3], 0.);
+    assert!(rates[4..].iter().all(|r| r.is_nan()));
+}
@@ -0,0 +1,165 @@
+//! The Hydrologic Rainfall Analysis Project (HRAP) grid, a polar
+//! stereographic grid of roughly 4 km cells used by Stage II/III and other
+//! legacy NWS precipitation products
+
+use crate::geomath::get_point_bearing_distance;
+use crate::parse::{coord_as_i64, GridData, PrecipRate};
+
+/// Radius of the spherical earth that HRAP is defined on, in km
+const HRAP_EARTH_RADIUS: f64 = 6371.2;
+/// Longitude that points straight down the grid from the pole
+const HRAP_STANDARD_LONGITUDE: f64 = -105.;
+/// Latitude at which cells are exactly [`HRAP_MESH`] wide
+const HRAP_TRUE_LATITUDE: f64 = 60.;
+/// Width of a cell at the true latitude, in km
+const HRAP_MESH: f64 = 4.7625;
+/// HRAP coordinates of the north pole
+const HRAP_POLE: (f64, f64) = (401., 1601.);
+
+/// Distance from the pole in cells per unit of `tan(45° - latitude / 2)`
+fn hrap_scale() -> f64 {
+    HRAP_EARTH_RADIUS * (1. + HRAP_TRUE_LATITUDE.to_radians().sin()) / HRAP_MESH
+}
+
+/// Convert a latitude and longitude in degrees to fractional HRAP (x, y)
+/// coordinates
+pub fn lat_long_to_hrap(latitude: f32, longitude: f32) -> (f32, f32) {
+    let latitude = (latitude as f64).to_radians();
+    let angle = (longitude as f64 - HRAP_STANDARD_LONGITUDE).to_radians();
+    let r = hrap_scale() * (std::f64::consts::FRAC_PI_4 - latitude / 2.).tan();
+    (
+        (HRAP_POLE.0 + r * angle.sin()) as f32,
+        (HRAP_POLE.1 - r * angle.cos()) as f32,
+    )
+}
+
+/// Convert fractional HRAP (x, y) coordinates to a latitude and longitude in
+/// degrees
+pub fn hrap_to_lat_long(x: f32, y: f32) -> (f32, f32) {
+    let (dx, dy) = (x as f64 - HRAP_POLE.0, y as f64 - HRAP_POLE.1);
+    let r = dx.hypot(dy);
+    let latitude = 90. - 2. * (r / hrap_scale()).atan().to_degrees();
+    let longitude = HRAP_STANDARD_LONGITUDE + dx.atan2(-dy).to_degrees();
+    (
+        latitude as f32,
+        ((longitude + 180.).rem_euclid(360.) - 180.) as f32,
+    )
+}
+
+/// A rectangular window of the national HRAP grid. Cell `(column, row)`
+/// spans HRAP coordinates `x_min + column` to `x_min + column + 1` and
+/// likewise for y, so its center is at `x_min + column + 0.5`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HrapGrid {
+    /// HRAP x coordinate of the western edge of the window
+    pub x_min: i32,
+    /// HRAP y coordinate of the southern edge of the window
+    pub y_min: i32,
+    pub columns: usize,
+    pub rows: usize,
+}
+
+impl HrapGrid {
+    /// The smallest window that covers a square of `radius` kilometers
+    /// around the given coordinate
+    pub fn around(latitude: f32, longitude: f32, radius: f32) -> Self {
+        let corners: Vec<(f32, f32)> = [315., 45., 135., 225.]
+            .iter()
+            .map(|bearing| {
+                let corner = get_point_bearing_distance(
+                    (latitude, longitude),
+                    *bearing,
+                    radius * std::f32::consts::SQRT_2,
+                );
+                lat_long_to_hrap(corner.0, corner.1)
+            })
+            .collect();
+        let x_min = corners.iter().map(|c| c.0).fold(f32::INFINITY, f32::min);
+        let x_max = corners
+            .iter()
+            .map(|c| c.0)
+            .fold(f32::NEG_INFINITY, f32::max);
+        let y_min = corners.iter().map(|c| c.1).fold(f32::INFINITY, f32::min);
+        let y_max = corners
+            .iter()
+            .map(|c| c.1)
+            .fold(f32::NEG_INFINITY, f32::max);
+        HrapGrid {
+            x_min: x_min.floor() as i32,
+            y_min: y_min.floor() as i32,
+            columns: (x_max.ceil() - x_min.floor()) as usize,
+            rows: (y_max.ceil() - y_min.floor()) as usize,
+        }
+    }
+
+    /// An empty grid with the latitude and longitude of each cell center.
+    /// Row 0 is the northernmost row, as with the other grids.
+    pub fn empty_grid(&self) -> GridData {
+        (0..self.rows)
+            .rev()
+            .map(|row| {
+                (0..self.columns)
+                    .map(|column| {
+                        let (lat, lon) = hrap_to_lat_long(
+                            self.x_min as f32 + column as f32 + 0.5,
+                            self.y_min as f32 + row as f32 + 0.5,
+                        );
+                        ([coord_as_i64(lat), coord_as_i64(lon)], 0.)
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// PROJ definition of the projected coordinate system that
+    /// [`HrapGrid::geotransform`] is in
+    pub fn proj4() -> &'static str {
+        "+proj=stere +lat_0=90 +lat_ts=60 +lon_0=-105 +x_0=0 +y_0=0 \
+         +a=6371200 +b=6371200 +units=m +no_defs"
+    }
+
+    /// GDAL-style affine transform from (column, row) of
+    /// [`HrapGrid::empty_grid`] to projected meters: the x of the western
+    /// edge, cell width, 0, the y of the northern edge, 0, and negative cell
+    /// height
+    pub fn geotransform(&self) -> [f64; 6] {
+        let mesh = HRAP_MESH * 1000.;
+        [
+            (self.x_min as f64 - HRAP_POLE.0) * mesh,
+            mesh,
+            0.,
+            (self.y_min as f64 + self.rows as f64 - HRAP_POLE.1) * mesh,
+            0.,
+            -mesh,
+        ]
+    }
+}
+
+impl PrecipRate {
+    /// Sample the scan onto the window of the HRAP grid that covers
+    /// `radius` kilometers around the station
+    pub fn sample_radials_to_hrap(&self, radius: f32) -> (HrapGrid, GridData) {
+        let hrap = HrapGrid::around(self.latitude, self.longitude, radius);
+        (hrap, self.sample_radials_to_grid(&hrap.empty_grid()))
+    }
+}
+
+#[test]
+fn test_hrap() {
+    // the pole is at (401, 1601), and cells are exactly 4.7625 km tall where
+    // the projection is true, at 60°N
+    assert_eq!(lat_long_to_hrap(90., 0.), (401., 1601.));
+    let (x, y) = lat_long_to_hrap(60., -105.);
+    let (north, _) = hrap_to_lat_long(x, y + 1.);
+    let height = (north - 60.).to_radians() * HRAP_EARTH_RADIUS as f32;
+    assert!((height - 4.7625).abs() < 0.001, "{}", height);
+    let (x, y) = lat_long_to_hrap(43.891, -70.256);
+    let (lat, lon) = hrap_to_lat_long(x, y);
+    assert!((lat - 43.891).abs() < 0.0001 && (lon + 70.256).abs() < 0.0001);
+
+    let grid = HrapGrid::around(43.891, -70.256, 100.);
+    assert!(grid.x_min as f32 <= x && x <= (grid.x_min + grid.columns as i32) as f32);
+    let cells = grid.empty_grid();
+    assert_eq!((cells.len(), cells[0].len()), (grid.rows, grid.columns));
+    assert!(cells[0][0].0[0] > cells[grid.rows - 1][0].0[0]);
+}
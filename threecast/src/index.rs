@@ -0,0 +1,149 @@
+use rstar::{RTree, RTreeObject, AABB};
+
+use crate::output::BinRef;
+use crate::parse::{coord_as_i64, PrecipRate};
+use crate::watch::contains;
+
+/// A bin stored in a [`BinIndex`]
+#[derive(Debug)]
+pub struct IndexedBin {
+    pub radial_index: usize,
+    pub bin_index: usize,
+    /// Precipitation rate in in/hr
+    pub rate: f32,
+    /// The (latitude, longitude) of the center of the bin in degrees
+    pub center: (f32, f32),
+    ring: Vec<[i64; 2]>,
+    envelope: AABB<[f32; 2]>,
+}
+
+impl IndexedBin {
+    fn new(bin: &BinRef) -> Self {
+        let polygon = bin.polygon();
+        IndexedBin {
+            radial_index: bin.radial_index,
+            bin_index: bin.bin_index,
+            rate: bin.rate(),
+            center: bin.center(),
+            ring: polygon
+                .iter()
+                .map(|v| [coord_as_i64(v.0), coord_as_i64(v.1)])
+                .collect(),
+            envelope: AABB::from_points(
+                polygon
+                    .iter()
+                    .map(|v| [v.0, v.1])
+                    .collect::<Vec<_>>()
+                    .iter(),
+            ),
+        }
+    }
+
+    fn contains(&self, latitude: f32, longitude: f32) -> bool {
+        contains(
+            &self.ring,
+            [coord_as_i64(latitude), coord_as_i64(longitude)],
+        )
+    }
+}
+
+impl RTreeObject for IndexedBin {
+    type Envelope = AABB<[f32; 2]>;
+
+    fn envelope(&self) -> Self::Envelope {
+        self.envelope
+    }
+}
+
+/// An R-tree over the bin polygons of a scan, for answering many location
+/// queries against the same scan. Coordinates are (latitude, longitude) in
+/// degrees.
+pub struct BinIndex {
+    tree: RTree<IndexedBin>,
+}
+
+impl BinIndex {
+    pub fn new(scan: &PrecipRate) -> Self {
+        BinIndex {
+            tree: RTree::bulk_load(scan.bins().map(|bin| IndexedBin::new(&bin)).collect()),
+        }
+    }
+
+    /// The bin that contains the given location, if any
+    pub fn at_point(&self, latitude: f32, longitude: f32) -> Option<&IndexedBin> {
+        self.tree
+            .locate_in_envelope_intersecting(&AABB::from_point([latitude, longitude]))
+            .find(|bin| bin.contains(latitude, longitude))
+    }
+
+    /// Every bin whose outline overlaps the given box, approximately
+    pub fn in_bbox(
+        &self,
+        south: f32,
+        west: f32,
+        north: f32,
+        east: f32,
+    ) -> impl Iterator<Item = &IndexedBin> {
+        self.tree
+            .locate_in_envelope_intersecting(&AABB::from_corners([south, west], [north, east]))
+    }
+
+    /// Every bin whose center lies inside the closed ring of `vertices`, plus
+    /// any bin that contains one of the vertices, so small polygons still
+    /// match the bins they fall in
+    pub fn in_polygon(&self, vertices: &[(f32, f32)]) -> Vec<&IndexedBin> {
+        let ring: Vec<[i64; 2]> = vertices
+            .iter()
+            .map(|v| [coord_as_i64(v.0), coord_as_i64(v.1)])
+            .collect();
+        let envelope = AABB::from_points(
+            vertices
+                .iter()
+                .map(|v| [v.0, v.1])
+                .collect::<Vec<_>>()
+                .iter(),
+        );
+        self.tree
+            .locate_in_envelope_intersecting(&envelope)
+            .filter(|bin| {
+                contains(
+                    &ring,
+                    [coord_as_i64(bin.center.0), coord_as_i64(bin.center.1)],
+                ) || vertices.iter().any(|v| bin.contains(v.0, v.1))
+            })
+            .collect()
+    }
+}
+
+impl PrecipRate {
+    /// Build an R-tree over the bins of the scan
+    pub fn build_index(&self) -> BinIndex {
+        BinIndex::new(self)
+    }
+}
+
+#[test]
+fn test_bin_index() {
+    let azimuths: Vec<f32> = (0..360).map(|a| a as f32 + 0.5).collect();
+    let mut scan = crate::coverage::scan_with_azimuths(&azimuths);
+    (scan.bin_size, scan.range_to_first_bin) = (10., 10.);
+    scan.radials[90].precip_rates = vec![0.2];
+    let index = scan.build_index();
+    let east =
+        crate::geomath::get_point_bearing_distance((scan.latitude, scan.longitude), 90.5, 15.);
+    let bin = index.at_point(east.0, east.1).unwrap();
+    assert_eq!((bin.radial_index, bin.rate), (90, 0.2));
+    assert!(index.at_point(0., 0.).is_none());
+    assert_eq!(
+        index
+            .in_bbox(
+                scan.latitude - 1.,
+                scan.longitude - 1.,
+                scan.latitude + 1.,
+                scan.longitude + 1.
+            )
+            .count(),
+        360
+    );
+    assert_eq!(index.in_polygon(&[east, east, east]).len(), 1);
+}
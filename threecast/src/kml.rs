@@ -0,0 +1,163 @@
+//! [KML](https://www.ogc.org/standards/kml/) output for Google Earth and
+//! other virtual globes
+//!
+//! Each scan becomes a `Folder` with the scan's time span, so several scans
+//! written to the same document can be animated with the time slider.
+
+use std::error::Error;
+use std::io::{Seek, Write};
+
+use chrono::NaiveDateTime;
+
+use crate::output::{BinRef, OutputSink};
+use crate::parse::PrecipRate;
+use crate::schedule::ScanCadence;
+use crate::util::precip_category;
+
+/// Fill colors by [`precip_category`] in KML's `aabbggrr` order, roughly
+/// following the usual radar color scale. Bins without data are gray, and
+/// bins without precipitation are fully transparent.
+const CATEGORY_STYLES: &[(&str, &str)] = &[
+    ("none", "00000000"),
+    ("light", "c0e7e904"),
+    ("moderate", "c001c501"),
+    ("heavy", "c002f8fd"),
+    ("violent", "c00000fd"),
+    ("missing", "80808080"),
+];
+
+const TIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%SZ";
+
+/// Writes bins as styled polygon placemarks in a KML document. Call
+/// [`KmlSink::finish_document`] after the last scan to close the document.
+pub struct KmlSink<W: Write> {
+    writer: W,
+    started: bool,
+}
+
+impl<W: Write> KmlSink<W> {
+    pub fn new(writer: W) -> Self {
+        KmlSink {
+            writer,
+            started: false,
+        }
+    }
+
+    /// Close the document and give back the underlying writer
+    pub fn finish_document(mut self) -> Result<W, Box<dyn Error>> {
+        self.start_document()?;
+        self.writer.write_all(b"</Document>\n</kml>\n")?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    fn start_document(&mut self) -> Result<(), Box<dyn Error>> {
+        if self.started {
+            return Ok(());
+        }
+        self.started = true;
+        self.writer.write_all(
+            b"<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+              <kml xmlns=\"http://www.opengis.net/kml/2.2\">\n<Document>\n",
+        )?;
+        for (category, color) in CATEGORY_STYLES {
+            writeln!(
+                self.writer,
+                "<Style id=\"{}\"><LineStyle><width>0</width></LineStyle>\
+                 <PolyStyle><color>{}</color><outline>0</outline></PolyStyle></Style>",
+                category, color
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// When a scan stops being the latest one: one typical scan interval after
+/// it started
+fn time_span(scan: &PrecipRate) -> (NaiveDateTime, NaiveDateTime) {
+    (
+        scan.capture_time,
+        scan.capture_time + ScanCadence::typical_interval(&scan.operational_mode),
+    )
+}
+
+impl<W: Write> OutputSink for KmlSink<W> {
+    fn begin(&mut self, scan: &PrecipRate) -> Result<(), Box<dyn Error>> {
+        self.start_document()?;
+        let (begin, end) = time_span(scan);
+        writeln!(
+            self.writer,
+            "<Folder><name>{} {}</name><TimeSpan><begin>{}</begin><end>{}</end></TimeSpan>",
+            escape(&scan.station_code.to_uppercase()),
+            begin.format(TIME_FORMAT),
+            begin.format(TIME_FORMAT),
+            end.format(TIME_FORMAT)
+        )?;
+        Ok(())
+    }
+
+    fn write_bin(&mut self, bin: &BinRef) -> Result<(), Box<dyn Error>> {
+        let coordinates: Vec<String> = bin
+            .polygon()
+            .into_iter()
+            .map(|(latitude, longitude)| format!("{},{}", longitude, latitude))
+            .collect();
+        writeln!(
+            self.writer,
+            "<Placemark><styleUrl>#{}</styleUrl><ExtendedData><Data name=\"precipRate\">\
+             <value>{}</value></Data></ExtendedData><Polygon><outerBoundaryIs><LinearRing>\
+             <coordinates>{}</coordinates></LinearRing></outerBoundaryIs></Polygon></Placemark>",
+            precip_category(bin.rate()),
+            bin.rate(),
+            coordinates.join(" ")
+        )?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Box<dyn Error>> {
+        self.writer.write_all(b"</Folder>\n")?;
+        Ok(())
+    }
+}
+
+pub(crate) fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Package a KML document as a KMZ archive, which is a zip file with the
+/// document as `doc.kml`
+pub fn write_kmz<W: Write + Seek>(writer: W, kml: &[u8]) -> Result<W, Box<dyn Error>> {
+    let mut zip = zip::ZipWriter::new(writer);
+    zip.start_file(
+        "doc.kml",
+        zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Deflated),
+    )?;
+    zip.write_all(kml)?;
+    Ok(zip.finish()?)
+}
+
+#[test]
+fn test_kml_sink() {
+    let mut scan = crate::coverage::scan_with_azimuths(&[0.5, 1.5]);
+    scan.radials[0].precip_rates = vec![0.2, f32::NAN];
+    let mut sink = KmlSink::new(Vec::new());
+    crate::output::write_scan(&scan, &mut sink).unwrap();
+    crate::output::write_scan(&scan, &mut sink).unwrap();
+    let kml = String::from_utf8(sink.finish_document().unwrap()).unwrap();
+    assert_eq!(kml.matches("<Style id=").count(), CATEGORY_STYLES.len());
+    assert_eq!(kml.matches("<Folder>").count(), 2);
+    assert!(kml.contains(
+        "<TimeSpan><begin>1970-01-01T00:00:00Z</begin><end>1970-01-01T00:05:00Z</end></TimeSpan>"
+    ));
+    assert_eq!(kml.matches("#moderate").count(), 2);
+    assert_eq!(kml.matches("#missing").count(), 2);
+    assert!(kml.ends_with("</Document>\n</kml>\n"));
+
+    let kmz = write_kmz(std::io::Cursor::new(Vec::new()), kml.as_bytes())
+        .unwrap()
+        .into_inner();
+    let mut archive = zip::ZipArchive::new(std::io::Cursor::new(kmz)).unwrap();
+    assert_eq!(archive.by_index(0).unwrap().name(), "doc.kml");
+}
@@ -0,0 +1,355 @@
+//! Rates estimated from NEXRAD Level II volumes, like the ones in the
+//! `noaa-nexrad-level2` bucket on AWS, for when the Level III rate product
+//! is late or missing
+//!
+//! Reflectivity from the lowest elevation is turned into a rate with a Z-R
+//! relationship, `Z = a * R^b`, which is much cruder than the
+//! dual-polarization algorithm behind the rate product but only needs the
+//! raw data. Only volumes made of message 31 radials are supported, which
+//! covers everything since 2008.
+
+use std::io::Read;
+
+use crate::parse::{julian_time, symbology_reader, OperationalMode, PrecipRate, Radial, ScanTimes};
+
+/// Every message besides message 31 takes up this many bytes, counting the
+/// 12 bytes in front of its header
+const FIXED_MESSAGE_SIZE: usize = 2432;
+
+/// A Z-R relationship, with `Z` in mm^6/m^3 and `R` in mm/hr
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ZrRelationship {
+    pub a: f32,
+    pub b: f32,
+    /// Reflectivity is capped here before conversion, so that hail doesn't
+    /// turn into absurd rates
+    pub max_dbz: f32,
+}
+
+impl ZrRelationship {
+    /// The WSR-88D default for convective rain
+    pub const CONVECTIVE: ZrRelationship = ZrRelationship {
+        a: 300.,
+        b: 1.4,
+        max_dbz: 53.,
+    };
+    /// Marshall-Palmer, for stratiform rain
+    pub const MARSHALL_PALMER: ZrRelationship = ZrRelationship {
+        a: 200.,
+        b: 1.6,
+        max_dbz: 53.,
+    };
+    /// Rosenfeld, for tropical rain
+    pub const TROPICAL: ZrRelationship = ZrRelationship {
+        a: 250.,
+        b: 1.2,
+        max_dbz: 53.,
+    };
+
+    /// Rate in in/hr for a reflectivity in dBZ
+    pub fn rate(&self, dbz: f32) -> f32 {
+        let z = 10f32.powf(dbz.min(self.max_dbz) / 10.);
+        (z / self.a).powf(1. / self.b) / 25.4
+    }
+}
+
+impl Default for ZrRelationship {
+    fn default() -> Self {
+        ZrRelationship::CONVECTIVE
+    }
+}
+
+fn slice(data: &[u8], offset: usize, n: usize) -> Result<&[u8], String> {
+    match data.get(offset..offset + n) {
+        Some(s) => Ok(s),
+        None => Err(format!(
+            "Level II data ended early: wanted {} bytes at offset {}",
+            n, offset
+        )),
+    }
+}
+
+fn u8_at(data: &[u8], offset: usize) -> Result<u8, String> {
+    Ok(slice(data, offset, 1)?[0])
+}
+
+fn u16_at(data: &[u8], offset: usize) -> Result<u16, String> {
+    Ok(u16::from_be_bytes(
+        slice(data, offset, 2)?.try_into().unwrap(),
+    ))
+}
+
+fn i16_at(data: &[u8], offset: usize) -> Result<i16, String> {
+    Ok(i16::from_be_bytes(
+        slice(data, offset, 2)?.try_into().unwrap(),
+    ))
+}
+
+fn u32_at(data: &[u8], offset: usize) -> Result<u32, String> {
+    Ok(u32::from_be_bytes(
+        slice(data, offset, 4)?.try_into().unwrap(),
+    ))
+}
+
+fn f32_at(data: &[u8], offset: usize) -> Result<f32, String> {
+    Ok(f32::from_be_bytes(
+        slice(data, offset, 4)?.try_into().unwrap(),
+    ))
+}
+
+/// Undo the LDM records after the volume header, each of which is a
+/// four-byte size and a bzip2 block. Very old volumes aren't compressed, in
+/// which case the messages follow the header directly.
+fn decompress(data: &[u8]) -> Result<Vec<u8>, String> {
+    if data.get(4..7) != Some(b"BZh") {
+        return Ok(data.to_vec());
+    }
+    let mut messages = Vec::new();
+    let mut offset = 0;
+    while offset < data.len() {
+        // the last record has a negative size
+        let size = u32_at(data, offset)? as i32;
+        let record = slice(data, offset + 4, size.unsigned_abs() as usize)?;
+        if let Err(e) = symbology_reader(1, record)?.read_to_end(&mut messages) {
+            return Err(format!("Failed to decompress Level II record: {}", e));
+        }
+        offset += 4 + record.len();
+    }
+    Ok(messages)
+}
+
+/// One radial of reflectivity from a message 31
+struct ReflectivityRadial {
+    elevation_number: u8,
+    radial: Radial,
+    first_gate: f32,
+    gate_spacing: f32,
+    collected: Option<chrono::NaiveDateTime>,
+    site: Option<(f32, f32, u16)>,
+}
+
+/// Parse a message 31 (Generic Digital Radar Data) that starts at
+/// `message`, which is just past the message header, converting its
+/// reflectivity with `zr`. Returns `None` if it has no reflectivity.
+fn radial(message: &[u8], zr: &ZrRelationship) -> Result<Option<ReflectivityRadial>, String> {
+    let collection_time = u32_at(message, 4)?;
+    let collection_date = u16_at(message, 8)?;
+    let azimuth = f32_at(message, 12)?;
+    let width = match u8_at(message, 20)? {
+        1 => 0.5,
+        _ => 1.,
+    };
+    let elevation_number = u8_at(message, 22)?;
+    let elevation = f32_at(message, 24)?;
+    let block_count = u16_at(message, 30)? as usize;
+
+    let mut site = None;
+    let mut reflectivity = None;
+    for i in 0..block_count.min(10) {
+        let pointer = u32_at(message, 32 + i * 4)? as usize;
+        match slice(message, pointer, 4)? {
+            b"RVOL" => {
+                let latitude = f32_at(message, pointer + 8)?;
+                let longitude = f32_at(message, pointer + 12)?;
+                let vcp = u16_at(message, pointer + 40)?;
+                site = Some((latitude, longitude, vcp));
+            }
+            b"DREF" => reflectivity = Some(pointer),
+            _ => (),
+        }
+    }
+    let pointer = match reflectivity {
+        Some(p) => p,
+        None => return Ok(None),
+    };
+    let gates = u16_at(message, pointer + 8)? as usize;
+    let first_gate = i16_at(message, pointer + 10)? as f32 / 1000.;
+    let gate_spacing = i16_at(message, pointer + 12)? as f32 / 1000.;
+    let word_size = u8_at(message, pointer + 19)?;
+    let scale = f32_at(message, pointer + 20)?;
+    let offset = f32_at(message, pointer + 24)?;
+    let words: Vec<u16> = match word_size {
+        8 => slice(message, pointer + 28, gates)?
+            .iter()
+            .map(|&w| w as u16)
+            .collect(),
+        16 => slice(message, pointer + 28, gates * 2)?
+            .chunks_exact(2)
+            .map(|w| u16::from_be_bytes([w[0], w[1]]))
+            .collect(),
+        size => return Err(format!("Unsupported Level II word size {}", size)),
+    };
+    let precip_rates = words
+        .into_iter()
+        .map(|word| match word {
+            // below the signal threshold, so there's nothing falling
+            0 => 0.,
+            // range folded
+            1 => f32::NAN,
+            n => zr.rate((n as f32 - offset) / scale),
+        })
+        .collect();
+    Ok(Some(ReflectivityRadial {
+        elevation_number,
+        radial: Radial {
+            azimuth,
+            elevation,
+            width,
+            precip_rates,
+            interpolated: false,
+        },
+        first_gate,
+        gate_spacing,
+        collected: julian_time(collection_date as i16, (collection_time / 1000) as i32),
+        site,
+    }))
+}
+
+/// Parse a Level II volume and estimate rates from the reflectivity of its
+/// lowest elevation with `zr`. The result has no scan number, since
+/// Level II volumes don't carry one.
+pub fn parse_level2(input: Vec<u8>, zr: &ZrRelationship) -> Result<PrecipRate, String> {
+    let header = slice(&input, 0, 24)?;
+    if !header.starts_with(b"AR2V") {
+        return Err(String::from("Not a Level II volume: missing AR2V header"));
+    }
+    let date = u32_at(header, 12)?;
+    let time = u32_at(header, 16)?;
+    let station_code = match String::from_utf8(header[20..24].to_vec()) {
+        Ok(s) => s,
+        Err(e) => return Err(format!("Failed to parse station code: {}", e)),
+    };
+    let capture_time = match julian_time(date as i16, (time / 1000) as i32) {
+        Some(t) => t,
+        None => return Err(String::from("Level II volume has no date")),
+    };
+
+    let messages = decompress(&input[24..])?;
+    let mut radials: Vec<ReflectivityRadial> = Vec::new();
+    let mut offset = 0;
+    while offset + 28 <= messages.len() {
+        // 12 bytes of padding, then the message header
+        let size = u16_at(&messages, offset + 12)? as usize * 2;
+        let message_type = u8_at(&messages, offset + 15)?;
+        if message_type == 31 {
+            if let Some(radial) =
+                radial(slice(&messages, offset + 28, size.saturating_sub(16))?, zr)?
+            {
+                radials.push(radial);
+            }
+            offset += 12 + size.max(16);
+        } else {
+            offset += FIXED_MESSAGE_SIZE;
+        }
+    }
+
+    let lowest = match radials.iter().map(|r| r.elevation_number).min() {
+        Some(e) => e,
+        None => return Err(String::from("Level II volume has no reflectivity")),
+    };
+    radials.retain(|r| r.elevation_number == lowest);
+    let first = &radials[0];
+    let (latitude, longitude, vcp) = match radials.iter().find_map(|r| r.site) {
+        Some(s) => s,
+        None => return Err(String::from("Level II volume has no site location")),
+    };
+    let (bin_size, range_to_first_bin) = (first.gate_spacing, first.first_gate);
+    let elevation_start = first.collected;
+    let radials: Vec<Radial> = radials.into_iter().map(|r| r.radial).collect();
+    Ok(PrecipRate {
+        station_code,
+        capture_time,
+        scan_number: 0,
+        latitude,
+        longitude,
+        // the clear air coverage patterns
+        operational_mode: match vcp {
+            31 | 32 | 35 => OperationalMode::CleanAir,
+            _ => OperationalMode::Precipitation,
+        },
+        precip_detected: radials
+            .iter()
+            .any(|r| r.precip_rates.iter().any(|&rate| rate > 0.)),
+        bin_size,
+        range_to_first_bin,
+        radials,
+        times: ScanTimes {
+            volume_start: Some(capture_time),
+            elevation_start,
+            ..Default::default()
+        },
+        metadata: None,
+        extra_components: Vec::new(),
+        alphanumeric: Default::default(),
+    })
+}
+
+#[test]
+fn test_parse_level2() {
+    let message = |azimuth: f32, elevation_number: u8, reflectivity: &[u8]| {
+        let mut body = Vec::new();
+        body.extend(b"KGYX");
+        body.extend(3_600_000u32.to_be_bytes());
+        body.extend(19000u16.to_be_bytes());
+        body.extend(1u16.to_be_bytes());
+        body.extend(azimuth.to_be_bytes());
+        body.extend([0, 0, 0, 0, 1, 0, elevation_number, 0]);
+        body.extend(0.5f32.to_be_bytes());
+        body.extend([0, 0]);
+        body.extend(2u16.to_be_bytes());
+        body.extend(40u32.to_be_bytes());
+        body.extend(84u32.to_be_bytes());
+        // volume block
+        body.extend(b"RVOL");
+        body.extend([0; 4]);
+        body.extend(43.891f32.to_be_bytes());
+        body.extend((-70.256f32).to_be_bytes());
+        body.extend([0; 24]);
+        body.extend(212u16.to_be_bytes());
+        body.extend([0; 2]);
+        // reflectivity block
+        body.extend(b"DREF");
+        body.extend([0; 4]);
+        body.extend((reflectivity.len() as u16).to_be_bytes());
+        body.extend(2125i16.to_be_bytes());
+        body.extend(250i16.to_be_bytes());
+        body.extend([0, 0, 0, 0, 0, 8]);
+        body.extend(2f32.to_be_bytes());
+        body.extend(66f32.to_be_bytes());
+        body.extend(reflectivity);
+        let mut message = vec![0; 12];
+        message.extend(((body.len() + 16) as u16 / 2).to_be_bytes());
+        message.extend([0, 31]);
+        message.extend([0; 12]);
+        message.extend(body);
+        message
+    };
+
+    let mut data = b"AR2V0006.001".to_vec();
+    data.extend(19000u32.to_be_bytes());
+    data.extend(3_600_000u32.to_be_bytes());
+    data.extend(b"KGYX");
+    // a metadata message, which is skipped over
+    data.extend([0; FIXED_MESSAGE_SIZE]);
+    data.extend(message(0.25, 1, &[0, 1, 126, 172]));
+    data.extend(message(0.75, 1, &[0, 0, 0, 0]));
+    data.extend(message(0.25, 2, &[0, 0, 0, 0]));
+
+    let scan = parse_level2(data, &ZrRelationship::default()).unwrap();
+    assert_eq!(scan.station_code, "KGYX");
+    assert_eq!(scan.capture_time, julian_time(19000, 3600).unwrap());
+    assert_eq!((scan.latitude, scan.longitude), (43.891, -70.256));
+    assert_eq!((scan.bin_size, scan.range_to_first_bin), (0.25, 2.125));
+    assert_eq!(scan.radials.len(), 2);
+    assert_eq!(scan.radials[0].width, 0.5);
+    let rates = &scan.radials[0].precip_rates;
+    assert_eq!(rates[0], 0.);
+    assert!(rates[1].is_nan());
+    // 30 dBZ is about 2.4 mm/hr
+    assert!((rates[2] * 25.4 - 2.36).abs() < 0.01);
+    // 53 dBZ is the cap, about 104 mm/hr
+    assert!((rates[3] * 25.4 - 103.8).abs() < 0.1);
+    assert!(scan.precip_detected);
+
+    assert!(parse_level2(b"SDUS51 KGYX".to_vec(), &ZrRelationship::default()).is_err());
+}
@@ -1,6 +1,53 @@
+pub mod accumulation;
+pub mod alphanumeric;
+pub mod blend;
+pub mod compare;
+pub mod coverage;
+#[cfg(feature = "geozero")]
+pub mod datasource;
+pub mod encode;
+#[cfg(feature = "fgb")]
+pub mod fgb;
+pub mod filter;
+#[cfg(feature = "gdal-cli")]
+pub mod gdal_cli;
+#[cfg(feature = "arrow")]
+pub mod geoarrow;
 pub mod geomath;
+#[cfg(feature = "geoparquet")]
+pub mod geoparquet;
+pub mod geotiff;
+pub mod gml;
+pub mod grib2;
+pub mod hdf5;
+pub mod hrap;
+#[cfg(feature = "rtree")]
+pub mod index;
+pub mod kml;
+#[cfg(feature = "level2")]
+pub mod level2;
+pub mod metrics;
+pub mod mqtt;
+pub mod mrms;
+pub mod mvt;
 pub mod net;
+pub mod netcdf;
+pub mod output;
 pub mod parse;
 pub mod predict;
+pub mod raster;
+pub mod reflectivity;
+pub mod render;
+pub mod s3;
+pub mod schedule;
+pub mod shapefile;
 pub mod stations;
+pub mod summary;
+pub mod synthetic;
+pub mod transform;
 pub mod util;
+pub mod verify;
+pub mod volume;
+pub mod watch;
+pub mod zarr;
+pub mod zonal;
@@ -0,0 +1,229 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// How long a scraper gets to send its request line or take the response
+/// before its connection is dropped
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Default)]
+struct StationMetrics {
+    scans_processed: u64,
+    parse_failures: u64,
+    fetch_failures: u64,
+    fetch_seconds_sum: f64,
+    fetch_count: u64,
+    conversion_seconds_sum: f64,
+    conversion_count: u64,
+    last_capture_time: Option<i64>,
+}
+
+/// Counters and timings for long-running modes, keyed by station and
+/// exposed in the Prometheus text format. This is shared between threads, so
+/// all of the methods take `&self`.
+#[derive(Default)]
+pub struct Metrics {
+    stations: Mutex<BTreeMap<String, StationMetrics>>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn with_station<F: FnOnce(&mut StationMetrics)>(&self, station: &str, f: F) {
+        let mut stations = self.stations.lock().unwrap();
+        f(stations.entry(station.to_uppercase()).or_default());
+    }
+
+    /// Record an attempt to download a data file and how long it took
+    pub fn record_fetch(&self, station: &str, duration: Duration, success: bool) {
+        self.with_station(station, |m| {
+            m.fetch_seconds_sum += duration.as_secs_f64();
+            m.fetch_count += 1;
+            if !success {
+                m.fetch_failures += 1;
+            }
+        });
+    }
+
+    pub fn record_parse_failure(&self, station: &str) {
+        self.with_station(station, |m| m.parse_failures += 1);
+    }
+
+    /// Record a successfully processed scan and how long it took to parse and
+    /// convert it
+    pub fn record_scan(
+        &self,
+        station: &str,
+        capture_time: chrono::NaiveDateTime,
+        duration: Duration,
+    ) {
+        self.with_station(station, |m| {
+            m.scans_processed += 1;
+            m.conversion_seconds_sum += duration.as_secs_f64();
+            m.conversion_count += 1;
+            m.last_capture_time = Some(capture_time.timestamp());
+        });
+    }
+
+    /// Render all metrics in the Prometheus text exposition format
+    pub fn render(&self) -> String {
+        let stations = self.stations.lock().unwrap();
+        let now = chrono::Utc::now().timestamp();
+        let mut output = String::new();
+        // each family has one sample per station for each suffix, like
+        // `_sum` and `_count` for summaries
+        type Sample<'a> = (&'a str, &'a dyn Fn(&StationMetrics) -> Option<String>);
+        let mut family = |name: &str, kind: &str, help: &str, samples: &[Sample]| {
+            output.push_str(&format!(
+                "# HELP {} {}\n# TYPE {} {}\n",
+                name, help, name, kind
+            ));
+            for (station, metrics) in stations.iter() {
+                for (suffix, value) in samples {
+                    if let Some(v) = value(metrics) {
+                        output.push_str(&format!(
+                            "{}{}{{station=\"{}\"}} {}\n",
+                            name, suffix, station, v
+                        ));
+                    }
+                }
+            }
+        };
+        family(
+            "threecast_scans_processed_total",
+            "counter",
+            "Number of scans parsed and processed",
+            &[("", &|m| Some(m.scans_processed.to_string()))],
+        );
+        family(
+            "threecast_parse_failures_total",
+            "counter",
+            "Number of data files that failed to parse",
+            &[("", &|m| Some(m.parse_failures.to_string()))],
+        );
+        family(
+            "threecast_fetch_failures_total",
+            "counter",
+            "Number of data file downloads that failed",
+            &[("", &|m| Some(m.fetch_failures.to_string()))],
+        );
+        family(
+            "threecast_fetch_duration_seconds",
+            "summary",
+            "Time spent downloading data files",
+            &[
+                ("_sum", &|m| Some(m.fetch_seconds_sum.to_string())),
+                ("_count", &|m| Some(m.fetch_count.to_string())),
+            ],
+        );
+        family(
+            "threecast_conversion_duration_seconds",
+            "summary",
+            "Time spent parsing and converting scans",
+            &[
+                ("_sum", &|m| Some(m.conversion_seconds_sum.to_string())),
+                ("_count", &|m| Some(m.conversion_count.to_string())),
+            ],
+        );
+        family(
+            "threecast_data_age_seconds",
+            "gauge",
+            "Time since the capture of the most recent scan",
+            &[("", &|m| m.last_capture_time.map(|t| (now - t).to_string()))],
+        );
+        output
+    }
+
+    /// Serve the metrics at `/metrics` over HTTP on a background thread
+    pub fn serve(self: &Arc<Self>, address: &str) -> Result<(), Box<dyn Error>> {
+        let listener = TcpListener::bind(address)?;
+        let metrics = Arc::clone(self);
+        std::thread::spawn(move || metrics.accept(listener));
+        Ok(())
+    }
+
+    /// Answer each connection on its own thread, so that a client that
+    /// connects and goes quiet can't hold up the others
+    fn accept(self: Arc<Self>, listener: TcpListener) {
+        for stream in listener.incoming().flatten() {
+            let metrics = Arc::clone(&self);
+            std::thread::spawn(move || metrics.respond(stream));
+        }
+    }
+
+    fn respond(&self, mut stream: TcpStream) {
+        if stream.set_read_timeout(Some(CLIENT_TIMEOUT)).is_err()
+            || stream.set_write_timeout(Some(CLIENT_TIMEOUT)).is_err()
+        {
+            return;
+        }
+        let mut request_line = String::new();
+        if BufReader::new(&stream)
+            .read_line(&mut request_line)
+            .is_err()
+        {
+            return;
+        }
+        let response = match request_line.split_whitespace().nth(1) {
+            Some("/metrics") => {
+                let body = self.render();
+                format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                )
+            }
+            _ => String::from(
+                "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+            ),
+        };
+        let _ = stream.write_all(response.as_bytes());
+    }
+}
+
+#[test]
+fn test_render_metrics() {
+    let metrics = Metrics::new();
+    metrics.record_fetch("kgyx", Duration::from_millis(500), true);
+    metrics.record_fetch("kgyx", Duration::from_millis(500), false);
+    metrics.record_parse_failure("KGYX");
+    let rendered = metrics.render();
+    assert!(rendered.contains("# TYPE threecast_parse_failures_total counter\n"));
+    assert!(rendered.contains("threecast_parse_failures_total{station=\"KGYX\"} 1\n"));
+    assert!(rendered.contains("threecast_fetch_failures_total{station=\"KGYX\"} 1\n"));
+    assert!(rendered.contains(
+        "# TYPE threecast_fetch_duration_seconds summary\n\
+         threecast_fetch_duration_seconds_sum{station=\"KGYX\"} 1\n\
+         threecast_fetch_duration_seconds_count{station=\"KGYX\"} 2\n"
+    ));
+    assert!(!rendered.contains("# TYPE threecast_fetch_duration_seconds_sum"));
+    assert!(!rendered.contains("threecast_data_age_seconds{"));
+}
+
+#[test]
+fn test_serve_metrics() {
+    use std::io::Read;
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let metrics = Arc::new(Metrics::new());
+    metrics.record_parse_failure("KGYX");
+    let accepting = Arc::clone(&metrics);
+    std::thread::spawn(move || accepting.accept(listener));
+
+    // a client that never sends anything doesn't block the next one
+    let _idle = TcpStream::connect(address).unwrap();
+    let mut client = TcpStream::connect(address).unwrap();
+    client
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    client.write_all(b"GET /metrics HTTP/1.1\r\n\r\n").unwrap();
+    let mut response = String::new();
+    client.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(response.contains("threecast_parse_failures_total{station=\"KGYX\"} 1\n"));
+}
@@ -0,0 +1,130 @@
+use std::error::Error;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+/// A minimal MQTT 3.1.1 client that can only publish messages at QoS 0. This
+/// is enough to push scan summaries, nowcasts, and alerts to a broker for
+/// home-automation setups without pulling in an async runtime.
+///
+/// The connection is closed cleanly when the client is dropped.
+///
+/// For more information, see the [MQTT 3.1.1 spec](http://docs.oasis-open.org/mqtt/mqtt/v3.1.1/os/mqtt-v3.1.1-os.html).
+pub struct MqttClient {
+    stream: TcpStream,
+}
+
+/// Encode the "remaining length" field of a fixed header (section 2.2.3)
+fn encode_remaining_length(mut length: usize) -> Vec<u8> {
+    let mut encoded = Vec::new();
+    loop {
+        let mut byte = (length % 128) as u8;
+        length /= 128;
+        if length > 0 {
+            byte |= 0x80;
+        }
+        encoded.push(byte);
+        if length == 0 {
+            return encoded;
+        }
+    }
+}
+
+/// Encode a UTF-8 string with its two-byte length prefix (section 1.5.3)
+fn encode_string(string: &str) -> Vec<u8> {
+    let mut encoded = (string.len() as u16).to_be_bytes().to_vec();
+    encoded.extend_from_slice(string.as_bytes());
+    encoded
+}
+
+fn packet(header: u8, body: Vec<u8>) -> Vec<u8> {
+    let mut packet = vec![header];
+    packet.extend(encode_remaining_length(body.len()));
+    packet.extend(body);
+    packet
+}
+
+/// A client ID that's unique to this process, like `threecast-myhost-1234`.
+/// Brokers drop the existing session when another client connects with the
+/// same ID, so separate processes that publish at the same time need
+/// different ones. Characters other than ASCII letters, digits, and `-` are
+/// left out of the host name, since brokers only have to accept those.
+pub fn default_client_id() -> String {
+    let hostname = std::env::var("HOSTNAME")
+        .or_else(|_| std::env::var("COMPUTERNAME"))
+        .or_else(|_| std::fs::read_to_string("/etc/hostname"))
+        .unwrap_or_default();
+    let hostname: String = hostname
+        .trim()
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '-')
+        .collect();
+    match hostname.is_empty() {
+        true => format!("threecast-{}", std::process::id()),
+        false => format!("threecast-{}-{}", hostname, std::process::id()),
+    }
+}
+
+impl MqttClient {
+    /// Connect to a broker at `address` (e.g. `"localhost:1883"`) and wait for
+    /// it to accept the connection. See [`default_client_id`] for a
+    /// `client_id` that won't clash with other processes.
+    pub fn connect(address: &str, client_id: &str) -> Result<Self, Box<dyn Error>> {
+        let mut stream = TcpStream::connect(address)?;
+        stream.set_read_timeout(Some(Duration::from_secs(10)))?;
+        // protocol name, protocol level 4, clean session, 60-second keep alive
+        let mut body = encode_string("MQTT");
+        body.extend_from_slice(&[4, 0x02, 0, 60]);
+        body.extend(encode_string(client_id));
+        stream.write_all(&packet(0x10, body))?;
+        let mut connack = [0; 4];
+        stream.read_exact(&mut connack)?;
+        match connack {
+            [0x20, 0x02, _, 0] => Ok(MqttClient { stream }),
+            [0x20, 0x02, _, code] => {
+                Err(format!("MQTT broker refused connection with code {}", code).into())
+            }
+            _ => Err("MQTT broker sent an invalid CONNACK packet".into()),
+        }
+    }
+
+    /// Publish `payload` to `topic` at QoS 0. Retained messages are kept by
+    /// the broker and delivered to clients that subscribe later.
+    pub fn publish(
+        &mut self,
+        topic: &str,
+        payload: &[u8],
+        retain: bool,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut body = encode_string(topic);
+        body.extend_from_slice(payload);
+        self.stream.write_all(&packet(0x30 | retain as u8, body))?;
+        Ok(())
+    }
+}
+
+impl Drop for MqttClient {
+    fn drop(&mut self) {
+        let _ = self.stream.write_all(&[0xe0, 0x00]);
+    }
+}
+
+#[test]
+fn test_default_client_id() {
+    let id = default_client_id();
+    assert!(id.starts_with("threecast-"));
+    assert!(id.ends_with(&format!("-{}", std::process::id())));
+    assert!(id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-'));
+}
+
+#[test]
+fn test_encode_remaining_length() {
+    assert_eq!(encode_remaining_length(0), vec![0x00]);
+    assert_eq!(encode_remaining_length(127), vec![0x7f]);
+    assert_eq!(encode_remaining_length(128), vec![0x80, 0x01]);
+    assert_eq!(encode_remaining_length(16_383), vec![0xff, 0x7f]);
+    assert_eq!(
+        encode_remaining_length(2_097_152),
+        vec![0x80, 0x80, 0x80, 0x01]
+    );
+}
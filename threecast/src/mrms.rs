@@ -0,0 +1,338 @@
+//! Reading [MRMS](https://www.nssl.noaa.gov/projects/mrms/) precipitation
+//! rate mosaics from GRIB2, so CONUS-wide rates can go through the same
+//! raster outputs as a single radar
+//!
+//! MRMS packs its grids with PNG (template 5.41) or simple packing
+//! (template 5.0), and marks cells without coverage with negative values.
+//! PRATE messages, like the ones [`crate::grib2::write_grib2`] writes, are
+//! read as well.
+
+use std::error::Error;
+
+use crate::raster::Raster;
+use crate::util::gunzip_if_needed;
+
+/// A precipitation rate grid and the time it's valid for
+#[derive(Debug)]
+pub struct MrmsRate {
+    pub time: chrono::NaiveDateTime,
+    /// Rates in in/hr, with `NaN` for cells without coverage
+    pub raster: Raster,
+}
+
+/// Read GRIB2's sign-and-magnitude integers
+fn signed32(bytes: &[u8]) -> i32 {
+    let value = u32::from_be_bytes(bytes.try_into().unwrap());
+    match value & 0x8000_0000 {
+        0 => value as i32,
+        _ => -((value & 0x7fff_ffff) as i32),
+    }
+}
+
+fn signed16(bytes: &[u8]) -> i16 {
+    let value = u16::from_be_bytes(bytes.try_into().unwrap());
+    match value & 0x8000 {
+        0 => value as i16,
+        _ => -((value & 0x7fff) as i16),
+    }
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+/// Read `count` big-endian fields of `bits` bits each
+fn unpack_bits(data: &[u8], bits: usize, count: usize) -> Result<Vec<u32>, Box<dyn Error>> {
+    if data.len() * 8 < bits * count {
+        return Err("GRIB2 data section is too short".into());
+    }
+    Ok((0..count)
+        .map(|i| {
+            let mut value = 0;
+            for bit in i * bits..(i + 1) * bits {
+                value = value << 1 | (data[bit / 8] >> (7 - bit % 8) & 1) as u32;
+            }
+            value
+        })
+        .collect())
+}
+
+/// Unpack PNG-encoded values, which are the grayscale pixels of the image
+fn unpack_png(data: &[u8]) -> Result<Vec<u32>, Box<dyn Error>> {
+    match image::load_from_memory_with_format(data, image::ImageFormat::Png)? {
+        image::DynamicImage::ImageLuma8(image) => Ok(image.pixels().map(|p| p[0] as u32).collect()),
+        image::DynamicImage::ImageLuma16(image) => {
+            Ok(image.pixels().map(|p| p[0] as u32).collect())
+        }
+        _ => Err("Only grayscale PNG packing is supported".into()),
+    }
+}
+
+/// Parse the first message of an MRMS precipitation rate GRIB2 file,
+/// gunzipping it first if needed
+pub fn parse_mrms_grib2(input: Vec<u8>) -> Result<MrmsRate, Box<dyn Error>> {
+    let input = gunzip_if_needed(input)?;
+    if !input.starts_with(b"GRIB") || input.get(7) != Some(&2) {
+        return Err("Not a GRIB2 file".into());
+    }
+    let discipline = input[6];
+
+    // gather sections by number, stopping at the end marker
+    let mut sections: [Option<&[u8]>; 8] = Default::default();
+    let mut offset = 16;
+    while offset + 5 <= input.len() && &input[offset..offset + 4] != b"7777" {
+        let len = u32_at(&input, offset) as usize;
+        let number = input[offset + 4] as usize;
+        if len < 5 || offset + len > input.len() || number > 7 {
+            return Err(format!("Bad GRIB2 section at offset {}", offset).into());
+        }
+        sections[number] = Some(&input[offset..offset + len]);
+        offset += len;
+    }
+    // the templates that are supported have fixed lengths, which are
+    // checked up front so the fields can be read at their offsets
+    let section = |n: usize, min_len: usize| match sections[n] {
+        Some(s) if s.len() >= min_len => Ok(s),
+        Some(s) => Err(format!(
+            "GRIB2 section {} is {} bytes, expected at least {}",
+            n,
+            s.len(),
+            min_len
+        )),
+        None => Err(format!("GRIB2 message is missing section {}", n)),
+    };
+    let (identification, grid, product) = (section(1, 21)?, section(3, 72)?, section(4, 34)?);
+    let (representation, bitmap, data) = (section(5, 21)?, section(6, 6)?, section(7, 5)?);
+
+    // MRMS PrecipRate is in mm/hr, and PRATE in kg m-2 s-1
+    let to_inches_per_hour = match (discipline, product[9], product[10]) {
+        (209, 6, 1) => 1. / 25.4,
+        (0, 1, 7) => 3600. / 25.4,
+        (d, c, p) => {
+            return Err(format!(
+                "Not a precipitation rate: discipline {}, category {}, parameter {}",
+                d, c, p
+            )
+            .into())
+        }
+    };
+
+    let year = u16::from_be_bytes([identification[12], identification[13]]) as i32;
+    let [month, day, hour, minute, second] = [14, 15, 16, 17, 18].map(|i| identification[i] as u32);
+    let time = match chrono::NaiveDate::from_ymd_opt(year, month, day)
+        .and_then(|date| date.and_hms_opt(hour, minute, second))
+    {
+        Some(t) => t,
+        None => return Err("GRIB2 reference time is invalid".into()),
+    };
+
+    if u16::from_be_bytes([grid[12], grid[13]]) != 0 {
+        return Err("Only regular latitude/longitude grids are supported".into());
+    }
+    let width = u32_at(grid, 30) as usize;
+    let height = u32_at(grid, 34) as usize;
+    let first_latitude = signed32(&grid[46..50]) as f64 / 1e6;
+    let mut first_longitude = signed32(&grid[50..54]) as f64 / 1e6;
+    if first_longitude > 180. {
+        first_longitude -= 360.;
+    }
+    let last_latitude = signed32(&grid[55..59]) as f64 / 1e6;
+    let (dx, dy) = (u32_at(grid, 63) as f64 / 1e6, u32_at(grid, 67) as f64 / 1e6);
+    let scanning_mode = grid[71];
+    if (dx - dy).abs() > 1e-9 {
+        return Err("Only grids with square cells are supported".into());
+    }
+    if scanning_mode & 0xa0 != 0 {
+        return Err(format!("Unsupported GRIB2 scanning mode {:#04x}", scanning_mode).into());
+    }
+    let south_to_north = scanning_mode & 0x40 != 0;
+
+    let present = u32_at(representation, 5) as usize;
+    let template = u16::from_be_bytes([representation[9], representation[10]]);
+    let reference = f32::from_be_bytes(representation[11..15].try_into().unwrap()) as f64;
+    let binary_scale = signed16(&representation[15..17]) as i32;
+    let decimal_scale = signed16(&representation[17..19]) as i32;
+    let bits = representation[19] as usize;
+    let packed = match (template, bits) {
+        (0 | 41, 0) => vec![0; present],
+        (0, _) => unpack_bits(&data[5..], bits, present)?,
+        (41, _) => unpack_png(&data[5..])?,
+        (t, _) => return Err(format!("Unsupported GRIB2 packing template 5.{}", t).into()),
+    };
+    if packed.len() < present {
+        return Err("GRIB2 message has fewer values than it says".into());
+    }
+    let mut values = packed.into_iter().map(|value| {
+        let value =
+            (reference + value as f64 * 2f64.powi(binary_scale)) / 10f64.powi(decimal_scale);
+        match value < 0. {
+            // no coverage or missing
+            true => f32::NAN,
+            false => (value * to_inches_per_hour) as f32,
+        }
+    });
+
+    // spread the values over the points the bitmap says have data
+    let points = width * height;
+    let mut rates: Vec<f32> = match bitmap[5] {
+        0 => {
+            let bitmap = unpack_bits(&bitmap[6..], 1, points)?;
+            bitmap
+                .into_iter()
+                .map(|bit| match bit {
+                    1 => values.next().unwrap_or(f32::NAN),
+                    _ => f32::NAN,
+                })
+                .collect()
+        }
+        255 => values.take(points).collect(),
+        _ => return Err("Predefined GRIB2 bitmaps aren't supported".into()),
+    };
+    if rates.len() != points {
+        return Err("GRIB2 message has fewer values than grid points".into());
+    }
+    if south_to_north {
+        rates = rates.chunks(width).rev().flatten().copied().collect();
+    }
+
+    let north = first_latitude.max(last_latitude) + dy / 2.;
+    Ok(MrmsRate {
+        time,
+        raster: Raster {
+            west: first_longitude - dx / 2.,
+            north,
+            resolution: dx,
+            width,
+            height,
+            data: rates,
+        },
+    })
+}
+
+#[test]
+fn test_parse_prate_round_trip() {
+    let scan = crate::coverage::scan_with_azimuths(&[0.5]);
+    let raster = Raster {
+        west: -71.,
+        north: 44.,
+        resolution: 0.5,
+        width: 3,
+        height: 2,
+        data: vec![0., 0.5, f32::NAN, 1., f32::NAN, 0.25],
+    };
+    let mut grib = Vec::new();
+    crate::grib2::write_grib2(&scan, &raster, &mut grib).unwrap();
+    let parsed = parse_mrms_grib2(grib).unwrap();
+    assert_eq!(parsed.time, scan.capture_time);
+    let parsed = parsed.raster;
+    assert_eq!((parsed.width, parsed.height), (3, 2));
+    assert!((parsed.west + 71.).abs() < 1e-6 && (parsed.north - 44.).abs() < 1e-6);
+    assert_eq!(parsed.resolution, 0.5);
+    for (parsed, rate) in parsed.data.iter().zip(raster.data) {
+        assert!((parsed - rate).abs() < 1e-4 || (parsed.is_nan() && rate.is_nan()));
+    }
+}
+
+#[test]
+fn test_parse_truncated_grib2() {
+    let scan = crate::coverage::scan_with_azimuths(&[0.5]);
+    let raster = Raster {
+        west: -71.,
+        north: 44.,
+        resolution: 0.5,
+        width: 3,
+        height: 2,
+        data: vec![0., 0.5, f32::NAN, 1., f32::NAN, 0.25],
+    };
+    let mut grib = Vec::new();
+    crate::grib2::write_grib2(&scan, &raster, &mut grib).unwrap();
+    // cutting the message off anywhere before the end marker is an error,
+    // not a panic
+    for len in 0..grib.len() - 4 {
+        assert!(parse_mrms_grib2(grib[..len].to_vec()).is_err(), "{}", len);
+    }
+
+    // and so is a section that's too short for its template
+    let mut sections = Vec::new();
+    let mut offset = 16;
+    while &grib[offset..offset + 4] != b"7777" {
+        let len = u32_at(&grib, offset) as usize;
+        sections.push(&grib[offset..offset + len]);
+        offset += len;
+    }
+    for number in [1, 3, 4, 5, 6] {
+        let mut short = grib[..16].to_vec();
+        for section in sections.iter() {
+            match section[4] {
+                n if n == number => short.extend(crate::grib2::section(n, &[])),
+                _ => short.extend(*section),
+            }
+        }
+        short.extend(b"7777");
+        let error = parse_mrms_grib2(short).unwrap_err().to_string();
+        assert!(
+            error.starts_with(&format!("GRIB2 section {} is 5 bytes", number)),
+            "{}",
+            error
+        );
+    }
+}
+
+#[test]
+fn test_parse_mrms_png() {
+    use crate::grib2::section;
+
+    // a 2 by 2 grid in fifths of mm/hr offset by -3, south row first
+    let mut png = Vec::new();
+    let image = image::GrayImage::from_raw(2, 2, vec![0, 15, 142, 16]).unwrap();
+    image::DynamicImage::ImageLuma8(image)
+        .write_to(&mut png, image::ImageOutputFormat::Png)
+        .unwrap();
+    let mut identification = vec![0; 16];
+    identification[7..14].copy_from_slice(&[7, 0xe6, 1, 7, 1, 0, 0]);
+    let mut grid = vec![0; 67];
+    grid[25..29].copy_from_slice(&2u32.to_be_bytes());
+    grid[29..33].copy_from_slice(&2u32.to_be_bytes());
+    grid[41..45].copy_from_slice(&20_005_000u32.to_be_bytes());
+    grid[45..49].copy_from_slice(&230_005_000u32.to_be_bytes());
+    grid[50..54].copy_from_slice(&20_015_000u32.to_be_bytes());
+    grid[58..62].copy_from_slice(&10_000u32.to_be_bytes());
+    grid[62..66].copy_from_slice(&10_000u32.to_be_bytes());
+    grid[66] = 0x40;
+    let mut product = vec![0; 29];
+    product[4..6].copy_from_slice(&[6, 1]);
+    let mut representation = 4u32.to_be_bytes().to_vec();
+    representation.extend(41u16.to_be_bytes());
+    representation.extend((-30f32).to_be_bytes());
+    representation.extend(crate::grib2::signed16(1));
+    representation.extend(crate::grib2::signed16(1));
+    representation.extend([8, 0]);
+
+    let mut grib = b"GRIB\0\0\xd1\x02".to_vec();
+    grib.extend([0; 8]);
+    for (number, body) in [
+        (1, identification),
+        (3, grid),
+        (4, product),
+        (5, representation),
+        (6, vec![255]),
+        (7, png),
+    ] {
+        grib.extend(section(number, &body));
+    }
+    grib.extend(b"7777");
+
+    let parsed = parse_mrms_grib2(grib).unwrap();
+    assert_eq!(parsed.time.to_string(), "2022-01-07 01:00:00");
+    let raster = parsed.raster;
+    assert!((raster.west + 130.).abs() < 1e-6);
+    assert!((raster.north - 20.02).abs() < 1e-6);
+    // the north row comes first, and the negative values have no coverage
+    assert_eq!(raster.data[0], 1.);
+    assert!((raster.data[1] - 0.2 / 25.4).abs() < 1e-6);
+    assert!(raster.data[2].is_nan());
+    assert_eq!(raster.data[3], 0.);
+    let ([latitude, longitude], rate) = raster.to_grid()[1][1];
+    assert!((latitude - 200050).abs() <= 1 && (longitude + 1299850).abs() <= 1);
+    assert_eq!(rate, 0.);
+}
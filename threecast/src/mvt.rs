@@ -0,0 +1,296 @@
+//! [Mapbox Vector Tile](https://github.com/mapbox/vector-tile-spec) output,
+//! so that web maps can show bins without going through tippecanoe
+//!
+//! Bins are cut into XYZ tiles in Web Mercator, each with one `bins` layer
+//! of polygons and a `precipRate` property. Bins are small enough that they
+//! aren't clipped to the tiles; a bin that crosses a tile edge is put in
+//! every tile it touches and renderers clip it. At low zooms, bins that
+//! shrink to nothing after snapping to the tile grid are left out.
+
+use std::collections::BTreeMap;
+use std::f64::consts::PI;
+use std::ops::RangeInclusive;
+
+use crate::filter::BinFilter;
+use crate::output::BinRef;
+use crate::parse::PrecipRate;
+
+/// Size of a tile in its own integer coordinates
+const EXTENT: u32 = 4096;
+
+const LAYER_NAME: &str = "bins";
+
+/// Address of a tile in the XYZ scheme, with `y` counted from the north
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TileId {
+    pub z: u8,
+    pub x: u32,
+    pub y: u32,
+}
+
+/// Web Mercator position in tiles at zoom `z`
+fn mercator(latitude: f32, longitude: f32, z: u8) -> (f64, f64) {
+    let tiles = (1u64 << z) as f64;
+    let latitude = (latitude as f64).to_radians();
+    (
+        (longitude as f64 + 180.) / 360. * tiles,
+        (1. - (latitude.tan() + 1. / latitude.cos()).ln() / PI) / 2. * tiles,
+    )
+}
+
+fn put_varint(buffer: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buffer.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    buffer.push(value as u8);
+}
+
+fn put_key(buffer: &mut Vec<u8>, field: u32, wire_type: u32) {
+    put_varint(buffer, (field << 3 | wire_type) as u64);
+}
+
+/// A length-delimited field: an embedded message, string, or packed array
+fn put_bytes(buffer: &mut Vec<u8>, field: u32, bytes: &[u8]) {
+    put_key(buffer, field, 2);
+    put_varint(buffer, bytes.len() as u64);
+    buffer.extend(bytes);
+}
+
+fn put_packed(buffer: &mut Vec<u8>, field: u32, values: &[u32]) {
+    let mut packed = Vec::new();
+    for value in values {
+        put_varint(&mut packed, *value as u64);
+    }
+    put_bytes(buffer, field, &packed);
+}
+
+fn zigzag(value: i32) -> u32 {
+    ((value << 1) ^ (value >> 31)) as u32
+}
+
+/// Encode a ring in tile coordinates as polygon geometry commands, or
+/// `None` if it has no area
+fn encode_ring(mut ring: Vec<(i32, i32)>) -> Option<Vec<u32>> {
+    ring.dedup();
+    if ring.len() > 1 && ring.first() == ring.last() {
+        ring.pop();
+    }
+    // exterior rings must be clockwise with y pointing down, which makes
+    // this sum positive
+    let area: i64 = (0..ring.len())
+        .map(|i| {
+            let (a, b) = (ring[i], ring[(i + 1) % ring.len()]);
+            a.0 as i64 * b.1 as i64 - b.0 as i64 * a.1 as i64
+        })
+        .sum();
+    if ring.len() < 3 || area == 0 {
+        return None;
+    }
+    if area < 0 {
+        ring.reverse();
+    }
+    let mut commands = Vec::new();
+    let mut cursor = (0, 0);
+    for (idx, point) in ring.iter().enumerate() {
+        match idx {
+            // MoveTo once
+            0 => commands.push(1 | 1 << 3),
+            // LineTo for the rest
+            1 => commands.push(2 | ((ring.len() as u32 - 1) << 3)),
+            _ => (),
+        }
+        commands.push(zigzag(point.0 - cursor.0));
+        commands.push(zigzag(point.1 - cursor.1));
+        cursor = *point;
+    }
+    // ClosePath
+    commands.push(7 | 1 << 3);
+    Some(commands)
+}
+
+/// The features of one tile's layer as it's built up
+#[derive(Default)]
+struct Layer {
+    features: Vec<u8>,
+    /// Index of each distinct rate in the layer's value table, by its bits
+    values: BTreeMap<u32, u32>,
+}
+
+impl Layer {
+    fn add(&mut self, geometry: &[u32], rate: f32) {
+        let mut feature = Vec::new();
+        if !rate.is_nan() {
+            let next = self.values.len() as u32;
+            let value = *self.values.entry(rate.to_bits()).or_insert(next);
+            put_packed(&mut feature, 2, &[0, value]);
+        }
+        // type: polygon
+        put_key(&mut feature, 3, 0);
+        put_varint(&mut feature, 3);
+        put_packed(&mut feature, 4, geometry);
+        put_bytes(&mut self.features, 2, &feature);
+    }
+
+    fn encode(self) -> Vec<u8> {
+        let mut layer = Vec::new();
+        put_key(&mut layer, 15, 0);
+        put_varint(&mut layer, 2);
+        put_bytes(&mut layer, 1, LAYER_NAME.as_bytes());
+        layer.extend(self.features);
+        put_bytes(&mut layer, 3, b"precipRate");
+        let mut values: Vec<(u32, u32)> = self.values.into_iter().collect();
+        values.sort_by_key(|(_, index)| *index);
+        for (bits, _) in values {
+            let mut value = vec![0x15]; // field 2, 32-bit: float_value
+            value.extend(f32::from_bits(bits).to_le_bytes());
+            put_bytes(&mut layer, 4, &value);
+        }
+        put_key(&mut layer, 5, 0);
+        put_varint(&mut layer, EXTENT as u64);
+        let mut tile = Vec::new();
+        put_bytes(&mut tile, 3, &layer);
+        tile
+    }
+}
+
+impl PrecipRate {
+    /// Cut the bins that pass `filter` into vector tiles for each zoom level
+    /// in `zooms`. Only tiles with at least one bin are returned.
+    pub fn vector_tiles(
+        &self,
+        filter: &dyn BinFilter,
+        zooms: RangeInclusive<u8>,
+    ) -> BTreeMap<TileId, Vec<u8>> {
+        let mut layers: BTreeMap<TileId, Layer> = BTreeMap::new();
+        let bins: Vec<(Vec<(f32, f32)>, f32)> = self
+            .bins_filtered(filter)
+            .map(|bin: BinRef| (bin.polygon(), bin.rate()))
+            .collect();
+        for z in zooms {
+            for (polygon, rate) in bins.iter() {
+                let points: Vec<(f64, f64)> = polygon
+                    .iter()
+                    .map(|(latitude, longitude)| mercator(*latitude, *longitude, z))
+                    .collect();
+                let last_tile = (1u64 << z) as f64 - 1.;
+                let range = |axis: fn(&(f64, f64)) -> f64| {
+                    let values = points.iter().map(axis);
+                    let min = values.clone().fold(f64::INFINITY, f64::min);
+                    let max = values.fold(f64::NEG_INFINITY, f64::max);
+                    (
+                        min.floor().clamp(0., last_tile) as u32,
+                        max.floor().clamp(0., last_tile) as u32,
+                    )
+                };
+                let (x_range, y_range) = (range(|p| p.0), range(|p| p.1));
+                for x in x_range.0..=x_range.1 {
+                    for y in y_range.0..=y_range.1 {
+                        let ring = points
+                            .iter()
+                            .map(|(px, py)| {
+                                (
+                                    ((px - x as f64) * EXTENT as f64).round() as i32,
+                                    ((py - y as f64) * EXTENT as f64).round() as i32,
+                                )
+                            })
+                            .collect();
+                        if let Some(geometry) = encode_ring(ring) {
+                            layers
+                                .entry(TileId { z, x, y })
+                                .or_default()
+                                .add(&geometry, *rate);
+                        }
+                    }
+                }
+            }
+        }
+        layers
+            .into_iter()
+            .map(|(id, layer)| (id, layer.encode()))
+            .collect()
+    }
+}
+
+/// The (field number, value) pairs of a protobuf message, with varints as
+/// numbers and length-delimited fields as bytes
+#[cfg(test)]
+fn read_fields(mut message: &[u8]) -> Vec<(u64, Result<u64, &[u8]>)> {
+    let read_varint = |message: &mut &[u8]| {
+        let mut value = 0;
+        let mut shift = 0;
+        loop {
+            let byte = message[0];
+            *message = &message[1..];
+            value |= ((byte & 0x7f) as u64) << shift;
+            shift += 7;
+            if byte < 0x80 {
+                return value;
+            }
+        }
+    };
+    let mut fields = Vec::new();
+    while !message.is_empty() {
+        let key = read_varint(&mut message);
+        let value = match key & 7 {
+            0 => Ok(read_varint(&mut message)),
+            2 => {
+                let len = read_varint(&mut message) as usize;
+                let (bytes, rest) = message.split_at(len);
+                message = rest;
+                Err(bytes)
+            }
+            5 => {
+                let (bytes, rest) = message.split_at(4);
+                message = rest;
+                Err(bytes)
+            }
+            _ => panic!("unexpected wire type"),
+        };
+        fields.push((key >> 3, value));
+    }
+    fields
+}
+
+#[test]
+fn test_vector_tiles() {
+    let mut scan = crate::coverage::scan_with_azimuths(&[0.5, 1.5]);
+    scan.radials[0].precip_rates = vec![0.25, f32::NAN];
+    let tiles = scan.vector_tiles(&|_: &BinRef| true, 0..=12);
+    // the whole scan fits in one tile at every zoom, but the bins are far
+    // too small to show up at the lowest ones
+    assert!(!tiles.contains_key(&TileId { z: 0, x: 0, y: 0 }));
+    let (x, y) = mercator(scan.latitude, scan.longitude, 12);
+    let tile = &tiles[&TileId {
+        z: 12,
+        x: x as u32,
+        y: y as u32,
+    }];
+
+    let layers = read_fields(tile);
+    assert_eq!(layers.len(), 1);
+    let layer = match layers[0] {
+        (3, Err(layer)) => read_fields(layer),
+        _ => panic!("expected a layer"),
+    };
+    let field = |number: u64| layer.iter().filter(move |(n, _)| *n == number);
+    assert_eq!(field(15).next().unwrap().1, Ok(2));
+    assert_eq!(field(1).next().unwrap().1, Err(&b"bins"[..]));
+    assert_eq!(field(3).next().unwrap().1, Err(&b"precipRate"[..]));
+    // three polygons, but only two rates since the missing bin has none
+    assert_eq!(field(2).count(), 3);
+    assert_eq!(field(4).count(), 2);
+    let tagged = field(2)
+        .filter(|(_, feature)| match feature {
+            Err(feature) => read_fields(feature).iter().any(|(n, _)| *n == 2),
+            Ok(_) => false,
+        })
+        .count();
+    assert_eq!(tagged, 2);
+
+    assert_eq!(
+        encode_ring(vec![(0, 0), (0, 10), (10, 10), (10, 0), (0, 0)]),
+        Some(vec![9, 20, 0, 26, 0, 20, 19, 0, 0, 19, 15])
+    );
+    assert_eq!(encode_ring(vec![(0, 0), (0, 0), (1, 0), (0, 0)]), None);
+}
@@ -1,6 +1,8 @@
 use regex::Regex;
 use std::{error::Error, io::Read};
 
+use crate::stations::find_nearest_stations;
+
 /// Get the complete listing of all data files available for a given station.
 /// This is useful for answering the question "which files are the two most
 /// recent available?"
@@ -28,7 +30,7 @@ pub fn get_data_file_listing(station_code: &str) -> Result<String, Box<dyn Error
 /// The station codes are the last four characters of the directory names. The
 /// station directories contain data from the last day or so, and the most
 /// recent data file is always called `sn.last`.
-/// 
+///
 /// `data_file_index` must be either `"last"` or between `"0000"` and `"0250"`,
 /// inclusive.
 ///
@@ -55,6 +57,20 @@ pub fn get_data_by_station(
     Ok(sn_data)
 }
 
+/// Download a data file from an arbitrary `http://` or `https://` URL, such
+/// as an archive mirror or a specific file on the NWS server
+pub fn get_data_by_url(url: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    let resp = reqwest::blocking::get(url)?;
+    match resp.status() {
+        reqwest::StatusCode::OK => Ok(resp.bytes()?.to_vec()),
+        status => Err(format!(
+            "Failed to get data file '{}': server responded with {}",
+            url, status
+        )
+        .into()),
+    }
+}
+
 /// Queries the NWS radar station status server and returns a `Vec` containing
 /// tuples of station codes and a boolean. The boolean indicates whether or not
 /// the station is online and operating, according to the status server.
@@ -76,3 +92,55 @@ pub fn get_station_statuses() -> Result<Vec<(String, bool)>, Box<dyn Error>> {
         .map(|s| (s[2].to_owned(), &s[1] == "33FF33"))
         .collect())
 }
+
+/// Download the two most recent data files for a station, returned in
+/// chronological order (older first).
+pub fn get_two_most_recent_data(station_code: &str) -> Result<(Vec<u8>, Vec<u8>), Box<dyn Error>> {
+    let file_listing = get_data_file_listing(station_code)?;
+    // parse the file listing and determine the number of the second-most recent file
+    let re = Regex::new(
+        r#"sn\.(0\d{3}|last)</a></td><td align="right">(\d{2}-\w{3}-\d{4} \d{2}:\d{2})"#,
+    )
+    .unwrap();
+    let mut files: Vec<(chrono::NaiveDateTime, String)> = re
+        .captures_iter(&file_listing)
+        .map(|cap| {
+            (
+                chrono::NaiveDateTime::parse_from_str(&cap[2], "%d-%b-%Y %H:%M").unwrap(),
+                cap[1].to_string(),
+            )
+        })
+        .collect();
+    if files.len() < 3 {
+        return Err(format!(
+            "Not enough data files available for station {}",
+            station_code
+        )
+        .into());
+    }
+    files.sort_by_key(|f| std::cmp::Reverse(f.0));
+    let second_to_last_index = files[2].1.as_str();
+    let sn_last = get_data_by_station(station_code, "last")?;
+    let sn_second_to_last = get_data_by_station(station_code, second_to_last_index)?;
+    Ok((sn_second_to_last, sn_last))
+}
+
+/// Find the nearest station to the given coordinate that the status server
+/// reports as online. Returns an error if the coordinate is out of range of
+/// all stations or if all stations in range are offline.
+pub fn find_nearest_online_station(
+    latitude: f32,
+    longitude: f32,
+) -> Result<String, Box<dyn Error>> {
+    let nearby_stations = match find_nearest_stations(latitude, longitude) {
+        Some(s) => s,
+        None => return Err("Given location is not within range of any radar stations".into()),
+    };
+    let station_statuses = get_station_statuses()?;
+    for station in nearby_stations {
+        if station_statuses.iter().any(|s| s.0 == station && s.1) {
+            return Ok(station.to_lowercase());
+        }
+    }
+    Err("All radar stations within range of this location are offline".into())
+}
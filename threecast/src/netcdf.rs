@@ -0,0 +1,266 @@
+//! Gridded [NetCDF](https://www.unidata.ucar.edu/software/netcdf/) output
+//! following the [CF conventions](https://cfconventions.org/), which is
+//! what hydrologic models and most gridded-data tools read
+//!
+//! Files are written in the classic format by hand, so the NetCDF C
+//! library isn't needed.
+
+use std::error::Error;
+use std::io::Write;
+
+use crate::parse::{GridData, PrecipRate};
+
+/// Written for cells without data
+const FILL_VALUE: f32 = -9999.;
+
+const NC_DIMENSION: u32 = 0x0a;
+const NC_VARIABLE: u32 = 0x0b;
+const NC_ATTRIBUTE: u32 = 0x0c;
+
+/// An attribute value of one of the classic types
+enum Value {
+    Text(String),
+    Float(f32),
+    Double(f64),
+}
+
+impl Value {
+    fn nc_type(&self) -> u32 {
+        match self {
+            Value::Text(_) => 2,
+            Value::Float(_) => 5,
+            Value::Double(_) => 6,
+        }
+    }
+}
+
+fn text(s: &str) -> Value {
+    Value::Text(String::from(s))
+}
+
+struct Variable {
+    name: &'static str,
+    dimensions: Vec<u32>,
+    attributes: Vec<(&'static str, Value)>,
+    nc_type: u32,
+    data: Vec<u8>,
+}
+
+/// Everything in the classic format is big endian and padded to four bytes
+fn pad(buffer: &mut Vec<u8>) {
+    while !buffer.len().is_multiple_of(4) {
+        buffer.push(0);
+    }
+}
+
+fn put_u32(buffer: &mut Vec<u8>, value: u32) {
+    buffer.extend(value.to_be_bytes());
+}
+
+fn put_name(buffer: &mut Vec<u8>, name: &str) {
+    put_u32(buffer, name.len() as u32);
+    buffer.extend(name.as_bytes());
+    pad(buffer);
+}
+
+fn put_attributes(buffer: &mut Vec<u8>, attributes: &[(&str, Value)]) {
+    if attributes.is_empty() {
+        // ABSENT
+        put_u32(buffer, 0);
+        put_u32(buffer, 0);
+        return;
+    }
+    put_u32(buffer, NC_ATTRIBUTE);
+    put_u32(buffer, attributes.len() as u32);
+    for (name, value) in attributes {
+        put_name(buffer, name);
+        put_u32(buffer, value.nc_type());
+        match value {
+            Value::Text(s) => {
+                put_u32(buffer, s.len() as u32);
+                buffer.extend(s.as_bytes());
+            }
+            Value::Float(f) => {
+                put_u32(buffer, 1);
+                buffer.extend(f.to_be_bytes());
+            }
+            Value::Double(d) => {
+                put_u32(buffer, 1);
+                buffer.extend(d.to_be_bytes());
+            }
+        }
+        pad(buffer);
+    }
+}
+
+/// The classic format header, with each variable's data starting at the
+/// matching offset in `begins`
+fn header(
+    dimensions: &[(&str, u32)],
+    attributes: &[(&str, Value)],
+    variables: &[Variable],
+    begins: &[u32],
+) -> Vec<u8> {
+    let mut buffer = b"CDF\x01".to_vec();
+    put_u32(&mut buffer, 0); // no record variables
+    put_u32(&mut buffer, NC_DIMENSION);
+    put_u32(&mut buffer, dimensions.len() as u32);
+    for (name, length) in dimensions {
+        put_name(&mut buffer, name);
+        put_u32(&mut buffer, *length);
+    }
+    put_attributes(&mut buffer, attributes);
+    put_u32(&mut buffer, NC_VARIABLE);
+    put_u32(&mut buffer, variables.len() as u32);
+    for (variable, begin) in variables.iter().zip(begins) {
+        put_name(&mut buffer, variable.name);
+        put_u32(&mut buffer, variable.dimensions.len() as u32);
+        for dimension in variable.dimensions.iter() {
+            put_u32(&mut buffer, *dimension);
+        }
+        put_attributes(&mut buffer, &variable.attributes);
+        put_u32(&mut buffer, variable.nc_type);
+        put_u32(&mut buffer, padded_len(&variable.data) as u32);
+        put_u32(&mut buffer, *begin);
+    }
+    buffer
+}
+
+fn floats(values: impl Iterator<Item = f32>) -> Vec<u8> {
+    values.flat_map(|v| v.to_be_bytes()).collect()
+}
+
+fn padded_len(data: &[u8]) -> usize {
+    data.len().div_ceil(4) * 4
+}
+
+/// Write `grid`, which was sampled from `scan`, as a CF NetCDF file with
+/// `time`, `lat`, and `lon` coordinates and a `precip_rate` variable in
+/// in/hr. The grid must be regularly spaced in latitude and longitude, like
+/// the equirectangular grids, and the coordinates are worked out from its
+/// corner cells.
+#[allow(clippy::ptr_arg)]
+pub fn write_netcdf<W: Write>(
+    scan: &PrecipRate,
+    grid: &GridData,
+    mut writer: W,
+) -> Result<(), Box<dyn Error>> {
+    let (rows, columns) = (grid.len(), grid.first().map_or(0, |row| row.len()));
+    if rows < 2 || columns < 2 {
+        return Err("Grid must be at least 2 by 2 to work out its spacing".into());
+    }
+    let degrees = |coord: i64| coord as f64 / 10000.;
+    let (north, west) = (degrees(grid[0][0].0[0]), degrees(grid[0][0].0[1]));
+    let dx = (degrees(grid[0][columns - 1].0[1]) - west) / (columns - 1) as f64;
+    let dy = (north - degrees(grid[rows - 1][0].0[0])) / (rows - 1) as f64;
+
+    let latitudes = floats((0..rows).map(|y| (north - dy * y as f64) as f32));
+    let longitudes = floats((0..columns).map(|x| (west + dx * x as f64) as f32));
+    let rates = floats(grid.iter().flatten().map(|(_, rate)| match rate.is_nan() {
+        true => FILL_VALUE,
+        false => *rate,
+    }));
+    let variables = [
+        Variable {
+            name: "time",
+            dimensions: vec![0],
+            attributes: vec![
+                ("standard_name", text("time")),
+                ("long_name", text("start of the volume scan")),
+                ("units", text("seconds since 1970-01-01 00:00:00")),
+                ("calendar", text("standard")),
+            ],
+            nc_type: 6,
+            data: (scan.capture_time.timestamp() as f64)
+                .to_be_bytes()
+                .to_vec(),
+        },
+        Variable {
+            name: "lat",
+            dimensions: vec![1],
+            attributes: vec![
+                ("standard_name", text("latitude")),
+                ("units", text("degrees_north")),
+            ],
+            nc_type: 5,
+            data: latitudes,
+        },
+        Variable {
+            name: "lon",
+            dimensions: vec![2],
+            attributes: vec![
+                ("standard_name", text("longitude")),
+                ("units", text("degrees_east")),
+            ],
+            nc_type: 5,
+            data: longitudes,
+        },
+        Variable {
+            name: "precip_rate",
+            dimensions: vec![0, 1, 2],
+            attributes: vec![
+                ("standard_name", text("lwe_precipitation_rate")),
+                ("long_name", text("digital precipitation rate")),
+                ("units", text("in h-1")),
+                ("_FillValue", Value::Float(FILL_VALUE)),
+            ],
+            nc_type: 5,
+            data: rates,
+        },
+    ];
+    let dimensions = [("time", 1), ("lat", rows as u32), ("lon", columns as u32)];
+    let attributes = [
+        ("Conventions", text("CF-1.8")),
+        ("title", text("NEXRAD Level III digital precipitation rate")),
+        ("source", text("threecast")),
+        ("station", Value::Text(scan.station_code.to_uppercase())),
+        ("station_latitude", Value::Double(scan.latitude as f64)),
+        ("station_longitude", Value::Double(scan.longitude as f64)),
+    ];
+
+    // the header's length doesn't depend on the offsets, so measure it with
+    // placeholders first
+    let placeholders = vec![0; variables.len()];
+    let mut offset = header(&dimensions, &attributes, &variables, &placeholders).len();
+    let mut begins = Vec::new();
+    for variable in variables.iter() {
+        begins.push(offset as u32);
+        offset += padded_len(&variable.data);
+    }
+    if offset > u32::MAX as usize {
+        return Err("Grid is too big for a classic NetCDF file".into());
+    }
+    writer.write_all(&header(&dimensions, &attributes, &variables, &begins))?;
+    for variable in variables.iter() {
+        let mut data = variable.data.clone();
+        pad(&mut data);
+        writer.write_all(&data)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+#[test]
+fn test_write_netcdf() {
+    let scan = crate::coverage::scan_with_azimuths(&[0.5]);
+    let grid: GridData = vec![
+        vec![([440000, -700000], 0.), ([440000, -695000], 0.5)],
+        vec![([435000, -700000], f32::NAN), ([435000, -695000], 0.)],
+        vec![([430000, -700000], 0.25), ([430000, -695000], 0.)],
+    ];
+    let mut output = Vec::new();
+    write_netcdf(&scan, &grid, &mut output).unwrap();
+    assert!(output.starts_with(b"CDF\x01\0\0\0\0"));
+    let find = |needle: &[u8]| output.windows(needle.len()).position(|w| w == needle);
+    assert!(find(b"CF-1.8").is_some());
+    assert!(find(b"lwe_precipitation_rate").is_some());
+    // precip_rate is the last variable, so its data ends the file
+    let rates: Vec<f32> = output[output.len() - 24..]
+        .chunks(4)
+        .map(|b| f32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+        .collect();
+    assert_eq!(rates, vec![0., 0.5, FILL_VALUE, 0., 0.25, 0.]);
+    // latitudes run north to south like the grid's rows
+    assert!(find(&floats([44., 43.5, 43.].into_iter())).is_some());
+    assert!(find(&floats([-70., -69.5].into_iter())).is_some());
+}
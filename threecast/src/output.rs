@@ -0,0 +1,1553 @@
+use std::error::Error;
+use std::io::Write;
+
+use crate::filter::BinFilter;
+use crate::geomath::{get_beam_height, get_point_bearing_distance, get_point_bearing_distance_f64};
+use crate::parse::{PrecipRate, Radial};
+use crate::stations::find_station;
+use crate::util::precip_category;
+
+/// A single range bin of a scan, borrowed from its parent [`PrecipRate`].
+/// Nothing is computed up front, so the geometry methods like [`center`]
+/// and [`polygon`] only cost anything when they're called.
+///
+/// [`center`]: Self::center
+/// [`polygon`]: Self::polygon
+#[derive(Debug, Clone, Copy)]
+pub struct BinRef<'a> {
+    scan: &'a PrecipRate,
+    /// Index of the radial that contains this bin
+    pub radial_index: usize,
+    /// Index of this bin within its radial
+    pub bin_index: usize,
+}
+
+impl<'a> BinRef<'a> {
+    /// The scan that this bin belongs to
+    pub fn scan(&self) -> &'a PrecipRate {
+        self.scan
+    }
+
+    /// The (radial, bin) indices of this bin within its scan
+    pub fn indices(&self) -> (usize, usize) {
+        (self.radial_index, self.bin_index)
+    }
+
+    /// Precipitation rate in in/hr, or `NaN` if the bin has no data
+    pub fn rate(&self) -> f32 {
+        self.scan.radials[self.radial_index].precip_rates[self.bin_index]
+    }
+
+    /// True if the radar has no data for this bin, as opposed to a rate of
+    /// zero
+    pub fn is_missing(&self) -> bool {
+        self.rate().is_nan()
+    }
+
+    /// True if the bin's radial was interpolated from its neighbors rather
+    /// than read from the file
+    pub fn is_interpolated(&self) -> bool {
+        self.scan.radials[self.radial_index].interpolated
+    }
+
+    /// Azimuth of the center of the bin in degrees clockwise from due north
+    pub fn azimuth(&self) -> f32 {
+        self.scan.radials[self.radial_index].azimuth
+    }
+
+    /// Elevation angle of the beam in degrees
+    pub fn elevation(&self) -> f32 {
+        self.scan.radials[self.radial_index].elevation
+    }
+
+    /// Angular width of the bin in degrees
+    pub fn width(&self) -> f32 {
+        self.scan.radials[self.radial_index].width
+    }
+
+    /// Distance in kilometers from the station to the near and far edges of
+    /// the bin
+    pub fn range(&self) -> (f32, f32) {
+        let near = self.scan.range_to_first_bin + self.scan.bin_size * self.bin_index as f32;
+        (near, near + self.scan.bin_size)
+    }
+
+    /// Area of the bin in square kilometers, as an annular sector on a flat
+    /// earth, which is plenty accurate at radar ranges
+    pub fn area(&self) -> f32 {
+        let (near, far) = self.range();
+        (far * far - near * near) / 2. * self.width().to_radians()
+    }
+
+    /// The (latitude, longitude) of the center of the bin in degrees
+    pub fn center(&self) -> (f32, f32) {
+        let (near, far) = self.range();
+        get_point_bearing_distance(
+            (self.scan.latitude, self.scan.longitude),
+            self.azimuth(),
+            (near + far) / 2.,
+        )
+    }
+
+    /// Like [`center`](Self::center), but computed in `f64`
+    pub fn center_f64(&self) -> (f64, f64) {
+        let (near, far) = self.range();
+        get_point_bearing_distance_f64(
+            (self.scan.latitude as f64, self.scan.longitude as f64),
+            self.azimuth() as f64,
+            (near as f64 + far as f64) / 2.,
+        )
+    }
+
+    /// Height of the beam centerline in kilometers above the ground at the
+    /// station, at the center of the bin. This includes the antenna tower if
+    /// the station is known.
+    pub fn beam_height(&self) -> f32 {
+        let (near, far) = self.range();
+        let tower = find_station(&self.scan.station_code).map_or(0., |s| s.tower_height());
+        get_beam_height((near + far) / 2., self.elevation()) + tower / 1000.
+    }
+
+    /// The outline of the bin as a closed ring of (latitude, longitude)
+    /// coordinates in degrees
+    pub fn polygon(&self) -> Vec<(f32, f32)> {
+        self.outline(0)
+    }
+
+    /// Like [`polygon`](Self::polygon), but computed in `f64`, for when
+    /// the corners of neighboring bins far from the radar need to line up
+    pub fn polygon_f64(&self) -> Vec<(f64, f64)> {
+        self.outline_f64(0)
+    }
+
+    /// Like [`polygon`](Self::polygon), with `arc_points` more vertices
+    /// along each of the near and far edges so that they follow the curve
+    /// around the station. Wide bins far from the station bulge noticeably
+    /// past their straight edges.
+    pub fn outline(&self, arc_points: usize) -> Vec<(f32, f32)> {
+        let station = (self.scan.latitude, self.scan.longitude);
+        self.ring(arc_points, |bearing, distance| {
+            get_point_bearing_distance(station, bearing as f32, distance as f32)
+        })
+    }
+
+    /// Like [`outline`](Self::outline), but computed in `f64`
+    pub fn outline_f64(&self, arc_points: usize) -> Vec<(f64, f64)> {
+        let station = (self.scan.latitude as f64, self.scan.longitude as f64);
+        self.ring(arc_points, |bearing, distance| {
+            get_point_bearing_distance_f64(station, bearing, distance)
+        })
+    }
+
+    /// Go counterclockwise around the bin from its near left corner, as RFC
+    /// 7946 wants for exterior rings, turning each bearing and distance from
+    /// the station into a vertex with `point`
+    fn ring<T: Copy>(&self, arc_points: usize, point: impl Fn(f64, f64) -> T) -> Vec<T> {
+        let (near, far) = self.range();
+        let (near, far) = (near as f64, far as f64);
+        let width = self.width() as f64;
+        let left = self.azimuth() as f64 - width / 2.;
+        let steps = arc_points + 1;
+        let bearings: Vec<f64> = (0..=steps)
+            .map(|step| left + width * step as f64 / steps as f64)
+            .collect();
+        let mut ring = vec![point(left, near)];
+        // the near edge of the first bin is just the station
+        if near > 0. {
+            let inner = &bearings[1..steps];
+            ring.extend(inner.iter().map(|&bearing| point(bearing, near)));
+        }
+        ring.push(point(bearings[steps], near));
+        ring.extend(bearings.iter().rev().map(|&bearing| point(bearing, far)));
+        ring.push(ring[0]);
+        ring
+    }
+
+    /// The outline of the bin as a little-endian
+    /// [WKB](https://libgeos.org/specifications/wkb/) polygon with
+    /// (longitude, latitude) coordinates
+    pub fn wkb(&self) -> Vec<u8> {
+        let ring = self.polygon();
+        let mut wkb = Vec::with_capacity(13 + ring.len() * 16);
+        wkb.push(1); // little endian
+        wkb.extend(3u32.to_le_bytes()); // polygon
+        wkb.extend(1u32.to_le_bytes());
+        wkb.extend((ring.len() as u32).to_le_bytes());
+        for (latitude, longitude) in ring {
+            wkb.extend((longitude as f64).to_le_bytes());
+            wkb.extend((latitude as f64).to_le_bytes());
+        }
+        wkb
+    }
+
+    /// The outline of the bin as a [WKT](https://libgeos.org/specifications/wkt/)
+    /// polygon with (longitude, latitude) coordinates
+    pub fn wkt(&self) -> String {
+        let ring: Vec<String> = self
+            .polygon()
+            .into_iter()
+            .map(|(latitude, longitude)| format!("{} {}", longitude, latitude))
+            .collect();
+        format!("POLYGON(({}))", ring.join(", "))
+    }
+}
+
+/// A single radial of a scan, borrowed from its parent [`PrecipRate`] so
+/// that its bins can be matched up with their ranges
+#[derive(Debug, Clone, Copy)]
+pub struct RadialRef<'a> {
+    scan: &'a PrecipRate,
+    /// Index of this radial within its scan
+    pub index: usize,
+}
+
+impl<'a> RadialRef<'a> {
+    /// The radial itself
+    pub fn radial(&self) -> &'a Radial {
+        &self.scan.radials[self.index]
+    }
+
+    pub fn bin_count(&self) -> usize {
+        self.radial().precip_rates.len()
+    }
+
+    /// Largest rate in the radial in in/hr, or `None` if it has no data
+    pub fn max_rate(&self) -> Option<f32> {
+        self.radial()
+            .precip_rates
+            .iter()
+            .copied()
+            .filter(|rate| !rate.is_nan())
+            .reduce(f32::max)
+    }
+
+    /// Rate in in/hr of the bin at `range` kilometers from the station, or
+    /// `None` if the radial doesn't reach that far. Like
+    /// [`BinRef::rate`], this is `NaN` if the bin has no data.
+    pub fn rate_at_range(&self, range: f32) -> Option<f32> {
+        let offset = (range - self.scan.range_to_first_bin) / self.scan.bin_size;
+        if offset.is_nan() || offset < 0. {
+            return None;
+        }
+        self.radial().precip_rates.get(offset as usize).copied()
+    }
+
+    /// Iterate over (range, rate) pairs for each bin, where the range is the
+    /// distance in kilometers from the station to the center of the bin
+    pub fn ranges(&self) -> impl Iterator<Item = (f32, f32)> + 'a {
+        let (first, size) = (self.scan.range_to_first_bin, self.scan.bin_size);
+        self.radial()
+            .precip_rates
+            .iter()
+            .enumerate()
+            .map(move |(idx, &rate)| (first + size * (idx as f32 + 0.5), rate))
+    }
+
+    /// Iterate over the bins of the radial, nearest first
+    pub fn bins(&self) -> impl Iterator<Item = BinRef<'a>> {
+        let (scan, radial_index) = (self.scan, self.index);
+        (0..self.bin_count()).map(move |bin_index| BinRef {
+            scan,
+            radial_index,
+            bin_index,
+        })
+    }
+}
+
+impl PrecipRate {
+    /// Iterate over the radials along with their scan, in radial order
+    pub fn radial_refs(&self) -> impl Iterator<Item = RadialRef<'_>> {
+        (0..self.radials.len()).map(move |index| RadialRef { scan: self, index })
+    }
+
+    /// Radial `idx` along with its scan, or `None` if there's no such
+    /// radial
+    pub fn radial_ref(&self, idx: usize) -> Option<RadialRef<'_>> {
+        (idx < self.radials.len()).then_some(RadialRef {
+            scan: self,
+            index: idx,
+        })
+    }
+
+    /// Iterate over every bin of every radial, in radial order
+    pub fn bins(&self) -> impl Iterator<Item = BinRef<'_>> {
+        self.radials
+            .iter()
+            .enumerate()
+            .flat_map(move |(radial_index, radial)| {
+                (0..radial.precip_rates.len()).map(move |bin_index| BinRef {
+                    scan: self,
+                    radial_index,
+                    bin_index,
+                })
+            })
+    }
+
+    /// Iterate over the bins that `options` keeps, in radial order. See
+    /// [`ConvertOptions`] for what's kept.
+    pub fn bins_with(&self, options: ConvertOptions) -> impl Iterator<Item = BinRef<'_>> {
+        self.bins().filter(move |bin| options.keep(bin))
+    }
+
+    /// Iterate over the bins that pass `filter`, in radial order
+    pub fn bins_filtered<'a>(
+        &'a self,
+        filter: &'a dyn BinFilter,
+    ) -> impl Iterator<Item = BinRef<'a>> {
+        self.bins().filter(move |bin| filter.keep(bin))
+    }
+}
+
+impl PrecipRate {
+    /// Turn the scan into (WKT polygon, rate) pairs for every bin, in radial
+    /// order, for loading into databases and other tools that speak WKT
+    pub fn into_wkt_iter(self) -> IntoGeometries<String> {
+        IntoGeometries::new(self, |bin| bin.wkt())
+    }
+
+    /// Turn the scan into (WKB polygon, rate) pairs for every bin, in radial
+    /// order. See [`BinRef::wkb`] for the encoding.
+    pub fn into_wkb_iter(self) -> IntoGeometries<Vec<u8>> {
+        IntoGeometries::new(self, |bin| bin.wkb())
+    }
+}
+
+/// With the `geo` feature, a scan turns into (polygon, rate) pairs for every
+/// bin, in radial order, with polygons as from [`BinRef::geo_polygon`]. Use
+/// [`PrecipRate::bins`] to iterate without giving up the scan.
+#[cfg(feature = "geo")]
+impl IntoIterator for PrecipRate {
+    type Item = (geo_types::Polygon<f32>, f32);
+    type IntoIter = IntoGeometries<geo_types::Polygon<f32>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoGeometries::new(self, |bin| bin.geo_polygon())
+    }
+}
+
+/// Owning iterator over the encoded outlines and rates of a scan's bins,
+/// from [`PrecipRate::into_wkt_iter`], [`PrecipRate::into_wkb_iter`], or
+/// the scan's [`IntoIterator`] implementation with the `geo` feature.
+/// Outlines are only encoded as they're asked for.
+pub struct IntoGeometries<T> {
+    scan: PrecipRate,
+    radial_index: usize,
+    bin_index: usize,
+    encode: fn(&BinRef) -> T,
+}
+
+impl<T> IntoGeometries<T> {
+    fn new(scan: PrecipRate, encode: fn(&BinRef) -> T) -> Self {
+        IntoGeometries {
+            scan,
+            radial_index: 0,
+            bin_index: 0,
+            encode,
+        }
+    }
+}
+
+impl<T> Iterator for IntoGeometries<T> {
+    type Item = (T, f32);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.radial_index < self.scan.radials.len() {
+            if self.bin_index >= self.scan.radials[self.radial_index].precip_rates.len() {
+                self.radial_index += 1;
+                self.bin_index = 0;
+                continue;
+            }
+            let bin = BinRef {
+                scan: &self.scan,
+                radial_index: self.radial_index,
+                bin_index: self.bin_index,
+            };
+            self.bin_index += 1;
+            return Some(((self.encode)(&bin), bin.rate()));
+        }
+        None
+    }
+}
+
+#[cfg(feature = "geo")]
+impl BinRef<'_> {
+    /// The outline of the bin as a polygon for use with the georust crates,
+    /// with (x, y) = (longitude, latitude) coordinates in degrees
+    pub fn geo_polygon(&self) -> geo_types::Polygon<f32> {
+        let ring: Vec<(f32, f32)> = self.polygon().iter().map(|c| (c.1, c.0)).collect();
+        geo_types::Polygon::new(ring.into(), vec![])
+    }
+}
+
+#[cfg(feature = "geo")]
+impl PrecipRate {
+    /// Every bin as a polygon, for use with the georust crates. Coordinates
+    /// are (x, y) = (longitude, latitude) in degrees.
+    pub fn geometry_collection(&self) -> geo_types::GeometryCollection<f32> {
+        self.geometry_collection_with_rates().0
+    }
+
+    /// Like [`geometry_collection`](Self::geometry_collection), but also
+    /// return the rate of each bin in the same order as the polygons
+    pub fn geometry_collection_with_rates(&self) -> (geo_types::GeometryCollection<f32>, Vec<f32>) {
+        let (geometries, rates) = self
+            .bins()
+            .map(|bin| (geo_types::Geometry::Polygon(bin.geo_polygon()), bin.rate()))
+            .unzip();
+        (geo_types::GeometryCollection(geometries), rates)
+    }
+
+    /// Like [`geometry_collection`](Self::geometry_collection), but with
+    /// the polygons computed in `f64`
+    pub fn geometry_collection_f64(&self) -> geo_types::GeometryCollection<f64> {
+        let geometries = self
+            .bins()
+            .map(|bin| {
+                let ring: Vec<(f64, f64)> = bin.polygon_f64().iter().map(|c| (c.1, c.0)).collect();
+                geo_types::Geometry::Polygon(geo_types::Polygon::new(ring.into(), vec![]))
+            })
+            .collect();
+        geo_types::GeometryCollection(geometries)
+    }
+}
+
+/// A destination for converted scans. A sink first receives the scan as a
+/// whole, so it can write any headers or metadata, then each bin in turn,
+/// and finally a call to `finish` once there are no more bins.
+///
+/// Implement this to add custom outputs, then pass the sink to [`write_scan`].
+pub trait OutputSink {
+    fn begin(&mut self, scan: &PrecipRate) -> Result<(), Box<dyn Error>>;
+    fn write_bin(&mut self, bin: &BinRef) -> Result<(), Box<dyn Error>>;
+    fn finish(&mut self) -> Result<(), Box<dyn Error>>;
+
+    /// Called before [`OutputSink::begin`] when some bins were left out to
+    /// respect a cap, so that sinks with room for metadata can say so
+    fn note_truncation(&mut self, _truncation: &Truncation) {}
+}
+
+/// How many bins [`write_scan_capped`] kept out of how many it could have
+/// written
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize)]
+pub struct Truncation {
+    pub kept: usize,
+    pub total: usize,
+}
+
+/// Send every bin of `scan` through `sink`
+pub fn write_scan(scan: &PrecipRate, sink: &mut dyn OutputSink) -> Result<(), Box<dyn Error>> {
+    write_scan_filtered(scan, sink, &|_: &BinRef| true)
+}
+
+/// Send the bins of `scan` that pass `filter` through `sink`
+pub fn write_scan_filtered(
+    scan: &PrecipRate,
+    sink: &mut dyn OutputSink,
+    filter: &dyn BinFilter,
+) -> Result<(), Box<dyn Error>> {
+    sink.begin(scan)?;
+    for bin in scan.bins_filtered(filter) {
+        sink.write_bin(&bin)?;
+    }
+    sink.finish()
+}
+
+/// Send at most `max_bins` of the bins of `scan` that pass `filter` through
+/// `sink`, keeping the ones with the highest rates if there are more than
+/// that. Bins still go out in scan order. Returns what was left out, if
+/// anything.
+pub fn write_scan_capped(
+    scan: &PrecipRate,
+    sink: &mut dyn OutputSink,
+    filter: &dyn BinFilter,
+    max_bins: usize,
+) -> Result<Option<Truncation>, Box<dyn Error>> {
+    let mut bins: Vec<BinRef> = scan.bins_filtered(filter).collect();
+    let truncation = if bins.len() > max_bins {
+        let total = bins.len();
+        // rank by rate, with missing data last, then put the survivors back
+        // in scan order
+        bins.sort_by(|a, b| {
+            a.is_missing()
+                .cmp(&b.is_missing())
+                .then(b.rate().total_cmp(&a.rate()))
+        });
+        bins.truncate(max_bins);
+        bins.sort_by_key(|bin| bin.indices());
+        let truncation = Truncation {
+            kept: max_bins,
+            total,
+        };
+        sink.note_truncation(&truncation);
+        Some(truncation)
+    } else {
+        None
+    };
+    sink.begin(scan)?;
+    for bin in bins.iter() {
+        sink.write_bin(bin)?;
+    }
+    sink.finish()?;
+    Ok(truncation)
+}
+
+/// Order of the two numbers in each coordinate that writers output
+#[derive(Debug, Clone, Copy, PartialEq, Default, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AxisOrder {
+    /// Longitude first, as RFC 7946 GeoJSON and most GIS software expect
+    #[default]
+    LonLat,
+    /// Latitude first, as EPSG:4326 officially defines it
+    LatLon,
+}
+
+impl AxisOrder {
+    /// Put a (latitude, longitude) pair in this order
+    pub fn arrange<T>(&self, (latitude, longitude): (T, T)) -> [T; 2] {
+        match self {
+            AxisOrder::LonLat => [longitude, latitude],
+            AxisOrder::LatLon => [latitude, longitude],
+        }
+    }
+
+    /// URN of the coordinate reference system with this axis order
+    pub fn crs_urn(&self) -> &'static str {
+        match self {
+            AxisOrder::LonLat => "urn:ogc:def:crs:OGC:1.3:CRS84",
+            AxisOrder::LatLon => "urn:ogc:def:crs:EPSG::4326",
+        }
+    }
+}
+
+/// Options shared by the vector writers for consumers that need something
+/// other than plain RFC 7946 output
+#[derive(Debug, Clone, Copy, PartialEq, Default, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WriterOptions {
+    pub axis_order: AxisOrder,
+    /// Name the coordinate reference system in the output, e.g. with the
+    /// `crs` member from the 2008 GeoJSON spec, which RFC 7946 dropped
+    pub legacy_crs: bool,
+}
+
+/// Units for the rates in converted output
+#[derive(Debug, Clone, Copy, PartialEq, Default, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RateUnit {
+    /// Inches per hour, which is what the products hold
+    #[default]
+    InchesPerHour,
+    MillimetersPerHour,
+}
+
+impl RateUnit {
+    /// Convert `rate` from in/hr to these units
+    pub fn from_inches_per_hour(&self, rate: f32) -> f32 {
+        match self {
+            RateUnit::InchesPerHour => rate,
+            RateUnit::MillimetersPerHour => rate * 25.4,
+        }
+    }
+
+    pub fn abbreviation(&self) -> &'static str {
+        match self {
+            RateUnit::InchesPerHour => "in/hr",
+            RateUnit::MillimetersPerHour => "mm/hr",
+        }
+    }
+}
+
+/// Choices about what to convert from a scan and how, accepted by
+/// [`PrecipRate::bins_with`] and the GeoJSON, GML, and shapefile writers.
+/// Start from the defaults, which keep every bin as a plain four cornered
+/// `f32` polygon with rates in in/hr, and change what's needed:
+///
+/// ```
+/// use threecast::output::{ConvertOptions, RateUnit};
+///
+/// let options = ConvertOptions::new()
+///     .with_skip_zeros(true)
+///     .with_min_rate(2.5)
+///     .with_arc_points(3)
+///     .with_units(RateUnit::MillimetersPerHour);
+/// assert_eq!(options.units(), RateUnit::MillimetersPerHour);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ConvertOptions {
+    skip_zeros: bool,
+    min_rate: Option<f32>,
+    double_precision: bool,
+    arc_points: usize,
+    units: RateUnit,
+}
+
+impl ConvertOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Leave out bins with a rate of zero, which are most of a typical scan
+    pub fn with_skip_zeros(mut self, skip_zeros: bool) -> Self {
+        self.skip_zeros = skip_zeros;
+        self
+    }
+
+    /// Leave out bins with a rate below `min_rate`, in the units chosen
+    /// with [`with_units`](Self::with_units). Bins without data are left out too.
+    pub fn with_min_rate(mut self, min_rate: f32) -> Self {
+        self.min_rate = Some(min_rate);
+        self
+    }
+
+    /// Compute coordinates in `f64` instead of `f32`, which is more
+    /// accurate far from the radar but makes for bigger output
+    pub fn with_double_precision(mut self, double_precision: bool) -> Self {
+        self.double_precision = double_precision;
+        self
+    }
+
+    /// Add this many vertices along the curved edges of each bin, as with
+    /// [`BinRef::outline`]
+    pub fn with_arc_points(mut self, arc_points: usize) -> Self {
+        self.arc_points = arc_points;
+        self
+    }
+
+    /// Write rates in `units` instead of in/hr
+    pub fn with_units(mut self, units: RateUnit) -> Self {
+        self.units = units;
+        self
+    }
+
+    pub fn units(&self) -> RateUnit {
+        self.units
+    }
+
+    pub fn double_precision(&self) -> bool {
+        self.double_precision
+    }
+
+    pub fn arc_points(&self) -> usize {
+        self.arc_points
+    }
+
+    /// The rate of `bin` in the chosen units
+    pub fn rate(&self, bin: &BinRef) -> f32 {
+        self.units.from_inches_per_hour(bin.rate())
+    }
+
+    /// The outline of `bin` with the chosen number of arc points. Without
+    /// double precision, the coordinates are computed in `f32` and then
+    /// widened.
+    pub fn outline(&self, bin: &BinRef) -> Vec<(f64, f64)> {
+        match self.double_precision {
+            true => bin.outline_f64(self.arc_points),
+            false => bin
+                .outline(self.arc_points)
+                .into_iter()
+                .map(|(latitude, longitude)| (latitude as f64, longitude as f64))
+                .collect(),
+        }
+    }
+}
+
+impl BinFilter for ConvertOptions {
+    fn keep(&self, bin: &BinRef) -> bool {
+        let rate = self.rate(bin);
+        !(self.skip_zeros && rate == 0.) && self.min_rate.is_none_or(|min| rate >= min)
+    }
+}
+
+/// Maps a bin to the `properties` object of its GeoJSON feature
+pub type PropertyMapper = Box<dyn Fn(&BinRef) -> serde_json::Map<String, serde_json::Value>>;
+
+/// The properties written by [`GeoJsonSink`] unless it's given a custom
+/// mapper: the rate as `precipRate`, plus `interpolated: true` for bins that
+/// were filled in from their neighbors
+pub fn default_properties(bin: &BinRef) -> serde_json::Map<String, serde_json::Value> {
+    let mut properties = serde_json::Map::new();
+    properties.insert(String::from("precipRate"), bin.rate().into());
+    if bin.is_interpolated() {
+        properties.insert(String::from("interpolated"), true.into());
+    }
+    properties
+}
+
+/// The default properties plus the beam height above the ground in
+/// kilometers as `beamHeight`
+pub fn properties_with_beam_height(bin: &BinRef) -> serde_json::Map<String, serde_json::Value> {
+    let mut properties = default_properties(bin);
+    properties.insert(String::from("beamHeight"), bin.beam_height().into());
+    properties
+}
+
+/// Writes bins as polygon features of a [GeoJSON](https://datatracker.ietf.org/doc/html/rfc7946)
+/// `FeatureCollection`. Features are streamed to the writer as they arrive
+/// instead of being collected in memory first.
+pub struct GeoJsonSink<W: Write> {
+    writer: W,
+    properties: PropertyMapper,
+    features_written: usize,
+    truncation: Option<Truncation>,
+    options: WriterOptions,
+    convert: ConvertOptions,
+}
+
+impl<W: Write> GeoJsonSink<W> {
+    pub fn new(writer: W) -> Self {
+        Self::with_properties(writer, default_properties)
+    }
+
+    /// Use `mapper` to build each feature's properties instead of the
+    /// default single `precipRate` property
+    pub fn with_properties<F>(writer: W, mapper: F) -> Self
+    where
+        F: Fn(&BinRef) -> serde_json::Map<String, serde_json::Value> + 'static,
+    {
+        GeoJsonSink {
+            writer,
+            properties: Box::new(mapper),
+            features_written: 0,
+            truncation: None,
+            options: WriterOptions::default(),
+            convert: ConvertOptions::default(),
+        }
+    }
+
+    /// Use `options` for the axis order and CRS member
+    pub fn with_options(mut self, options: WriterOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Use `convert` to pick the bins, their outlines, and the units of
+    /// the `precipRate` property
+    pub fn with_convert_options(mut self, convert: ConvertOptions) -> Self {
+        self.convert = convert;
+        self
+    }
+
+    /// Give back the underlying writer
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write> OutputSink for GeoJsonSink<W> {
+    fn begin(&mut self, _scan: &PrecipRate) -> Result<(), Box<dyn Error>> {
+        self.features_written = 0;
+        self.writer.write_all(br#"{"type":"FeatureCollection","#)?;
+        if self.options.legacy_crs {
+            write!(
+                self.writer,
+                r#""crs":{{"type":"name","properties":{{"name":"{}"}}}},"#,
+                self.options.axis_order.crs_urn()
+            )?;
+        }
+        // a foreign member, which GeoJSON readers pass through or ignore
+        if let Some(truncation) = self.truncation.take() {
+            self.writer.write_all(br#""truncated":"#)?;
+            serde_json::to_writer(&mut self.writer, &truncation)?;
+            self.writer.write_all(b",")?;
+        }
+        self.writer.write_all(br#""features":["#)?;
+        Ok(())
+    }
+
+    fn write_bin(&mut self, bin: &BinRef) -> Result<(), Box<dyn Error>> {
+        if !self.convert.keep(bin) {
+            return Ok(());
+        }
+        let feature = feature(bin, &self.properties, &self.options, &self.convert);
+        if self.features_written > 0 {
+            self.writer.write_all(b",")?;
+        }
+        serde_json::to_writer(&mut self.writer, &feature)?;
+        self.features_written += 1;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Box<dyn Error>> {
+        self.writer.write_all(b"]}")?;
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    fn note_truncation(&mut self, truncation: &Truncation) {
+        self.truncation = Some(*truncation);
+    }
+}
+
+/// A bin as a GeoJSON polygon feature. Coordinates are written with the
+/// shortest decimal form of their `f32` value unless `convert` asks for
+/// double precision, since the extra digits would be noise.
+/// Widen `value` to the `f64` with the same shortest decimal form, since
+/// `serde_json` would otherwise print the digits that a plain cast makes up
+/// (43.891 as 43.89099884033203)
+fn shortest_f64(value: f32) -> f64 {
+    value.to_string().parse().unwrap_or(value as f64)
+}
+
+fn feature(
+    bin: &BinRef,
+    properties: &PropertyMapper,
+    options: &WriterOptions,
+    convert: &ConvertOptions,
+) -> serde_json::Value {
+    let axis_order = options.axis_order;
+    let coordinates = match convert.double_precision() {
+        true => serde_json::json!(bin
+            .outline_f64(convert.arc_points())
+            .into_iter()
+            .map(|c| axis_order.arrange(c))
+            .collect::<Vec<_>>()),
+        false => serde_json::json!(bin
+            .outline(convert.arc_points())
+            .into_iter()
+            .map(|(latitude, longitude)| {
+                axis_order.arrange((shortest_f64(latitude), shortest_f64(longitude)))
+            })
+            .collect::<Vec<_>>()),
+    };
+    serde_json::json!({
+        "type": "Feature",
+        "geometry": {
+            "type": "Polygon",
+            "coordinates": [coordinates],
+        },
+        "properties": converted_properties(bin, properties, convert),
+    })
+}
+
+/// The properties of `bin` from `properties`, with `precipRate` in the
+/// units from `convert`
+pub(crate) fn converted_properties(
+    bin: &BinRef,
+    properties: &PropertyMapper,
+    convert: &ConvertOptions,
+) -> serde_json::Map<String, serde_json::Value> {
+    let mut properties = properties(bin);
+    if convert.units() != RateUnit::InchesPerHour {
+        if let Some(rate) = properties.get_mut("precipRate") {
+            *rate = convert.rate(bin).into();
+        }
+    }
+    properties
+}
+
+/// Writes bins as a [GeoJSON text sequence](https://datatracker.ietf.org/doc/html/rfc8142)
+/// with one feature per line, so that tools like tippecanoe can read the
+/// features as they come instead of parsing one huge `FeatureCollection`.
+/// Each feature also gets `station` and `captureTime` properties, since
+/// the sequence can hold features from many scans.
+pub struct GeoJsonSeqSink<W: Write> {
+    writer: W,
+    properties: PropertyMapper,
+    options: WriterOptions,
+    convert: ConvertOptions,
+    record_separators: bool,
+    scan: Option<(String, String)>,
+}
+
+impl<W: Write> GeoJsonSeqSink<W> {
+    pub fn new(writer: W) -> Self {
+        Self::with_properties(writer, default_properties)
+    }
+
+    /// Use `mapper` to build each feature's properties instead of the
+    /// default single `precipRate` property
+    pub fn with_properties<F>(writer: W, mapper: F) -> Self
+    where
+        F: Fn(&BinRef) -> serde_json::Map<String, serde_json::Value> + 'static,
+    {
+        GeoJsonSeqSink {
+            writer,
+            properties: Box::new(mapper),
+            options: WriterOptions::default(),
+            convert: ConvertOptions::default(),
+            record_separators: true,
+            scan: None,
+        }
+    }
+
+    /// Use the axis order from `options`. There's nowhere to name the CRS
+    /// in a sequence, so `legacy_crs` is ignored.
+    pub fn with_options(mut self, options: WriterOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Use `convert` to pick the bins, their outlines, and the units of
+    /// the `precipRate` property
+    pub fn with_convert_options(mut self, convert: ConvertOptions) -> Self {
+        self.convert = convert;
+        self
+    }
+
+    /// Leave out the ASCII record separator that RFC 8142 puts before each
+    /// feature, for readers that want plain newline-delimited GeoJSON
+    pub fn without_record_separators(mut self) -> Self {
+        self.record_separators = false;
+        self
+    }
+
+    /// Give back the underlying writer
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write> OutputSink for GeoJsonSeqSink<W> {
+    fn begin(&mut self, scan: &PrecipRate) -> Result<(), Box<dyn Error>> {
+        self.scan = Some((
+            scan.station_code.to_uppercase(),
+            scan.capture_time.format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+        ));
+        Ok(())
+    }
+
+    fn write_bin(&mut self, bin: &BinRef) -> Result<(), Box<dyn Error>> {
+        if !self.convert.keep(bin) {
+            return Ok(());
+        }
+        let mut feature = feature(bin, &self.properties, &self.options, &self.convert);
+        if let Some((station, capture_time)) = &self.scan {
+            feature["properties"]["station"] = station.as_str().into();
+            feature["properties"]["captureTime"] = capture_time.as_str().into();
+        }
+        if self.record_separators {
+            self.writer.write_all(b"\x1e")?;
+        }
+        serde_json::to_writer(&mut self.writer, &feature)?;
+        self.writer.write_all(b"\n")?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Box<dyn Error>> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Writes bins as CSV rows of `azimuth_deg,range_km,rate` with no geometry,
+/// where the range is to the center of the bin
+pub struct PolarCsvSink<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> PolarCsvSink<W> {
+    pub fn new(writer: W) -> Self {
+        PolarCsvSink { writer }
+    }
+
+    /// Give back the underlying writer
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write> OutputSink for PolarCsvSink<W> {
+    fn begin(&mut self, _scan: &PrecipRate) -> Result<(), Box<dyn Error>> {
+        writeln!(self.writer, "azimuth_deg,range_km,rate")?;
+        Ok(())
+    }
+
+    fn write_bin(&mut self, bin: &BinRef) -> Result<(), Box<dyn Error>> {
+        let (near, far) = bin.range();
+        writeln!(
+            self.writer,
+            "{},{},{}",
+            bin.azimuth(),
+            (near + far) / 2.,
+            bin.rate()
+        )?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Box<dyn Error>> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Writes bins as rows of a flat table for pandas, R, and spreadsheets:
+/// `station,capture_time,longitude,latitude,range_km,azimuth_deg,rate`,
+/// where the position and range are of the center of the bin and the rate
+/// is empty for bins without data. The header is only written once, so
+/// many scans can go into the same table.
+pub struct CsvSink<W: Write> {
+    writer: W,
+    delimiter: char,
+    header_written: bool,
+    scan: Option<(String, String)>,
+}
+
+impl<W: Write> CsvSink<W> {
+    pub fn new(writer: W) -> Self {
+        CsvSink {
+            writer,
+            delimiter: ',',
+            header_written: false,
+            scan: None,
+        }
+    }
+
+    /// Separate fields with tabs instead of commas
+    pub fn tab_separated(mut self) -> Self {
+        self.delimiter = '\t';
+        self
+    }
+
+    /// Give back the underlying writer
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write> OutputSink for CsvSink<W> {
+    fn begin(&mut self, scan: &PrecipRate) -> Result<(), Box<dyn Error>> {
+        if !self.header_written {
+            let header = [
+                "station",
+                "capture_time",
+                "longitude",
+                "latitude",
+                "range_km",
+                "azimuth_deg",
+                "rate",
+            ];
+            writeln!(self.writer, "{}", header.join(&self.delimiter.to_string()))?;
+            self.header_written = true;
+        }
+        self.scan = Some((
+            scan.station_code.to_uppercase(),
+            scan.capture_time.format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+        ));
+        Ok(())
+    }
+
+    fn write_bin(&mut self, bin: &BinRef) -> Result<(), Box<dyn Error>> {
+        let (station, capture_time) = match &self.scan {
+            Some(scan) => scan,
+            None => return Err("CsvSink got a bin before a scan".into()),
+        };
+        let (latitude, longitude) = bin.center();
+        let (near, far) = bin.range();
+        let rate = if bin.is_missing() {
+            String::new()
+        } else {
+            bin.rate().to_string()
+        };
+        let d = self.delimiter;
+        writeln!(
+            self.writer,
+            "{}{d}{}{d}{}{d}{}{d}{}{d}{}{d}{}",
+            station,
+            capture_time,
+            longitude,
+            latitude,
+            (near + far) / 2.,
+            bin.azimuth(),
+            rate
+        )?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Box<dyn Error>> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// How [`SplitSink`] divides bins between its parts
+#[derive(Debug, Clone, Copy, PartialEq, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Split {
+    /// One part per [`precip_category`], named after the category
+    Category,
+    /// A new part after every `n` bins, numbered from zero
+    Chunks(usize),
+}
+
+/// What went into one part of a split scan, for building an index of the
+/// parts so that consumers can load only the ones they need
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct PartSummary {
+    pub name: String,
+    pub bins: usize,
+    /// `[south, west, north, east]` in degrees, around every bin outline
+    pub bbox: [f32; 4],
+}
+
+impl PartSummary {
+    fn add(&mut self, bin: &BinRef) {
+        self.bins += 1;
+        for (latitude, longitude) in bin.polygon() {
+            self.bbox[0] = self.bbox[0].min(latitude);
+            self.bbox[1] = self.bbox[1].min(longitude);
+            self.bbox[2] = self.bbox[2].max(latitude);
+            self.bbox[3] = self.bbox[3].max(longitude);
+        }
+    }
+}
+
+/// Makes the sink for one part of a split scan, given the part's name
+pub type SinkFactory = Box<dyn FnMut(&str) -> Result<Box<dyn OutputSink>, Box<dyn Error>>>;
+
+/// Routes the bins of a scan to several sinks in a single pass, e.g. to
+/// write one file per intensity class. Each part's sink is made the first
+/// time a bin goes to it, so parts without any bins are never made.
+pub struct SplitSink {
+    split: Split,
+    make_sink: SinkFactory,
+    parts: Vec<(String, Box<dyn OutputSink>)>,
+    summaries: Vec<PartSummary>,
+    bins_written: usize,
+    truncation: Option<Truncation>,
+}
+
+impl SplitSink {
+    pub fn new<F>(split: Split, make_sink: F) -> Self
+    where
+        F: FnMut(&str) -> Result<Box<dyn OutputSink>, Box<dyn Error>> + 'static,
+    {
+        SplitSink {
+            split,
+            make_sink: Box::new(make_sink),
+            parts: Vec::new(),
+            summaries: Vec::new(),
+            bins_written: 0,
+            truncation: None,
+        }
+    }
+
+    /// A summary of each part written for the last scan, in the order the
+    /// parts were made
+    pub fn summaries(&self) -> &[PartSummary] {
+        &self.summaries
+    }
+}
+
+impl OutputSink for SplitSink {
+    fn begin(&mut self, _scan: &PrecipRate) -> Result<(), Box<dyn Error>> {
+        self.parts.clear();
+        self.summaries.clear();
+        self.bins_written = 0;
+        Ok(())
+    }
+
+    fn write_bin(&mut self, bin: &BinRef) -> Result<(), Box<dyn Error>> {
+        let name = match self.split {
+            Split::Category => String::from(precip_category(bin.rate())),
+            Split::Chunks(n) => (self.bins_written / n.max(1)).to_string(),
+        };
+        self.bins_written += 1;
+        match self.summaries.iter_mut().find(|part| part.name == name) {
+            Some(summary) => summary.add(bin),
+            None => {
+                let mut summary = PartSummary {
+                    name: name.clone(),
+                    bins: 0,
+                    bbox: [
+                        f32::INFINITY,
+                        f32::INFINITY,
+                        f32::NEG_INFINITY,
+                        f32::NEG_INFINITY,
+                    ],
+                };
+                summary.add(bin);
+                self.summaries.push(summary);
+            }
+        }
+        let idx = match self.parts.iter().position(|(part, _)| *part == name) {
+            Some(idx) => idx,
+            None => {
+                // chunks never get revisited, so finish the last one early
+                // rather than holding every part open until the end
+                if let Split::Chunks(_) = self.split {
+                    for (_, mut sink) in self.parts.drain(..) {
+                        sink.finish()?;
+                    }
+                }
+                let mut sink = (self.make_sink)(&name)?;
+                if let Some(truncation) = &self.truncation {
+                    sink.note_truncation(truncation);
+                }
+                sink.begin(bin.scan())?;
+                self.parts.push((name, sink));
+                self.parts.len() - 1
+            }
+        };
+        self.parts[idx].1.write_bin(bin)
+    }
+
+    fn finish(&mut self) -> Result<(), Box<dyn Error>> {
+        self.truncation = None;
+        for (_, mut sink) in self.parts.drain(..) {
+            sink.finish()?;
+        }
+        Ok(())
+    }
+
+    fn note_truncation(&mut self, truncation: &Truncation) {
+        self.truncation = Some(*truncation);
+    }
+}
+
+#[test]
+fn test_geojson_sink() {
+    use crate::parse::{OperationalMode, Radial};
+    let scan = PrecipRate {
+        station_code: String::from("KGYX"),
+        capture_time: chrono::NaiveDateTime::from_timestamp(0, 0),
+        scan_number: 1,
+        latitude: 43.891,
+        longitude: -70.256,
+        operational_mode: OperationalMode::Precipitation,
+        precip_detected: true,
+        bin_size: 0.25,
+        range_to_first_bin: 0.,
+        times: Default::default(),
+        metadata: None,
+        extra_components: Vec::new(),
+        alphanumeric: Default::default(),
+        radials: vec![
+            Radial {
+                azimuth: 0.5,
+                elevation: 0.5,
+                width: 1.,
+                precip_rates: vec![0., 0.1],
+                interpolated: false,
+            },
+            Radial {
+                azimuth: 1.5,
+                elevation: 0.5,
+                width: 1.,
+                precip_rates: vec![0.2, 0.],
+                interpolated: false,
+            },
+        ],
+    };
+    assert_eq!(scan.bins().count(), 4);
+    let third = scan.bins().nth(2).unwrap();
+    assert_eq!(third.indices(), (1, 0));
+    assert_eq!(third.rate(), 0.2);
+    assert_eq!(third.range(), (0., 0.25));
+    let filters: Vec<Box<dyn BinFilter>> = vec![
+        Box::new(crate::filter::MinRate(0.05)),
+        Box::new(|bin: &BinRef| bin.radial_index == 1),
+    ];
+    assert_eq!(scan.bins_filtered(&filters).count(), 1);
+    let mut sink = GeoJsonSink::new(Vec::new());
+    write_scan(&scan, &mut sink).unwrap();
+    let text = String::from_utf8(sink.into_inner()).unwrap();
+    // coordinates keep the shortest form of their f32 value rather than
+    // the digits that widening to f64 makes up
+    assert!(text.contains("[[[-70.256,"));
+    let geojson: serde_json::Value = serde_json::from_str(&text).unwrap();
+    for feature in geojson["features"].as_array().unwrap() {
+        for position in feature["geometry"]["coordinates"][0].as_array().unwrap() {
+            for value in position.as_array().unwrap() {
+                let value = value.as_f64().unwrap();
+                assert_eq!(value.to_string(), (value as f32).to_string());
+            }
+        }
+    }
+    assert_eq!(geojson["features"].as_array().unwrap().len(), 4);
+    assert_eq!(
+        geojson["features"][1]["properties"]["precipRate"],
+        0.1f32 as f64
+    );
+    assert_eq!(
+        geojson["features"][0]["geometry"]["coordinates"][0]
+            .as_array()
+            .unwrap()
+            .len(),
+        5
+    );
+    // RFC 7946 exterior rings are counterclockwise, so their signed area
+    // is positive
+    for feature in geojson["features"].as_array().unwrap() {
+        let ring = feature["geometry"]["coordinates"][0].as_array().unwrap();
+        let area: f64 = ring
+            .windows(2)
+            .map(|pair| {
+                let (a, b) = (&pair[0], &pair[1]);
+                a[0].as_f64().unwrap() * b[1].as_f64().unwrap()
+                    - b[0].as_f64().unwrap() * a[1].as_f64().unwrap()
+            })
+            .sum();
+        assert!(area > 0.);
+    }
+    let mut sink = GeoJsonSink::with_properties(Vec::new(), |bin| {
+        let mut properties = serde_json::Map::new();
+        properties.insert(String::from("azimuth"), bin.azimuth().into());
+        properties.insert(String::from("gate"), bin.bin_index.into());
+        properties
+    });
+    write_scan(&scan, &mut sink).unwrap();
+    let geojson: serde_json::Value = serde_json::from_slice(&sink.into_inner()).unwrap();
+    assert_eq!(
+        geojson["features"][3]["properties"],
+        serde_json::json!({"azimuth": 1.5, "gate": 1})
+    );
+    let mut sink = GeoJsonSink::new(Vec::new());
+    let truncation = write_scan_capped(&scan, &mut sink, &|_: &BinRef| true, 2).unwrap();
+    assert_eq!(truncation, Some(Truncation { kept: 2, total: 4 }));
+    let geojson: serde_json::Value = serde_json::from_slice(&sink.into_inner()).unwrap();
+    assert_eq!(
+        geojson["truncated"],
+        serde_json::json!({"kept": 2, "total": 4})
+    );
+    let rates: Vec<&serde_json::Value> = geojson["features"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|f| &f["properties"]["precipRate"])
+        .collect();
+    assert_eq!(rates, [0.1f32 as f64, 0.2f32 as f64]);
+    let mut sink = GeoJsonSink::new(Vec::new()).with_options(WriterOptions {
+        axis_order: AxisOrder::LatLon,
+        legacy_crs: true,
+    });
+    write_scan(&scan, &mut sink).unwrap();
+    let geojson: serde_json::Value = serde_json::from_slice(&sink.into_inner()).unwrap();
+    assert_eq!(
+        geojson["crs"]["properties"]["name"],
+        "urn:ogc:def:crs:EPSG::4326"
+    );
+    let first = &geojson["features"][0]["geometry"]["coordinates"][0][0];
+    assert!((first[0].as_f64().unwrap() - scan.latitude as f64).abs() < 0.01);
+    #[cfg(feature = "geo")]
+    {
+        let (geometries, rates) = scan.geometry_collection_with_rates();
+        assert_eq!(geometries.len(), 4);
+        assert_eq!(rates, vec![0., 0.1, 0.2, 0.]);
+    }
+    let mut sink = GeoJsonSeqSink::new(Vec::new());
+    write_scan_filtered(&scan, &mut sink, &crate::filter::MinRate(0.15)).unwrap();
+    write_scan_filtered(&scan, &mut sink, &crate::filter::MinRate(0.15)).unwrap();
+    let seq = String::from_utf8(sink.into_inner()).unwrap();
+    let records: Vec<&str> = seq.split('\x1e').skip(1).collect();
+    assert_eq!(records.len(), 2);
+    let feature: serde_json::Value = serde_json::from_str(records[0].trim_end()).unwrap();
+    assert_eq!(feature["properties"]["precipRate"], 0.2f32 as f64);
+    assert_eq!(feature["properties"]["captureTime"], "1970-01-01T00:00:00Z");
+    let mut sink = GeoJsonSeqSink::new(Vec::new()).without_record_separators();
+    write_scan(&scan, &mut sink).unwrap();
+    let seq = String::from_utf8(sink.into_inner()).unwrap();
+    assert_eq!(seq.lines().count(), 4);
+    assert!(seq.starts_with('{'));
+    let mut sink = CsvSink::new(Vec::new());
+    write_scan(&scan, &mut sink).unwrap();
+    write_scan(&scan, &mut sink).unwrap();
+    let csv = String::from_utf8(sink.into_inner()).unwrap();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines.len(), 9);
+    assert_eq!(
+        lines[0],
+        "station,capture_time,longitude,latitude,range_km,azimuth_deg,rate"
+    );
+    assert!(lines[3].starts_with("KGYX,1970-01-01T00:00:00Z,-70.25"));
+    assert!(lines[3].ends_with(",0.125,1.5,0.2"));
+    let mut sink = CsvSink::new(Vec::new()).tab_separated();
+    write_scan_filtered(&scan, &mut sink, &crate::filter::MinRate(0.15)).unwrap();
+    let tsv = String::from_utf8(sink.into_inner()).unwrap();
+    assert_eq!(tsv.lines().nth(1).unwrap().split('\t').count(), 7);
+    let mut sink = PolarCsvSink::new(Vec::new());
+    write_scan_filtered(&scan, &mut sink, &crate::filter::MinRate(0.15)).unwrap();
+    assert_eq!(
+        String::from_utf8(sink.into_inner()).unwrap(),
+        "azimuth_deg,range_km,rate\n1.5,0.125,0.2\n"
+    );
+}
+
+#[test]
+fn test_split_sink() {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// Records the part name of every bin it's given
+    struct Recorder(String, Rc<RefCell<Vec<String>>>);
+    impl OutputSink for Recorder {
+        fn begin(&mut self, _scan: &PrecipRate) -> Result<(), Box<dyn Error>> {
+            Ok(())
+        }
+        fn write_bin(&mut self, _bin: &BinRef) -> Result<(), Box<dyn Error>> {
+            self.1.borrow_mut().push(self.0.clone());
+            Ok(())
+        }
+        fn finish(&mut self) -> Result<(), Box<dyn Error>> {
+            Ok(())
+        }
+    }
+
+    let mut scan = crate::coverage::scan_with_azimuths(&[0.5, 1.5, 2.5]);
+    scan.radials[1].precip_rates = vec![0.2];
+    for (split, expected) in [
+        (Split::Category, ["none", "moderate", "none"]),
+        (Split::Chunks(2), ["0", "0", "1"]),
+    ] {
+        let written = Rc::new(RefCell::new(Vec::new()));
+        let recorded = written.clone();
+        let mut sink = SplitSink::new(split, move |name| {
+            Ok(Box::new(Recorder(String::from(name), recorded.clone())) as Box<dyn OutputSink>)
+        });
+        write_scan(&scan, &mut sink).unwrap();
+        assert_eq!(*written.borrow(), expected);
+        let bins: Vec<usize> = sink.summaries().iter().map(|part| part.bins).collect();
+        assert_eq!(bins, vec![2, 1]);
+    }
+}
+
+#[test]
+fn test_geometry_iters() {
+    let scan = || {
+        let mut scan = crate::coverage::scan_with_azimuths(&[0.5, 1.5, 2.5]);
+        scan.radials[0].precip_rates = vec![0.25, f32::NAN];
+        scan.radials[1].precip_rates.clear();
+        scan
+    };
+    let scan_ref = scan();
+    let bins: Vec<BinRef> = scan_ref.bins().collect();
+    let wkt: Vec<(String, f32)> = scan().into_wkt_iter().collect();
+    let wkb: Vec<(Vec<u8>, f32)> = scan().into_wkb_iter().collect();
+    assert_eq!((wkt.len(), wkb.len()), (3, 3));
+    for ((bin, (wkt, rate)), (wkb, _)) in bins.iter().zip(&wkt).zip(&wkb) {
+        assert_eq!(*wkt, bin.wkt());
+        assert_eq!(*wkb, bin.wkb());
+        assert!(*rate == bin.rate() || rate.is_nan() && bin.is_missing());
+    }
+    assert!(wkt[0].0.starts_with("POLYGON((-70.25"));
+    assert_eq!(wkt[0].0.matches(", ").count(), 4);
+    assert_eq!(wkb[0].0.len(), 13 + 5 * 16);
+
+    #[cfg(feature = "geo")]
+    {
+        let mut polygons = 0;
+        for (polygon, rate) in scan() {
+            assert_eq!(polygon, bins[polygons].geo_polygon());
+            let first = polygon.exterior().0[0];
+            assert_eq!((first.y, first.x), bins[polygons].polygon()[0]);
+            assert!(rate == bins[polygons].rate() || rate.is_nan());
+            polygons += 1;
+        }
+        assert_eq!(polygons, 3);
+    }
+    assert_eq!(scan_ref.radials().len(), 3);
+    assert_eq!(scan_ref.radial(2).unwrap().azimuth, 2.5);
+    assert!(scan_ref.radial(3).is_none());
+}
+
+#[test]
+fn test_double_precision() {
+    let mut scan = crate::coverage::scan_with_azimuths(&[0.5]);
+    scan.radials[0].precip_rates = vec![0.; 900];
+    let bin = scan.bins().last().unwrap();
+    for (single, double) in bin.polygon().into_iter().zip(bin.polygon_f64()) {
+        assert!((single.0 as f64 - double.0).abs() < 1e-4);
+        assert!((single.1 as f64 - double.1).abs() < 1e-4);
+    }
+    let (latitude, longitude) = bin.center_f64();
+    assert!((latitude as f32 - bin.center().0).abs() < 1e-4);
+    assert!((longitude as f32 - bin.center().1).abs() < 1e-4);
+
+    let mut sink = GeoJsonSink::new(Vec::new())
+        .with_convert_options(ConvertOptions::new().with_double_precision(true));
+    write_scan(&scan, &mut sink).unwrap();
+    let geojson: serde_json::Value = serde_json::from_slice(&sink.into_inner()).unwrap();
+    let corner = &geojson["features"][899]["geometry"]["coordinates"][0][0];
+    let expected = bin.polygon_f64()[0];
+    assert_eq!(corner[0].as_f64().unwrap(), expected.1);
+    assert_eq!(corner[1].as_f64().unwrap(), expected.0);
+}
+
+#[test]
+fn test_convert_options() {
+    let mut scan = crate::coverage::scan_with_azimuths(&[0.5, 1.5]);
+    scan.radials[0].precip_rates = vec![0., 0.05, 0.5, f32::NAN];
+    scan.radials[1].precip_rates = vec![0.1];
+    let options = ConvertOptions::new()
+        .with_skip_zeros(true)
+        .with_units(RateUnit::MillimetersPerHour);
+    let rates: Vec<f32> = scan
+        .bins_with(options)
+        .map(|bin| options.rate(&bin))
+        .collect();
+    assert_eq!(rates.len(), 4);
+    assert_eq!(rates[1], 0.5 * 25.4);
+    assert!(rates[2].is_nan());
+    let options = options.with_min_rate(2.);
+    let kept: Vec<(usize, usize)> = scan.bins_with(options).map(|bin| bin.indices()).collect();
+    assert_eq!(kept, [(0, 2), (1, 0)]);
+
+    // the extra vertices sit on the arcs between the corners
+    let bin = scan.bins().nth(1).unwrap();
+    let (corners, outline) = (bin.polygon(), bin.outline(2));
+    assert_eq!(bin.outline(0), corners);
+    assert_eq!(outline.len(), 9);
+    assert_eq!(
+        (outline[0], outline[3], outline[4]),
+        (corners[0], corners[1], corners[2])
+    );
+    assert_eq!((outline[7], outline[8]), (corners[3], corners[4]));
+    let station = (scan.latitude, scan.longitude);
+    for (idx, &point) in outline.iter().enumerate().take(8) {
+        let distance = crate::geomath::get_distance_between_points(station, point);
+        let (near, far) = bin.range();
+        let expected = if (4..=7).contains(&idx) { far } else { near };
+        assert!((distance - expected).abs() < 0.01);
+    }
+    // the first bin's near edge is the station, so it has no arc
+    assert_eq!(scan.bins().next().unwrap().outline(2).len(), 7);
+
+    let mut sink = GeoJsonSink::new(Vec::new()).with_convert_options(options.with_arc_points(2));
+    write_scan(&scan, &mut sink).unwrap();
+    let geojson: serde_json::Value = serde_json::from_slice(&sink.into_inner()).unwrap();
+    let features = geojson["features"].as_array().unwrap();
+    assert_eq!(features.len(), 2);
+    assert_eq!(
+        features[0]["properties"]["precipRate"],
+        (0.5f32 * 25.4) as f64
+    );
+    assert_eq!(
+        features[0]["geometry"]["coordinates"][0]
+            .as_array()
+            .unwrap()
+            .len(),
+        9
+    );
+}
+
+#[test]
+fn test_radial_ref() {
+    let mut scan = crate::coverage::scan_with_azimuths(&[0.5, 1.5]);
+    scan.range_to_first_bin = 2.;
+    scan.radials[0].precip_rates = vec![0.1, f32::NAN, 0.3];
+    scan.radials[1].precip_rates = vec![f32::NAN];
+    let radials: Vec<RadialRef> = scan.radial_refs().collect();
+    assert_eq!(radials.len(), 2);
+    assert_eq!(radials[0].bin_count(), 3);
+    assert_eq!(radials[0].max_rate(), Some(0.3));
+    assert_eq!(radials[1].max_rate(), None);
+
+    let bin_size = scan.bin_size;
+    assert_eq!(radials[0].rate_at_range(1.), None);
+    assert_eq!(radials[0].rate_at_range(2.), Some(0.1));
+    assert!(radials[0]
+        .rate_at_range(2. + bin_size * 1.5)
+        .unwrap()
+        .is_nan());
+    assert_eq!(radials[0].rate_at_range(2. + bin_size * 2.5), Some(0.3));
+    assert_eq!(radials[0].rate_at_range(2. + bin_size * 3.5), None);
+
+    let ranges: Vec<(f32, f32)> = radials[0].ranges().collect();
+    assert_eq!(ranges[2], (2. + bin_size * 2.5, 0.3));
+    let bins: Vec<BinRef> = radials[0].bins().collect();
+    assert_eq!(bins.len(), 3);
+    let (near, far) = bins[2].range();
+    assert_eq!((near + far) / 2., ranges[2].0);
+    assert_eq!(scan.radial_ref(1).unwrap().radial().azimuth, 1.5);
+    assert!(scan.radial_ref(2).is_none());
+}
@@ -1,21 +1,31 @@
+use std::io::Read;
+
+use crate::alphanumeric::{parse_alphanumeric, AlphanumericBlocks};
 use crate::geomath::get_point_bearing_distance;
+use crate::util::gunzip_if_needed;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum OperationalMode {
     Maintenance,
     CleanAir,
     Precipitation,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Radial {
     pub azimuth: f32,
     pub elevation: f32,
     pub width: f32,
+    /// Rates in in/hr, or `NaN` for bins without data. Use
+    /// [`PrecipRate::fill_missing`] to replace those with a number.
     pub precip_rates: Vec<f32>,
+    /// True if the radial wasn't in the file but was interpolated from its
+    /// neighbors, e.g. by [`PrecipRate::fill_radial_gaps`]
+    pub interpolated: bool,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct PrecipRate {
     pub station_code: String,
     pub capture_time: chrono::NaiveDateTime,
@@ -27,11 +37,91 @@ pub struct PrecipRate {
     pub bin_size: f32,
     pub range_to_first_bin: f32,
     pub radials: Vec<Radial>,
+    pub times: ScanTimes,
+    /// Everything in the product description block, or `None` for scans
+    /// that weren't parsed from a Level III file
+    pub metadata: Option<ProductMetadata>,
+    /// Radial components after the first, for the rare product with more
+    /// than one. The first one's data is in `radials`, and
+    /// [`PrecipRate::into_component`] switches to another.
+    pub extra_components: Vec<RadialComponent>,
+    /// Text from the alphanumeric blocks after the symbology block, if the
+    /// product has any
+    pub alphanumeric: AlphanumericBlocks,
+}
+
+/// Times recorded in a file besides [`PrecipRate::capture_time`], which is
+/// when the volume scan started. Each is `None` if the file leaves it out.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ScanTimes {
+    /// When the message was sent, from the message header
+    pub message: Option<chrono::NaiveDateTime>,
+    /// Start of the volume scan, from the product description block. This
+    /// should match the capture time.
+    pub volume_start: Option<chrono::NaiveDateTime>,
+    /// When the product was generated, which is shortly after the volume
+    /// scan ends
+    pub generation: Option<chrono::NaiveDateTime>,
+    /// Start of the elevation scan that the product was made from, from the
+    /// symbology block
+    pub elevation_start: Option<chrono::NaiveDateTime>,
+}
+
+impl PrecipRate {
+    /// Whether the scan is from a WSR-88D or a TDWR, going by its station
+    /// code
+    pub fn radar_type(&self) -> RadarType {
+        RadarType::from_station_code(&self.station_code)
+    }
+
+    /// The radials in the order they were read
+    pub fn radials(&self) -> &[Radial] {
+        &self.radials
+    }
+
+    /// Radial `idx` in the order they were read, or `None` if there's no
+    /// such radial
+    pub fn radial(&self, idx: usize) -> Option<&Radial> {
+        self.radials.get(idx)
+    }
+
+    /// Use the data from radial component `idx` instead, where 0 is the
+    /// first one, which is already in `radials`. The other components are
+    /// dropped. Returns `None` if there's no such component.
+    pub fn into_component(mut self, idx: usize) -> Option<PrecipRate> {
+        if idx > self.extra_components.len() {
+            return None;
+        }
+        if idx > 0 {
+            let component = self.extra_components.swap_remove(idx - 1);
+            self.bin_size = component.bin_size;
+            self.range_to_first_bin = component.range_to_first_bin;
+            self.radials = component.radials;
+        }
+        self.extra_components.clear();
+        Some(self)
+    }
+
+    /// Time from the start of the volume scan to `now`
+    pub fn data_age(&self, now: chrono::NaiveDateTime) -> chrono::Duration {
+        now - self.capture_time
+    }
+
+    /// Time from the start of the volume scan until the product was
+    /// generated, which is roughly how long the scan took plus processing
+    pub fn generation_delay(&self) -> Option<chrono::Duration> {
+        self.times.generation.map(|t| t - self.capture_time)
+    }
 }
 
 type DataPoint = ([i64; 2], f32);
 pub type GridData = Vec<Vec<DataPoint>>;
 
+/// Bin value that marks missing data rather than a rate. Every other value
+/// is a rate in thousandths of an inch per hour, so zero really means no
+/// precipitation.
+const MISSING_DATA_CODE: u16 = u16::MAX;
+
 pub fn coord_as_i64(coord: f32) -> i64 {
     (coord * 10000.) as i64
 }
@@ -42,29 +132,13 @@ impl PrecipRate {
     ///
     /// [0]: https://en.wikipedia.org/wiki/Equirectangular_projection
     pub fn sample_radials_to_equirectangular(&self, height: usize, width: usize) -> GridData {
-        // first, convert every point from azimuth/bin to lat/lon
-        let mut radials_equirectangular: Vec<DataPoint> = Vec::new();
-        let mut coords: (f32, f32);
-        for radial in self.radials.iter() {
-            for (idx, bin) in radial.precip_rates.iter().enumerate() {
-                coords = get_point_bearing_distance(
-                    (self.latitude, self.longitude),
-                    radial.azimuth,
-                    self.bin_size * idx as f32 + 1. + self.range_to_first_bin,
-                );
-                radials_equirectangular
-                    .push(([coord_as_i64(coords.0), coord_as_i64(coords.1)], *bin));
-            }
-        }
-        // next, rearrange Vec<DataPoint> into a k-d tree for faster querying
-        let radials_kdmap: kd_tree::KdMap<[i64; 2], f32> =
-            kd_tree::KdMap::build(radials_equirectangular);
-        // finally, sample the radial data into a grid
+        let radials_kdmap = self.build_kdmap();
+        // sample the radial data into a grid
         let (mut current_lat, start_lon) =
             get_point_bearing_distance((self.latitude, self.longitude), 315., 325.2691);
         let mut coords;
         let mut samples: GridData = Vec::new();
-        let mut current_sample: kd_tree::ItemAndDistance<DataPoint, i64>;
+        let mut current_sample: Option<kd_tree::ItemAndDistance<DataPoint, i64>>;
         for y in 0..height {
             // TODO: refactor get_point_bearing_distance such that the latitude and
             // longitude computations are separate; in these loops, we only need one
@@ -76,13 +150,12 @@ impl PrecipRate {
                 // we use current_lat instead of coords.0 here because get_point_bearing_distance
                 // seems to have some latitude error even when bearing == 90 degrees
                 // but since we know the latitude shouldn't change as we go east, we can just fix its value
-                current_sample = radials_kdmap
-                    .nearest(&[coord_as_i64(current_lat), coord_as_i64(coords.1)])
-                    .unwrap();
+                current_sample =
+                    radials_kdmap.nearest(&[coord_as_i64(current_lat), coord_as_i64(coords.1)]);
                 samples[y].push((
                     [coord_as_i64(current_lat), coord_as_i64(coords.1)],
-                    match current_sample.squared_distance {
-                        d if d < 100000 => current_sample.item.1,
+                    match current_sample {
+                        Some(s) if s.squared_distance < 100000 => s.item.1,
                         _ => 0.0,
                     },
                 ));
@@ -103,219 +176,1958 @@ impl PrecipRate {
         }
         samples
     }
+
+    /// Sample the precip data onto an existing grid of points, such as the
+    /// grid of a reference dataset. The coordinates of `grid` are kept as-is
+    /// and each value is replaced with the rate of the nearest bin, or zero if
+    /// no bin is close enough.
+    #[allow(clippy::ptr_arg)]
+    pub fn sample_radials_to_grid(&self, grid: &GridData) -> GridData {
+        let radials_kdmap = self.build_kdmap();
+        grid.iter()
+            .map(|row| {
+                row.iter()
+                    .map(|(coords, _)| {
+                        (
+                            *coords,
+                            match radials_kdmap.nearest(coords) {
+                                Some(n) if n.squared_distance < 100000 => n.item.1,
+                                _ => 0.0,
+                            },
+                        )
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Convert every bin from azimuth/range to lat/lon and arrange the results
+    /// into a k-d tree for faster querying. Bins without data are left out,
+    /// so that samples near them take the nearest bin that has data, or zero
+    /// if there isn't one close enough, instead of NaN.
+    fn build_kdmap(&self) -> kd_tree::KdMap<[i64; 2], f32> {
+        let mut radials_equirectangular: Vec<DataPoint> = Vec::new();
+        let mut coords: (f32, f32);
+        for radial in self.radials.iter() {
+            for (idx, bin) in radial.precip_rates.iter().enumerate() {
+                if bin.is_nan() {
+                    continue;
+                }
+                coords = get_point_bearing_distance(
+                    (self.latitude, self.longitude),
+                    radial.azimuth,
+                    self.bin_size * idx as f32 + 1. + self.range_to_first_bin,
+                );
+                radials_equirectangular
+                    .push(([coord_as_i64(coords.0), coord_as_i64(coords.1)], *bin));
+            }
+        }
+        kd_tree::KdMap::build(radials_equirectangular)
+    }
 }
 
 type ParseResult<T> = Result<(T, Vec<u8>), String>;
 
-/// Pop `n` bytes off the front of `input` and return the two pieces
-fn take_bytes(input: Vec<u8>, n: u16) -> ParseResult<Vec<u8>> {
-    let x = input.split_at(n as usize);
-    Ok((x.0.to_vec(), x.1.to_vec()))
+/// Product code of digital precipitation rate products
+pub const DPR_PRODUCT_CODE: i16 = 176;
+
+/// Length of the message header and product description
+const MESSAGE_HEADERS_SIZE: usize = 18 + 102;
+
+/// Length of the usual 30 byte text header, message header, and product
+/// description
+#[cfg(test)]
+const HEADERS_SIZE: usize = 30 + MESSAGE_HEADERS_SIZE;
+
+/// How far into a file to look for the end of the text header, which
+/// leaves room for the sequence number line that LDM puts in front of the
+/// WMO heading. This has to be shorter than [`MESSAGE_HEADERS_SIZE`] for
+/// [`read_headers`].
+const MAX_TEXT_HEADER_SIZE: usize = 96;
+
+/// Farthest a radar can see, in meters
+const MAX_RANGE: f32 = 460_000.;
+
+/// Widest bin that makes sense for a precipitation product, in meters
+const MAX_BIN_SIZE: f32 = 4000.;
+
+/// How closely [`parse_dpr_with`] holds a file to the spec
+#[derive(Debug, Clone, Default)]
+pub struct ParseOptions {
+    /// Let values outside the ranges in the spec through with a warning
+    /// instead of failing, fixing the ones that can be fixed, like azimuths
+    /// a little past 360 degrees
+    pub lenient: bool,
+    /// Fail if the structure of the file is inconsistent: block dividers,
+    /// block and message lengths, and the decompressed size of the
+    /// symbology block. Otherwise only what's needed to find the data is
+    /// trusted.
+    pub strict: bool,
+}
+
+/// Checks values against the ranges in the spec, failing on the first one
+/// that's out of range or, in lenient mode, collecting them as warnings.
+/// Structural checks only fail in strict mode.
+#[derive(Default)]
+struct Checks {
+    lenient: bool,
+    strict: bool,
+    warnings: Vec<String>,
+}
+
+impl Checks {
+    fn check<F>(&mut self, in_range: bool, message: F) -> Result<(), String>
+    where
+        F: FnOnce() -> String,
+    {
+        match (in_range, self.lenient) {
+            (true, _) => Ok(()),
+            (false, true) => {
+                self.warnings.push(message());
+                Ok(())
+            }
+            (false, false) => Err(message()),
+        }
+    }
+
+    fn structure<F>(&self, consistent: bool, message: F) -> Result<(), String>
+    where
+        F: FnOnce() -> String,
+    {
+        match consistent || !self.strict {
+            true => Ok(()),
+            false => Err(message()),
+        }
+    }
 }
 
-/// Consume one byte from `input` and parse an `i8`
-fn take_i8(input: Vec<u8>) -> ParseResult<i8> {
-    let (number, tail) = take_bytes(input, 1)?;
-    let buf: [u8; 1] = number.try_into().unwrap(); // TODO: handle error
-    Ok((i8::from_be_bytes(buf), tail))
+/// Pop `n` bytes off the front of `input` and return the two pieces, or
+/// fail if there aren't that many
+fn take_bytes(mut input: Vec<u8>, n: u16) -> ParseResult<Vec<u8>> {
+    if input.len() < n as usize {
+        return Err(format!(
+            "Unexpected end of file: wanted {} bytes, but only {} are left",
+            n,
+            input.len()
+        ));
+    }
+    let tail = input.split_off(n as usize);
+    Ok((input, tail))
 }
 
 /// Consume two bytes from `input` and parse an `i16`
 fn take_i16(input: Vec<u8>) -> ParseResult<i16> {
     let (number, tail) = take_bytes(input, 2)?;
-    let buf: [u8; 2] = number.try_into().unwrap(); // TODO: handle error
-    Ok((i16::from_be_bytes(buf), tail))
+    Ok((i16::from_be_bytes([number[0], number[1]]), tail))
 }
 
 /// Consume four bytes from `input` and parse an `i32`
 fn take_i32(input: Vec<u8>) -> ParseResult<i32> {
     let (number, tail) = take_bytes(input, 4)?;
-    let buf: [u8; 4] = number.try_into().unwrap(); // TODO: handle error
-    Ok((i32::from_be_bytes(buf), tail))
+    Ok((
+        i32::from_be_bytes([number[0], number[1], number[2], number[3]]),
+        tail,
+    ))
 }
 
-/// Consume four bytes from `input` and parse a `u32`
-fn take_u32(input: Vec<u8>) -> ParseResult<u32> {
-    let (number, tail) = take_bytes(input, 4)?;
-    let buf: [u8; 4] = number.try_into().unwrap(); // TODO: handle error
-    Ok((u32::from_be_bytes(buf), tail))
+/// Decompress `input` if it's gzipped, like the files from some NWS
+/// mirrors, so the text header parses
+fn gunzip(input: Vec<u8>) -> Result<Vec<u8>, String> {
+    gunzip_if_needed(input).map_err(|e| format!("Failed to gunzip input: {}", e))
 }
 
-/// Parse an XDR string from the head of the input
-///
-/// XDR strings are not null-terminated. Instead, they start with an unsigned
-/// four-byte integer that contains the total string length. Then, the contents
-/// of the string follow, padded with zero bytes to a multiple of four.
-///
-/// For more information, see [RFC 1832](https://datatracker.ietf.org/doc/html/rfc1832#section-3.11).
-fn take_string(input: Vec<u8>) -> ParseResult<String> {
-    let (length, tail) = take_u32(input)?;
-    // grab the string
-    let (string_bytes, tail) = take_bytes(tail, length as u16)?;
-    let string = match String::from_utf8(string_bytes) {
-        Ok(s) => s,
-        Err(e) => return Err(format!("Failed to parse string: {}", e)),
+/// Source IDs in the message header from this one up belong to the
+/// supplemental product generators of TDWRs instead of WSR-88D RPGs
+pub(crate) const FIRST_TDWR_SOURCE_ID: i16 = 3000;
+
+/// The kind of radar that a product came from
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RadarType {
+    Wsr88d,
+    /// Terminal Doppler Weather Radar, which covers a major airport and
+    /// has shorter bins and range than a WSR-88D
+    Tdwr,
+}
+
+impl RadarType {
+    /// TDWR station codes are a T followed by the code of the airport,
+    /// while WSR-88D codes start with K, P, R, or, for San Juan, TJ
+    pub fn from_station_code(code: &str) -> RadarType {
+        let code = code.to_uppercase();
+        match code.starts_with('T') && !code.starts_with("TJ") {
+            true => RadarType::Tdwr,
+            false => RadarType::Wsr88d,
+        }
+    }
+}
+
+/// The WMO and AWIPS headers in front of the message
+struct TextHeader {
+    /// The data type and area of the WMO heading, like `SDUS51`
+    wmo_id: String,
+    /// The WMO office that sent the product, which is the radar itself for
+    /// WSR-88D products
+    office: String,
+    /// The AWIPS product ID, a three letter product category and a three
+    /// letter site
+    awips_id: String,
+}
+
+impl TextHeader {
+    /// The code of the radar that made the product. TDWR products are sent
+    /// by the office that covers the airport, so their station code is
+    /// taken from the site in the AWIPS ID instead.
+    fn station_code(self, source_id: i16) -> String {
+        match self.awips_id.get(3..6) {
+            Some(site) if source_id >= FIRST_TDWR_SOURCE_ID => format!("T{}", site),
+            _ => self.office,
+        }
+    }
+}
+
+/// Find the WMO heading and AWIPS ID line near the start of `input`. They
+/// usually take up exactly 30 bytes, but files from LDM and other feeds can
+/// have a sequence number line or a second WMO heading in front, and lines
+/// that end with `\n` or `\r\n` instead of `\r\r\n`. If no heading is
+/// found, the usual layout is assumed.
+fn text_header(input: Vec<u8>) -> ParseResult<TextHeader> {
+    static PATTERN: std::sync::OnceLock<regex::bytes::Regex> = std::sync::OnceLock::new();
+    let pattern = PATTERN.get_or_init(|| {
+        regex::bytes::Regex::new(
+            r"(?-u)([A-Z]{4}[0-9]{2}) ([A-Z0-9]{4}) [0-9]{6}(?: [A-Z]{3})?[ \t\r]*\n([A-Z0-9]{4,6})[ \t\r]*\n",
+        )
+        .unwrap()
+    });
+    let window = &input[..input.len().min(MAX_TEXT_HEADER_SIZE)];
+    let (wmo_id, office, awips_id, tail) = match pattern.captures(window) {
+        Some(captures) => (
+            captures[1].to_vec(),
+            captures[2].to_vec(),
+            captures[3].to_vec(),
+            input[captures.get(0).unwrap().end()..].to_vec(),
+        ),
+        None => {
+            let (wmo_id, tail) = take_bytes(input, 6)?;
+            let (_, tail) = take_bytes(tail, 1)?;
+            let (office, tail) = take_bytes(tail, 4)?;
+            let (_, tail) = take_bytes(tail, 10)?;
+            let (awips_id, tail) = take_bytes(tail, 6)?;
+            let (_, tail) = take_bytes(tail, 3)?;
+            (wmo_id, office, awips_id, tail)
+        }
     };
-    // pad out to the next four-byte boundary if needed
-    if length % 4 != 0 {
-        let (_, tail) = take_bytes(tail, 4 - (length % 4) as u16)?;
-        Ok((string, tail))
-    } else {
-        Ok((string, tail))
+    match (
+        String::from_utf8(wmo_id),
+        String::from_utf8(office),
+        String::from_utf8(awips_id),
+    ) {
+        (Ok(wmo_id), Ok(office), Ok(awips_id)) => Ok((
+            TextHeader {
+                wmo_id: wmo_id.trim().to_string(),
+                office,
+                awips_id: awips_id.trim().to_string(),
+            },
+            tail,
+        )),
+        (Err(e), _, _) | (_, Err(e), _) | (_, _, Err(e)) => {
+            Err(format!("Failed to parse station code: {}", e))
+        }
     }
 }
 
-/// Consume four bytes from `input` and parse an `f32`
-fn take_float(input: Vec<u8>) -> ParseResult<f32> {
-    let (number, tail) = take_bytes(input, 4)?;
-    let buf: [u8; 4] = number.try_into().unwrap(); // TODO: handle error
-    Ok((f32::from_be_bytes(buf), tail))
+/// Length of the text header at the front of `input`, which is where the
+/// message header starts. This is usually 30 bytes, but see [`text_header`]
+/// for the variations.
+pub fn text_header_length(input: &[u8]) -> Result<usize, String> {
+    let window = &input[..input.len().min(MAX_TEXT_HEADER_SIZE)];
+    let (_, tail) = text_header(window.to_vec())?;
+    Ok(window.len() - tail.len())
 }
 
-fn text_header(input: Vec<u8>) -> ParseResult<String> {
-    let (_, tail) = take_bytes(input, 7)?;
-    let (station_code, tail) = take_bytes(tail, 4)?;
-    let (_, tail) = take_bytes(tail, 19)?;
-    match String::from_utf8(station_code) {
-        Ok(s) => Ok((s, tail)),
-        Err(e) => Err(format!("Failed to parse station code: {}", e)),
+/// Convert a date in days since 1969-12-31 and a time in seconds after
+/// midnight, or `None` if the date is zero
+pub(crate) fn julian_time(date: i16, seconds: i32) -> Option<chrono::NaiveDateTime> {
+    if date <= 0 {
+        return None;
     }
+    chrono::NaiveDateTime::from_timestamp_opt((date as i64 - 1) * 86400 + seconds as i64, 0)
 }
 
-fn message_header(input: Vec<u8>) -> ParseResult<()> {
-    let (_, tail) = take_bytes(input, 18)?;
-    Ok(((), tail))
+/// What's needed from the message header (Figure 3-3)
+struct MessageHeader {
+    /// When the message was sent
+    time: Option<chrono::NaiveDateTime>,
+    /// ID of the product generator that sent it
+    source_id: i16,
 }
 
-fn product_description(input: Vec<u8>) -> ParseResult<(f32, f32, OperationalMode, bool, i32)> {
+fn message_header(input: Vec<u8>) -> ParseResult<MessageHeader> {
     let (_, tail) = take_bytes(input, 2)?;
+    let (date, tail) = take_i16(tail)?;
+    let (time, tail) = take_i32(tail)?;
+    let (_, tail) = take_bytes(tail, 4)?;
+    let (source_id, tail) = take_i16(tail)?;
+    let (_, tail) = take_bytes(tail, 4)?;
+    Ok((
+        MessageHeader {
+            time: julian_time(date, time),
+            source_id,
+        },
+        tail,
+    ))
+}
+
+/// Every field of the product description block (Figure 3-6), as the
+/// radar reported it
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProductMetadata {
+    pub latitude: f32,
+    pub longitude: f32,
+    /// Height of the radar above sea level in feet
+    pub height: i16,
+    pub product_code: i16,
+    pub operational_mode: OperationalMode,
+    pub volume_coverage_pattern: i16,
+    pub sequence_number: i16,
+    pub volume_scan_number: i16,
+    pub volume_start: Option<chrono::NaiveDateTime>,
+    pub generation_time: Option<chrono::NaiveDateTime>,
+    pub elevation_number: i16,
+    /// Product dependent parameters 1 through 10, whose meanings depend on
+    /// the product. Parameters 1 to 3 come before the thresholds in the
+    /// block and 4 to 10 after them.
+    pub parameters: [i16; 10],
+    /// Halfwords 31 through 46, which hold data level thresholds or scale
+    /// factors depending on the product
+    pub thresholds: [i16; 16],
+    pub version: u8,
+    pub spot_blank: bool,
+    /// Offsets of the blocks from the start of the message in halfwords, or
+    /// zero for blocks that aren't there
+    pub symbology_offset: i32,
+    pub graphic_offset: i32,
+    pub tabular_offset: i32,
+}
+
+impl ProductMetadata {
+    /// The precipitation detected flag in the high byte of parameter 3
+    pub fn precip_detected(&self) -> bool {
+        self.parameters[2] >> 8 != 0
+    }
+
+    /// Elevation angle in degrees from parameter 3, for products made from a
+    /// single elevation
+    pub fn elevation_angle(&self) -> f32 {
+        self.parameters[2] as f32 / 10.
+    }
+
+    /// How the symbology block is compressed, from parameter 8: 0 for not at
+    /// all and 1 for bzip2
+    pub fn compression_method(&self) -> i16 {
+        self.parameters[7]
+    }
+
+    /// Size of the symbology block once it's decompressed, from parameters 9
+    /// and 10
+    pub fn uncompressed_size(&self) -> u32 {
+        (self.parameters[8] as u16 as u32) << 16 | self.parameters[9] as u16 as u32
+    }
+
+    /// Threshold halfword `n`, counting from halfword 31 of the message
+    pub(crate) fn threshold_i16(&self, n: usize) -> i16 {
+        self.thresholds[n - 31]
+    }
+
+    /// The float stored in threshold halfwords `n` and `n + 1`
+    pub(crate) fn threshold_f32(&self, n: usize) -> f32 {
+        let [a, b] = self.thresholds[n - 31].to_be_bytes();
+        let [c, d] = self.thresholds[n - 30].to_be_bytes();
+        f32::from_be_bytes([a, b, c, d])
+    }
+
+    fn times(&self) -> ScanTimes {
+        ScanTimes {
+            volume_start: self.volume_start,
+            generation: self.generation_time,
+            ..Default::default()
+        }
+    }
+}
+
+fn product_description(input: Vec<u8>, checks: &mut Checks) -> ParseResult<ProductMetadata> {
+    let (divider, tail) = take_i16(input)?;
+    checks.structure(divider == -1, || {
+        format!(
+            "Product description block divider is {}, expected -1",
+            divider
+        )
+    })?;
     let (latitude_int, tail) = take_i32(tail)?;
     let (longitude_int, tail) = take_i32(tail)?;
-    let (_, tail) = take_bytes(tail, 4)?;
+    let (height, tail) = take_i16(tail)?;
+    let (product_code, tail) = take_i16(tail)?;
     let (operational_mode_int, tail) = take_i16(tail)?;
-    let (_, tail) = take_bytes(tail, 24)?;
-    let (precip_detected_int, tail) = take_i8(tail)?;
-    let (_, tail) = take_bytes(tail, 43)?;
-    let (uncompressed_size, tail) = take_i32(tail)?;
-    let (_, tail) = take_bytes(tail, 14)?;
+    let (volume_coverage_pattern, tail) = take_i16(tail)?;
+    let (sequence_number, tail) = take_i16(tail)?;
+    let (volume_scan_number, tail) = take_i16(tail)?;
+    let (volume_date, tail) = take_i16(tail)?;
+    let (volume_time, tail) = take_i32(tail)?;
+    let (generation_date, tail) = take_i16(tail)?;
+    let (generation_time, tail) = take_i32(tail)?;
+    let (mut parameters, mut thresholds) = ([0; 10], [0; 16]);
+    let (parameter_1, tail) = take_i16(tail)?;
+    let (parameter_2, tail) = take_i16(tail)?;
+    let (elevation_number, tail) = take_i16(tail)?;
+    let (parameter_3, mut tail) = take_i16(tail)?;
+    parameters[..3].copy_from_slice(&[parameter_1, parameter_2, parameter_3]);
+    for threshold in thresholds.iter_mut() {
+        (*threshold, tail) = take_i16(tail)?;
+    }
+    for parameter in parameters[3..].iter_mut() {
+        (*parameter, tail) = take_i16(tail)?;
+    }
+    let (version_and_spot_blank, tail) = take_i16(tail)?;
+    let (symbology_offset, tail) = take_i32(tail)?;
+    let (graphic_offset, tail) = take_i32(tail)?;
+    let (tabular_offset, tail) = take_i32(tail)?;
+    let [version, spot_blank] = version_and_spot_blank.to_be_bytes();
+    checks.check((-90_000..=90_000).contains(&latitude_int), || {
+        format!(
+            "Latitude of {} is out of range",
+            latitude_int as f32 / 1000.
+        )
+    })?;
+    checks.check((-180_000..=180_000).contains(&longitude_int), || {
+        format!(
+            "Longitude of {} is out of range",
+            longitude_int as f32 / 1000.
+        )
+    })?;
+    checks.check((0..=2).contains(&operational_mode_int), || {
+        format!("Unknown operational mode {}", operational_mode_int)
+    })?;
     Ok((
-        (
-            latitude_int as f32 / 1000.0,
-            longitude_int as f32 / 1000.0,
-            match operational_mode_int {
+        ProductMetadata {
+            latitude: latitude_int as f32 / 1000.0,
+            longitude: longitude_int as f32 / 1000.0,
+            height,
+            product_code,
+            operational_mode: match operational_mode_int {
                 0 => OperationalMode::Maintenance,
                 1 => OperationalMode::CleanAir,
                 2 => OperationalMode::Precipitation,
-                _ => OperationalMode::Maintenance, // TODO: throw error here
+                _ => OperationalMode::Maintenance,
             },
-            !matches!(precip_detected_int, 0),
-            uncompressed_size,
-        ),
+            volume_coverage_pattern,
+            sequence_number,
+            volume_scan_number,
+            volume_start: julian_time(volume_date, volume_time),
+            generation_time: julian_time(generation_date, generation_time),
+            elevation_number,
+            parameters,
+            thresholds,
+            version,
+            spot_blank: spot_blank != 0,
+            symbology_offset,
+            graphic_offset,
+            tabular_offset,
+        },
         tail,
     ))
 }
 
+/// Read up to `n` bytes from the front of `reader`, and return them along
+/// with a reader that still starts with them
+fn peek<R: Read>(mut reader: R, n: usize) -> Result<(Vec<u8>, impl Read), String> {
+    let mut prefix = Vec::new();
+    if let Err(e) = reader.by_ref().take(n as u64).read_to_end(&mut prefix) {
+        return Err(format!("Failed to read input: {}", e));
+    }
+    Ok((prefix.clone(), std::io::Cursor::new(prefix).chain(reader)))
+}
+
+/// True if `data` starts with a zlib header: deflate with a window of at
+/// most 32 KiB, and a checksum that makes the first two bytes a multiple of
+/// 31
+fn is_zlib(data: &[u8]) -> bool {
+    match data {
+        [cmf, flg, ..] => {
+            cmf & 0x0f == 8 && cmf >> 4 <= 7 && u16::from_be_bytes([*cmf, *flg]) % 31 == 0
+        }
+        _ => false,
+    }
+}
+
+/// Decompress the symbology block in `data` as it's read. If the product
+/// description says it's compressed, the method is worked out from the
+/// first bytes, since some sources use zlib instead of bzip2 even though
+/// the field says bzip2. With the `libbz2` feature, bzip2 is decoded by the
+/// system libbz2 (or a bundled copy) instead of the pure-Rust decoder,
+/// which is quite a bit faster.
+pub(crate) fn symbology_reader<'a, R: Read + 'a>(
+    compression: i16,
+    data: R,
+) -> Result<Box<dyn Read + 'a>, String> {
+    if compression == 0 {
+        return Ok(Box::new(data));
+    }
+    let (prefix, data) = peek(data, 3)?;
+    match compression {
+        _ if prefix.starts_with(b"BZh") => {
+            #[cfg(feature = "libbz2")]
+            return Ok(Box::new(bzip2::read::BzDecoder::new(data)));
+            #[cfg(not(feature = "libbz2"))]
+            return Ok(Box::new(bzip2_rs::DecoderReader::new(data)));
+        }
+        _ if is_zlib(&prefix) => Ok(Box::new(flate2::read::ZlibDecoder::new(data))),
+        method => Err(format!(
+            "Unsupported compression method {}: the symbology block is neither bzip2 nor zlib",
+            method
+        )),
+    }
+}
+
+/// Counts the bytes that pass through a reader
+struct CountingReader<R: Read> {
+    inner: R,
+    count: usize,
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.count += n;
+        Ok(n)
+    }
+}
+
+/// Keeps track of which field of the symbology block is being read and
+/// where it starts, so that errors can say where they happened
+struct FieldReader<R: Read> {
+    inner: CountingReader<R>,
+    /// Structures around the field, like the radial it's in
+    path: Vec<String>,
+    field: &'static str,
+    /// Offset of the field from the start of the decompressed block
+    offset: usize,
+}
+
+impl<R: Read> FieldReader<R> {
+    fn new(inner: R) -> Self {
+        FieldReader {
+            inner: CountingReader { inner, count: 0 },
+            path: Vec::new(),
+            field: "",
+            offset: 0,
+        }
+    }
+
+    /// Mark the start of the field called `name`
+    fn field(&mut self, name: &'static str) {
+        self.field = name;
+        self.offset = self.inner.count;
+    }
+
+    /// Add where the last field started to `error`
+    fn locate(&self, error: String) -> String {
+        let mut path = vec!["product symbology"];
+        path.extend(self.path.iter().map(|s| s.as_str()));
+        path.push(self.field);
+        format!(
+            "{} at byte {} of the symbology block: {}",
+            path.join(" → "),
+            self.offset,
+            error
+        )
+    }
+}
+
+impl<R: Read> Read for FieldReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+/// Read exactly `n` bytes from `reader`
+fn read_bytes<R: Read>(reader: &mut R, n: usize) -> Result<Vec<u8>, String> {
+    let mut buf = Vec::new();
+    match reader.take(n as u64).read_to_end(&mut buf) {
+        Ok(len) if len == n => Ok(buf),
+        Ok(len) => Err(format!(
+            "Unexpected end of file: wanted {} bytes, but only {} are left",
+            n, len
+        )),
+        Err(e) => Err(format!("Failed to decompress symbology block: {}", e)),
+    }
+}
+
+/// Read and discard `n` bytes from `reader`
+fn skip_bytes<R: Read>(reader: &mut R, n: usize) -> Result<(), String> {
+    read_bytes(reader, n).map(|_| ())
+}
+
+/// Read two bytes from `reader` and parse an `i16`
+fn read_i16<R: Read>(reader: &mut R) -> Result<i16, String> {
+    Ok(i16::from_be_bytes(
+        read_bytes(reader, 2)?.try_into().unwrap(),
+    ))
+}
+
+/// Read four bytes from `reader` and parse an `i32`
+fn read_i32<R: Read>(reader: &mut R) -> Result<i32, String> {
+    Ok(i32::from_be_bytes(
+        read_bytes(reader, 4)?.try_into().unwrap(),
+    ))
+}
+
+/// Read four bytes from `reader` and parse a `u32`
+fn read_u32<R: Read>(reader: &mut R) -> Result<u32, String> {
+    Ok(u32::from_be_bytes(
+        read_bytes(reader, 4)?.try_into().unwrap(),
+    ))
+}
+
+/// Read four bytes from `reader` and parse an `f32`
+fn read_float<R: Read>(reader: &mut R) -> Result<f32, String> {
+    Ok(f32::from_be_bytes(
+        read_bytes(reader, 4)?.try_into().unwrap(),
+    ))
+}
+
+/// Read an XDR string from `reader`
+///
+/// XDR strings are not null-terminated. Instead, they start with an unsigned
+/// four-byte integer that contains the total string length. Then, the contents
+/// of the string follow, padded with zero bytes to a multiple of four.
+///
+/// For more information, see [RFC 1832](https://datatracker.ietf.org/doc/html/rfc1832#section-3.11).
+fn read_string<R: Read>(reader: &mut R) -> Result<String, String> {
+    let length = read_u32(reader)? as usize;
+    let string = match String::from_utf8(read_bytes(reader, length)?) {
+        Ok(s) => s,
+        Err(e) => return Err(format!("Failed to parse string: {}", e)),
+    };
+    // pad out to the next four-byte boundary if needed
+    if !length.is_multiple_of(4) {
+        skip_bytes(reader, 4 - length % 4)?;
+    }
+    Ok(string)
+}
+
 /// Parse Radial Information Data Structure (Figure E-4)
-fn radial(input: Vec<u8>) -> ParseResult<Radial> {
-    let (azimuth, tail) = take_float(input)?;
-    let (elevation, tail) = take_float(tail)?;
-    let (width, tail) = take_float(tail)?;
-    let (num_bins, tail) = take_i32(tail)?;
-    let (_attributes, tail) = take_string(tail)?;
-    let (_, tail) = take_bytes(tail, 4)?;
-    let mut precip_rates: Vec<f32> = Vec::with_capacity(num_bins as usize);
-    let (precip_rate_bytes, tail) = take_bytes(tail, (num_bins * 4) as u16)?;
-    for idx in 0..num_bins {
-        let buf: [u8; 2] = precip_rate_bytes[(idx * 4 + 2) as usize..(idx * 4 + 4) as usize]
-            .try_into()
-            .unwrap();
-        precip_rates.push(u16::from_be_bytes(buf) as f32 / 1000.0);
+fn radial<R: Read>(reader: &mut FieldReader<R>, checks: &mut Checks) -> Result<Radial, String> {
+    // check each field right after reading it, so that errors point at it
+    reader.field("azimuth");
+    let azimuth = read_float(reader)?;
+    checks.check((0. ..360.).contains(&azimuth), || {
+        format!("Radial azimuth of {} is out of range", azimuth)
+    })?;
+    reader.field("elevation");
+    let elevation = read_float(reader)?;
+    checks.check((-1. ..=45.).contains(&elevation), || {
+        format!("Radial elevation of {} is out of range", elevation)
+    })?;
+    reader.field("width");
+    let width = read_float(reader)?;
+    checks.check(width > 0. && width <= 2., || {
+        format!("Radial width of {} is out of range", width)
+    })?;
+    // the usual problem is an azimuth just past 360, which wraps around
+    let azimuth = azimuth.rem_euclid(360.);
+    reader.field("number of bins");
+    let num_bins = read_i32(reader)?.max(0) as usize;
+    reader.field("attributes");
+    let _attributes = read_string(reader)?;
+    skip_bytes(reader, 4)?;
+    reader.field("bins");
+    let precip_rates = read_bytes(reader, num_bins * 4)?
+        .chunks_exact(4)
+        .map(|bin| match u16::from_be_bytes([bin[2], bin[3]]) {
+            MISSING_DATA_CODE => f32::NAN,
+            code => code as f32 / 1000.0,
+        })
+        .collect();
+    Ok(Radial {
+        azimuth,
+        elevation,
+        width,
+        precip_rates,
+        interpolated: false,
+    })
+}
+
+/// One radial of a digital radial data array packet, with one byte per bin
+pub(crate) struct DigitalRadial {
+    /// Azimuth of the center of the radial
+    pub(crate) azimuth: f32,
+    pub(crate) width: f32,
+    pub(crate) levels: Vec<u8>,
+}
+
+/// Parse a symbology block that holds a single digital radial data array
+/// packet (Figure 3-11c), as used by the eight-bit products. Returns the
+/// index of the first bin, the bin size in km, and the radials.
+fn digital_radial_symbology<R: Read>(
+    reader: &mut R,
+) -> Result<(usize, f32, Vec<DigitalRadial>), String> {
+    // header (Figure 3-6, Sheet 7)
+    skip_bytes(reader, 16)?;
+
+    let packet_code = read_i16(reader)?;
+    if packet_code != 16 {
+        return Err(format!(
+            "Expected a digital radial data array packet (16), found packet {}",
+            packet_code
+        ));
     }
-    Ok((
-        Radial {
-            azimuth,
-            elevation,
+    let first_bin = read_i16(reader)?.max(0) as usize;
+    let num_bins = read_i16(reader)?.max(0) as usize;
+    skip_bytes(reader, 4)?; // i and j of the center
+    let range_scale = read_i16(reader)?;
+    let num_radials = read_i16(reader)?.max(0) as usize;
+
+    let mut radials = Vec::with_capacity(num_radials.min(720));
+    for _ in 0..num_radials {
+        let num_bytes = read_i16(reader)?.max(0) as usize;
+        let start = read_i16(reader)? as f32 / 10.;
+        let width = read_i16(reader)? as f32 / 10.;
+        // radials are padded to a whole number of halfwords
+        let mut levels = read_bytes(reader, num_bytes + num_bytes % 2)?;
+        levels.truncate(num_bins.min(num_bytes));
+        radials.push(DigitalRadial {
+            azimuth: (start + width / 2.) % 360.,
             width,
-            precip_rates,
-        },
-        tail,
-    ))
+            levels,
+        });
+    }
+    Ok((first_bin, range_scale as f32 / 1000., radials))
+}
+
+/// A product whose symbology block is a digital radial data array, with the
+/// data levels not yet converted to values
+pub(crate) struct DigitalProduct {
+    pub(crate) station_code: String,
+    pub(crate) message_time: Option<chrono::NaiveDateTime>,
+    pub(crate) description: ProductMetadata,
+    pub(crate) first_bin: usize,
+    pub(crate) bin_size: f32,
+    pub(crate) radials: Vec<DigitalRadial>,
+}
+
+impl DigitalProduct {
+    /// Convert to a scan at `elevation` degrees, turning each data level
+    /// into a value with `convert`. The values are stored in the rates of
+    /// each radial, whatever they actually measure.
+    pub(crate) fn into_scan<F: Fn(u8) -> f32>(self, elevation: f32, convert: F) -> PrecipRate {
+        let description = self.description;
+        PrecipRate {
+            station_code: self.station_code,
+            capture_time: description
+                .volume_start
+                .unwrap_or_else(|| chrono::NaiveDateTime::from_timestamp(0, 0)),
+            scan_number: description.volume_scan_number as i32,
+            latitude: description.latitude,
+            longitude: description.longitude,
+            operational_mode: description.operational_mode.clone(),
+            precip_detected: description.precip_detected(),
+            bin_size: self.bin_size,
+            range_to_first_bin: self.first_bin as f32 * self.bin_size,
+            radials: self
+                .radials
+                .into_iter()
+                .map(|radial| Radial {
+                    azimuth: radial.azimuth,
+                    elevation,
+                    width: radial.width,
+                    precip_rates: radial.levels.into_iter().map(&convert).collect(),
+                    interpolated: false,
+                })
+                .collect(),
+            times: ScanTimes {
+                message: self.message_time,
+                ..description.times()
+            },
+            metadata: Some(description),
+            extra_components: Vec::new(),
+            alphanumeric: AlphanumericBlocks::default(),
+        }
+    }
 }
 
-fn product_symbology(
+/// Parse a product whose symbology block is a digital radial data array,
+/// checking that its product code is one of `codes`
+pub(crate) fn parse_digital_product(
     input: Vec<u8>,
-    uncompressed_size: i32,
-) -> ParseResult<(f32, f32, i32, chrono::NaiveDateTime, Vec<Radial>)> {
-    // decompress remaining input, which should all be compressed with bzip2
-    let mut tmp = Vec::with_capacity(uncompressed_size as usize);
-    let mut reader = bzip2_rs::DecoderReader::new(input.as_slice());
-    match std::io::copy(&mut reader, &mut tmp) {
-        Ok(_) => (),
-        Err(e) => return Err(format!("Failed to decompress symbology block: {}", e)),
-    };
+    codes: &[i16],
+) -> Result<DigitalProduct, String> {
+    let (text, tail) = text_header(gunzip(input)?)?;
+    let (message, tail) = message_header(tail)?;
+    let (station_code, message_time) = (text.station_code(message.source_id), message.time);
+    let (description, tail) = product_description(tail, &mut Checks::default())?;
+    if !codes.contains(&description.product_code) {
+        return Err(format!(
+            "Expected product {:?}, found product {}",
+            codes, description.product_code
+        ));
+    }
+    let mut reader = symbology_reader(description.compression_method(), tail.as_slice())?;
+    let (first_bin, bin_size, radials) = digital_radial_symbology(&mut reader)?;
+    Ok(DigitalProduct {
+        station_code,
+        message_time,
+        description,
+        first_bin,
+        bin_size,
+        radials,
+    })
+}
+
+/// One radial component of a generic radial product (Figure E-3).
+/// Digital precipitation rate products usually have only one, whose data
+/// ends up in [`PrecipRate::radials`].
+#[derive(Debug, Clone)]
+pub struct RadialComponent {
+    pub description: String,
+    /// Length of each bin in km
+    pub bin_size: f32,
+    /// Distance from the radar to the start of the first bin in km
+    pub range_to_first_bin: f32,
+    /// Names and values of the component's parameters
+    pub parameters: Vec<(String, String)>,
+    pub radials: Vec<Radial>,
+}
 
+/// What [`product_symbology`] finds in the symbology block
+struct Symbology {
+    scan_number: i32,
+    capture_time: chrono::NaiveDateTime,
+    elevation_time: Option<chrono::NaiveDateTime>,
+    /// Every radial component, of which there's at least one
+    components: Vec<RadialComponent>,
+    /// Length of the whole block, as given in its header
+    block_length: u32,
+}
+
+/// Parse the symbology block as it's decompressed, without holding the
+/// whole decompressed block in memory
+fn product_symbology<R: Read>(reader: &mut R, checks: &mut Checks) -> Result<Symbology, String> {
+    let mut reader = FieldReader::new(reader);
+    symbology_fields(&mut reader, checks).map_err(|e| reader.locate(e))
+}
+
+fn symbology_fields<R: Read>(
+    reader: &mut FieldReader<R>,
+    checks: &mut Checks,
+) -> Result<Symbology, String> {
     // header (Figure 3-6, Sheet 7)
-    let (_, tail) = take_bytes(tmp, 16)?;
+    reader.field("block divider");
+    let divider = read_i16(reader)?;
+    checks.structure(divider == -1, || {
+        format!("Symbology block divider is {}, expected -1", divider)
+    })?;
+    reader.field("block ID");
+    let block_id = read_i16(reader)?;
+    checks.structure(block_id == 1, || {
+        format!("Symbology block ID is {}, expected 1", block_id)
+    })?;
+    reader.field("block length");
+    let block_length = read_u32(reader)?;
+    reader.field("number of layers");
+    let layers = read_i16(reader)?;
+    checks.structure(layers == 1, || {
+        format!("Symbology block has {} layers, expected 1", layers)
+    })?;
+    reader.field("layer divider");
+    let layer_divider = read_i16(reader)?;
+    checks.structure(layer_divider == -1, || {
+        format!("Symbology layer divider is {}, expected -1", layer_divider)
+    })?;
+    reader.field("layer length");
+    let layer_length = read_u32(reader)?;
+    checks.structure(layer_length as u64 + 16 == block_length as u64, || {
+        format!(
+            "Symbology layer length is {} bytes, but the block length of {} bytes leaves {}",
+            layer_length,
+            block_length,
+            block_length.saturating_sub(16)
+        )
+    })?;
 
     // another header (Figure 3-15c)
-    let (_, tail) = take_bytes(tail, 8)?;
+    reader.field("packet code");
+    let packet_code = read_i16(reader)?;
+    checks.structure(packet_code == 28, || {
+        format!("Packet code is {}, expected 28", packet_code)
+    })?;
+    reader.field("spare");
+    skip_bytes(reader, 2)?;
+    reader.field("packet length");
+    let packet_length = read_u32(reader)?;
+    checks.structure(packet_length as u64 + 8 == layer_length as u64, || {
+        format!(
+            "Generic packet length is {} bytes, but the layer length of {} bytes leaves {}",
+            packet_length,
+            layer_length,
+            layer_length.saturating_sub(8)
+        )
+    })?;
 
     // Product Description Data Structure header (Figure E-1)
-    let (_, tail) = take_string(tail)?; // name
-    let (_, tail) = take_string(tail)?; // description
-    let (_, tail) = take_bytes(tail, 12)?;
-    let (_, tail) = take_string(tail)?; // radar name
-    let (_, tail) = take_bytes(tail, 12)?;
-    let (capture_time, tail) = take_u32(tail)?;
-    let (_, tail) = take_bytes(tail, 8)?;
-    let (scan_number, tail) = take_i32(tail)?;
-    let (_, tail) = take_bytes(tail, 36)?;
-
-    // Radial Component Data Structure (Figure E-3)
-    let (_, tail) = take_bytes(tail, 4)?;
-    let (_, tail) = take_string(tail)?; // description
-    let (bin_size, tail) = take_float(tail)?;
-    let (range_to_first_bin, tail) = take_float(tail)?;
-    let (_, tail) = take_bytes(tail, 8)?;
-    let (num_radials, mut tail) = take_i32(tail)?;
-
-    // parse the radials themselves
-    let mut radials: Vec<Radial> = Vec::with_capacity(num_radials as usize);
-    for _ in 0..num_radials {
-        let tmp = radial(tail)?;
-        radials.push(tmp.0);
-        tail = tmp.1;
+    reader.field("name");
+    read_string(reader)?; // name
+    reader.field("description");
+    read_string(reader)?; // description
+    reader.field("spare");
+    skip_bytes(reader, 12)?;
+    reader.field("radar name");
+    read_string(reader)?; // radar name
+    reader.field("spare");
+    skip_bytes(reader, 12)?;
+    reader.field("capture time");
+    let capture_time = read_u32(reader)?;
+    reader.field("elevation time");
+    let elevation_time = read_u32(reader)?;
+    reader.field("spare");
+    skip_bytes(reader, 4)?;
+    reader.field("scan number");
+    let scan_number = read_i32(reader)?;
+    for name in [
+        "operational mode",
+        "volume coverage pattern",
+        "elevation number",
+        "compression method",
+        "uncompressed size",
+    ] {
+        reader.field(name);
+        skip_bytes(reader, 4)?;
     }
+    product_parameters(reader)?;
 
-    Ok((
-        (
-            range_to_first_bin / 1000.,
-            bin_size / 1000.,
-            scan_number,
-            chrono::NaiveDateTime::from_timestamp(capture_time as i64, 0),
-            radials,
-        ),
-        tail,
-    ))
+    reader.field("number of components");
+    let num_components = read_i32(reader)?.max(0) as usize;
+    reader.field("component pointer");
+    skip_bytes(reader, 4)?;
+    let mut components = Vec::with_capacity(num_components.min(16));
+    for idx in 0..num_components {
+        reader.path = vec![format!("component {}", idx)];
+        reader.field("component type");
+        let component_type = read_i32(reader)?;
+        if component_type != 1 {
+            return Err(format!(
+                "Unsupported component type {}: only radial components (1) can be read",
+                component_type
+            ));
+        }
+        components.push(radial_component(reader, checks)?);
+        // the pointer to the next component
+        if idx + 1 < num_components {
+            reader.field("component pointer");
+            skip_bytes(reader, 4)?;
+        }
+    }
+    reader.path.clear();
+    if components.is_empty() {
+        reader.field("number of components");
+        return Err(String::from("Symbology block has no radial components"));
+    }
+
+    Ok(Symbology {
+        scan_number,
+        capture_time: chrono::NaiveDateTime::from_timestamp(capture_time as i64, 0),
+        elevation_time: match elevation_time {
+            0 => None,
+            t => Some(chrono::NaiveDateTime::from_timestamp(t as i64, 0)),
+        },
+        components,
+        block_length,
+    })
+}
+
+/// Parse a list of parameters, which are pairs of XDR strings
+fn product_parameters<R: Read>(
+    reader: &mut FieldReader<R>,
+) -> Result<Vec<(String, String)>, String> {
+    reader.field("number of parameters");
+    let num_parameters = read_i32(reader)?.max(0) as usize;
+    reader.field("parameter pointer");
+    skip_bytes(reader, 4)?;
+    let mut parameters = Vec::with_capacity(num_parameters.min(16));
+    for idx in 0..num_parameters {
+        reader.field("parameter");
+        parameters.push((read_string(reader)?, read_string(reader)?));
+        if idx + 1 < num_parameters {
+            reader.field("parameter pointer");
+            skip_bytes(reader, 4)?;
+        }
+    }
+    Ok(parameters)
+}
+
+/// Parse Radial Component Data Structure (Figure E-3)
+fn radial_component<R: Read>(
+    reader: &mut FieldReader<R>,
+    checks: &mut Checks,
+) -> Result<RadialComponent, String> {
+    reader.field("component description");
+    let description = read_string(reader)?;
+    reader.field("bin size");
+    let bin_size = read_float(reader)?;
+    checks.check(bin_size > 0. && bin_size <= MAX_BIN_SIZE, || {
+        format!("Bin size of {} m is out of range", bin_size)
+    })?;
+    reader.field("range to first bin");
+    let range_to_first_bin = read_float(reader)?;
+    checks.check((0. ..=MAX_RANGE).contains(&range_to_first_bin), || {
+        format!(
+            "Range to first bin of {} m is out of range",
+            range_to_first_bin
+        )
+    })?;
+    let parameters = product_parameters(reader)?;
+    reader.field("number of radials");
+    let num_radials = read_i32(reader)?.max(0) as usize;
+
+    // parse the radials themselves, without trusting the count for the
+    // initial allocation
+    let mut radials: Vec<Radial> = Vec::with_capacity(num_radials.min(720));
+    for idx in 0..num_radials {
+        reader.path.truncate(1);
+        reader.path.push(format!("radial {}", idx));
+        radials.push(radial(reader, checks)?);
+    }
+    reader.path.truncate(1);
+
+    Ok(RadialComponent {
+        description,
+        bin_size: bin_size / 1000.,
+        range_to_first_bin: range_to_first_bin / 1000.,
+        parameters,
+        radials,
+    })
+}
+
+/// Where one section of a data file starts, how long it is, and how long it
+/// took to parse
+#[derive(Debug)]
+pub struct SectionReport {
+    pub name: &'static str,
+    /// Offset in bytes from the start of the file
+    pub offset: usize,
+    pub length: usize,
+    pub duration: std::time::Duration,
+}
+
+/// Statistics collected while parsing a file, for tracking down files that
+/// don't parse the way they should
+#[derive(Debug, Default)]
+pub struct ParseReport {
+    pub sections: Vec<SectionReport>,
+    pub file_size: usize,
+    pub decompressed_size: usize,
+    /// Decompressed bytes left over after the last radial
+    pub trailing_bytes: usize,
+    pub radials: usize,
+    pub bins: usize,
+    pub duration: std::time::Duration,
+    /// Values outside the spec that were let through in lenient mode
+    pub warnings: Vec<String>,
+}
+
+impl ParseReport {
+    /// Time a section parser and record how much of `input` it consumed
+    fn section<T, F>(
+        &mut self,
+        name: &'static str,
+        offset: usize,
+        input: Vec<u8>,
+        parser: F,
+    ) -> ParseResult<T>
+    where
+        F: FnOnce(Vec<u8>) -> ParseResult<T>,
+    {
+        let start = std::time::Instant::now();
+        let length = input.len();
+        let (value, tail) =
+            parser(input).map_err(|e| format!("{} at byte {}: {}", name, offset, e))?;
+        self.sections.push(SectionReport {
+            name,
+            offset,
+            length: length - tail.len(),
+            duration: start.elapsed(),
+        });
+        Ok((value, tail))
+    }
+
+    /// Offset of the end of the last section that was recorded
+    fn end(&self) -> usize {
+        self.sections.last().map_or(0, |s| s.offset + s.length)
+    }
 }
 
+/// Parse a digital precipitation rate file, which may be gzipped
 pub fn parse_dpr(input: Vec<u8>) -> Result<PrecipRate, String> {
-    let (station_code, tail) = text_header(input)?;
-    let (_, tail) = message_header(tail)?;
-    let ((latitude, longitude, operational_mode, precip_detected, uncompressed_size), tail) =
-        product_description(tail)?;
-    let ((range_to_first_bin, bin_size, scan_number, capture_time, radials), _) =
-        product_symbology(tail, uncompressed_size)?;
-    Ok(PrecipRate {
+    parse_dpr_with_report(input).map(|(dpr, _)| dpr)
+}
+
+/// Parse many data files at once, spread over all available cores, and
+/// return the results in the same order as `inputs`. Each file's symbology
+/// block is usually a single bzip2 block that can't be split up, so this is
+/// the way to go faster when there's a lot of data, like when processing an
+/// archive or the latest scans from every station.
+pub fn parse_dpr_many(inputs: Vec<Vec<u8>>) -> Vec<Result<PrecipRate, String>> {
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let chunk_size = inputs.len().div_ceil(threads).max(1);
+    let mut chunks: Vec<Vec<Vec<u8>>> = Vec::new();
+    let mut inputs = inputs.into_iter().peekable();
+    while inputs.peek().is_some() {
+        chunks.push(inputs.by_ref().take(chunk_size).collect());
+    }
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = chunks
+            .into_iter()
+            .map(|chunk| scope.spawn(move || chunk.into_iter().map(parse_dpr).collect::<Vec<_>>()))
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap())
+            .collect()
+    })
+}
+
+/// Parse every digital precipitation rate product in `input`, which may
+/// hold several messages back to back, like some of the `sn.*` files on
+/// TGFTP. Messages are framed by the length in their headers, and ones
+/// holding other products are skipped.
+pub fn parse_dpr_all(input: &[u8]) -> Result<Vec<PrecipRate>, String> {
+    let input = gunzip(input.to_vec())?;
+    let mut scans = Vec::new();
+    let mut offset = 0;
+    loop {
+        // skip whatever separates the messages, like line breaks and the
+        // control characters that wrap WMO bulletins
+        while matches!(input.get(offset), Some(b) if b.is_ascii_whitespace() || b.is_ascii_control())
+        {
+            offset += 1;
+        }
+        if offset >= input.len() {
+            break;
+        }
+        // the message header follows the text header, which can be a
+        // different length in each message
+        let message_start = match text_header_length(&input[offset..]) {
+            Ok(length) => offset + length,
+            Err(_) => return Err(format!("Message at offset {} ended early", offset)),
+        };
+        let header = match input.get(message_start..message_start + 12) {
+            Some(h) => h,
+            None => return Err(format!("Message at offset {} ended early", offset)),
+        };
+        let code = i16::from_be_bytes([header[0], header[1]]);
+        let length = u32::from_be_bytes(header[8..12].try_into().unwrap()) as usize;
+        let end = message_start + length;
+        if length < 18 || end > input.len() {
+            return Err(format!(
+                "Message at offset {} has a bad length of {} bytes",
+                offset, length
+            ));
+        }
+        if code == DPR_PRODUCT_CODE {
+            scans.push(parse_dpr(input[offset..end].to_vec())?);
+        }
+        offset = end;
+    }
+    Ok(scans)
+}
+
+/// Like [`parse_dpr`], but also return a [`ParseReport`] that describes how
+/// the file was laid out
+pub fn parse_dpr_with_report(input: Vec<u8>) -> Result<(PrecipRate, ParseReport), String> {
+    parse_dpr_with(input, &ParseOptions::default())
+}
+
+/// Like [`parse_dpr_with_report`], with `options` deciding what to do about
+/// values outside the spec. In lenient mode they end up in the report's
+/// warnings instead of failing the parse.
+pub fn parse_dpr_with(
+    input: Vec<u8>,
+    options: &ParseOptions,
+) -> Result<(PrecipRate, ParseReport), String> {
+    let mut checks = Checks {
+        lenient: options.lenient,
+        strict: options.strict,
+        ..Default::default()
+    };
+    let start = std::time::Instant::now();
+    let input = gunzip(input)?;
+    let mut report = ParseReport {
+        file_size: input.len(),
+        ..Default::default()
+    };
+    let (text, tail) = report.section("text header", 0, input, text_header)?;
+    if let Some(length) = tail.get(8..12) {
+        let length = u32::from_be_bytes(length.try_into().unwrap()) as usize;
+        checks.structure(length == tail.len(), || {
+            format!(
+                "Message length is {} bytes, but {} bytes follow the text header",
+                length,
+                tail.len()
+            )
+        })?;
+    }
+    let (message, tail) = report.section("message header", report.end(), tail, message_header)?;
+    let (station_code, message_time) = (text.station_code(message.source_id), message.time);
+    let (description, tail) =
+        report.section("product description", report.end(), tail, |input| {
+            product_description(input, &mut checks)
+        })?;
+    check_dpr_code(&description)?;
+    // the symbology block is usually compressed with bzip2, and gets parsed
+    // as it's decompressed
+    let symbology_start = std::time::Instant::now();
+    let mut reader = CountingReader {
+        inner: symbology_reader(description.compression_method(), tail.as_slice())?,
+        count: 0,
+    };
+    let symbology = product_symbology(&mut reader, &mut checks)?;
+    checks.structure(reader.count as u64 == symbology.block_length as u64, || {
+        format!(
+            "Symbology block length is {} bytes, but {} bytes were read",
+            symbology.block_length, reader.count
+        )
+    })?;
+    let (alphanumeric, trailing_bytes) = alphanumeric_blocks(&mut reader)?;
+    report.trailing_bytes = trailing_bytes;
+    checks.structure(trailing_bytes == 0, || {
+        format!(
+            "{} bytes after the symbology block aren't part of any block",
+            trailing_bytes
+        )
+    })?;
+    report.decompressed_size = reader.count;
+    if description.compression_method() != 0 {
+        checks.structure(
+            reader.count as u64 == description.uncompressed_size() as u64,
+            || {
+                format!(
+                "Uncompressed size is {} bytes, but the symbology block decompressed to {} bytes",
+                description.uncompressed_size(),
+                reader.count
+            )
+            },
+        )?;
+    }
+    report.sections.push(SectionReport {
+        name: "symbology block",
+        offset: report.end(),
+        length: tail.len(),
+        duration: symbology_start.elapsed(),
+    });
+    let radials = symbology.components.iter().flat_map(|c| c.radials.iter());
+    report.radials = radials.clone().count();
+    report.bins = radials.map(|r| r.precip_rates.len()).sum();
+    report.duration = start.elapsed();
+    report.warnings = checks.warnings;
+    let mut scan = assemble_dpr(station_code, message_time, description, symbology);
+    scan.alphanumeric = alphanumeric;
+    Ok((scan, report))
+}
+
+/// Put the pieces of a digital precipitation rate file together
+fn assemble_dpr(
+    station_code: String,
+    message_time: Option<chrono::NaiveDateTime>,
+    description: ProductMetadata,
+    symbology: Symbology,
+) -> PrecipRate {
+    let mut components = symbology.components.into_iter();
+    let first = components
+        .next()
+        .expect("The symbology parser checks for a component");
+    PrecipRate {
         station_code,
+        capture_time: symbology.capture_time,
+        scan_number: symbology.scan_number,
+        latitude: description.latitude,
+        longitude: description.longitude,
+        operational_mode: description.operational_mode.clone(),
+        precip_detected: description.precip_detected(),
+        bin_size: first.bin_size,
+        range_to_first_bin: first.range_to_first_bin,
+        radials: first.radials,
+        times: ScanTimes {
+            message: message_time,
+            elevation_start: symbology.elevation_time,
+            ..description.times()
+        },
+        metadata: Some(description),
+        extra_components: components.collect(),
+        alphanumeric: AlphanumericBlocks::default(),
+    }
+}
+
+/// Read the rest of the decompressed data after the symbology block, and
+/// parse any alphanumeric blocks at the start of it. Returns the blocks and
+/// how many bytes were left over after them.
+fn alphanumeric_blocks<R: Read>(reader: &mut R) -> Result<(AlphanumericBlocks, usize), String> {
+    let mut rest = Vec::new();
+    if let Err(e) = reader.read_to_end(&mut rest) {
+        return Err(format!("Failed to decompress symbology block: {}", e));
+    }
+    let (blocks, length) = parse_alphanumeric(&rest)?;
+    Ok((blocks, rest.len() - length))
+}
+
+/// Check that a product description is for a digital precipitation rate
+/// product
+fn check_dpr_code(description: &ProductMetadata) -> Result<(), String> {
+    match description.product_code {
+        DPR_PRODUCT_CODE => Ok(()),
+        code => Err(format!(
+            "Expected a digital precipitation rate product ({}), found product {}",
+            DPR_PRODUCT_CODE, code
+        )),
+    }
+}
+
+/// Wrap `reader` in a gzip decoder if it starts with the gzip magic bytes
+fn gunzip_reader<'a, R: Read + 'a>(reader: R) -> Result<Box<dyn Read + 'a>, String> {
+    let (prefix, reader) = peek(reader, 2)?;
+    Ok(match prefix.as_slice() {
+        [0x1f, 0x8b] => Box::new(flate2::read::MultiGzDecoder::new(reader)),
+        _ => Box::new(reader),
+    })
+}
+
+/// Read and parse the headers of a digital precipitation rate file from
+/// the front of `reader`, which are small. Only the text header can change
+/// in size, so whatever of the message header was read along with it is
+/// topped up to the fixed size of the rest.
+fn read_headers<R: Read>(
+    reader: &mut R,
+) -> Result<(TextHeader, MessageHeader, ProductMetadata), String> {
+    let mut prefix = Vec::new();
+    if let Err(e) = reader
+        .by_ref()
+        .take(MAX_TEXT_HEADER_SIZE as u64)
+        .read_to_end(&mut prefix)
+    {
+        return Err(format!("Failed to read headers: {}", e));
+    }
+    let (text, mut headers) = text_header(prefix)?;
+    let start = headers.len();
+    headers.resize(MESSAGE_HEADERS_SIZE, 0);
+    if let Err(e) = reader.read_exact(&mut headers[start..]) {
+        return Err(format!("Failed to read headers: {}", e));
+    }
+    let (message, tail) = message_header(headers)?;
+    let (description, _) = product_description(tail, &mut Checks::default())?;
+    check_dpr_code(&description)?;
+    Ok((text, message, description))
+}
+
+/// The metadata at the front of a digital precipitation rate file
+#[derive(Debug, Clone)]
+pub struct ScanHeader {
+    pub station_code: String,
+    /// The data type and area of the WMO heading, like `SDUS51`
+    pub wmo_id: String,
+    /// Start of the volume scan
+    pub capture_time: chrono::NaiveDateTime,
+    pub scan_number: i32,
+    pub latitude: f32,
+    pub longitude: f32,
+    pub operational_mode: OperationalMode,
+    pub precip_detected: bool,
+    /// Largest rate in the scan in in/hr, as reported by the radar
+    pub max_rate: f32,
+    pub times: ScanTimes,
+    pub metadata: ProductMetadata,
+}
+
+/// Parse only the headers of a digital precipitation rate file, stopping
+/// before the symbology block. This skips decompression, which is nearly
+/// all of the work of [`parse_dpr`], for when only the metadata is needed,
+/// like when polling for new scans.
+pub fn parse_dpr_header(input: &[u8]) -> Result<ScanHeader, String> {
+    let (text, message, description) = read_headers(&mut gunzip_reader(input)?)?;
+    let capture_time = match description.volume_start {
+        Some(t) => t,
+        None => return Err(String::from("Product description has no volume scan time")),
+    };
+    Ok(ScanHeader {
+        wmo_id: text.wmo_id.clone(),
+        station_code: text.station_code(message.source_id),
         capture_time,
-        scan_number,
-        latitude,
-        longitude,
-        operational_mode,
-        precip_detected,
-        bin_size,
-        range_to_first_bin,
-        radials,
+        scan_number: description.volume_scan_number as i32,
+        latitude: description.latitude,
+        longitude: description.longitude,
+        operational_mode: description.operational_mode.clone(),
+        precip_detected: description.precip_detected(),
+        max_rate: description.parameters[3] as f32 / 1000.,
+        times: ScanTimes {
+            message: message.time,
+            ..description.times()
+        },
+        metadata: description,
     })
 }
+
+/// Like [`parse_dpr`], but read the file from `reader` as it's parsed
+/// instead of holding all of it in memory first, which helps with large
+/// inputs and network streams
+pub fn parse_dpr_from_reader<R: Read>(reader: R) -> Result<PrecipRate, String> {
+    let mut reader = gunzip_reader(reader)?;
+    let (text, message, description) = read_headers(&mut reader)?;
+    let (station_code, message_time) = (text.station_code(message.source_id), message.time);
+    let mut reader = symbology_reader(description.compression_method(), reader)?;
+    let symbology = product_symbology(&mut reader, &mut Checks::default())?;
+    let (alphanumeric, _) = alphanumeric_blocks(&mut reader)?;
+    let mut scan = assemble_dpr(station_code, message_time, description, symbology);
+    scan.alphanumeric = alphanumeric;
+    Ok(scan)
+}
+
+#[test]
+fn test_text_header_variants() {
+    let message = [0, 176];
+    for header in [
+        &b"SDUS51 KGYX 010000\r\r\nDPRGYX\r\r\n"[..],
+        b"\x01\r\r\n123 \r\r\nSDUS51 KGYX 010000\r\r\nDPRGYX\r\r\n",
+        b"SDUS51 KGYX 010000 RRA\nDPRGYX\n",
+        b"SDUS51 KGYX 010000\r\nSDUS51 KGYX 010000\r\nDPRGYX  \r\n",
+        b"SDUS51 KGYX 010000\r\r\nDPRGYX   ",
+    ] {
+        let (text, tail) = text_header([header, &message].concat()).unwrap();
+        assert_eq!(text.wmo_id, "SDUS51");
+        assert_eq!(text.office, "KGYX");
+        assert_eq!(text.awips_id, "DPRGYX");
+        assert_eq!(tail, message);
+    }
+
+    let mut data = dpr_bytes(&[vec![0, 250]]);
+    let header = parse_dpr_header(&data).unwrap();
+    assert_eq!(header.wmo_id, "SDUS51");
+    data.splice(..30, b"\x01\n042\nSDUS51 KGYX 010000\nDPRGYX\n".to_vec());
+    let options = ParseOptions {
+        strict: true,
+        ..Default::default()
+    };
+    let (scan, report) = parse_dpr_with(data.clone(), &options).unwrap();
+    assert_eq!(scan.station_code, "KGYX");
+    assert_eq!(report.sections[0].length, 32);
+    assert_eq!(text_header_length(&data), Ok(32));
+    assert_eq!(parse_dpr_header(&data).unwrap().station_code, "KGYX");
+    let scan = parse_dpr_from_reader(data.as_slice()).unwrap();
+    assert_eq!(scan.radials[0].precip_rates[1], 0.25);
+    assert_eq!(parse_dpr_all(&data).unwrap().len(), 1);
+}
+
+#[test]
+fn test_parse_dpr_many() {
+    // bad files shouldn't get in the way of the other results
+    let results = parse_dpr_many(vec![vec![0; 10], Vec::new(), vec![0; 200]]);
+    assert_eq!(results.len(), 3);
+    assert!(results.iter().all(|r| r.is_err()));
+}
+
+#[test]
+fn test_message_header_time() {
+    let mut header = vec![0, 176];
+    header.extend(19000i16.to_be_bytes());
+    header.extend(3600i32.to_be_bytes());
+    header.extend([0; 10]);
+    let (message, tail) = message_header(header).unwrap();
+    assert!(tail.is_empty());
+    assert_eq!(
+        message
+            .time
+            .unwrap()
+            .format("%Y-%m-%dT%H:%M:%SZ")
+            .to_string(),
+        "2022-01-07T01:00:00Z"
+    );
+    assert_eq!(julian_time(0, 0), None);
+}
+
+#[test]
+fn test_radial_missing_data() {
+    let mut data = Vec::new();
+    for value in [12.5f32, 0.5, 1.] {
+        data.extend(value.to_be_bytes());
+    }
+    data.extend(3i32.to_be_bytes());
+    data.extend([0; 8]); // empty attributes string and spare bytes
+    data.extend([0, 0, 0, 0, 0, 0, 0, 200, 0, 0, 0xff, 0xff]);
+    let radial = radial(
+        &mut FieldReader::new(data.as_slice()),
+        &mut Checks::default(),
+    )
+    .unwrap();
+    assert_eq!(radial.precip_rates[..2], [0., 0.2]);
+    assert!(radial.precip_rates[2].is_nan());
+}
+
+/// The text header, message header, and product description of a file
+/// with an uncompressed symbology block. The message length is filled in
+/// by [`set_message_length`].
+#[cfg(test)]
+fn product_headers(product_code: i16, thresholds: [u8; 32]) -> Vec<u8> {
+    let mut data = b"SDUS51 KGYX 010000\r\r\nDSAGYX\r\r\n".to_vec();
+    data.extend(product_code.to_be_bytes());
+    data.extend(19000i16.to_be_bytes());
+    data.extend([0; 14]);
+    // product description
+    data.extend((-1i16).to_be_bytes());
+    data.extend(43891i32.to_be_bytes());
+    data.extend((-70256i32).to_be_bytes());
+    data.extend([0; 2]);
+    data.extend(product_code.to_be_bytes());
+    data.extend(2i16.to_be_bytes());
+    data.extend([0; 4]);
+    data.extend(7i16.to_be_bytes());
+    data.extend(19000i16.to_be_bytes());
+    data.extend(3600i32.to_be_bytes());
+    data.extend([0; 14]);
+    data.extend(thresholds);
+    data.extend([0; 28]);
+    data
+}
+
+#[cfg(test)]
+fn set_message_length(data: &mut [u8]) {
+    let length = (data.len() - 30) as u32;
+    data[38..42].copy_from_slice(&length.to_be_bytes());
+}
+
+/// Build a file holding a product with an uncompressed digital radial data
+/// array, with one radial per entry of `levels`, each one degree wide
+#[cfg(test)]
+pub(crate) fn digital_product_bytes(
+    product_code: i16,
+    thresholds: [u8; 32],
+    levels: &[Vec<u8>],
+) -> Vec<u8> {
+    let mut data = product_headers(product_code, thresholds);
+    // symbology block with one digital radial data array packet
+    data.extend([0; 16]);
+    data.extend(16i16.to_be_bytes());
+    data.extend(0i16.to_be_bytes());
+    data.extend((levels[0].len() as i16).to_be_bytes());
+    data.extend([0; 4]);
+    data.extend(1000i16.to_be_bytes());
+    data.extend((levels.len() as i16).to_be_bytes());
+    for (azimuth, levels) in levels.iter().enumerate() {
+        data.extend((levels.len() as i16).to_be_bytes());
+        data.extend((azimuth as i16 * 10).to_be_bytes());
+        data.extend(10i16.to_be_bytes());
+        data.extend(levels);
+        if levels.len() % 2 == 1 {
+            data.push(0);
+        }
+    }
+    set_message_length(&mut data);
+    data
+}
+
+/// Build a digital precipitation rate file with an uncompressed symbology
+/// block, with one radial per entry of `codes`, each one degree wide with
+/// 250 m bins
+#[cfg(test)]
+pub(crate) fn dpr_bytes(codes: &[Vec<u16>]) -> Vec<u8> {
+    let string = |data: &mut Vec<u8>, s: &str| {
+        data.extend((s.len() as u32).to_be_bytes());
+        data.extend(s.as_bytes());
+        data.resize(data.len() + (4 - s.len() % 4) % 4, 0);
+    };
+    let mut data = product_headers(176, [0; 32]);
+    // symbology block with one generic radial packet, whose lengths get
+    // filled in at the end
+    let symbology_start = data.len();
+    data.extend((-1i16).to_be_bytes());
+    data.extend(1i16.to_be_bytes());
+    data.extend([0; 4]);
+    data.extend(1i16.to_be_bytes());
+    data.extend((-1i16).to_be_bytes());
+    data.extend([0; 4]);
+    data.extend(28i16.to_be_bytes());
+    data.extend([0; 6]);
+    string(&mut data, "DPR");
+    string(&mut data, "Digital Precipitation Rate");
+    data.extend([0; 12]);
+    string(&mut data, "KGYX");
+    data.extend([0; 12]);
+    data.extend(3600u32.to_be_bytes());
+    data.extend(3630u32.to_be_bytes());
+    data.extend([0; 4]);
+    data.extend(7i32.to_be_bytes());
+    data.extend([0; 28]);
+    // one radial component
+    data.extend(1i32.to_be_bytes());
+    data.extend([0; 4]);
+    data.extend(1i32.to_be_bytes());
+    string(&mut data, "");
+    data.extend(250f32.to_be_bytes());
+    data.extend(0f32.to_be_bytes());
+    data.extend([0; 8]);
+    data.extend((codes.len() as i32).to_be_bytes());
+    for (azimuth, codes) in codes.iter().enumerate() {
+        data.extend((azimuth as f32 + 0.5).to_be_bytes());
+        data.extend(0.5f32.to_be_bytes());
+        data.extend(1f32.to_be_bytes());
+        data.extend((codes.len() as i32).to_be_bytes());
+        string(&mut data, "");
+        data.extend([0; 4]);
+        for code in codes {
+            data.extend([0, 0]);
+            data.extend(code.to_be_bytes());
+        }
+    }
+    let block_length = (data.len() - symbology_start) as u32;
+    for (offset, length) in [
+        (4, block_length),
+        (12, block_length - 16),
+        (20, block_length - 24),
+    ] {
+        let offset = symbology_start + offset;
+        data[offset..offset + 4].copy_from_slice(&length.to_be_bytes());
+    }
+    set_message_length(&mut data);
+    data
+}
+
+#[test]
+fn test_parse_digital_product() {
+    let data = digital_product_bytes(94, [0; 32], &[vec![1, 2, 3], vec![4, 5, 6]]);
+    assert!(parse_digital_product(data.clone(), &[176]).is_err());
+    let product = parse_digital_product(data, &[94]).unwrap();
+    assert_eq!(product.description.volume_scan_number, 7);
+    let scan = product.into_scan(0.5, |level| level as f32 * 2.);
+    assert_eq!(scan.station_code, "KGYX");
+    assert_eq!(scan.latitude, 43.891);
+    assert_eq!(scan.bin_size, 1.);
+    assert_eq!(scan.radials.len(), 2);
+    assert_eq!(scan.radials[1].azimuth, 1.5);
+    assert_eq!(scan.radials[1].precip_rates, [8., 10., 12.]);
+    assert_eq!(scan.capture_time, julian_time(19000, 3600).unwrap());
+}
+
+#[test]
+fn test_parse_gzipped() {
+    use std::io::Write;
+
+    let data = digital_product_bytes(94, [0; 32], &[vec![1, 2, 3]]);
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(&data).unwrap();
+    let gzipped = encoder.finish().unwrap();
+    assert!(parse_digital_product(gzipped.clone(), &[94]).is_ok());
+    // gets as far as the product code instead of tripping over the header
+    let error = parse_dpr(gzipped).unwrap_err();
+    assert!(error.contains("found product 94"), "{}", error);
+}
+
+#[test]
+fn test_symbology_reader_zlib() {
+    use std::io::Write;
+
+    let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(b"symbology").unwrap();
+    let compressed = encoder.finish().unwrap();
+    let mut decompressed = Vec::new();
+    symbology_reader(1, compressed.as_slice())
+        .unwrap()
+        .read_to_end(&mut decompressed)
+        .unwrap();
+    assert_eq!(decompressed, b"symbology");
+    assert!(symbology_reader(1, b"symbology".as_slice()).is_err());
+    assert!(symbology_reader(0, b"symbology".as_slice()).is_ok());
+}
+
+#[test]
+fn test_parse_dpr_all() {
+    let mut data = dpr_bytes(&[vec![0, 250, u16::MAX]]);
+    data.extend(b"\r\r\n\x03");
+    data.extend(digital_product_bytes(94, [0; 32], &[vec![1]]));
+    data.extend(dpr_bytes(&[vec![1000], vec![0]]));
+    let scans = parse_dpr_all(&data).unwrap();
+    assert_eq!(scans.len(), 2);
+    assert_eq!(scans[0].radials[0].precip_rates[..2], [0., 0.25]);
+    assert!(scans[0].radials[0].precip_rates[2].is_nan());
+    assert_eq!(scans[0].bin_size, 0.25);
+    assert_eq!(scans[0].scan_number, 7);
+    assert_eq!(scans[1].radials.len(), 2);
+
+    data.truncate(data.len() - 1);
+    assert!(parse_dpr_all(&data).is_err());
+}
+
+#[test]
+fn test_parse_dpr_from_reader() {
+    use std::io::Write;
+
+    let data = dpr_bytes(&[vec![0, 250], vec![500]]);
+    let scan = parse_dpr_from_reader(data.as_slice()).unwrap();
+    assert_eq!(scan.radials.len(), 2);
+    assert_eq!(scan.radials[1].precip_rates, [0.5]);
+    assert_eq!(
+        scan.capture_time,
+        parse_dpr(data.clone()).unwrap().capture_time
+    );
+
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(&data).unwrap();
+    let gzipped = encoder.finish().unwrap();
+    assert_eq!(
+        parse_dpr_from_reader(gzipped.as_slice())
+            .unwrap()
+            .radials
+            .len(),
+        2
+    );
+
+    assert!(parse_dpr_from_reader(&data[..100]).is_err());
+    assert!(parse_dpr_from_reader(&data[..data.len() - 1]).is_err());
+}
+
+#[test]
+fn test_parse_dpr_header() {
+    let mut data = dpr_bytes(&[vec![0, 250]]);
+    data[122..124].copy_from_slice(&1234i16.to_be_bytes());
+    let header = parse_dpr_header(&data[..HEADERS_SIZE]).unwrap();
+    assert_eq!(header.station_code, "KGYX");
+    assert_eq!(header.scan_number, 7);
+    assert_eq!(header.max_rate, 1.234);
+    assert_eq!(header.operational_mode, OperationalMode::Precipitation);
+    assert_eq!(header.capture_time, julian_time(19000, 3600).unwrap());
+    assert!(parse_dpr_header(&data[..HEADERS_SIZE - 1]).is_err());
+
+    let metadata = parse_dpr(data).unwrap().metadata.unwrap();
+    assert_eq!(metadata, header.metadata);
+    assert_eq!(metadata.product_code, 176);
+    assert_eq!(metadata.parameters[3], 1234);
+    assert_eq!(metadata.compression_method(), 0);
+    assert_eq!(metadata.volume_start, Some(header.capture_time));
+}
+
+#[test]
+fn test_parse_dpr_with_lenient() {
+    let mut data = dpr_bytes(&[vec![0, 250], vec![500]]);
+    // move the first radial just past north
+    let radial: Vec<u8> = [0.5f32, 0.5, 1.]
+        .iter()
+        .flat_map(|f| f.to_be_bytes())
+        .collect();
+    let offset = data.windows(12).position(|w| w == radial).unwrap();
+    data[offset..offset + 4].copy_from_slice(&360.25f32.to_be_bytes());
+    assert!(parse_dpr(data.clone()).is_err());
+
+    let options = ParseOptions {
+        lenient: true,
+        ..Default::default()
+    };
+    let (scan, report) = parse_dpr_with(data, &options).unwrap();
+    assert_eq!(scan.radials[0].azimuth, 0.25);
+    assert_eq!(report.warnings.len(), 1);
+    assert!(report.warnings[0].contains("360.25"));
+}
+
+#[test]
+fn test_parse_dpr_with_strict() {
+    let strict = ParseOptions {
+        strict: true,
+        ..Default::default()
+    };
+    let data = dpr_bytes(&[vec![0, 250], vec![500]]);
+    parse_dpr_with(data.clone(), &strict).unwrap();
+
+    let mut bad_length = data.clone();
+    bad_length.push(0);
+    assert!(parse_dpr(bad_length.clone()).is_ok());
+    let error = parse_dpr_with(bad_length, &strict).unwrap_err();
+    assert!(error.starts_with("Message length"), "{}", error);
+
+    let mut bad_divider = data;
+    bad_divider[HEADERS_SIZE] = 0;
+    assert!(parse_dpr(bad_divider.clone()).is_ok());
+    let error = parse_dpr_with(bad_divider, &strict).unwrap_err();
+    assert!(
+        error.contains(
+            "→ block divider at byte 0 of the symbology block: Symbology block divider is 255"
+        ),
+        "{}",
+        error
+    );
+}
+
+#[test]
+fn test_radial_range_error_location() {
+    let strict = ParseOptions {
+        strict: true,
+        ..Default::default()
+    };
+    let data = dpr_bytes(&[vec![0, 250], vec![500]]);
+    let radial: Vec<u8> = [0.5f32, 0.5, 1.]
+        .iter()
+        .flat_map(|f| f.to_be_bytes())
+        .collect();
+    let offset = data.windows(12).position(|w| w == radial).unwrap();
+    for (field, at, value, message) in [
+        (
+            "azimuth",
+            0,
+            360.25,
+            "Radial azimuth of 360.25 is out of range",
+        ),
+        ("width", 8, 0., "Radial width of 0 is out of range"),
+    ] {
+        let mut bad = data.clone();
+        bad[offset + at..offset + at + 4].copy_from_slice(&f32::to_be_bytes(value));
+        let error = parse_dpr_with(bad, &strict).unwrap_err();
+        assert_eq!(
+            error,
+            format!(
+                "product symbology → component 0 → radial 0 → {} at byte {} of the symbology block: {}",
+                field,
+                offset + at - HEADERS_SIZE,
+                message
+            )
+        );
+    }
+}
+
+#[test]
+fn test_parse_error_location() {
+    let data = dpr_bytes(&[vec![0, 250], vec![500]]);
+    // cut the file off partway through the bins of the second radial
+    let error = parse_dpr(data[..data.len() - 2].to_vec()).unwrap_err();
+    let offset = data.len() - 4 - HEADERS_SIZE;
+    assert!(
+        error.starts_with(&format!(
+            "product symbology → component 0 → radial 1 → bins at byte {} of the symbology block: ",
+            offset
+        )),
+        "{}",
+        error
+    );
+
+    let error = parse_dpr(data[..HEADERS_SIZE + 6].to_vec()).unwrap_err();
+    assert!(
+        error.starts_with("product symbology → block length at byte 4 "),
+        "{}",
+        error
+    );
+}
+
+#[test]
+fn test_parse_truncated() {
+    // cutting a file off anywhere is an error, not a panic
+    let data = dpr_bytes(&[vec![0, 250], vec![500]]);
+    for len in 0..data.len() {
+        let error = parse_dpr(data[..len].to_vec()).unwrap_err();
+        assert!(
+            error.contains("Unexpected end of file"),
+            "{}: {}",
+            len,
+            error
+        );
+        assert!(parse_dpr_header(&data[..len.min(HEADERS_SIZE - 1)]).is_err());
+        assert!(parse_dpr_from_reader(&data[..len]).is_err());
+    }
+    let data = digital_product_bytes(94, [0; 32], &[vec![1, 2, 3]]);
+    for len in 0..data.len() {
+        assert!(parse_digital_product(data[..len].to_vec(), &[94]).is_err());
+    }
+}
+
+#[test]
+fn test_multiple_components() {
+    let mut scan = parse_dpr(dpr_bytes(&[vec![0, 250], vec![500]])).unwrap();
+    assert!(scan.extra_components.is_empty());
+    scan.extra_components.push(RadialComponent {
+        description: String::from("Second"),
+        bin_size: 1.,
+        range_to_first_bin: 2.,
+        parameters: vec![(String::from("units"), String::from("in/hr"))],
+        radials: vec![scan.radials[1].clone()],
+    });
+    let scan = parse_dpr(crate::encode::write_dpr(&scan)).unwrap();
+    assert_eq!(scan.radials.len(), 2);
+    assert_eq!(scan.extra_components.len(), 1);
+    let component = &scan.extra_components[0];
+    assert_eq!(component.description, "Second");
+    assert_eq!(component.parameters[0].1, "in/hr");
+
+    let second = scan.into_component(1).unwrap();
+    assert_eq!((second.bin_size, second.range_to_first_bin), (1., 2.));
+    assert_eq!(second.radials.len(), 1);
+    assert_eq!(second.radials[0].precip_rates, [0.5]);
+    assert!(second.extra_components.is_empty());
+    assert!(second.into_component(1).is_none());
+
+    // other kinds of components can't be read
+    let mut data = dpr_bytes(&[vec![0]]);
+    // the component type follows the scan number, the rest of the product
+    // description, and the component count and pointer
+    let mut header = 7i32.to_be_bytes().to_vec();
+    header.extend([0; 28]);
+    header.extend(1i32.to_be_bytes());
+    let offset = data.windows(36).position(|w| w == header).unwrap() + 40;
+    data[offset..offset + 4].copy_from_slice(&2i32.to_be_bytes());
+    let error = parse_dpr(data).unwrap_err();
+    assert!(error.contains("Unsupported component type 2"), "{}", error);
+}
+
+#[test]
+fn test_tdwr_station_code() {
+    let mut data = dpr_bytes(&[vec![0, 250]]);
+    assert_eq!(
+        parse_dpr(data.clone()).unwrap().radar_type(),
+        RadarType::Wsr88d
+    );
+
+    // a TDWR product is sent by the office with the site in the AWIPS ID,
+    // and its bins are shorter
+    data[7..11].copy_from_slice(b"KBOX");
+    data[21..27].copy_from_slice(b"DPRBOS");
+    data[42..44].copy_from_slice(&3005i16.to_be_bytes());
+    let offset = data
+        .windows(4)
+        .position(|w| w == 250f32.to_be_bytes())
+        .unwrap();
+    data[offset..offset + 4].copy_from_slice(&150f32.to_be_bytes());
+    let scan = parse_dpr(data.clone()).unwrap();
+    assert_eq!(scan.station_code, "TBOS");
+    assert_eq!(scan.radar_type(), RadarType::Tdwr);
+    assert_eq!(scan.bin_size, 0.15);
+    assert_eq!(parse_dpr_header(&data).unwrap().station_code, "TBOS");
+
+    assert_eq!(RadarType::from_station_code("tjua"), RadarType::Wsr88d);
+    assert_eq!(RadarType::from_station_code("KGYX"), RadarType::Wsr88d);
+}
+
+#[test]
+fn test_alphanumeric_blocks() {
+    let mut data = dpr_bytes(&[vec![0, 250]]);
+    data.extend(crate::alphanumeric::alphanumeric_bytes());
+    set_message_length(&mut data);
+    let strict = ParseOptions {
+        strict: true,
+        ..Default::default()
+    };
+    let (scan, report) = parse_dpr_with(data.clone(), &strict).unwrap();
+    assert_eq!(report.trailing_bytes, 0);
+    assert_eq!(scan.alphanumeric.graphic[0][1], "MAX 1.25 IN/HR");
+    assert_eq!(scan.alphanumeric.tabular.len(), 2);
+    let streamed = parse_dpr_from_reader(data.as_slice()).unwrap();
+    assert_eq!(streamed.alphanumeric, scan.alphanumeric);
+    let written = parse_dpr(crate::encode::write_dpr(&scan)).unwrap();
+    assert_eq!(written.alphanumeric, scan.alphanumeric);
+
+    // anything else after the symbology block is left over
+    data.extend([0; 4]);
+    set_message_length(&mut data);
+    let (scan, report) = parse_dpr_with_report(data.clone()).unwrap();
+    assert_eq!(report.trailing_bytes, 4);
+    assert_eq!(scan.alphanumeric.tabular.len(), 2);
+    assert!(parse_dpr_with(data, &strict).is_err());
+}
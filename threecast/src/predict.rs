@@ -1,12 +1,14 @@
-use crate::parse::GridData;
+use std::error::Error;
+
+use crate::parse::{GridData, PrecipRate};
+use crate::util::find_pixel_by_lat_long;
 
 #[allow(clippy::ptr_arg)]
-fn shift(input: &GridData, dx: i32, dy: i32) -> GridData {
+pub(crate) fn shift(input: &GridData, dx: i32, dy: i32) -> GridData {
     let y_n = input.len() as i32;
     let x_n = input[0].len() as i32;
     let mut shifted: GridData = input.clone();
     for (y_t1, y_t2) in (0.max(-dy)..y_n.min(y_n - dy)).zip(0.max(dy)..y_n.min(y_n + dy)) {
-        shifted.push(Vec::new());
         for (x_t1, x_t2) in (0.max(-dx)..x_n.min(x_n - dx)).zip(0.max(dx)..x_n.min(x_n + dx)) {
             shifted[y_t2 as usize][x_t2 as usize].1 = input[y_t1 as usize][x_t1 as usize].1;
         }
@@ -32,7 +34,7 @@ fn compute_mse_for_offset(t1: &GridData, t2: &GridData, dx: i32, dy: i32) -> f32
 }
 
 #[allow(clippy::ptr_arg)]
-fn find_best_offset(t1: &GridData, t2: &GridData) -> (i32, i32) {
+pub(crate) fn find_best_offset(t1: &GridData, t2: &GridData) -> (i32, i32) {
     let n = t1.len() as i32;
     // TODO: compute r in terms of physical pixel size and maximum reasonable storm speed
     let r = match n {
@@ -77,73 +79,148 @@ pub fn predict_two(input: [&GridData; 2], delta_t_image: u16, delta_t_now: u16)
     );
     [
         shift(
-            input[0],
+            input[1],
             (offset_per_second.1 * delta_t_now as f32) as i32,
             (offset_per_second.0 * delta_t_now as f32) as i32,
         ),
         shift(
-            input[0],
+            input[1],
             (offset_per_second.1 * (delta_t_now + 5 * 60) as f32) as i32,
             (offset_per_second.0 * (delta_t_now + 5 * 60) as f32) as i32,
         ),
         shift(
-            input[0],
+            input[1],
             (offset_per_second.1 * (delta_t_now + 10 * 60) as f32) as i32,
             (offset_per_second.0 * (delta_t_now + 10 * 60) as f32) as i32,
         ),
         shift(
-            input[0],
+            input[1],
             (offset_per_second.1 * (delta_t_now + 15 * 60) as f32) as i32,
             (offset_per_second.0 * (delta_t_now + 15 * 60) as f32) as i32,
         ),
         shift(
-            input[0],
+            input[1],
             (offset_per_second.1 * (delta_t_now + 20 * 60) as f32) as i32,
             (offset_per_second.0 * (delta_t_now + 20 * 60) as f32) as i32,
         ),
         shift(
-            input[0],
+            input[1],
             (offset_per_second.1 * (delta_t_now + 25 * 60) as f32) as i32,
             (offset_per_second.0 * (delta_t_now + 25 * 60) as f32) as i32,
         ),
         shift(
-            input[0],
+            input[1],
             (offset_per_second.1 * (delta_t_now + 30 * 60) as f32) as i32,
             (offset_per_second.0 * (delta_t_now + 30 * 60) as f32) as i32,
         ),
         shift(
-            input[0],
+            input[1],
             (offset_per_second.1 * (delta_t_now + 35 * 60) as f32) as i32,
             (offset_per_second.0 * (delta_t_now + 35 * 60) as f32) as i32,
         ),
         shift(
-            input[0],
+            input[1],
             (offset_per_second.1 * (delta_t_now + 40 * 60) as f32) as i32,
             (offset_per_second.0 * (delta_t_now + 40 * 60) as f32) as i32,
         ),
         shift(
-            input[0],
+            input[1],
             (offset_per_second.1 * (delta_t_now + 45 * 60) as f32) as i32,
             (offset_per_second.0 * (delta_t_now + 45 * 60) as f32) as i32,
         ),
         shift(
-            input[0],
+            input[1],
             (offset_per_second.1 * (delta_t_now + 50 * 60) as f32) as i32,
             (offset_per_second.0 * (delta_t_now + 50 * 60) as f32) as i32,
         ),
         shift(
-            input[0],
+            input[1],
             (offset_per_second.1 * (delta_t_now + 55 * 60) as f32) as i32,
             (offset_per_second.0 * (delta_t_now + 55 * 60) as f32) as i32,
         ),
         shift(
-            input[0],
+            input[1],
             (offset_per_second.1 * (delta_t_now + 60 * 60) as f32) as i32,
             (offset_per_second.0 * (delta_t_now + 60 * 60) as f32) as i32,
         ),
     ]
 }
 
+/// Width and height in pixels of the grids used by [`nowcast`]
+pub const GRID_SIZE: usize = 256;
+
+/// Sample two consecutive scans onto grids and run [`predict_two`] on them,
+/// with the prediction starting at the current time.
+pub fn nowcast(older: &PrecipRate, newer: &PrecipRate) -> [GridData; 13] {
+    let precip_older = older.sample_radials_to_equirectangular(GRID_SIZE, GRID_SIZE);
+    let precip_newer = newer.sample_radials_to_equirectangular(GRID_SIZE, GRID_SIZE);
+    let delta_t_image = (newer.capture_time - older.capture_time).num_seconds() as u16;
+    let delta_t_now = (chrono::Utc::now().timestamp() - newer.capture_time.timestamp()) as u16;
+    predict_two([&precip_older, &precip_newer], delta_t_image, delta_t_now)
+}
+
+/// For each grid in `predictions`, estimate the probability that the rate
+/// exceeds `threshold` at each cell using the neighborhood method. The
+/// probability at a cell is the fraction of cells within `radius` pixels
+/// (in a square window, clipped at the edges of the grid) whose rate exceeds
+/// the threshold. This accounts for the positional uncertainty of the
+/// prediction, which grows with lead time.
+pub fn probability_of_exceedance(
+    predictions: &[GridData],
+    threshold: f32,
+    radius: usize,
+) -> Vec<GridData> {
+    predictions
+        .iter()
+        .map(|grid| {
+            let y_n = grid.len();
+            let x_n = grid.first().map_or(0, |row| row.len());
+            // summed-area table of exceedances, with an extra leading row and column of zeros
+            let mut sums = vec![vec![0u32; x_n + 1]; y_n + 1];
+            for y in 0..y_n {
+                for x in 0..x_n {
+                    sums[y + 1][x + 1] =
+                        (grid[y][x].1 > threshold) as u32 + sums[y][x + 1] + sums[y + 1][x]
+                            - sums[y][x];
+                }
+            }
+            (0..y_n)
+                .map(|y| {
+                    let (y_0, y_1) = (y.saturating_sub(radius), (y + radius + 1).min(y_n));
+                    (0..x_n)
+                        .map(|x| {
+                            let (x_0, x_1) = (x.saturating_sub(radius), (x + radius + 1).min(x_n));
+                            let count =
+                                sums[y_1][x_1] + sums[y_0][x_0] - sums[y_0][x_1] - sums[y_1][x_0];
+                            let area = (y_1 - y_0) * (x_1 - x_0);
+                            (grid[y][x].0, count as f32 / area as f32)
+                        })
+                        .collect()
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Given the output of [`predict_two`], estimate the number of minutes until
+/// the rate at the given location first exceeds `threshold`. Returns `None` if
+/// the threshold isn't exceeded within the prediction horizon.
+pub fn time_to_rain(
+    predictions: &[GridData],
+    latitude: f32,
+    longitude: f32,
+    threshold: f32,
+) -> Result<Option<u32>, Box<dyn Error>> {
+    let (y, x) = match predictions.first() {
+        Some(grid) => find_pixel_by_lat_long(grid, latitude, longitude)?,
+        None => return Ok(None),
+    };
+    Ok(predictions
+        .iter()
+        .position(|grid| grid[y][x].1 > threshold)
+        .map(|idx| idx as u32 * 5))
+}
+
 #[test]
 fn find_best_offset_simple() {
     let t1: GridData = vec![
@@ -160,3 +237,89 @@ fn find_best_offset_simple() {
     ];
     assert_eq!(find_best_offset(&t1, &t2), (1, 1));
 }
+
+#[test]
+fn shift_keeps_dimensions() {
+    let grid: GridData = vec![
+        vec![([0, 0], 1.), ([0, 0], 2.)],
+        vec![([0, 0], 3.), ([0, 0], 4.)],
+    ];
+    let shifted = shift(&grid, 1, 1);
+    assert_eq!(shifted.len(), 2);
+    assert!(shifted.iter().all(|row| row.len() == 2));
+    assert_eq!(shifted[1][1].1, 1.);
+}
+
+#[test]
+fn probability_of_exceedance_simple() {
+    let grid: GridData = vec![
+        vec![([0, 0], 1.), ([0, 0], 0.), ([0, 0], 0.)],
+        vec![([0, 0], 0.), ([0, 0], 0.), ([0, 0], 0.)],
+        vec![([0, 0], 0.), ([0, 0], 0.), ([0, 0], 1.)],
+    ];
+    let probabilities = probability_of_exceedance(&[grid], 0.5, 1);
+    assert_eq!(probabilities[0][0][0].1, 0.25);
+    assert_eq!(probabilities[0][1][1].1, 2. / 9.);
+    assert_eq!(probabilities[0][0][2].1, 0.);
+}
+
+#[test]
+fn nowcast_with_missing_bins() {
+    use crate::synthetic::SyntheticDpr;
+    let now = chrono::Utc::now().naive_utc();
+    // a cell moving east, with a sector of missing data next to it
+    let scan = |minutes_ago: i64, azimuth: f32| {
+        SyntheticDpr::new("KGYX")
+            .with_time(now - chrono::Duration::minutes(minutes_ago), 1)
+            .with_geometry(360, 230, 1.)
+            .with_gaussian_cell(azimuth, 100., 1., 20.)
+            .with_missing_sector(120., 150.)
+            .build()
+    };
+    let (older, newer) = (scan(10, 80.), scan(5, 90.));
+    assert!(newer.radials[135].precip_rates[100].is_nan());
+    let older_grid = older.sample_radials_to_equirectangular(GRID_SIZE, GRID_SIZE);
+    let newer_grid = newer.sample_radials_to_equirectangular(GRID_SIZE, GRID_SIZE);
+    assert!(newer_grid.iter().flatten().all(|(_, rate)| !rate.is_nan()));
+    assert_ne!(find_best_offset(&older_grid, &newer_grid), (0, 0));
+    let predictions = nowcast(&older, &newer);
+    assert!(predictions
+        .iter()
+        .flatten()
+        .flatten()
+        .all(|(_, rate)| !rate.is_nan()));
+}
+
+#[test]
+fn time_to_rain_moving_cell() {
+    use crate::geomath::get_point_bearing_distance;
+    use crate::synthetic::SyntheticDpr;
+    let now = chrono::Utc::now().naive_utc();
+    // a cell 80 km west of the radar, coming east at 4 km/min
+    let scan = |minutes_ago: i64, range: f32| {
+        SyntheticDpr::new("KGYX")
+            .with_time(now - chrono::Duration::minutes(minutes_ago), 1)
+            .with_geometry(360, 230, 1.)
+            .with_gaussian_cell(270., range, 1., 10.)
+            .build()
+    };
+    let (older, newer) = (scan(5, 100.), scan(0, 80.));
+    let older_grid = older.sample_radials_to_equirectangular(GRID_SIZE, GRID_SIZE);
+    let newer_grid = newer.sample_radials_to_equirectangular(GRID_SIZE, GRID_SIZE);
+    let predictions = predict_two([&older_grid, &newer_grid], 300, 0);
+    let minutes = |bearing: f32, distance: f32, threshold: f32| {
+        let (lat, lon) =
+            get_point_bearing_distance((newer.latitude, newer.longitude), bearing, distance);
+        time_to_rain(&predictions, lat, lon, threshold).unwrap()
+    };
+    // already raining under the newest position of the cell
+    assert_eq!(minutes(270., 80., 0.5), Some(0));
+    // rates above 0.5 in/hr reach 11.8 km from the center, so the edge gets
+    // to the radar after 17 minutes and to 40 km east of it after 27
+    assert_eq!(minutes(0., 0., 0.5), Some(20));
+    assert_eq!(minutes(90., 40., 0.5), Some(30));
+    // off the track, or above the peak of the cell, it never rains
+    assert_eq!(minutes(0., 100., 0.5), None);
+    assert_eq!(minutes(270., 80., 2.), None);
+    assert_eq!(time_to_rain(&[], 43.9, -70.3, 0.5).unwrap(), None);
+}
@@ -0,0 +1,217 @@
+//! Burning bins into regular latitude/longitude rasters, which are much
+//! smaller and easier to tile than one polygon per bin
+
+use crate::filter::BoundingBox;
+use crate::geomath::{get_bearing_between_points, get_distance_between_points};
+use crate::parse::{coord_as_i64, GridData, PrecipRate};
+
+/// Azimuth lookup steps per degree in [`PrecipRate::rasterize`]
+const AZIMUTH_STEPS: usize = 10;
+
+/// A single-band raster in WGS84 with square pixels, stored row by row from
+/// the north edge. Values are rates in in/hr, or `NaN` where there's no
+/// data.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Raster {
+    /// Longitude of the west edge of the raster in degrees
+    pub west: f64,
+    /// Latitude of the north edge of the raster in degrees
+    pub north: f64,
+    /// Width and height of a pixel in degrees
+    pub resolution: f64,
+    pub width: usize,
+    pub height: usize,
+    pub data: Vec<f32>,
+}
+
+impl Raster {
+    /// The value of the pixel in column `x` and row `y`
+    pub fn get(&self, x: usize, y: usize) -> f32 {
+        self.data[y * self.width + x]
+    }
+
+    /// The (latitude, longitude) of the center of a pixel in degrees
+    pub fn pixel_center(&self, x: usize, y: usize) -> (f64, f64) {
+        (
+            self.north - (y as f64 + 0.5) * self.resolution,
+            self.west + (x as f64 + 0.5) * self.resolution,
+        )
+    }
+
+    /// The pixel centers and values as a grid, for everything that works on
+    /// sampled grids
+    pub fn to_grid(&self) -> GridData {
+        (0..self.height)
+            .map(|y| {
+                (0..self.width)
+                    .map(|x| {
+                        let (latitude, longitude) = self.pixel_center(x, y);
+                        (
+                            [
+                                coord_as_i64(latitude as f32),
+                                coord_as_i64(longitude as f32),
+                            ],
+                            self.get(x, y),
+                        )
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// A copy at half the resolution, where each pixel is the mean of the
+    /// pixels with data in the 2x2 block it covers. Odd widths and heights
+    /// round up.
+    pub fn halved(&self) -> Raster {
+        let (width, height) = (self.width.div_ceil(2), self.height.div_ceil(2));
+        let mut data = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                let (mut sum, mut count) = (0., 0);
+                for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                    let (sx, sy) = (x * 2 + dx, y * 2 + dy);
+                    if sx < self.width && sy < self.height && !self.get(sx, sy).is_nan() {
+                        sum += self.get(sx, sy);
+                        count += 1;
+                    }
+                }
+                data.push(match count {
+                    0 => f32::NAN,
+                    _ => sum / count as f32,
+                });
+            }
+        }
+        Raster {
+            west: self.west,
+            north: self.north,
+            resolution: self.resolution * 2.,
+            width,
+            height,
+            data,
+        }
+    }
+}
+
+impl PrecipRate {
+    /// The box around everything the scan covers, out to its farthest bin
+    pub fn coverage_extent(&self) -> BoundingBox {
+        let [south, west, north, east] = self.summary().bbox;
+        BoundingBox {
+            south,
+            west,
+            north,
+            east,
+        }
+    }
+
+    /// Burn the scan into a raster covering `extent` with square pixels
+    /// `resolution` degrees on a side. Each pixel takes the rate of the bin
+    /// that contains its center, or `NaN` if no bin does.
+    ///
+    /// # Panics
+    ///
+    /// If `resolution` isn't positive.
+    pub fn rasterize(&self, resolution: f32, extent: &BoundingBox) -> Raster {
+        assert!(resolution > 0., "Raster resolution must be positive");
+        let resolution = resolution as f64;
+        // round up to whole pixels, but not for the rounding error in the
+        // extent
+        let pixels = |span: f32| ((span as f64 / resolution - 0.01).ceil() as usize).max(1);
+        let (width, height) = (
+            pixels(extent.east - extent.west),
+            pixels(extent.north - extent.south),
+        );
+        let mut raster = Raster {
+            west: extent.west as f64,
+            north: extent.north as f64,
+            resolution,
+            width,
+            height,
+            data: Vec::with_capacity(width * height),
+        };
+
+        // which radial covers each small step of azimuth, so that every
+        // pixel doesn't have to search all of them
+        let radials: Vec<Option<usize>> = (0..360 * AZIMUTH_STEPS)
+            .map(|step| {
+                let azimuth = (step as f32 + 0.5) / AZIMUTH_STEPS as f32;
+                self.radials
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, radial)| {
+                        let offset = (azimuth - radial.azimuth).rem_euclid(360.);
+                        (idx, offset.min(360. - offset), radial.width / 2.)
+                    })
+                    .filter(|(_, offset, half_width)| offset <= half_width)
+                    .min_by(|a, b| a.1.total_cmp(&b.1))
+                    .map(|(idx, _, _)| idx)
+            })
+            .collect();
+
+        let station = (self.latitude, self.longitude);
+        for y in 0..height {
+            for x in 0..width {
+                let (latitude, longitude) = raster.pixel_center(x, y);
+                let point = (latitude as f32, longitude as f32);
+                let bearing = get_bearing_between_points(station, point);
+                let step = (bearing * AZIMUTH_STEPS as f32) as usize % radials.len();
+                let range = get_distance_between_points(station, point) - self.range_to_first_bin;
+                let value = match radials[step] {
+                    Some(radial) if range >= 0. => self.radials[radial]
+                        .precip_rates
+                        .get((range / self.bin_size) as usize)
+                        .copied()
+                        .unwrap_or(f32::NAN),
+                    _ => f32::NAN,
+                };
+                raster.data.push(value);
+            }
+        }
+        raster
+    }
+}
+
+#[test]
+fn test_rasterize() {
+    let mut scan = crate::coverage::scan_with_azimuths(&[0., 90., 180.]);
+    scan.radials[0].precip_rates = vec![0.5, 0.25];
+    scan.radials[1].precip_rates = vec![0.1];
+    scan.radials[2].precip_rates = vec![f32::NAN];
+    // put a pixel center right on the station
+    let half = 0.01025;
+    let extent = BoundingBox {
+        south: scan.latitude - half,
+        west: scan.longitude - half,
+        north: scan.latitude + half,
+        east: scan.longitude + half,
+    };
+    let raster = scan.rasterize(0.0005, &extent);
+    assert_eq!((raster.width, raster.height), (41, 41));
+    // pixels are about 56 m tall and 40 m wide here, and bins are 250 m long
+    let pixel = |north: i32, east: i32| raster.get((20 + east) as usize, (20 - north) as usize);
+    assert_eq!(pixel(2, 0), 0.5);
+    assert_eq!(pixel(6, 0), 0.25);
+    assert!(pixel(12, 0).is_nan());
+    assert_eq!(pixel(0, 3), 0.1);
+    assert!(pixel(0, 9).is_nan());
+    assert!(pixel(-2, 0).is_nan());
+    assert!(pixel(0, -3).is_nan());
+    assert!(pixel(5, 5).is_nan());
+
+    let halved = raster.halved();
+    assert_eq!((halved.width, halved.height), (21, 21));
+    assert_eq!(halved.resolution, raster.resolution * 2.);
+    // the block with the station has 0.5 north of it and 0.1 east of it
+    let block = [
+        raster.get(20, 20),
+        raster.get(21, 20),
+        raster.get(20, 21),
+        raster.get(21, 21),
+    ];
+    let with_data: Vec<f32> = block.into_iter().filter(|v| !v.is_nan()).collect();
+    assert_eq!(
+        halved.get(10, 10),
+        with_data.iter().sum::<f32>() / with_data.len() as f32
+    );
+    assert!(halved.get(0, 0).is_nan());
+}
@@ -0,0 +1,96 @@
+//! Digital base reflectivity, from the WSR-88D (product 94) and TDWR
+//! (product 186) radars, and digital hybrid scan reflectivity (DHR, product
+//! 32), which is the reflectivity that the precipitation algorithms start
+//! from
+//!
+//! Like the accumulation products, reflectivity is parsed into a
+//! [`PrecipRate`] whose rates hold the values, here in dBZ, so it can go
+//! through the same outputs as the rate product for a side-by-side look.
+
+use crate::parse::{parse_digital_product, PrecipRate};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReflectivityProduct {
+    /// Base reflectivity from a WSR-88D
+    Base,
+    /// Long range base reflectivity from a TDWR
+    TdwrBase,
+    /// Reflectivity from the lowest elevation at each bin that isn't
+    /// blocked, the input to the rate product
+    HybridScan,
+}
+
+impl ReflectivityProduct {
+    pub fn code(&self) -> i16 {
+        match self {
+            ReflectivityProduct::Base => 94,
+            ReflectivityProduct::TdwrBase => 186,
+            ReflectivityProduct::HybridScan => 32,
+        }
+    }
+
+    fn from_code(code: i16) -> Option<ReflectivityProduct> {
+        match code {
+            94 => Some(ReflectivityProduct::Base),
+            186 => Some(ReflectivityProduct::TdwrBase),
+            32 => Some(ReflectivityProduct::HybridScan),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Reflectivity {
+    pub product: ReflectivityProduct,
+    /// Reflectivity in dBZ, stored as the rates of each radial. Bins below
+    /// the threshold or with range-folded data are `NaN`.
+    pub scan: PrecipRate,
+}
+
+/// Parse a digital base or hybrid scan reflectivity product. Data levels are converted
+/// with the minimum and increment in the product description, where levels
+/// 0 and 1 mean below threshold and range folded.
+pub fn parse_reflectivity(input: Vec<u8>) -> Result<Reflectivity, String> {
+    let product = parse_digital_product(input, &[94, 186, 32])?;
+    let description = &product.description;
+    let kind = ReflectivityProduct::from_code(description.product_code).unwrap();
+    let minimum = description.threshold_i16(31) as f32 / 10.;
+    let increment = description.threshold_i16(32) as f32 / 10.;
+    let levels = description.threshold_i16(33);
+    // the hybrid scan mixes elevations, so it doesn't have one of its own
+    let elevation = match kind {
+        ReflectivityProduct::HybridScan => 0.,
+        _ => description.elevation_angle(),
+    };
+    let scan = product.into_scan(elevation, |level| match level as i16 {
+        0 | 1 => f32::NAN,
+        l if l >= levels => f32::NAN,
+        l => minimum + (l - 2) as f32 * increment,
+    });
+    Ok(Reflectivity {
+        product: kind,
+        scan,
+    })
+}
+
+#[test]
+fn test_parse_reflectivity() {
+    let mut thresholds = [0; 32];
+    thresholds[0..2].copy_from_slice(&(-320i16).to_be_bytes());
+    thresholds[2..4].copy_from_slice(&5i16.to_be_bytes());
+    thresholds[4..6].copy_from_slice(&256i16.to_be_bytes());
+    let data = crate::parse::digital_product_bytes(94, thresholds, &[vec![0, 1, 2, 132]]);
+    let reflectivity = parse_reflectivity(data).unwrap();
+    assert_eq!(reflectivity.product, ReflectivityProduct::Base);
+    let values = &reflectivity.scan.radials[0].precip_rates;
+    assert!(values[0].is_nan() && values[1].is_nan());
+    assert_eq!(values[2..], [-32., 33.]);
+
+    let data = crate::parse::digital_product_bytes(32, thresholds, &[vec![42]]);
+    let reflectivity = parse_reflectivity(data).unwrap();
+    assert_eq!(reflectivity.product, ReflectivityProduct::HybridScan);
+    assert_eq!(reflectivity.scan.radials[0].precip_rates, [-12.]);
+
+    let data = crate::parse::digital_product_bytes(172, thresholds, &[vec![0]]);
+    assert!(parse_reflectivity(data).is_err());
+}
@@ -0,0 +1,159 @@
+//! Drawing rasters as PNG images for previews and web map overlays
+
+use std::error::Error;
+
+use crate::raster::Raster;
+use crate::util::precip_category;
+
+/// Colors of the familiar NWS reflectivity scale, at rates in in/hr that
+/// roughly match its 5 dBZ steps from 20 dBZ up
+const NWS_STEPS: &[(f32, [u8; 3])] = &[
+    (0.01, [4, 233, 231]),
+    (0.02, [1, 159, 244]),
+    (0.04, [3, 0, 244]),
+    (0.07, [2, 253, 2]),
+    (0.12, [1, 197, 1]),
+    (0.2, [0, 142, 0]),
+    (0.35, [253, 248, 2]),
+    (0.6, [229, 188, 0]),
+    (1., [253, 149, 0]),
+    (1.7, [253, 0, 0]),
+    (2.8, [212, 0, 0]),
+    (4.7, [188, 0, 0]),
+    (8., [248, 0, 253]),
+    (13., [152, 84, 198]),
+];
+
+/// Colors by [`precip_category`], matching the KML styles
+const CATEGORY_COLORS: &[(&str, [u8; 3])] = &[
+    ("light", [4, 233, 231]),
+    ("moderate", [1, 197, 1]),
+    ("heavy", [253, 248, 2]),
+    ("violent", [253, 0, 0]),
+];
+
+/// How to color rates. Pixels without precipitation or data are always
+/// transparent.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Colormap {
+    /// One color per [`precip_category`]
+    Categories,
+    /// The NWS reflectivity colors
+    Nws,
+    /// Each color starts at its rate in in/hr and goes up to the next one.
+    /// Rates below the first step are transparent.
+    Steps(Vec<(f32, [u8; 3])>),
+}
+
+impl Colormap {
+    /// Parse `"categories"`, `"nws"`, or a list of steps like
+    /// `"0.01:04e9e7,0.1:01c501,0.5:fdf802"`
+    pub fn parse(spec: &str) -> Result<Colormap, String> {
+        match spec {
+            "categories" => return Ok(Colormap::Categories),
+            "nws" => return Ok(Colormap::Nws),
+            _ => (),
+        }
+        let mut steps = Vec::new();
+        for step in spec.split(',') {
+            let (rate, color) = match step.trim().split_once(':') {
+                Some(pair) => pair,
+                None => return Err(format!("Colormap step {} isn't RATE:RRGGBB", step)),
+            };
+            let rate = match rate.parse::<f32>() {
+                Ok(r) if r.is_finite() => r,
+                _ => return Err(format!("Bad rate in colormap step {}", step)),
+            };
+            let color = match u32::from_str_radix(color.trim_start_matches('#'), 16) {
+                Ok(c) if color.trim_start_matches('#').len() == 6 => {
+                    let [_, r, g, b] = c.to_be_bytes();
+                    [r, g, b]
+                }
+                _ => return Err(format!("Bad color in colormap step {}", step)),
+            };
+            steps.push((rate, color));
+        }
+        steps.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Ok(Colormap::Steps(steps))
+    }
+
+    /// The color of `rate`, or `None` if it should be transparent
+    pub fn color(&self, rate: f32) -> Option<[u8; 3]> {
+        if rate.is_nan() || rate <= 0. {
+            return None;
+        }
+        let steps = match self {
+            Colormap::Categories => {
+                let category = precip_category(rate);
+                return CATEGORY_COLORS
+                    .iter()
+                    .find(|(c, _)| *c == category)
+                    .map(|(_, color)| *color);
+            }
+            Colormap::Nws => NWS_STEPS,
+            Colormap::Steps(steps) => steps.as_slice(),
+        };
+        steps
+            .iter()
+            .rev()
+            .find(|(start, _)| rate >= *start)
+            .map(|(_, color)| *color)
+    }
+}
+
+/// Draw `raster` as a PNG with one image pixel per raster pixel
+pub fn render_png(raster: &Raster, colormap: &Colormap) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut image = image::RgbaImage::new(raster.width as u32, raster.height as u32);
+    for y in 0..raster.height {
+        for x in 0..raster.width {
+            if let Some([r, g, b]) = colormap.color(raster.get(x, y)) {
+                image.put_pixel(x as u32, y as u32, image::Rgba([r, g, b, 255]));
+            }
+        }
+    }
+    let mut png = Vec::new();
+    image::DynamicImage::ImageRgba8(image).write_to(&mut png, image::ImageOutputFormat::Png)?;
+    Ok(png)
+}
+
+/// An [Esri world file](https://en.wikipedia.org/wiki/World_file) that
+/// places an image of `raster` on the map in WGS84, usually saved next to a
+/// PNG with a `.pgw` extension
+pub fn world_file(raster: &Raster) -> String {
+    let (latitude, longitude) = raster.pixel_center(0, 0);
+    format!(
+        "{}\n0\n0\n{}\n{}\n{}\n",
+        raster.resolution, -raster.resolution, longitude, latitude
+    )
+}
+
+#[test]
+fn test_render() {
+    assert_eq!(Colormap::Nws.color(0.), None);
+    assert_eq!(Colormap::Nws.color(f32::NAN), None);
+    assert_eq!(Colormap::Nws.color(0.005), None);
+    assert_eq!(Colormap::Nws.color(0.5), Some([253, 248, 2]));
+    assert_eq!(Colormap::Nws.color(100.), Some([152, 84, 198]));
+    assert_eq!(Colormap::Categories.color(0.2), Some([1, 197, 1]));
+    let custom = Colormap::parse("0.5:#ff0000, 0.1:00ff00").unwrap();
+    assert_eq!(custom.color(0.05), None);
+    assert_eq!(custom.color(0.2), Some([0, 255, 0]));
+    assert_eq!(custom.color(0.5), Some([255, 0, 0]));
+    assert!(Colormap::parse("0.1:green").is_err());
+    assert!(Colormap::parse("viridis").is_err());
+
+    let raster = Raster {
+        west: -71.,
+        north: 44.,
+        resolution: 0.5,
+        width: 2,
+        height: 1,
+        data: vec![0., 0.5],
+    };
+    let png = render_png(&raster, &Colormap::Nws).unwrap();
+    let image = image::load_from_memory(&png).unwrap().to_rgba8();
+    assert_eq!(image.dimensions(), (2, 1));
+    assert_eq!(image.get_pixel(0, 0)[3], 0);
+    assert_eq!(image.get_pixel(1, 0).0, [253, 248, 2, 255]);
+    assert_eq!(world_file(&raster), "0.5\n0\n0\n-0.5\n-70.75\n43.75\n");
+}
@@ -0,0 +1,114 @@
+use chrono::{Duration, NaiveDateTime};
+
+use crate::parse::OperationalMode;
+
+/// Time to wait after a scan is expected before fetching it, since the NWS
+/// server takes a little while to publish new data files
+const PUBLICATION_DELAY_SEC: i64 = 60;
+/// Time to wait before checking again when a scan is overdue
+const RETRY_DELAY_SEC: i64 = 45;
+const MIN_INTERVAL_SEC: i64 = 120;
+const MAX_INTERVAL_SEC: i64 = 900;
+
+/// Tracks the scan cadence of a single station so that new data files can be
+/// fetched shortly after they're published, instead of polling on a fixed
+/// interval.
+///
+/// Stations in precipitation mode complete a volume scan roughly every four to
+/// six minutes, while stations in clean air mode take about ten. The estimate
+/// starts from those typical values and then follows the observed time
+/// between scans.
+pub struct ScanCadence {
+    last_capture_time: Option<NaiveDateTime>,
+    interval: Option<Duration>,
+}
+
+impl Default for ScanCadence {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ScanCadence {
+    pub fn new() -> Self {
+        ScanCadence {
+            last_capture_time: None,
+            interval: None,
+        }
+    }
+
+    /// The usual time between scans in `mode`
+    pub fn typical_interval(mode: &OperationalMode) -> Duration {
+        match mode {
+            OperationalMode::Precipitation => Duration::seconds(300),
+            OperationalMode::CleanAir | OperationalMode::Maintenance => Duration::seconds(600),
+        }
+    }
+
+    /// The current estimate of the time between scans
+    pub fn interval(&self) -> Duration {
+        self.interval.unwrap_or_else(|| Duration::seconds(300))
+    }
+
+    /// Record the capture time of the latest scan for this station. Calling
+    /// this again with the same scan has no effect.
+    pub fn observe(&mut self, capture_time: NaiveDateTime, mode: &OperationalMode) {
+        let typical = Self::typical_interval(mode);
+        match self.last_capture_time {
+            Some(last) if capture_time > last => {
+                let observed = (capture_time - last).num_seconds();
+                let previous = self.interval.unwrap_or(typical).num_seconds();
+                // a long gap probably means we missed scans or the station
+                // switched modes, so lean on the typical value instead
+                let observed = if observed > MAX_INTERVAL_SEC {
+                    typical.num_seconds()
+                } else {
+                    observed.max(MIN_INTERVAL_SEC)
+                };
+                self.interval = Some(Duration::seconds((previous + observed) / 2));
+            }
+            Some(_) => return,
+            None => self.interval = Some(typical),
+        }
+        self.last_capture_time = Some(capture_time);
+    }
+
+    /// How long to wait from `now` before fetching the next scan
+    pub fn next_fetch_delay(&self, now: NaiveDateTime) -> std::time::Duration {
+        let delay = match self.last_capture_time {
+            None => Duration::seconds(0),
+            Some(last) => {
+                let expected = last + self.interval() + Duration::seconds(PUBLICATION_DELAY_SEC);
+                (expected - now).max(Duration::seconds(RETRY_DELAY_SEC))
+            }
+        };
+        delay.to_std().unwrap_or_default()
+    }
+}
+
+#[test]
+fn test_scan_cadence() {
+    let start = NaiveDateTime::from_timestamp(1_600_000_000, 0);
+    let mut cadence = ScanCadence::new();
+    assert_eq!(cadence.next_fetch_delay(start).as_secs(), 0);
+    cadence.observe(start, &OperationalMode::Precipitation);
+    assert_eq!(cadence.interval().num_seconds(), 300);
+    assert_eq!(cadence.next_fetch_delay(start).as_secs(), 360);
+    cadence.observe(
+        start + Duration::seconds(240),
+        &OperationalMode::Precipitation,
+    );
+    assert_eq!(cadence.interval().num_seconds(), 270);
+    // the same scan again doesn't change anything
+    cadence.observe(
+        start + Duration::seconds(240),
+        &OperationalMode::Precipitation,
+    );
+    assert_eq!(cadence.interval().num_seconds(), 270);
+    // overdue scans are retried soon
+    let late = start + Duration::seconds(3600);
+    assert_eq!(
+        cadence.next_fetch_delay(late).as_secs(),
+        RETRY_DELAY_SEC as u64
+    );
+}
@@ -0,0 +1,440 @@
+//! [Esri Shapefile](https://www.esri.com/content/dam/esrisites/sitecore-archive/Files/Pdfs/library/whitepapers/pdfs/shapefile.pdf)
+//! output for desktop GIS software
+//!
+//! A shapefile is several files that share a name: the geometry in `.shp`,
+//! an index of it in `.shx`, the attributes in a dBASE `.dbf` table, and
+//! the coordinate reference system as WKT in `.prj`. They're all built in
+//! memory since the headers need totals that aren't known until the end.
+
+use std::error::Error;
+use std::io::{Seek, Write};
+use std::path::Path;
+
+use crate::filter::BinFilter;
+use crate::output::{BinRef, ConvertOptions, OutputSink};
+use crate::parse::PrecipRate;
+use crate::util::precip_category;
+
+/// WGS84 in the WKT dialect that Esri software writes to `.prj` files,
+/// which is also the one that most other tools expect to find there
+pub const WGS84_PRJ: &str = "GEOGCS[\"GCS_WGS_1984\",DATUM[\"D_WGS_1984\",\
+    SPHEROID[\"WGS_1984\",6378137.0,298.257223563]],PRIMEM[\"Greenwich\",0.0],\
+    UNIT[\"Degree\",0.0174532925199433]]";
+
+const POLYGON: i32 = 5;
+
+/// Size of the `.shp` and `.shx` headers in bytes
+const HEADER_LEN: usize = 100;
+
+/// An attribute column in the `.dbf` table. `PrecipRate` is always the
+/// first one, and the others are optional.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DbfField {
+    /// Rate in in/hr, or the units from [`ShapefileSink::with_convert_options`],
+    /// blank for bins without data
+    PrecipRate,
+    /// Station code in upper case
+    Station,
+    /// Start of the scan, like `2022-01-01T00:00:00Z`
+    CaptureTime,
+    /// Azimuth of the center of the bin in degrees
+    Azimuth,
+    /// Index of the bin within its radial
+    BinIndex,
+    /// Distance from the station to the center of the bin in kilometers
+    Range,
+    /// Intensity class from [`precip_category`]
+    Category,
+}
+
+impl DbfField {
+    /// The optional fields in the order they're written
+    pub const OPTIONAL: [DbfField; 6] = [
+        DbfField::Station,
+        DbfField::CaptureTime,
+        DbfField::Azimuth,
+        DbfField::BinIndex,
+        DbfField::Range,
+        DbfField::Category,
+    ];
+
+    pub fn from_name(name: &str) -> Option<DbfField> {
+        match name {
+            "station" => Some(DbfField::Station),
+            "capture-time" => Some(DbfField::CaptureTime),
+            "azimuth" => Some(DbfField::Azimuth),
+            "bin-index" => Some(DbfField::BinIndex),
+            "range" => Some(DbfField::Range),
+            "category" => Some(DbfField::Category),
+            _ => None,
+        }
+    }
+
+    /// Column name, type, width, and decimal places. Names can't be longer
+    /// than 10 characters.
+    fn descriptor(&self) -> (&'static str, u8, u8, u8) {
+        match self {
+            DbfField::PrecipRate => ("PrecipRate", b'N', 13, 5),
+            DbfField::Station => ("Station", b'C', 4, 0),
+            DbfField::CaptureTime => ("Captured", b'C', 20, 0),
+            DbfField::Azimuth => ("Azimuth", b'N', 7, 2),
+            DbfField::BinIndex => ("BinIndex", b'N', 5, 0),
+            DbfField::Range => ("RangeKm", b'N', 8, 3),
+            DbfField::Category => ("Category", b'C', 8, 0),
+        }
+    }
+
+    /// The field's value for `bin`, padded or cut to the field's width
+    fn value(&self, bin: &BinRef, convert: &ConvertOptions) -> String {
+        let (_, kind, len, decimals) = self.descriptor();
+        let number = |value: f32| format!("{:.*}", decimals as usize, value);
+        let text = match self {
+            DbfField::PrecipRate if bin.is_missing() => String::new(),
+            DbfField::PrecipRate => number(convert.rate(bin)),
+            DbfField::Station => bin.scan().station_code.to_uppercase(),
+            DbfField::CaptureTime => bin
+                .scan()
+                .capture_time
+                .format("%Y-%m-%dT%H:%M:%SZ")
+                .to_string(),
+            DbfField::Azimuth => number(bin.azimuth()),
+            DbfField::BinIndex => bin.bin_index.to_string(),
+            DbfField::Range => {
+                let (near, far) = bin.range();
+                number((near + far) / 2.)
+            }
+            DbfField::Category => String::from(precip_category(bin.rate())),
+        };
+        // numbers are right aligned and text is left aligned
+        match kind {
+            b'N' => format!("{:>1$.1$}", text, len as usize),
+            _ => format!("{:<1$.1$}", text, len as usize),
+        }
+    }
+}
+
+/// Collects bins as polygons with a `PrecipRate` attribute and any other
+/// [`DbfField`]s, then writes them out with [`ShapefileSink::save`]. Each scan replaces the last one,
+/// so save after every scan to convert several.
+pub struct ShapefileSink {
+    /// `.shp` records after the header
+    shapes: Vec<u8>,
+    /// `.shx` records after the header
+    index: Vec<u8>,
+    /// `.dbf` records after the header
+    records: Vec<u8>,
+    count: usize,
+    /// Min x, min y, max x, max y of every shape so far
+    bbox: [f64; 4],
+    /// Years since 1900, month, and day, for the `.dbf` header
+    date: [u8; 3],
+    prj: String,
+    fields: Vec<DbfField>,
+    convert: ConvertOptions,
+}
+
+impl Default for ShapefileSink {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ShapefileSink {
+    pub fn new() -> Self {
+        ShapefileSink {
+            shapes: Vec::new(),
+            index: Vec::new(),
+            records: Vec::new(),
+            count: 0,
+            bbox: [
+                f64::INFINITY,
+                f64::INFINITY,
+                f64::NEG_INFINITY,
+                f64::NEG_INFINITY,
+            ],
+            date: [0; 3],
+            prj: String::from(WGS84_PRJ),
+            fields: vec![DbfField::PrecipRate],
+            convert: ConvertOptions::default(),
+        }
+    }
+
+    /// Write `wkt` to the `.prj` file instead of WGS84. The coordinates are
+    /// always WGS84 longitudes and latitudes, so this is only for tools that
+    /// want the same CRS spelled differently.
+    pub fn with_prj(mut self, wkt: &str) -> Self {
+        self.prj = String::from(wkt.trim());
+        self
+    }
+
+    /// Add `fields` to the `.dbf` after `PrecipRate`, in the order given
+    pub fn with_fields(mut self, fields: &[DbfField]) -> Self {
+        for field in fields {
+            if !self.fields.contains(field) {
+                self.fields.push(*field);
+            }
+        }
+        self
+    }
+
+    /// Use `convert` to pick the bins, their outlines, and the units of
+    /// `PrecipRate`
+    pub fn with_convert_options(mut self, convert: ConvertOptions) -> Self {
+        self.convert = convert;
+        self
+    }
+
+    /// The contents of each file in the shapefile, by extension
+    pub fn files(&self) -> Vec<(&'static str, Vec<u8>)> {
+        let bbox = match self.count {
+            0 => [0.; 4],
+            _ => self.bbox,
+        };
+        let mut shp = header(HEADER_LEN + self.shapes.len(), bbox);
+        shp.extend(&self.shapes);
+        let mut shx = header(HEADER_LEN + self.index.len(), bbox);
+        shx.extend(&self.index);
+        vec![
+            ("shp", shp),
+            ("shx", shx),
+            ("dbf", self.dbf()),
+            ("prj", self.prj.clone().into_bytes()),
+        ]
+    }
+
+    /// Write the files next to each other at `path`, replacing its
+    /// extension with each file's own
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        for (extension, data) in self.files() {
+            std::fs::write(path.with_extension(extension), data)?;
+        }
+        Ok(())
+    }
+
+    /// Write the files into a zip archive as `name.shp`, `name.shx`, and so
+    /// on, which most GIS software opens like a plain shapefile
+    pub fn write_zip<W: Write + Seek>(&self, writer: W, name: &str) -> Result<W, Box<dyn Error>> {
+        let mut zip = zip::ZipWriter::new(writer);
+        let options =
+            zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+        for (extension, data) in self.files() {
+            zip.start_file(format!("{}.{}", name, extension), options)?;
+            zip.write_all(&data)?;
+        }
+        Ok(zip.finish()?)
+    }
+
+    fn dbf(&self) -> Vec<u8> {
+        let fields: Vec<_> = self.fields.iter().map(|f| f.descriptor()).collect();
+        let record_len = 1 + fields.iter().map(|f| f.2 as u16).sum::<u16>();
+        let mut dbf = vec![0x03];
+        dbf.extend(self.date);
+        dbf.extend((self.count as u32).to_le_bytes());
+        dbf.extend((32 + 32 * fields.len() as u16 + 1).to_le_bytes());
+        dbf.extend(record_len.to_le_bytes());
+        dbf.extend([0; 20]);
+        for (name, kind, len, decimals) in fields {
+            let mut descriptor = [0; 32];
+            descriptor[..name.len()].copy_from_slice(name.as_bytes());
+            descriptor[11] = kind;
+            descriptor[16] = len;
+            descriptor[17] = decimals;
+            dbf.extend(descriptor);
+        }
+        dbf.push(0x0d);
+        dbf.extend(&self.records);
+        dbf.push(0x1a);
+        dbf
+    }
+}
+
+/// The header shared by `.shp` and `.shx` files, which is big endian for
+/// the first few fields and little endian for the rest
+fn header(len: usize, bbox: [f64; 4]) -> Vec<u8> {
+    let mut header = Vec::with_capacity(HEADER_LEN);
+    header.extend(9994i32.to_be_bytes());
+    header.extend([0; 20]);
+    // lengths are in 16-bit words
+    header.extend(((len / 2) as i32).to_be_bytes());
+    header.extend(1000i32.to_le_bytes());
+    header.extend(POLYGON.to_le_bytes());
+    for value in bbox {
+        header.extend(value.to_le_bytes());
+    }
+    // no z or m ranges
+    header.extend([0; 32]);
+    header
+}
+
+impl OutputSink for ShapefileSink {
+    fn begin(&mut self, scan: &PrecipRate) -> Result<(), Box<dyn Error>> {
+        let (prj, fields, convert) = (
+            std::mem::take(&mut self.prj),
+            std::mem::take(&mut self.fields),
+            self.convert,
+        );
+        *self = ShapefileSink::new();
+        (self.prj, self.fields, self.convert) = (prj, fields, convert);
+        let date = scan.capture_time.date();
+        self.date = [
+            (chrono::Datelike::year(&date) - 1900).clamp(0, 255) as u8,
+            chrono::Datelike::month(&date) as u8,
+            chrono::Datelike::day(&date) as u8,
+        ];
+        Ok(())
+    }
+
+    fn write_bin(&mut self, bin: &BinRef) -> Result<(), Box<dyn Error>> {
+        if !self.convert.keep(bin) {
+            return Ok(());
+        }
+        // shapefiles expect outer rings to go clockwise, the opposite of the
+        // outline
+        let points: Vec<(f64, f64)> = self
+            .convert
+            .outline(bin)
+            .into_iter()
+            .rev()
+            .map(|(latitude, longitude)| (longitude, latitude))
+            .collect();
+        let mut bbox = [
+            f64::INFINITY,
+            f64::INFINITY,
+            f64::NEG_INFINITY,
+            f64::NEG_INFINITY,
+        ];
+        for (x, y) in points.iter() {
+            bbox = [
+                bbox[0].min(*x),
+                bbox[1].min(*y),
+                bbox[2].max(*x),
+                bbox[3].max(*y),
+            ];
+        }
+        self.bbox = [
+            self.bbox[0].min(bbox[0]),
+            self.bbox[1].min(bbox[1]),
+            self.bbox[2].max(bbox[2]),
+            self.bbox[3].max(bbox[3]),
+        ];
+
+        let mut content = Vec::new();
+        content.extend(POLYGON.to_le_bytes());
+        for value in bbox {
+            content.extend(value.to_le_bytes());
+        }
+        content.extend(1i32.to_le_bytes()); // parts
+        content.extend((points.len() as i32).to_le_bytes());
+        content.extend(0i32.to_le_bytes()); // where the only part starts
+        for (x, y) in points {
+            content.extend(x.to_le_bytes());
+            content.extend(y.to_le_bytes());
+        }
+
+        self.count += 1;
+        let offset = (HEADER_LEN + self.shapes.len()) / 2;
+        self.index.extend((offset as i32).to_be_bytes());
+        self.index
+            .extend(((content.len() / 2) as i32).to_be_bytes());
+        self.shapes.extend((self.count as i32).to_be_bytes());
+        self.shapes
+            .extend(((content.len() / 2) as i32).to_be_bytes());
+        self.shapes.extend(content);
+
+        // not deleted, then the fields
+        self.records.push(b' ');
+        for field in self.fields.iter() {
+            self.records
+                .extend(field.value(bin, &self.convert).as_bytes());
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_shapefile_sink() {
+    let mut scan = crate::coverage::scan_with_azimuths(&[0.5, 1.5]);
+    scan.radials[0].precip_rates = vec![0.25, f32::NAN];
+    let mut sink = ShapefileSink::new();
+    crate::output::write_scan(&scan, &mut sink).unwrap();
+    let files = sink.files();
+    let file = |extension: &str| &files.iter().find(|f| f.0 == extension).unwrap().1;
+    let int = |data: &[u8], at: usize| i32::from_be_bytes(data[at..at + 4].try_into().unwrap());
+
+    // three polygons of five points each
+    let (shp, shx) = (file("shp"), file("shx"));
+    let record_len = 4 + 32 + 4 + 4 + 4 + 5 * 16;
+    assert_eq!(shp.len(), HEADER_LEN + 3 * (8 + record_len));
+    assert_eq!(int(shp, 0), 9994);
+    assert_eq!(int(shp, 24) as usize * 2, shp.len());
+    assert_eq!(shx.len(), HEADER_LEN + 3 * 8);
+    assert_eq!(int(shx, 24) as usize * 2, shx.len());
+    // the second record starts right after the first
+    assert_eq!(
+        int(shx, HEADER_LEN + 8) as usize * 2,
+        HEADER_LEN + 8 + record_len
+    );
+    assert_eq!(int(shp, HEADER_LEN + 8 + record_len), 2);
+    let x_min = f64::from_le_bytes(shp[36..44].try_into().unwrap());
+    // the bins are just east of north, so the station is the west edge
+    assert!((x_min - scan.longitude as f64).abs() < 1e-6);
+    // outer rings go clockwise, so their signed area is negative
+    let float = |at: usize| f64::from_le_bytes(shp[at..at + 8].try_into().unwrap());
+    let points: Vec<(f64, f64)> = (0..5)
+        .map(|idx| HEADER_LEN + 8 + 48 + idx * 16)
+        .map(|at| (float(at), float(at + 8)))
+        .collect();
+    let area: f64 = points
+        .windows(2)
+        .map(|pair| pair[0].0 * pair[1].1 - pair[1].0 * pair[0].1)
+        .sum();
+    assert!(area < 0.);
+
+    let dbf = file("dbf");
+    assert_eq!(&dbf[..4], &[0x03, 70, 1, 1]);
+    assert_eq!(u32::from_le_bytes(dbf[4..8].try_into().unwrap()), 3);
+    assert_eq!(&dbf[32..42], b"PrecipRate");
+    let records = &dbf[65..dbf.len() - 1];
+    let expected = format!(" {:>13} {:>13} {:>13}", "0.25000", "", "0.00000");
+    assert_eq!(records, expected.as_bytes());
+    assert_eq!(file("prj"), WGS84_PRJ.as_bytes());
+
+    let sink = sink.with_prj("GEOGCS[\"WGS 84\"]\n");
+    assert_eq!(sink.files()[3].1, b"GEOGCS[\"WGS 84\"]");
+
+    let zip = sink
+        .write_zip(std::io::Cursor::new(Vec::new()), "bins")
+        .unwrap()
+        .into_inner();
+    let mut archive = zip::ZipArchive::new(std::io::Cursor::new(zip)).unwrap();
+    let names: Vec<&str> = archive.file_names().collect();
+    assert_eq!(names.len(), 4);
+    assert!(names.contains(&"bins.dbf"));
+    let mut prj = String::new();
+    std::io::Read::read_to_string(&mut archive.by_name("bins.prj").unwrap(), &mut prj).unwrap();
+    assert_eq!(prj, "GEOGCS[\"WGS 84\"]");
+
+    let mut sink = ShapefileSink::new().with_fields(&DbfField::OPTIONAL);
+    crate::output::write_scan_filtered(&scan, &mut sink, &crate::filter::MinRate(0.1)).unwrap();
+    let dbf = &sink.files()[2].1;
+    let header_len = 32 + 32 * 7 + 1;
+    assert_eq!(u16::from_le_bytes([dbf[8], dbf[9]]) as usize, header_len);
+    assert_eq!(&dbf[32 * 4..32 * 4 + 7], b"Azimuth");
+    let record = std::str::from_utf8(&dbf[header_len..dbf.len() - 1]).unwrap();
+    assert_eq!(
+        record,
+        format!(
+            " {:>13}KGYX1970-01-01T00:00:00Z{:>7}{:>5}{:>8}moderate",
+            "0.25000", "0.50", "0", "0.125"
+        )
+    );
+    assert_eq!(
+        DbfField::from_name("capture-time"),
+        Some(DbfField::CaptureTime)
+    );
+    assert_eq!(DbfField::from_name("elevation"), None);
+}
@@ -1,9 +1,42 @@
 use crate::geomath::get_distance_between_points;
 
+/// Nominal height of the antenna above the ground. The actual tower heights
+/// vary from about 10 to 30 meters between sites.
+pub const NOMINAL_TOWER_HEIGHT: f32 = 20.;
+
 pub struct Station {
     pub code: &'static str,
     latitude: f32,
     longitude: f32,
+    /// Ground elevation in meters above mean sea level
+    elevation: f32,
+}
+
+impl Station {
+    /// The (latitude, longitude) of the station in degrees
+    pub fn location(&self) -> (f32, f32) {
+        (self.latitude, self.longitude)
+    }
+
+    /// Ground elevation in meters above mean sea level
+    pub fn elevation(&self) -> f32 {
+        self.elevation
+    }
+
+    /// Height of the antenna tower in meters
+    pub fn tower_height(&self) -> f32 {
+        NOMINAL_TOWER_HEIGHT
+    }
+
+    /// Height of the antenna in meters above mean sea level
+    pub fn antenna_height(&self) -> f32 {
+        self.elevation + self.tower_height()
+    }
+}
+
+/// Look up a station by its code, ignoring case
+pub fn find_station(code: &str) -> Option<&'static Station> {
+    STATIONS.iter().find(|s| s.code.eq_ignore_ascii_case(code))
 }
 
 /// Given a coordinate, return an `Option<Vec>` containing the station codes for
@@ -33,805 +66,974 @@ pub const STATIONS: [Station; 161] = [
         code: "TJUA",
         latitude: 18.1155,
         longitude: -66.0780,
+        elevation: 867.,
     },
     Station {
         code: "KCBW",
         latitude: 46.0391,
         longitude: -67.8066,
+        elevation: 227.,
     },
     Station {
         code: "KGYX",
         latitude: 43.8913,
         longitude: -70.2565,
+        elevation: 125.,
     },
     Station {
         code: "KCXX",
         latitude: 44.5109,
         longitude: -73.1664,
+        elevation: 97.,
     },
     Station {
         code: "KBOX",
         latitude: 41.9558,
         longitude: -71.1369,
+        elevation: 36.,
     },
     Station {
         code: "KENX",
         latitude: 42.5865,
         longitude: -74.0639,
+        elevation: 557.,
     },
     Station {
         code: "KBGM",
         latitude: 42.1997,
         longitude: -75.9847,
+        elevation: 490.,
     },
     Station {
         code: "KBUF",
         latitude: 42.9488,
         longitude: -78.7369,
+        elevation: 211.,
     },
     Station {
         code: "KTYX",
         latitude: 43.7556,
         longitude: -75.6799,
+        elevation: 562.,
     },
     Station {
         code: "KOKX",
         latitude: 40.8655,
         longitude: -72.8638,
+        elevation: 26.,
     },
     Station {
         code: "KDOX",
         latitude: 38.8257,
         longitude: -75.4400,
+        elevation: 15.,
     },
     Station {
         code: "KDIX",
         latitude: 39.9470,
         longitude: -74.4108,
+        elevation: 45.,
     },
     Station {
         code: "KPBZ",
         latitude: 40.5316,
         longitude: -80.2179,
+        elevation: 361.,
     },
     Station {
         code: "KCCX",
         latitude: 40.9228,
         longitude: -78.0038,
+        elevation: 733.,
     },
     Station {
         code: "KRLX",
         latitude: 38.3110,
         longitude: -81.7229,
+        elevation: 329.,
     },
     Station {
         code: "KAKQ",
         latitude: 36.9840,
         longitude: -77.0073,
+        elevation: 34.,
     },
     Station {
         code: "KFCX",
         latitude: 37.0242,
         longitude: -80.2736,
+        elevation: 874.,
     },
     Station {
         code: "KLWX",
         latitude: 38.9753,
         longitude: -77.4778,
+        elevation: 83.,
     },
     Station {
         code: "KMHX",
         latitude: 34.7759,
         longitude: -76.8762,
+        elevation: 9.,
     },
     Station {
         code: "KRAX",
         latitude: 35.6654,
         longitude: -78.4897,
+        elevation: 106.,
     },
     Station {
         code: "KLTX",
         latitude: 33.9891,
         longitude: -78.4291,
+        elevation: 19.,
     },
     Station {
         code: "KCLX",
         latitude: 32.6554,
         longitude: -81.0423,
+        elevation: 30.,
     },
     Station {
         code: "KCAE",
         latitude: 33.9487,
         longitude: -81.1184,
+        elevation: 70.,
     },
     Station {
         code: "KGSP",
         latitude: 34.8833,
         longitude: -82.2200,
+        elevation: 296.,
     },
     Station {
         code: "KFFC",
         latitude: 33.3635,
         longitude: -84.5658,
+        elevation: 262.,
     },
     Station {
         code: "KVAX",
         latitude: 30.8903,
         longitude: -83.0019,
+        elevation: 54.,
     },
     Station {
         code: "KJGX",
         latitude: 32.6755,
         longitude: -83.3508,
+        elevation: 159.,
     },
     Station {
         code: "KEVX",
         latitude: 30.5649,
         longitude: -85.9215,
+        elevation: 43.,
     },
     Station {
         code: "KJAX",
         latitude: 30.4846,
         longitude: -81.7018,
+        elevation: 10.,
     },
     Station {
         code: "KBYX",
         latitude: 24.5974,
         longitude: -81.7032,
+        elevation: 3.,
     },
     Station {
         code: "KMLB",
         latitude: 28.1131,
         longitude: -80.6540,
+        elevation: 11.,
     },
     Station {
         code: "KAMX",
         latitude: 25.6111,
         longitude: -80.4127,
+        elevation: 4.,
     },
     Station {
         code: "KTLH",
         latitude: 30.3975,
         longitude: -84.3289,
+        elevation: 19.,
     },
     Station {
         code: "KTBW",
         latitude: 27.7054,
         longitude: -82.4017,
+        elevation: 13.,
     },
     Station {
         code: "KBMX",
         latitude: 33.1722,
         longitude: -86.7698,
+        elevation: 197.,
     },
     Station {
         code: "KEOX",
         latitude: 31.4605,
         longitude: -85.4592,
+        elevation: 132.,
     },
     Station {
         code: "KHTX",
         latitude: 34.9305,
         longitude: -86.0837,
+        elevation: 536.,
     },
     Station {
         code: "KMXX",
         latitude: 32.5366,
         longitude: -85.7897,
+        elevation: 122.,
     },
     Station {
         code: "KMOB",
         latitude: 30.6795,
         longitude: -88.2397,
+        elevation: 63.,
     },
     Station {
         code: "KDGX",
         latitude: 32.2797,
         longitude: -89.9846,
+        elevation: 149.,
     },
     Station {
         code: "KGWX",
         latitude: 33.8967,
         longitude: -88.3293,
+        elevation: 145.,
     },
     Station {
         code: "KMRX",
         latitude: 36.1685,
         longitude: -83.4017,
+        elevation: 408.,
     },
     Station {
         code: "KNQA",
         latitude: 35.3447,
         longitude: -89.8734,
+        elevation: 86.,
     },
     Station {
         code: "KOHX",
         latitude: 36.2472,
         longitude: -86.5625,
+        elevation: 176.,
     },
     Station {
         code: "KHPX",
         latitude: 36.7368,
         longitude: -87.2854,
+        elevation: 177.,
     },
     Station {
         code: "KJKL",
         latitude: 37.5907,
         longitude: -83.3130,
+        elevation: 415.,
     },
     Station {
         code: "KLVX",
         latitude: 37.9753,
         longitude: -85.9438,
+        elevation: 219.,
     },
     Station {
         code: "KPAH",
         latitude: 37.0683,
         longitude: -88.7720,
+        elevation: 119.,
     },
     Station {
         code: "KILN",
         latitude: 39.4202,
         longitude: -83.8216,
+        elevation: 322.,
     },
     Station {
         code: "KCLE",
         latitude: 41.4131,
         longitude: -81.8597,
+        elevation: 233.,
     },
     Station {
         code: "KDTX",
         latitude: 42.6999,
         longitude: -83.4718,
+        elevation: 327.,
     },
     Station {
         code: "KAPX",
         latitude: 44.9071,
         longitude: -84.7198,
+        elevation: 446.,
     },
     Station {
         code: "KGRR",
         latitude: 42.8938,
         longitude: -85.5449,
+        elevation: 237.,
     },
     Station {
         code: "KMQT",
         latitude: 46.5311,
         longitude: -87.5487,
+        elevation: 430.,
     },
     Station {
         code: "KVWX",
         latitude: 38.2603,
         longitude: -87.7246,
+        elevation: 155.,
     },
     Station {
         code: "KIND",
         latitude: 39.7074,
         longitude: -86.2803,
+        elevation: 241.,
     },
     Station {
         code: "KIWX",
         latitude: 41.3586,
         longitude: -85.7000,
+        elevation: 293.,
     },
     Station {
         code: "KLOT",
         latitude: 41.6044,
         longitude: -88.0843,
+        elevation: 202.,
     },
     Station {
         code: "KILX",
         latitude: 40.1505,
         longitude: -89.3368,
+        elevation: 177.,
     },
     Station {
         code: "KGRB",
         latitude: 44.4984,
         longitude: -88.1111,
+        elevation: 208.,
     },
     Station {
         code: "KARX",
         latitude: 43.8227,
         longitude: -91.1915,
+        elevation: 389.,
     },
     Station {
         code: "KMKX",
         latitude: 42.9678,
         longitude: -88.5506,
+        elevation: 292.,
     },
     Station {
         code: "KDLH",
         latitude: 46.8368,
         longitude: -92.2097,
+        elevation: 435.,
     },
     Station {
         code: "KMPX",
         latitude: 44.8488,
         longitude: -93.5654,
+        elevation: 288.,
     },
     Station {
         code: "KDVN",
         latitude: 41.6115,
         longitude: -90.5809,
+        elevation: 230.,
     },
     Station {
         code: "KDMX",
         latitude: 41.7311,
         longitude: -93.7229,
+        elevation: 299.,
     },
     Station {
         code: "KEAX",
         latitude: 38.8102,
         longitude: -94.2644,
+        elevation: 303.,
     },
     Station {
         code: "KSGF",
         latitude: 37.2352,
         longitude: -93.4006,
+        elevation: 390.,
     },
     Station {
         code: "KLSX",
         latitude: 38.6986,
         longitude: -90.6828,
+        elevation: 185.,
     },
     Station {
         code: "KSRX",
         latitude: 35.2904,
         longitude: -94.3619,
+        elevation: 195.,
     },
     Station {
         code: "KLZK",
         latitude: 34.8365,
         longitude: -92.2621,
+        elevation: 173.,
     },
     Station {
         code: "KPOE",
         latitude: 31.1556,
         longitude: -92.9762,
+        elevation: 124.,
     },
     Station {
         code: "KLCH",
         latitude: 30.1253,
         longitude: -93.2161,
+        elevation: 4.,
     },
     Station {
         code: "KLIX",
         latitude: 30.3367,
         longitude: -89.8256,
+        elevation: 7.,
     },
     Station {
         code: "KSHV",
         latitude: 32.4508,
         longitude: -93.8412,
+        elevation: 83.,
     },
     Station {
         code: "KAMA",
         latitude: 35.2334,
         longitude: -101.7092,
+        elevation: 1093.,
     },
     Station {
         code: "KEWX",
         latitude: 29.7039,
         longitude: -98.0285,
+        elevation: 193.,
     },
     Station {
         code: "KBRO",
         latitude: 25.9159,
         longitude: -97.4189,
+        elevation: 7.,
     },
     Station {
         code: "KCRP",
         latitude: 27.7840,
         longitude: -97.5112,
+        elevation: 14.,
     },
     Station {
         code: "KFWS",
         latitude: 32.5730,
         longitude: -97.3031,
+        elevation: 208.,
     },
     Station {
         code: "KDYX",
         latitude: 32.5386,
         longitude: -99.2542,
+        elevation: 463.,
     },
     Station {
         code: "KEPZ",
         latitude: 31.8731,
         longitude: -106.6979,
+        elevation: 1251.,
     },
     Station {
         code: "KGRK",
         latitude: 30.7217,
         longitude: -97.3829,
+        elevation: 164.,
     },
     Station {
         code: "KHGX",
         latitude: 29.4718,
         longitude: -95.0788,
+        elevation: 5.,
     },
     Station {
         code: "KDFX",
         latitude: 29.2730,
         longitude: -100.2802,
+        elevation: 345.,
     },
     Station {
         code: "KLBB",
         latitude: 33.6541,
         longitude: -101.8141,
+        elevation: 993.,
     },
     Station {
         code: "KMAF",
         latitude: 31.9433,
         longitude: -102.1894,
+        elevation: 874.,
     },
     Station {
         code: "KSJT",
         latitude: 31.3712,
         longitude: -100.4925,
+        elevation: 576.,
     },
     Station {
         code: "KFDR",
         latitude: 34.3620,
         longitude: -98.9766,
+        elevation: 386.,
     },
     Station {
         code: "KTLX",
         latitude: 35.3333,
         longitude: -97.2778,
+        elevation: 370.,
     },
     Station {
         code: "KINX",
         latitude: 36.1750,
         longitude: -95.5642,
+        elevation: 204.,
     },
     Station {
         code: "KVNX",
         latitude: 36.7406,
         longitude: -98.1279,
+        elevation: 369.,
     },
     Station {
         code: "KDDC",
         latitude: 37.7608,
         longitude: -99.9688,
+        elevation: 790.,
     },
     Station {
         code: "KGLD",
         latitude: 39.3667,
         longitude: -101.7004,
+        elevation: 1113.,
     },
     Station {
         code: "KTWX",
         latitude: 38.9969,
         longitude: -96.2326,
+        elevation: 417.,
     },
     Station {
         code: "KICT",
         latitude: 37.6545,
         longitude: -97.4431,
+        elevation: 407.,
     },
     Station {
         code: "KUEX",
         latitude: 40.3209,
         longitude: -98.4418,
+        elevation: 602.,
     },
     Station {
         code: "KLNX",
         latitude: 41.9579,
         longitude: -100.5759,
+        elevation: 906.,
     },
     Station {
         code: "KOAX",
         latitude: 41.3202,
         longitude: -96.3667,
+        elevation: 350.,
     },
     Station {
         code: "KABR",
         latitude: 45.4558,
         longitude: -98.4132,
+        elevation: 397.,
     },
     Station {
         code: "KUDX",
         latitude: 44.1248,
         longitude: -102.8298,
+        elevation: 919.,
     },
     Station {
         code: "KFSD",
         latitude: 43.5877,
         longitude: -96.7293,
+        elevation: 436.,
     },
     Station {
         code: "KBIS",
         latitude: 46.7709,
         longitude: -100.7605,
+        elevation: 505.,
     },
     Station {
         code: "KMVX",
         latitude: 47.5279,
         longitude: -97.3256,
+        elevation: 301.,
     },
     Station {
         code: "KMBX",
         latitude: 48.3930,
         longitude: -100.8644,
+        elevation: 455.,
     },
     Station {
         code: "KBLX",
         latitude: 45.8537,
         longitude: -108.6068,
+        elevation: 1097.,
     },
     Station {
         code: "KGGW",
         latitude: 48.2064,
         longitude: -106.6252,
+        elevation: 694.,
     },
     Station {
         code: "KTFX",
         latitude: 47.4595,
         longitude: -111.3855,
+        elevation: 1132.,
     },
     Station {
         code: "KMSX",
         latitude: 47.0412,
         longitude: -113.9864,
+        elevation: 2394.,
     },
     Station {
         code: "KCYS",
         latitude: 41.1519,
         longitude: -104.806,
+        elevation: 1868.,
     },
     Station {
         code: "KRIW",
         latitude: 43.0660,
         longitude: -108.4773,
+        elevation: 1697.,
     },
     Station {
         code: "KFTG",
         latitude: 39.7866,
         longitude: -104.5458,
+        elevation: 1675.,
     },
     Station {
         code: "KGJX",
         latitude: 39.0619,
         longitude: -108.2137,
+        elevation: 3045.,
     },
     Station {
         code: "KPUX",
         latitude: 38.4595,
         longitude: -104.1816,
+        elevation: 1600.,
     },
     Station {
         code: "KABX",
         latitude: 35.1497,
         longitude: -106.8239,
+        elevation: 1789.,
     },
     Station {
         code: "KFDX",
         latitude: 34.6341,
         longitude: -103.6186,
+        elevation: 1417.,
     },
     Station {
         code: "KHDX",
         latitude: 33.0768,
         longitude: -106.12,
+        elevation: 1287.,
     },
     Station {
         code: "KFSX",
         latitude: 34.5744,
         longitude: -111.1983,
+        elevation: 2261.,
     },
     Station {
         code: "KIWA",
         latitude: 33.2891,
         longitude: -111.67,
+        elevation: 413.,
     },
     Station {
         code: "KEMX",
         latitude: 31.8937,
         longitude: -110.6304,
+        elevation: 1586.,
     },
     Station {
         code: "KYUX",
         latitude: 32.4953,
         longitude: -114.6567,
+        elevation: 53.,
     },
     Station {
         code: "KICX",
         latitude: 37.5908,
         longitude: -112.8622,
+        elevation: 3231.,
     },
     Station {
         code: "KMTX",
         latitude: 41.2627,
         longitude: -112.448,
+        elevation: 1969.,
     },
     Station {
         code: "KCBX",
         latitude: 43.4902,
         longitude: -116.236,
+        elevation: 933.,
     },
     Station {
         code: "KSFX",
         latitude: 43.1055,
         longitude: -112.686,
+        elevation: 1364.,
     },
     Station {
         code: "KLRX",
         latitude: 40.7396,
         longitude: -116.8025,
+        elevation: 2056.,
     },
     Station {
         code: "KESX",
         latitude: 35.7012,
         longitude: -114.8918,
+        elevation: 1483.,
     },
     Station {
         code: "KRGX",
         latitude: 39.7541,
         longitude: -119.462,
+        elevation: 2530.,
     },
     Station {
         code: "KBBX",
         latitude: 39.4956,
         longitude: -121.6316,
+        elevation: 53.,
     },
     Station {
         code: "KEYX",
         latitude: 35.0979,
         longitude: -117.5608,
+        elevation: 840.,
     },
     Station {
         code: "KBHX",
         latitude: 40.4986,
         longitude: -124.2918,
+        elevation: 732.,
     },
     Station {
         code: "KVTX",
         latitude: 34.4116,
         longitude: -119.1795,
+        elevation: 831.,
     },
     Station {
         code: "KDAX",
         latitude: 38.5011,
         longitude: -121.6778,
+        elevation: 9.,
     },
     Station {
         code: "KNKX",
         latitude: 32.9189,
         longitude: -117.0418,
+        elevation: 291.,
     },
     Station {
         code: "KMUX",
         latitude: 37.1551,
         longitude: -121.8984,
+        elevation: 1057.,
     },
     Station {
         code: "KHNX",
         latitude: 36.3142,
         longitude: -119.632,
+        elevation: 74.,
     },
     Station {
         code: "KSOX",
         latitude: 33.8176,
         longitude: -117.6359,
+        elevation: 923.,
     },
     Station {
         code: "PHKI",
         latitude: 21.8938,
         longitude: -159.5524,
+        elevation: 55.,
     },
     Station {
         code: "PHKM",
         latitude: 20.1254,
         longitude: -155.778,
+        elevation: 1161.,
     },
     Station {
         code: "PHMO",
         latitude: 21.1327,
         longitude: -157.1802,
+        elevation: 416.,
     },
     Station {
         code: "PHWA",
         latitude: 19.0950,
         longitude: -155.5688,
+        elevation: 421.,
     },
     Station {
         code: "KMAX",
         latitude: 42.0810,
         longitude: -122.7173,
+        elevation: 2290.,
     },
     Station {
         code: "KPDT",
         latitude: 45.6906,
         longitude: -118.8529,
+        elevation: 462.,
     },
     Station {
         code: "KRTX",
         latitude: 45.7150,
         longitude: -122.965,
+        elevation: 479.,
     },
     Station {
         code: "KLGX",
         latitude: 47.1168,
         longitude: -124.1062,
+        elevation: 67.,
     },
     Station {
         code: "KATX",
         latitude: 48.1945,
         longitude: -122.4957,
+        elevation: 151.,
     },
     Station {
         code: "KOTX",
         latitude: 47.6803,
         longitude: -117.6267,
+        elevation: 728.,
     },
     Station {
         code: "PABC",
         latitude: 60.7919,
         longitude: -161.8765,
+        elevation: 48.,
     },
     Station {
         code: "PAPD",
         latitude: 65.0351,
         longitude: -147.5014,
+        elevation: 790.,
     },
     Station {
         code: "PAHG",
         latitude: 60.6156,
         longitude: -151.2832,
+        elevation: 74.,
     },
     Station {
         code: "PAKC",
         latitude: 58.6794,
         longitude: -156.6293,
+        elevation: 19.,
     },
     Station {
         code: "PAIH",
         latitude: 59.4619,
         longitude: -146.3011,
+        elevation: 20.,
     },
     Station {
         code: "PAEC",
         latitude: 64.5114,
         longitude: -165.2949,
+        elevation: 16.,
     },
     Station {
         code: "PACG",
         latitude: 56.8521,
         longitude: -135.5524,
+        elevation: 63.,
     },
     Station {
         code: "PGUA",
         latitude: 13.4559,
         longitude: 144.8111,
+        elevation: 78.,
     },
     Station {
         code: "RKJK",
         latitude: 35.9241,
         longitude: 126.6222,
+        elevation: 24.,
     },
     Station {
         code: "RKSG",
         latitude: 37.2076,
         longitude: 127.2856,
+        elevation: 16.,
     },
     Station {
         code: "RODN",
         latitude: 26.3077,
         longitude: 127.9034,
+        elevation: 66.,
     },
     Station {
         code: "KKSG",
         latitude: 37.206985,
         longitude: 127.28502,
+        elevation: 16.,
     },
     Station {
         code: "KVBX",
         latitude: 34.838314,
         longitude: -120.39778,
+        elevation: 373.,
     },
     Station {
         code: "KKJK",
         latitude: 35.92398,
         longitude: 126.62202,
+        elevation: 24.,
     },
 ];
+
+#[test]
+fn test_find_station() {
+    let station = find_station("kgyx").unwrap();
+    assert_eq!(station.location(), (43.8913, -70.2565));
+    assert_eq!(station.antenna_height(), 145.);
+    assert!(STATIONS.iter().all(|s| s.elevation() > 0.));
+}
@@ -0,0 +1,164 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::geomath::get_point_bearing_distance;
+use crate::parse::{OperationalMode, PrecipRate};
+
+/// A short description of a scan that can be serialized for catalogs and
+/// other tools
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScanSummary {
+    pub station: String,
+    #[serde(with = "timestamp")]
+    pub capture_time: NaiveDateTime,
+    /// When the product was generated, for telling how long it took to show
+    /// up
+    #[serde(default, with = "timestamp::option")]
+    pub generation_time: Option<NaiveDateTime>,
+    pub scan_number: i32,
+    pub operational_mode: OperationalMode,
+    pub precip_detected: bool,
+    /// Highest rate of any bin in in/hr
+    pub max_rate: f32,
+    pub radials: usize,
+    pub bins: usize,
+    /// Fraction of bins with nonzero precip
+    pub precip_fraction: f32,
+    /// Area covered by bins with nonzero precip in square kilometers
+    #[serde(default)]
+    pub rainy_area: f32,
+    /// `[south, west, north, east]` in degrees, out to the farthest bin
+    pub bbox: [f32; 4],
+}
+
+/// Times are written like `2022-01-01T00:00:00Z`, the same as in file names
+mod timestamp {
+    use chrono::NaiveDateTime;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    const FORMAT: &str = "%Y-%m-%dT%H:%M:%SZ";
+
+    pub fn serialize<S: Serializer>(time: &NaiveDateTime, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(&time.format(FORMAT).to_string())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<NaiveDateTime, D::Error> {
+        let s = String::deserialize(d)?;
+        NaiveDateTime::parse_from_str(&s, FORMAT).map_err(serde::de::Error::custom)
+    }
+
+    /// The same for optional times, which are written as `null` if missing
+    pub mod option {
+        use chrono::NaiveDateTime;
+        use serde::{Deserialize, Deserializer, Serializer};
+
+        pub fn serialize<S: Serializer>(
+            time: &Option<NaiveDateTime>,
+            s: S,
+        ) -> Result<S::Ok, S::Error> {
+            match time {
+                Some(time) => super::serialize(time, s),
+                None => s.serialize_none(),
+            }
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            d: D,
+        ) -> Result<Option<NaiveDateTime>, D::Error> {
+            match Option::<String>::deserialize(d)? {
+                Some(s) => NaiveDateTime::parse_from_str(&s, super::FORMAT)
+                    .map(Some)
+                    .map_err(serde::de::Error::custom),
+                None => Ok(None),
+            }
+        }
+    }
+}
+
+impl PrecipRate {
+    /// Fraction of bins with nonzero precip, or zero if there are no bins
+    pub fn precip_fraction(&self) -> f32 {
+        let (rainy_bins, total_bins) = self
+            .radials
+            .iter()
+            .flat_map(|r| r.precip_rates.iter())
+            .fold((0, 0), |(rainy, total), &rate| {
+                (rainy + (rate > 0.) as usize, total + 1)
+            });
+        if total_bins == 0 {
+            0.
+        } else {
+            rainy_bins as f32 / total_bins as f32
+        }
+    }
+
+    /// Area covered by bins with nonzero precip in square kilometers
+    pub fn rainy_area(&self) -> f32 {
+        self.bins()
+            .filter(|bin| bin.rate() > 0.)
+            .map(|bin| bin.area())
+            .sum()
+    }
+
+    pub fn summary(&self) -> ScanSummary {
+        let max_range = self.max_range();
+        let station = (self.latitude, self.longitude);
+        let edge = |bearing| get_point_bearing_distance(station, bearing, max_range);
+        ScanSummary {
+            station: self.station_code.clone(),
+            capture_time: self.capture_time,
+            generation_time: self.times.generation,
+            scan_number: self.scan_number,
+            operational_mode: self.operational_mode.clone(),
+            precip_detected: self.precip_detected,
+            max_rate: self
+                .radials
+                .iter()
+                .flat_map(|r| r.precip_rates.iter())
+                .cloned()
+                .fold(0., f32::max),
+            radials: self.radials.len(),
+            bins: self.radials.iter().map(|r| r.precip_rates.len()).sum(),
+            precip_fraction: self.precip_fraction(),
+            rainy_area: self.rainy_area(),
+            bbox: [edge(180.).0, edge(270.).1, edge(0.).0, edge(90.).1],
+        }
+    }
+}
+
+impl fmt::Display for ScanSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {} scan {} ({:?}): {} radials, {} bins, max {:.3} in/hr, {:.1}% precip over {:.0} km²",
+            self.station,
+            self.capture_time.format("%Y-%m-%dT%H:%M:%SZ"),
+            self.scan_number,
+            self.operational_mode,
+            self.radials,
+            self.bins,
+            self.max_rate,
+            self.precip_fraction * 100.,
+            self.rainy_area
+        )
+    }
+}
+
+#[test]
+fn test_scan_summary() {
+    let mut scan = crate::coverage::scan_with_azimuths(&[0.5, 1.5]);
+    scan.radials[0].precip_rates = vec![0., 0.5, 0., 0.];
+    scan.radials[1].precip_rates = vec![0., 0., 0., 0.];
+    let summary = scan.summary();
+    assert_eq!((summary.radials, summary.bins), (2, 8));
+    assert_eq!((summary.max_rate, summary.precip_fraction), (0.5, 0.125));
+    let bin = scan.bins().nth(1).unwrap();
+    assert_eq!(summary.rainy_area, bin.area());
+    assert!(summary.bbox[0] < scan.latitude && summary.bbox[2] > scan.latitude);
+    let json = serde_json::to_string(&summary).unwrap();
+    assert!(json.contains(r#""capture_time":"1970-01-01T00:00:00Z""#));
+    assert!(json.contains(r#""operational_mode":"precipitation""#));
+    assert!(json.contains(r#""generation_time":null"#));
+    assert_eq!(serde_json::from_str::<ScanSummary>(&json).unwrap(), summary);
+}
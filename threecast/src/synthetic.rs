@@ -0,0 +1,232 @@
+//! Made-up scans with storms of a known shape, for tests and demos that
+//! need deterministic data without shipping real radar files
+//!
+//! ```
+//! use threecast::synthetic::SyntheticDpr;
+//!
+//! let scan = SyntheticDpr::new("KGYX")
+//!     .with_background(0.01)
+//!     .with_gaussian_cell(90., 50., 2., 5.)
+//!     .build();
+//! assert_eq!(scan.radials.len(), 360);
+//! ```
+
+use crate::parse::{OperationalMode, PrecipRate, Radial, ScanTimes};
+use crate::stations::find_station;
+
+/// A storm cell whose rate falls off from its peak like a 2D Gaussian
+#[derive(Debug, Clone)]
+struct GaussianCell {
+    /// Center of the cell in km east and north of the radar
+    x: f32,
+    y: f32,
+    /// Rate at the center in in/hr
+    peak: f32,
+    /// Standard deviation in km
+    radius: f32,
+}
+
+/// Builds a [`PrecipRate`] with one-degree radials and rates made up from
+/// a background rate and any number of storm cells. The defaults match a
+/// real digital precipitation rate product: 360 radials of 920 bins that
+/// are 250 m long.
+#[derive(Debug, Clone)]
+pub struct SyntheticDpr {
+    station_code: String,
+    latitude: f32,
+    longitude: f32,
+    capture_time: chrono::NaiveDateTime,
+    scan_number: i32,
+    bin_size: f32,
+    num_bins: usize,
+    num_radials: usize,
+    background: f32,
+    cells: Vec<GaussianCell>,
+    /// Azimuth ranges in degrees that have no data
+    missing: Vec<(f32, f32)>,
+}
+
+impl SyntheticDpr {
+    /// Start a scan from `station_code` at the start of 2022, with no
+    /// precipitation. The station's location is looked up, and unknown
+    /// stations are put at 0°, 0° unless given a location with
+    /// [`SyntheticDpr::with_location`].
+    pub fn new(station_code: &str) -> Self {
+        let (latitude, longitude) = find_station(station_code).map_or((0., 0.), |s| s.location());
+        SyntheticDpr {
+            station_code: station_code.to_uppercase(),
+            latitude,
+            longitude,
+            capture_time: chrono::NaiveDate::from_ymd(2022, 1, 1).and_hms(0, 0, 0),
+            scan_number: 1,
+            bin_size: 0.25,
+            num_bins: 920,
+            num_radials: 360,
+            background: 0.,
+            cells: Vec::new(),
+            missing: Vec::new(),
+        }
+    }
+
+    /// Put the radar at (`latitude`, `longitude`) in degrees
+    pub fn with_location(mut self, latitude: f32, longitude: f32) -> Self {
+        self.latitude = latitude;
+        self.longitude = longitude;
+        self
+    }
+
+    /// Set the start of the volume scan and its number
+    pub fn with_time(mut self, capture_time: chrono::NaiveDateTime, scan_number: i32) -> Self {
+        self.capture_time = capture_time;
+        self.scan_number = scan_number;
+        self
+    }
+
+    /// Use `num_radials` radials evenly spread around the radar, each with
+    /// `num_bins` bins of `bin_size` km
+    pub fn with_geometry(mut self, num_radials: usize, num_bins: usize, bin_size: f32) -> Self {
+        self.num_radials = num_radials;
+        self.num_bins = num_bins;
+        self.bin_size = bin_size;
+        self
+    }
+
+    /// Rain at `rate` in/hr everywhere, under any cells
+    pub fn with_background(mut self, rate: f32) -> Self {
+        self.background = rate;
+        self
+    }
+
+    /// Add a cell centered `range` km from the radar at `azimuth` degrees,
+    /// with a rate of `peak` in/hr at its center that falls off with a
+    /// standard deviation of `radius` km. Overlapping cells add up.
+    pub fn with_gaussian_cell(mut self, azimuth: f32, range: f32, peak: f32, radius: f32) -> Self {
+        let angle = azimuth.to_radians();
+        self.cells.push(GaussianCell {
+            x: range * angle.sin(),
+            y: range * angle.cos(),
+            peak,
+            radius,
+        });
+        self
+    }
+
+    /// Leave out the data in radials from `start` to `end` degrees
+    /// clockwise, like a sector blocked by terrain
+    pub fn with_missing_sector(mut self, start: f32, end: f32) -> Self {
+        self.missing.push((start, end));
+        self
+    }
+
+    /// Rate in in/hr at `range` km from the radar at `azimuth` degrees
+    fn rate(&self, azimuth: f32, range: f32) -> f32 {
+        let angle = azimuth.to_radians();
+        let (x, y) = (range * angle.sin(), range * angle.cos());
+        let rate = self.background
+            + self
+                .cells
+                .iter()
+                .map(|cell| {
+                    let distance_squared = (x - cell.x).powi(2) + (y - cell.y).powi(2);
+                    cell.peak * (-distance_squared / (2. * cell.radius.powi(2))).exp()
+                })
+                .sum::<f32>();
+        // the product stores thousandths of an inch per hour
+        (rate * 1000.).round() / 1000.
+    }
+
+    fn is_missing(&self, azimuth: f32) -> bool {
+        self.missing.iter().any(|&(start, end)| {
+            (azimuth - start).rem_euclid(360.) <= (end - start).rem_euclid(360.)
+        })
+    }
+
+    /// Make the scan, with rates rounded to the thousandths of an in/hr
+    /// that the product can hold
+    pub fn build(&self) -> PrecipRate {
+        let width = 360. / self.num_radials as f32;
+        let radials: Vec<Radial> = (0..self.num_radials)
+            .map(|idx| {
+                let azimuth = (idx as f32 + 0.5) * width;
+                let precip_rates = (0..self.num_bins)
+                    .map(|bin| match self.is_missing(azimuth) {
+                        true => f32::NAN,
+                        false => self.rate(azimuth, (bin as f32 + 0.5) * self.bin_size),
+                    })
+                    .collect();
+                Radial {
+                    azimuth,
+                    elevation: 0.5,
+                    width,
+                    precip_rates,
+                    interpolated: false,
+                }
+            })
+            .collect();
+        let precip_detected = radials
+            .iter()
+            .flat_map(|r| r.precip_rates.iter())
+            .any(|&rate| rate > 0.);
+        PrecipRate {
+            station_code: self.station_code.clone(),
+            capture_time: self.capture_time,
+            scan_number: self.scan_number,
+            latitude: self.latitude,
+            longitude: self.longitude,
+            operational_mode: match precip_detected {
+                true => OperationalMode::Precipitation,
+                false => OperationalMode::CleanAir,
+            },
+            precip_detected,
+            bin_size: self.bin_size,
+            range_to_first_bin: 0.,
+            radials,
+            times: ScanTimes {
+                volume_start: Some(self.capture_time),
+                ..Default::default()
+            },
+            metadata: None,
+            extra_components: Vec::new(),
+            alphanumeric: Default::default(),
+        }
+    }
+
+    /// Build the scan and encode it as a digital precipitation rate
+    /// product with [`crate::encode::write_dpr`]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        crate::encode::write_dpr(&self.build())
+    }
+}
+
+#[test]
+fn test_synthetic_dpr() {
+    let synthetic = SyntheticDpr::new("kgyx")
+        .with_background(0.01)
+        .with_gaussian_cell(90., 50., 2., 5.)
+        .with_missing_sector(350., 10.);
+    let scan = synthetic.build();
+    assert_eq!(scan.station_code, "KGYX");
+    assert_eq!(scan.latitude, find_station("KGYX").unwrap().location().0);
+    assert_eq!(scan.radials.len(), 360);
+    assert_eq!(scan.radials[0].precip_rates.len(), 920);
+    assert!(scan.precip_detected);
+
+    // the peak is in the two bins either side of the center of the cell
+    let east = &scan.radials[89].precip_rates;
+    let (peak, rate) = east
+        .iter()
+        .enumerate()
+        .max_by(|a, b| a.1.total_cmp(b.1))
+        .unwrap();
+    assert!((199..=200).contains(&peak));
+    assert!((rate - 2.01).abs() < 0.01);
+    assert_eq!(east[0], 0.01);
+    assert_eq!(scan.radials[269].precip_rates[199], 0.01);
+    assert!(scan.radials[355].precip_rates[0].is_nan());
+    assert!(scan.radials[5].precip_rates[0].is_nan());
+    assert!(!scan.radials[11].precip_rates[0].is_nan());
+
+    let parsed = crate::parse::parse_dpr(synthetic.to_bytes()).unwrap();
+    assert_eq!(parsed.capture_time, scan.capture_time);
+    assert_eq!(parsed.radials[89].precip_rates[199], east[199]);
+}
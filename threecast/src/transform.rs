@@ -0,0 +1,602 @@
+use crate::coverage::TOLERANCE;
+use crate::parse::{PrecipRate, Radial};
+
+/// How to combine several bins into one
+#[derive(Debug, Clone, Copy)]
+pub enum Aggregation {
+    Mean,
+    Max,
+}
+
+/// Combines rates one at a time, skipping bins without data
+#[derive(Debug, Clone, Copy, Default)]
+struct Accumulator {
+    value: f32,
+    /// Total weight of the rates with data so far
+    weight: f32,
+}
+
+impl Accumulator {
+    fn add(&mut self, rate: f32, weight: f32, aggregation: Aggregation) {
+        if rate.is_nan() {
+            return;
+        }
+        self.value = match aggregation {
+            Aggregation::Mean => self.value + rate * weight,
+            Aggregation::Max if self.weight == 0. => rate,
+            Aggregation::Max => self.value.max(rate),
+        };
+        self.weight += weight;
+    }
+
+    /// The combined rate, or missing if no rate had data
+    fn finish(&self, aggregation: Aggregation) -> f32 {
+        match aggregation {
+            _ if self.weight <= 0. => f32::NAN,
+            Aggregation::Mean => self.value / self.weight,
+            Aggregation::Max => self.value,
+        }
+    }
+}
+
+impl PrecipRate {
+    /// Drop all bins that are farther than `max_range` kilometers from the
+    /// station
+    pub fn crop_range(&mut self, max_range: f32) {
+        let max_bins = ((max_range - self.range_to_first_bin) / self.bin_size)
+            .ceil()
+            .max(0.) as usize;
+        for radial in self.radials.iter_mut() {
+            radial.precip_rates.truncate(max_bins);
+        }
+    }
+
+    /// Set every rate below `min_rate` to zero
+    pub fn clip_below(&mut self, min_rate: f32) {
+        for rate in self
+            .radials
+            .iter_mut()
+            .flat_map(|r| r.precip_rates.iter_mut())
+        {
+            if *rate < min_rate {
+                *rate = 0.;
+            }
+        }
+    }
+
+    /// Replace the rate of every bin without data with `rate`, usually zero
+    pub fn fill_missing(&mut self, rate: f32) {
+        for value in self
+            .radials
+            .iter_mut()
+            .flat_map(|r| r.precip_rates.iter_mut())
+        {
+            if value.is_nan() {
+                *value = rate;
+            }
+        }
+    }
+
+    /// Sort the radials by azimuth, starting from north, and merge radials
+    /// with the same azimuth by averaging their rates. Each bin averages
+    /// only the radials that have data there, and stays missing if none do.
+    /// If `max_fill_gap` is
+    /// given, gaps between radials up to that many degrees wide are filled
+    /// with new radials whose rates are interpolated from their neighbors.
+    pub fn normalize_radials(mut self, max_fill_gap: Option<f32>) -> Self {
+        for radial in self.radials.iter_mut() {
+            radial.azimuth = radial.azimuth.rem_euclid(360.);
+        }
+        self.radials.sort_by(|a, b| a.azimuth.total_cmp(&b.azimuth));
+
+        // merge duplicates into the first radial with each azimuth, keeping
+        // sums and the number of radials with data for each bin
+        let mut merged: Vec<(Radial, Vec<u32>)> = Vec::with_capacity(self.radials.len());
+        for mut radial in self.radials.drain(..) {
+            match merged.last_mut() {
+                Some((last, counts)) if (radial.azimuth - last.azimuth).abs() <= TOLERANCE => {
+                    if radial.precip_rates.len() > last.precip_rates.len() {
+                        last.precip_rates.resize(radial.precip_rates.len(), 0.);
+                        counts.resize(radial.precip_rates.len(), 0);
+                    }
+                    let sums = last.precip_rates.iter_mut().zip(counts.iter_mut());
+                    for ((sum, count), rate) in sums.zip(radial.precip_rates) {
+                        if !rate.is_nan() {
+                            *sum += rate;
+                            *count += 1;
+                        }
+                    }
+                    last.width = last.width.max(radial.width);
+                }
+                _ => {
+                    let counts = radial
+                        .precip_rates
+                        .iter()
+                        .map(|rate| u32::from(!rate.is_nan()))
+                        .collect();
+                    for rate in radial.precip_rates.iter_mut() {
+                        if rate.is_nan() {
+                            *rate = 0.;
+                        }
+                    }
+                    merged.push((radial, counts));
+                }
+            }
+        }
+        self.radials = merged
+            .into_iter()
+            .map(|(mut radial, counts)| {
+                for (rate, count) in radial.precip_rates.iter_mut().zip(counts) {
+                    *rate = match count {
+                        0 => f32::NAN,
+                        count => *rate / count as f32,
+                    };
+                }
+                radial
+            })
+            .collect();
+
+        if let Some(max_gap) = max_fill_gap {
+            let mut filled = Vec::with_capacity(self.radials.len());
+            for idx in 0..self.radials.len() {
+                let (current, next) = (
+                    &self.radials[idx],
+                    &self.radials[(idx + 1) % self.radials.len()],
+                );
+                let end = current.azimuth + current.width / 2.;
+                let mut next_start = next.azimuth - next.width / 2.;
+                if next_start < end - TOLERANCE {
+                    next_start += 360.;
+                }
+                let gap = next_start - end;
+                let fill = if gap > TOLERANCE && gap <= max_gap {
+                    interpolate_radials(current, next, end, gap)
+                } else {
+                    Vec::new()
+                };
+                filled.push(self.radials[idx].clone());
+                filled.extend(fill);
+            }
+            self.radials = filled;
+            self.radials.sort_by(|a, b| a.azimuth.total_cmp(&b.azimuth));
+        }
+        self
+    }
+
+    /// Fill in runs of up to `max_radials` radials that are either absent
+    /// from the scan or have no data in any bin, interpolating their rates
+    /// from the radials on either side and marking them as
+    /// [`interpolated`](Radial::interpolated). Longer runs are left alone.
+    /// The radials are normalized first, as by [`Self::normalize_radials`].
+    pub fn fill_radial_gaps(self, max_radials: usize) -> Self {
+        let mut widths: Vec<f32> = self.radials.iter().map(|r| r.width).collect();
+        widths.sort_by(|a, b| a.total_cmp(b));
+        let width = widths.get(widths.len() / 2).cloned().unwrap_or(1.);
+        let mut scan = self.normalize_radials(Some(max_radials as f32 * width + TOLERANCE));
+
+        let is_empty = |r: &Radial| {
+            !r.precip_rates.is_empty() && r.precip_rates.iter().all(|rate| rate.is_nan())
+        };
+        let count = scan.radials.len();
+        let start = match scan.radials.iter().position(|r| !is_empty(r)) {
+            Some(start) => start,
+            None => return scan,
+        };
+        // walk around the circle from a radial with data, so every run of
+        // empty radials has a radial with data on both sides
+        let mut idx = 0;
+        while idx < count {
+            let before = (start + idx) % count;
+            let run = (1..count)
+                .take_while(|offset| is_empty(&scan.radials[(before + offset) % count]))
+                .count();
+            if run > 0 && run <= max_radials {
+                let after = (before + run + 1) % count;
+                for offset in 1..=run {
+                    let t = offset as f32 / (run + 1) as f32;
+                    let current = (before + offset) % count;
+                    let (before, after) = (&scan.radials[before], &scan.radials[after]);
+                    let precip_rates = (0..scan.radials[current].precip_rates.len())
+                        .map(
+                            |b| match (before.precip_rates.get(b), after.precip_rates.get(b)) {
+                                (Some(x), Some(y)) => x * (1. - t) + y * t,
+                                _ => f32::NAN,
+                            },
+                        )
+                        .collect();
+                    scan.radials[current].precip_rates = precip_rates;
+                    scan.radials[current].interpolated = true;
+                }
+            }
+            idx += run + 1;
+        }
+        scan
+    }
+
+    /// Replace each rate with the median of the rates within `radius` bins
+    /// along the radial and `radius` radials to either side, wrapping around
+    /// north. Bins without data are ignored and stay missing. The radials
+    /// should be in azimuth order, as after [`Self::normalize_radials`].
+    pub fn median_filter(&mut self, radius: usize) {
+        let smoothed = self.map_neighborhoods(radius, |values| {
+            values.sort_by(|a, b| a.total_cmp(b));
+            let mid = values.len() / 2;
+            if values.len() % 2 == 0 {
+                (values[mid - 1] + values[mid]) / 2.
+            } else {
+                values[mid]
+            }
+        });
+        self.radials = smoothed;
+    }
+
+    /// Smooth the rates with a Gaussian kernel whose standard deviation is
+    /// `sigma` bins along the radial and `sigma` radials across them,
+    /// wrapping around north. Bins without data are ignored and stay
+    /// missing. The radials should be in azimuth order, as after
+    /// [`Self::normalize_radials`].
+    pub fn gaussian_smooth(&mut self, sigma: f32) {
+        if sigma <= 0. {
+            return;
+        }
+        let radius = (3. * sigma).ceil() as usize;
+        let kernel: Vec<f32> = (0..=radius)
+            .map(|d| (-((d * d) as f32) / (2. * sigma * sigma)).exp())
+            .collect();
+        let count = self.radials.len();
+        let convolve = |values: &mut dyn Iterator<Item = (usize, f32)>| {
+            let (sum, weight) = values
+                .filter(|(_, v)| !v.is_nan())
+                .fold((0., 0.), |(sum, weight), (d, v)| {
+                    (sum + v * kernel[d], weight + kernel[d])
+                });
+            sum / weight
+        };
+        // the kernel is separable, so smooth along each radial and then
+        // across them
+        let along: Vec<Vec<f32>> = self
+            .radials
+            .iter()
+            .map(|radial| {
+                let rates = &radial.precip_rates;
+                (0..rates.len())
+                    .map(|b| {
+                        if rates[b].is_nan() {
+                            return f32::NAN;
+                        }
+                        let (first, last) =
+                            (b.saturating_sub(radius), (b + radius).min(rates.len() - 1));
+                        convolve(&mut (first..=last).map(|n| (n.abs_diff(b), rates[n])))
+                    })
+                    .collect()
+            })
+            .collect();
+        for (r, radial) in self.radials.iter_mut().enumerate() {
+            for (b, rate) in radial.precip_rates.iter_mut().enumerate() {
+                if rate.is_nan() {
+                    continue;
+                }
+                let span = radius.min(count / 2);
+                *rate = convolve(&mut (0..=2 * span).filter_map(|i| {
+                    let n = (r + count + i - span) % count;
+                    along[n].get(b).map(|&v| (i.abs_diff(span), v))
+                }));
+            }
+        }
+    }
+
+    /// Build new radials where each rate is `reduce` applied to the rates
+    /// with data within `radius` bins and radials of it, or missing if the
+    /// bin itself is missing
+    fn map_neighborhoods<F: Fn(&mut Vec<f32>) -> f32>(
+        &self,
+        radius: usize,
+        reduce: F,
+    ) -> Vec<Radial> {
+        let count = self.radials.len();
+        let span = radius.min(count / 2);
+        let mut values = Vec::new();
+        self.radials
+            .iter()
+            .enumerate()
+            .map(|(r, radial)| {
+                let precip_rates = radial
+                    .precip_rates
+                    .iter()
+                    .enumerate()
+                    .map(|(b, rate)| {
+                        if rate.is_nan() {
+                            return f32::NAN;
+                        }
+                        values.clear();
+                        for i in 0..=2 * span {
+                            let rates = &self.radials[(r + count + i - span) % count].precip_rates;
+                            let (first, last) = (b.saturating_sub(radius), b + radius + 1);
+                            values.extend(
+                                rates.iter().take(last).skip(first).filter(|v| !v.is_nan()),
+                            );
+                        }
+                        reduce(&mut values)
+                    })
+                    .collect();
+                Radial {
+                    precip_rates,
+                    ..radial.clone()
+                }
+            })
+            .collect()
+    }
+
+    /// Resample the radials onto exactly 360 radials that are one degree wide
+    /// and centered on each half degree, starting from north. Every new bin
+    /// combines the bins at the same range from all radials that overlap it.
+    /// With [`Aggregation::Mean`], each one counts in proportion to how much
+    /// it overlaps. Bins without data are skipped, and new bins that nothing
+    /// with data contributes to, like those in degrees that no radial
+    /// covers, are missing.
+    pub fn resample_azimuths(mut self, aggregation: Aggregation) -> Self {
+        let num_bins = self
+            .radials
+            .iter()
+            .map(|r| r.precip_rates.len())
+            .max()
+            .unwrap_or(0);
+        let elevation = self.radials.first().map_or(0., |r| r.elevation);
+        let mut degrees = vec![vec![Accumulator::default(); num_bins]; 360];
+        let mut interpolated = [false; 360];
+        for radial in self.radials.iter() {
+            let start = (radial.azimuth - radial.width / 2.).rem_euclid(360.);
+            let end = start + radial.width;
+            for degree in start.floor() as usize..end.ceil() as usize {
+                let overlap = (end.min(degree as f32 + 1.) - start.max(degree as f32)).max(0.);
+                if overlap <= TOLERANCE {
+                    continue;
+                }
+                interpolated[degree % 360] |= radial.interpolated;
+                let bins = degrees[degree % 360].iter_mut();
+                for (bin, rate) in bins.zip(radial.precip_rates.iter()) {
+                    bin.add(*rate, overlap, aggregation);
+                }
+            }
+        }
+        self.radials = degrees
+            .into_iter()
+            .enumerate()
+            .map(|(degree, bins)| Radial {
+                azimuth: degree as f32 + 0.5,
+                elevation,
+                width: 1.,
+                precip_rates: bins.iter().map(|bin| bin.finish(aggregation)).collect(),
+                interpolated: interpolated[degree],
+            })
+            .collect();
+        self
+    }
+
+    /// Combine each block of `azimuth_factor` neighboring radials and
+    /// `range_factor` neighboring bins into a single bin. The bin size and
+    /// radial widths grow to match. Blocks at the end of the scan or of a
+    /// radial may be smaller than the rest. Bins without data are skipped,
+    /// so a block is only missing if none of its bins have data.
+    pub fn coarsen(
+        mut self,
+        range_factor: usize,
+        azimuth_factor: usize,
+        aggregation: Aggregation,
+    ) -> Self {
+        let (range_factor, azimuth_factor) = (range_factor.max(1), azimuth_factor.max(1));
+        self.radials = self
+            .radials
+            .chunks(azimuth_factor)
+            .map(|group| {
+                let width: f32 = group.iter().map(|r| r.width).sum();
+                let num_bins = group
+                    .iter()
+                    .map(|r| r.precip_rates.len())
+                    .max()
+                    .unwrap_or(0);
+                let precip_rates = (0..num_bins)
+                    .step_by(range_factor)
+                    .map(|first| {
+                        let mut block = Accumulator::default();
+                        for radial in group {
+                            for rate in radial.precip_rates.iter().skip(first).take(range_factor) {
+                                block.add(*rate, 1., aggregation);
+                            }
+                        }
+                        block.finish(aggregation)
+                    })
+                    .collect();
+                Radial {
+                    azimuth: (group[0].azimuth - group[0].width / 2. + width / 2.).rem_euclid(360.),
+                    elevation: group[0].elevation,
+                    width,
+                    precip_rates,
+                    interpolated: group.iter().any(|r| r.interpolated),
+                }
+            })
+            .collect();
+        self.bin_size *= range_factor as f32;
+        self
+    }
+}
+
+/// Make enough radials to cover the `gap` degrees starting at azimuth
+/// `start`, with rates that change linearly from `before` to `after`
+fn interpolate_radials(before: &Radial, after: &Radial, start: f32, gap: f32) -> Vec<Radial> {
+    let count = (gap / before.width).round().max(1.) as usize;
+    let width = gap / count as f32;
+    let num_bins = before.precip_rates.len().min(after.precip_rates.len());
+    (0..count)
+        .map(|i| {
+            let t = (i + 1) as f32 / (count + 1) as f32;
+            Radial {
+                azimuth: (start + width * (i as f32 + 0.5)).rem_euclid(360.),
+                elevation: before.elevation,
+                width,
+                precip_rates: (0..num_bins)
+                    .map(|b| before.precip_rates[b] * (1. - t) + after.precip_rates[b] * t)
+                    .collect(),
+                interpolated: true,
+            }
+        })
+        .collect()
+}
+
+#[test]
+fn test_normalize_radials() {
+    let mut azimuths: Vec<f32> = (0..360).map(|a| (a as f32 + 90.5) % 360.).collect();
+    azimuths.retain(|&a| !(10. ..13.).contains(&a));
+    azimuths.push(20.5);
+    let mut scan = crate::coverage::scan_with_azimuths(&azimuths);
+    for radial in scan.radials.iter_mut() {
+        radial.precip_rates = match radial.azimuth {
+            9.5 => vec![0.4],
+            13.5 => vec![0.8],
+            _ => vec![0.],
+        };
+    }
+    assert!(!scan.radial_diagnostics().is_clean());
+    let scan = scan.normalize_radials(Some(5.));
+    assert!(scan.radial_diagnostics().is_clean());
+    assert_eq!(scan.radials.len(), 360);
+    assert_eq!(scan.radials[0].azimuth, 0.5);
+    assert_eq!(scan.radials[11].precip_rates, vec![0.6]);
+}
+
+#[test]
+fn test_normalize_duplicate_radials() {
+    let mut scan = crate::coverage::scan_with_azimuths(&[0.5, 0.5, 1.5, f32::NAN]);
+    scan.radials[0].precip_rates = vec![0.2, f32::NAN, f32::NAN];
+    scan.radials[1].precip_rates = vec![0.4, 0.6, 0.8, f32::NAN, 1.];
+    let scan = scan.normalize_radials(None);
+    assert_eq!(scan.radials.len(), 3);
+    // bins only average the radials that have data there
+    let rates = &scan.radials[0].precip_rates;
+    assert!((rates[0] - 0.3).abs() < 1e-6);
+    assert_eq!(rates[1..3], [0.6, 0.8]);
+    assert!(rates[3].is_nan());
+    assert_eq!(rates[4], 1.);
+    // a radial without an azimuth sorts last instead of panicking
+    assert!(scan.radials[2].azimuth.is_nan());
+}
+
+#[test]
+fn test_resample_azimuths() {
+    // half-degree radials
+    let azimuths: Vec<f32> = (0..720).map(|a| a as f32 / 2. + 0.25).collect();
+    let mut scan = crate::coverage::scan_with_azimuths(&azimuths);
+    for radial in scan.radials.iter_mut() {
+        radial.width = 0.5;
+    }
+    scan.radials[0].precip_rates = vec![1.];
+    let mean = scan.resample_azimuths(Aggregation::Mean);
+    assert_eq!(mean.radials.len(), 360);
+    assert_eq!(mean.radials[0].azimuth, 0.5);
+    assert_eq!(mean.radials[0].precip_rates, vec![0.5]);
+    assert!(mean.radial_diagnostics().is_clean());
+}
+
+#[test]
+fn test_resample_azimuths_missing() {
+    // nothing covers 10° to 12°, and 20° to 21° only has missing bins
+    let azimuths: Vec<f32> = (0..360)
+        .filter(|a| !(10..12).contains(a))
+        .map(|a| a as f32 + 0.5)
+        .collect();
+    let mut scan = crate::coverage::scan_with_azimuths(&azimuths);
+    scan.radials[0].precip_rates = vec![0.5, f32::NAN];
+    scan.radials[18].precip_rates = vec![f32::NAN];
+    for aggregation in [Aggregation::Mean, Aggregation::Max] {
+        let resampled = scan.clone().resample_azimuths(aggregation);
+        assert_eq!(resampled.radials[0].precip_rates[0], 0.5);
+        assert!(resampled.radials[0].precip_rates[1].is_nan());
+        assert!(resampled.radials[10].precip_rates[0].is_nan());
+        assert!(resampled.radials[11].precip_rates[0].is_nan());
+        assert!(resampled.radials[20].precip_rates[0].is_nan());
+        assert_eq!(resampled.radials[21].precip_rates[0], 0.);
+    }
+}
+
+#[test]
+fn test_coarsen() {
+    let azimuths: Vec<f32> = (0..360).map(|a| a as f32 + 0.5).collect();
+    let mut scan = crate::coverage::scan_with_azimuths(&azimuths);
+    scan.radials[0].precip_rates = vec![0.1, 0.3, 0.2, 0.];
+    scan.radials[1].precip_rates = vec![0., 0.4, 0., 0.];
+    let scan = scan.coarsen(2, 2, Aggregation::Max);
+    assert_eq!(scan.radials.len(), 180);
+    assert_eq!((scan.radials[0].azimuth, scan.radials[0].width), (1., 2.));
+    assert_eq!(scan.radials[0].precip_rates, vec![0.4, 0.2]);
+    assert_eq!(scan.bin_size, 0.5);
+    assert!(scan.radial_diagnostics().is_clean());
+}
+
+#[test]
+fn test_coarsen_missing() {
+    let mut scan = crate::coverage::scan_with_azimuths(&[0.5, 1.5]);
+    scan.radials[0].precip_rates = vec![0.2, f32::NAN, f32::NAN, f32::NAN];
+    scan.radials[1].precip_rates = vec![f32::NAN, 0.4, f32::NAN, f32::NAN];
+    for aggregation in [Aggregation::Mean, Aggregation::Max] {
+        let coarse = scan.clone().coarsen(2, 2, aggregation);
+        let rates = &coarse.radials[0].precip_rates;
+        assert!(rates[1].is_nan());
+        match aggregation {
+            Aggregation::Mean => assert!((rates[0] - 0.3).abs() < 1e-6),
+            Aggregation::Max => assert_eq!(rates[0], 0.4),
+        }
+    }
+}
+
+#[test]
+fn test_fill_radial_gaps() {
+    let azimuths: Vec<f32> = (0..360)
+        .filter(|a| *a != 100 && *a != 101)
+        .map(|a| a as f32 + 0.5)
+        .collect();
+    let mut scan = crate::coverage::scan_with_azimuths(&azimuths);
+    scan.radials[99].precip_rates = vec![0.3];
+    scan.radials[100].precip_rates = vec![0.9];
+    // one radial without data and a run of three that's too long to fill
+    scan.radials[200].precip_rates = vec![f32::NAN];
+    for radial in scan.radials[250..253].iter_mut() {
+        radial.precip_rates = vec![f32::NAN];
+    }
+    let scan = scan.fill_radial_gaps(2);
+    assert_eq!(scan.radials.len(), 360);
+    assert!(scan.radial_diagnostics().is_clean());
+    assert!((scan.radials[100].precip_rates[0] - 0.5).abs() < 1e-6);
+    assert!(scan.radials[100].interpolated && scan.radials[101].interpolated);
+    assert_eq!(scan.radials[202].precip_rates, vec![0.]);
+    assert!(scan.radials[202].interpolated);
+    assert!(scan.radials[252].precip_rates[0].is_nan());
+    assert!(!scan.radials[252].interpolated);
+}
+
+#[test]
+fn test_smoothing() {
+    // a speckle that the median removes, next to a bin without data
+    let speckled = || {
+        let azimuths: Vec<f32> = (0..360).map(|a| a as f32 + 0.5).collect();
+        let mut scan = crate::coverage::scan_with_azimuths(&azimuths);
+        for radial in scan.radials.iter_mut() {
+            radial.precip_rates = vec![0.2; 5];
+        }
+        scan.radials[0].precip_rates[2] = 5.;
+        scan.radials[0].precip_rates[3] = f32::NAN;
+        scan
+    };
+    let mut median = speckled();
+    median.median_filter(1);
+    assert_eq!(median.radials[0].precip_rates[2], 0.2);
+    assert!(median.radials[0].precip_rates[3].is_nan());
+
+    let mut scan = speckled();
+    scan.gaussian_smooth(1.);
+    let smoothed = &scan.radials[0].precip_rates;
+    assert!(smoothed[2] > 0.2 && smoothed[2] < 5.);
+    // the speckle spreads to the neighbors on both sides of north
+    assert!(scan.radials[359].precip_rates[2] > 0.2);
+    assert!(smoothed[3].is_nan());
+    assert!((scan.radials[180].precip_rates[0] - 0.2).abs() < 1e-6);
+}
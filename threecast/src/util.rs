@@ -1,7 +1,73 @@
 use std::error::Error;
+use std::io::Read;
 
+use crate::net::get_data_by_url;
 use crate::parse::{coord_as_i64, GridData};
 
+/// The first two bytes of every gzip stream
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Decompress `data` if it's gzipped, otherwise return it unchanged. Data
+/// files never start with the gzip magic bytes, since they start with an
+/// ASCII WMO header.
+pub fn gunzip_if_needed(data: Vec<u8>) -> Result<Vec<u8>, Box<dyn Error>> {
+    if !data.starts_with(&GZIP_MAGIC) {
+        return Ok(data);
+    }
+    let mut decompressed = Vec::new();
+    flate2::read::MultiGzDecoder::new(data.as_slice()).read_to_end(&mut decompressed)?;
+    Ok(decompressed)
+}
+
+/// Read a data file from `input`, which is a path, an `http://`,
+/// `https://`, or `s3://` URL to download, or `-` for stdin, and decompress
+/// it if it's gzipped
+pub fn read_input(input: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    let data = if input == "-" {
+        let mut data = Vec::new();
+        std::io::stdin().read_to_end(&mut data)?;
+        data
+    } else if input.starts_with("http://") || input.starts_with("https://") {
+        get_data_by_url(input)?
+    } else if input.starts_with("s3://") {
+        crate::s3::get_object(input)?
+    } else {
+        std::fs::read(input)?
+    };
+    gunzip_if_needed(data)
+}
+
+/// The name of an input and its contents
+pub type NamedInput = (String, Vec<u8>);
+
+/// True if `data` is a tar archive, judging by the magic in its first header
+fn is_tar(data: &[u8]) -> bool {
+    data.get(257..262) == Some(b"ustar".as_slice())
+}
+
+/// Read `input` like [`read_input`], but if it's a tar archive (optionally
+/// gzipped) like the bundles NCEI delivers, return every regular file in it
+/// instead. Each file is named `input:member` and gunzipped if needed.
+/// Anything else comes back as a single file named `input`.
+pub fn read_inputs(input: &str) -> Result<Vec<NamedInput>, Box<dyn Error>> {
+    let data = read_input(input)?;
+    if !is_tar(&data) {
+        return Ok(vec![(String::from(input), data)]);
+    }
+    let mut files = Vec::new();
+    for entry in tar::Archive::new(data.as_slice()).entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let name = format!("{}:{}", input, entry.path()?.display());
+        let mut member = Vec::new();
+        entry.read_to_end(&mut member)?;
+        files.push((name, gunzip_if_needed(member)?));
+    }
+    Ok(files)
+}
+
 #[allow(clippy::ptr_arg)]
 pub fn find_pixel_by_lat_long(
     pixels: &GridData,
@@ -28,3 +94,47 @@ pub fn find_pixel_by_lat_long(
 
     Ok((y, x))
 }
+
+/// Classify a precipitation rate in in/hr as `"none"`, `"light"`,
+/// `"moderate"`, `"heavy"`, or `"violent"`, or `"missing"` if it's `NaN`
+pub fn precip_category(rate: f32) -> &'static str {
+    match rate {
+        r if r.is_nan() => "missing",
+        r if r <= 0. => "none",
+        r if r < 0.098 => "light",
+        r if r < 0.35 => "moderate",
+        r if r < 2. => "heavy",
+        _ => "violent",
+    }
+}
+
+#[test]
+fn test_gunzip_if_needed() {
+    use std::io::Write;
+    let data = b"SDUS51 KGYX 010000".to_vec();
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(&data).unwrap();
+    let gzipped = encoder.finish().unwrap();
+    assert_eq!(gunzip_if_needed(gzipped).unwrap(), data);
+    assert_eq!(gunzip_if_needed(data.clone()).unwrap(), data);
+}
+
+#[test]
+fn test_read_inputs_tar() {
+    let mut builder = tar::Builder::new(Vec::new());
+    for (name, contents) in [("a", b"SDUS51 KGYX"), ("b", b"SDUS52 KCBW")] {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(contents.len() as u64);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, name, contents.as_slice())
+            .unwrap();
+    }
+    let path = std::env::temp_dir().join("threecast-test-read-inputs.tar");
+    std::fs::write(&path, builder.into_inner().unwrap()).unwrap();
+    let path = path.to_string_lossy().to_string();
+    let files = read_inputs(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(files.len(), 2);
+    assert_eq!(files[1], (format!("{}:b", path), b"SDUS52 KCBW".to_vec()));
+}
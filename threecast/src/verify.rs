@@ -0,0 +1,157 @@
+use crate::geomath::get_point_bearing_distance;
+use crate::hrap::HrapGrid;
+use crate::parse::{coord_as_i64, GridData, PrecipRate};
+
+/// Latitude of the center of the northernmost row of the MRMS CONUS grid
+const MRMS_NORTH_LAT: f32 = 54.995;
+/// Longitude of the center of the westernmost column of the MRMS CONUS grid
+const MRMS_WEST_LON: f32 = -129.995;
+/// Spacing between MRMS grid cells in degrees
+const MRMS_SPACING: f32 = 0.01;
+const MRMS_ROWS: usize = 3500;
+const MRMS_COLUMNS: usize = 7000;
+
+/// Comparison statistics between a radar-derived grid and a reference grid,
+/// such as an MRMS or Stage IV quantitative precipitation estimate
+#[derive(Debug)]
+pub struct Verification {
+    /// Mean of the estimate minus the reference
+    pub bias: f32,
+    /// Pearson correlation coefficient, or `NaN` if either grid is constant
+    pub correlation: f32,
+    /// Mean absolute error
+    pub mae: f32,
+    /// Number of grid cells that went into the statistics
+    pub samples: usize,
+}
+
+/// Build an empty grid aligned with the MRMS CONUS 0.01-degree grid that
+/// covers a square of `radius` kilometers around the given coordinate. This is
+/// useful as the target for [`PrecipRate::sample_radials_to_grid`] when the
+/// reference data is an MRMS product. Returns `None` if the area is entirely
+/// outside the MRMS domain.
+pub fn mrms_grid_around(latitude: f32, longitude: f32, radius: f32) -> Option<GridData> {
+    let north = get_point_bearing_distance((latitude, longitude), 0., radius).0;
+    let south = get_point_bearing_distance((latitude, longitude), 180., radius).0;
+    let west = get_point_bearing_distance((latitude, longitude), 270., radius).1;
+    let east = get_point_bearing_distance((latitude, longitude), 90., radius).1;
+    if south > MRMS_NORTH_LAT
+        || north < MRMS_NORTH_LAT - MRMS_SPACING * MRMS_ROWS as f32
+        || east < MRMS_WEST_LON
+        || west > MRMS_WEST_LON + MRMS_SPACING * MRMS_COLUMNS as f32
+    {
+        return None;
+    }
+    let to_index = |offset: f32, max: usize| {
+        (offset / MRMS_SPACING).round().clamp(0., max as f32 - 1.) as usize
+    };
+    let (y_0, y_n) = (
+        to_index(MRMS_NORTH_LAT - north, MRMS_ROWS),
+        to_index(MRMS_NORTH_LAT - south, MRMS_ROWS),
+    );
+    let (x_0, x_n) = (
+        to_index(west - MRMS_WEST_LON, MRMS_COLUMNS),
+        to_index(east - MRMS_WEST_LON, MRMS_COLUMNS),
+    );
+    Some(
+        (y_0..=y_n)
+            .map(|y| {
+                let lat = MRMS_NORTH_LAT - MRMS_SPACING * y as f32;
+                (x_0..=x_n)
+                    .map(|x| {
+                        let lon = MRMS_WEST_LON + MRMS_SPACING * x as f32;
+                        ([coord_as_i64(lat), coord_as_i64(lon)], 0.)
+                    })
+                    .collect()
+            })
+            .collect(),
+    )
+}
+
+/// Build an empty grid of Stage IV cells that covers a square of `radius`
+/// kilometers around the given coordinate. Stage IV is on the HRAP grid, so
+/// this is [`HrapGrid::empty_grid`] for the window from [`HrapGrid::around`],
+/// and the cells can be matched up with a Stage IV file by their HRAP
+/// coordinates. Reading the Stage IV GRIB files themselves is left to the
+/// caller, since they use a polar stereographic grid that
+/// [`crate::mrms::parse_mrms_grib2`] doesn't handle.
+pub fn stage_iv_grid_around(latitude: f32, longitude: f32, radius: f32) -> (HrapGrid, GridData) {
+    let hrap = HrapGrid::around(latitude, longitude, radius);
+    (hrap, hrap.empty_grid())
+}
+
+/// Compare two grids of the same dimensions cell by cell. Both grids must use
+/// the same units; `threecast` reports inches per hour, while MRMS and Stage IV
+/// use millimeters, so divide those by 25.4 first.
+#[allow(clippy::ptr_arg)]
+pub fn compare_grids(estimate: &GridData, reference: &GridData) -> Result<Verification, String> {
+    if estimate.len() != reference.len()
+        || estimate
+            .iter()
+            .zip(reference.iter())
+            .any(|(e, r)| e.len() != r.len())
+    {
+        return Err("Cannot compare grids with different dimensions".into());
+    }
+    let pairs: Vec<(f32, f32)> = estimate
+        .iter()
+        .flatten()
+        .zip(reference.iter().flatten())
+        .map(|(e, r)| (e.1, r.1))
+        .collect();
+    if pairs.is_empty() {
+        return Err("Cannot compare empty grids".into());
+    }
+    let n = pairs.len() as f32;
+    let mean_estimate = pairs.iter().map(|p| p.0).sum::<f32>() / n;
+    let mean_reference = pairs.iter().map(|p| p.1).sum::<f32>() / n;
+    let (mut covariance, mut variance_estimate, mut variance_reference) = (0., 0., 0.);
+    for (e, r) in pairs.iter() {
+        covariance += (e - mean_estimate) * (r - mean_reference);
+        variance_estimate += (e - mean_estimate).powi(2);
+        variance_reference += (r - mean_reference).powi(2);
+    }
+    Ok(Verification {
+        bias: mean_estimate - mean_reference,
+        correlation: covariance / (variance_estimate * variance_reference).sqrt(),
+        mae: pairs.iter().map(|(e, r)| (e - r).abs()).sum::<f32>() / n,
+        samples: pairs.len(),
+    })
+}
+
+/// Regrid a scan onto the grid of a reference dataset and compare the two
+#[allow(clippy::ptr_arg)]
+pub fn verify_scan(scan: &PrecipRate, reference: &GridData) -> Result<Verification, String> {
+    compare_grids(&scan.sample_radials_to_grid(reference), reference)
+}
+
+#[test]
+fn test_compare_grids() {
+    let estimate: GridData = vec![
+        vec![([0, 0], 1.), ([0, 0], 2.)],
+        vec![([0, 0], 3.), ([0, 0], 4.)],
+    ];
+    let reference: GridData = vec![
+        vec![([0, 0], 0.), ([0, 0], 1.)],
+        vec![([0, 0], 2.), ([0, 0], 3.)],
+    ];
+    let verification = compare_grids(&estimate, &reference).unwrap();
+    assert_eq!(verification.bias, 1.);
+    assert_eq!(verification.mae, 1.);
+    assert!((verification.correlation - 1.).abs() < 1e-6);
+    assert_eq!(verification.samples, 4);
+    assert!(compare_grids(&estimate, &reference[..1].to_vec()).is_err());
+}
+
+#[test]
+fn test_stage_iv_grid_around() {
+    let (hrap, grid) = stage_iv_grid_around(43.891, -70.256, 50.);
+    assert_eq!(hrap, HrapGrid::around(43.891, -70.256, 50.));
+    assert_eq!((grid.len(), grid[0].len()), (hrap.rows, hrap.columns));
+    let mut scan = crate::coverage::scan_with_azimuths(&[0.5, 1.5]);
+    scan.radials[0].precip_rates = vec![0.5; 200];
+    assert_eq!(
+        verify_scan(&scan, &grid).unwrap().samples,
+        hrap.rows * hrap.columns
+    );
+}
@@ -0,0 +1,98 @@
+//! How much water is falling, rather than how hard, for questions like "how
+//! much rain fell on my catchment?"
+
+use crate::filter::BinFilter;
+use crate::output::BinRef;
+use crate::parse::PrecipRate;
+
+/// Cubic meters per second of water falling at one in/hr on one square
+/// kilometer
+const FLUX_PER_INCH_HOUR_KM2: f64 = 0.0254 * 1e6 / 3600.;
+
+impl PrecipRate {
+    /// Water falling over the whole scan in cubic meters per second. Bins
+    /// without data count as dry.
+    pub fn water_flux(&self) -> f64 {
+        self.water_flux_filtered(&|_: &BinRef| true)
+    }
+
+    /// Water falling on the bins that pass `filter` in cubic meters per
+    /// second, e.g. with [`crate::filter::InPolygon`] for a catchment
+    pub fn water_flux_filtered(&self, filter: &dyn BinFilter) -> f64 {
+        self.bins_filtered(filter)
+            .filter(|bin| !bin.is_missing())
+            .map(|bin| bin.rate() as f64 * bin.area() as f64 * FLUX_PER_INCH_HOUR_KM2)
+            .sum()
+    }
+}
+
+/// Scans of one station in time order, for integrating over time
+#[derive(Debug, Default)]
+pub struct ScanSeries {
+    scans: Vec<PrecipRate>,
+}
+
+impl ScanSeries {
+    pub fn new(mut scans: Vec<PrecipRate>) -> Self {
+        scans.sort_by_key(|scan| scan.capture_time);
+        ScanSeries { scans }
+    }
+
+    /// Add a scan, keeping the series in time order
+    pub fn push(&mut self, scan: PrecipRate) {
+        let idx = self
+            .scans
+            .partition_point(|s| s.capture_time <= scan.capture_time);
+        self.scans.insert(idx, scan);
+    }
+
+    pub fn scans(&self) -> &[PrecipRate] {
+        &self.scans
+    }
+
+    /// Total water in cubic meters that fell on the bins that pass `filter`
+    /// between the first and last scans, assuming the flux changes linearly
+    /// from one scan to the next
+    pub fn water_volume(&self, filter: &dyn BinFilter) -> f64 {
+        let fluxes: Vec<(chrono::NaiveDateTime, f64)> = self
+            .scans
+            .iter()
+            .map(|scan| (scan.capture_time, scan.water_flux_filtered(filter)))
+            .collect();
+        fluxes
+            .windows(2)
+            .map(|pair| {
+                let seconds = (pair[1].0 - pair[0].0).num_milliseconds() as f64 / 1000.;
+                (pair[0].1 + pair[1].1) / 2. * seconds
+            })
+            .sum()
+    }
+}
+
+#[test]
+fn test_water_volume() {
+    // one inch per hour over a full circle of radius 1 km
+    let azimuths: Vec<f32> = (0..360).map(|a| a as f32 + 0.5).collect();
+    let scan = |minutes: i64| {
+        let mut scan = crate::coverage::scan_with_azimuths(&azimuths);
+        scan.bin_size = 1.;
+        scan.capture_time += chrono::Duration::minutes(minutes);
+        for radial in scan.radials.iter_mut() {
+            radial.precip_rates = vec![1.];
+        }
+        scan
+    };
+    let flux = scan(0).water_flux();
+    let expected = std::f64::consts::PI * FLUX_PER_INCH_HOUR_KM2;
+    assert!((flux - expected).abs() / expected < 1e-4, "{}", flux);
+
+    let mut series = ScanSeries::new(vec![scan(60)]);
+    series.push(scan(0));
+    // an inch over a square kilometer is 25,400 cubic meters
+    let volume = series.water_volume(&|_: &BinRef| true);
+    assert!(
+        (volume - std::f64::consts::PI * 25400.).abs() < 10.,
+        "{}",
+        volume
+    );
+}
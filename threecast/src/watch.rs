@@ -0,0 +1,212 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::time::{Duration, Instant};
+
+use crate::parse::{coord_as_i64, GridData};
+use crate::util::find_pixel_by_lat_long;
+
+/// The area covered by a [`Watch`]
+#[derive(Debug, Clone)]
+pub enum WatchArea {
+    /// A single (latitude, longitude) coordinate
+    Point(f32, f32),
+    /// A closed ring of (latitude, longitude) vertices
+    Polygon(Vec<(f32, f32)>),
+}
+
+impl WatchArea {
+    /// A representative coordinate for the area, used to pick a station
+    pub fn center(&self) -> (f32, f32) {
+        match self {
+            WatchArea::Point(lat, lon) => (*lat, *lon),
+            WatchArea::Polygon(vertices) => {
+                let n = vertices.len() as f32;
+                (
+                    vertices.iter().map(|v| v.0).sum::<f32>() / n,
+                    vertices.iter().map(|v| v.1).sum::<f32>() / n,
+                )
+            }
+        }
+    }
+
+    /// The highest rate within the area on the given grid
+    #[allow(clippy::ptr_arg)]
+    fn peak_rate(&self, grid: &GridData) -> Result<f32, Box<dyn Error>> {
+        match self {
+            WatchArea::Point(lat, lon) => {
+                let (y, x) = find_pixel_by_lat_long(grid, *lat, *lon)?;
+                Ok(grid[y][x].1)
+            }
+            WatchArea::Polygon(vertices) => {
+                let ring: Vec<[i64; 2]> = vertices
+                    .iter()
+                    .map(|v| [coord_as_i64(v.0), coord_as_i64(v.1)])
+                    .collect();
+                Ok(grid
+                    .iter()
+                    .flatten()
+                    .filter(|point| contains(&ring, point.0))
+                    .map(|point| point.1)
+                    .fold(0., f32::max))
+            }
+        }
+    }
+}
+
+/// Ray-casting point-in-polygon test
+pub(crate) fn contains(ring: &[[i64; 2]], point: [i64; 2]) -> bool {
+    let mut inside = false;
+    let mut j = ring.len().wrapping_sub(1);
+    for i in 0..ring.len() {
+        let (a, b) = (ring[i], ring[j]);
+        if (a[0] > point[0]) != (b[0] > point[0])
+            && (point[1] as f64)
+                < (b[1] - a[1]) as f64 * (point[0] - a[0]) as f64 / (b[0] - a[0]) as f64
+                    + a[1] as f64
+        {
+            inside = !inside;
+        }
+        j = i;
+    }
+    inside
+}
+
+/// A named area to monitor for precipitation
+#[derive(Debug, Clone)]
+pub struct Watch {
+    pub name: String,
+    pub area: WatchArea,
+    /// Rate in in/hr above which an [`AlertKind::Exceedance`] is raised
+    pub threshold: f32,
+}
+
+impl Watch {
+    /// The highest rate within the watch area for each of the given grids
+    pub fn peak_rates(&self, predictions: &[GridData]) -> Result<Vec<f32>, Box<dyn Error>> {
+        predictions
+            .iter()
+            .map(|grid| self.area.peak_rate(grid))
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AlertKind {
+    /// Precipitation is observed or predicted where there was none
+    Onset,
+    /// The rate is observed or predicted to exceed the watch threshold
+    Exceedance,
+    /// No precipitation is observed or predicted anymore
+    AllClear,
+}
+
+impl AlertKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AlertKind::Onset => "onset",
+            AlertKind::Exceedance => "exceedance",
+            AlertKind::AllClear => "all-clear",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Alert {
+    pub watch: String,
+    pub kind: AlertKind,
+    /// Minutes from now until the condition is reached, where zero means that
+    /// it's observed right now
+    pub minutes: u32,
+    /// Highest rate in the area over the whole prediction horizon
+    pub peak_rate: f32,
+}
+
+/// Turns predictions into alerts for a set of watches. Alerts are only raised
+/// when the condition for a watch changes, and an alert of a given kind is
+/// suppressed if the same kind was already raised for the same watch within
+/// the cooldown period.
+pub struct AlertTracker {
+    cooldown: Duration,
+    states: HashMap<String, AlertKind>,
+    last_sent: HashMap<(String, AlertKind), Instant>,
+}
+
+impl AlertTracker {
+    pub fn new(cooldown: Duration) -> Self {
+        AlertTracker {
+            cooldown,
+            states: HashMap::new(),
+            last_sent: HashMap::new(),
+        }
+    }
+
+    /// Evaluate a watch against a set of predictions (as returned by
+    /// [`crate::predict::nowcast`]) and return an alert if one is due
+    pub fn update(
+        &mut self,
+        watch: &Watch,
+        predictions: &[GridData],
+    ) -> Result<Option<Alert>, Box<dyn Error>> {
+        let rates = watch.peak_rates(predictions)?;
+        let peak_rate = rates.iter().cloned().fold(0., f32::max);
+        let (kind, minutes) = if let Some(idx) = rates.iter().position(|r| *r > watch.threshold) {
+            (AlertKind::Exceedance, idx as u32 * 5)
+        } else if let Some(idx) = rates.iter().position(|r| *r > 0.) {
+            (AlertKind::Onset, idx as u32 * 5)
+        } else {
+            (AlertKind::AllClear, 0)
+        };
+        // the first evaluation of a clear watch is not news
+        let previous = *self.states.get(&watch.name).unwrap_or(&AlertKind::AllClear);
+        self.states.insert(watch.name.clone(), kind);
+        if previous == kind || (previous == AlertKind::Exceedance && kind == AlertKind::Onset) {
+            return Ok(None);
+        }
+        let key = (watch.name.clone(), kind);
+        if let Some(sent) = self.last_sent.get(&key) {
+            if sent.elapsed() < self.cooldown {
+                return Ok(None);
+            }
+        }
+        self.last_sent.insert(key, Instant::now());
+        Ok(Some(Alert {
+            watch: watch.name.clone(),
+            kind,
+            minutes,
+            peak_rate,
+        }))
+    }
+}
+
+#[test]
+fn test_alert_tracker() {
+    let dry: GridData = vec![vec![([10000, 0], 0.), ([10000, 10000], 0.)]];
+    let wet: GridData = vec![vec![([10000, 0], 0.2), ([10000, 10000], 0.)]];
+    let watch = Watch {
+        name: String::from("home"),
+        area: WatchArea::Point(1., 0.),
+        threshold: 0.5,
+    };
+    let mut tracker = AlertTracker::new(Duration::from_secs(3600));
+    assert!(tracker
+        .update(&watch, std::slice::from_ref(&dry))
+        .unwrap()
+        .is_none());
+    let alert = tracker
+        .update(&watch, &[dry.clone(), wet.clone()])
+        .unwrap()
+        .unwrap();
+    assert_eq!(alert.kind, AlertKind::Onset);
+    assert_eq!(alert.minutes, 5);
+    assert!(tracker
+        .update(&watch, std::slice::from_ref(&wet))
+        .unwrap()
+        .is_none());
+    let alert = tracker
+        .update(&watch, std::slice::from_ref(&dry))
+        .unwrap()
+        .unwrap();
+    assert_eq!(alert.kind, AlertKind::AllClear);
+    // the second onset falls within the cooldown
+    assert!(tracker.update(&watch, &[wet]).unwrap().is_none());
+}
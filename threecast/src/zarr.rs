@@ -0,0 +1,264 @@
+//! [Zarr](https://zarr.readthedocs.io/en/stable/spec/v2.html) datacubes of
+//! resampled scans for xarray and the rest of the Pangeo stack
+//!
+//! A store is a directory holding a `precip_rate` array with dimensions
+//! `(time, y, x)`, chunked one scan per chunk, plus `time` and 2D `lat` and
+//! `lon` coordinates. Appending a scan writes one new chunk and bumps the
+//! shapes in the metadata, so a whole archive can be converted one file at
+//! a time. Metadata is also consolidated into `.zmetadata` so that xarray
+//! can open the store without listing it.
+
+use std::error::Error;
+use std::io::Write;
+use std::path::Path;
+
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use serde_json::{json, Value};
+
+use crate::parse::{GridData, PrecipRate};
+
+/// The arrays in a store, each in its own directory
+const ARRAYS: [&str; 4] = ["precip_rate", "time", "lat", "lon"];
+
+fn read_json(path: &Path) -> Result<Value, Box<dyn Error>> {
+    Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+}
+
+fn write_json(path: &Path, value: &Value) -> Result<(), Box<dyn Error>> {
+    std::fs::write(path, serde_json::to_string_pretty(value)?)?;
+    Ok(())
+}
+
+/// Array metadata with zlib compression and no filters
+fn array_metadata(shape: &[usize], chunks: &[usize], dtype: &str, fill_value: Value) -> Value {
+    json!({
+        "zarr_format": 2,
+        "shape": shape,
+        "chunks": chunks,
+        "dtype": dtype,
+        "compressor": { "id": "zlib", "level": 6 },
+        "fill_value": fill_value,
+        "order": "C",
+        "filters": null,
+    })
+}
+
+fn write_chunk(path: &Path, data: &[u8]) -> Result<(), Box<dyn Error>> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data)?;
+    std::fs::write(path, encoder.finish()?)?;
+    Ok(())
+}
+
+/// Start a store for grids of `rows` by `columns` at `path`, with the
+/// coordinates of `grid` and no time slices yet
+fn create(scan: &PrecipRate, grid: &GridData, path: &Path) -> Result<(), Box<dyn Error>> {
+    let (rows, columns) = (grid.len(), grid[0].len());
+    std::fs::create_dir_all(path)?;
+    write_json(&path.join(".zgroup"), &json!({ "zarr_format": 2 }))?;
+    write_json(
+        &path.join(".zattrs"),
+        &json!({
+            "Conventions": "CF-1.8",
+            "title": "NEXRAD Level III digital precipitation rate",
+            "source": "threecast",
+            "station": scan.station_code.to_uppercase(),
+            "station_latitude": scan.latitude,
+            "station_longitude": scan.longitude,
+        }),
+    )?;
+    let attributes = [
+        json!({
+            "_ARRAY_DIMENSIONS": ["time", "y", "x"],
+            "standard_name": "lwe_precipitation_rate",
+            "long_name": "digital precipitation rate",
+            "units": "in h-1",
+            "coordinates": "lat lon",
+        }),
+        json!({
+            "_ARRAY_DIMENSIONS": ["time"],
+            "standard_name": "time",
+            "long_name": "start of the volume scan",
+            "units": "seconds since 1970-01-01 00:00:00",
+            "calendar": "standard",
+        }),
+        json!({
+            "_ARRAY_DIMENSIONS": ["y", "x"],
+            "standard_name": "latitude",
+            "units": "degrees_north",
+        }),
+        json!({
+            "_ARRAY_DIMENSIONS": ["y", "x"],
+            "standard_name": "longitude",
+            "units": "degrees_east",
+        }),
+    ];
+    let metadata = [
+        array_metadata(
+            &[0, rows, columns],
+            &[1, rows, columns],
+            "<f4",
+            "NaN".into(),
+        ),
+        array_metadata(&[0], &[1], "<i8", Value::Null),
+        array_metadata(&[rows, columns], &[rows, columns], "<f4", "NaN".into()),
+        array_metadata(&[rows, columns], &[rows, columns], "<f4", "NaN".into()),
+    ];
+    for ((name, attributes), metadata) in ARRAYS.iter().zip(attributes).zip(metadata) {
+        let directory = path.join(name);
+        std::fs::create_dir_all(&directory)?;
+        write_json(&directory.join(".zattrs"), &attributes)?;
+        write_json(&directory.join(".zarray"), &metadata)?;
+    }
+
+    // coordinates are stored as degrees * 10000 in the grid
+    let coordinate = |axis: usize| -> Vec<u8> {
+        grid.iter()
+            .flatten()
+            .flat_map(|(coords, _)| (coords[axis] as f32 / 10000.).to_le_bytes())
+            .collect()
+    };
+    write_chunk(&path.join("lat").join("0.0"), &coordinate(0))?;
+    write_chunk(&path.join("lon").join("0.0"), &coordinate(1))?;
+    Ok(())
+}
+
+/// Gather every metadata file into `.zmetadata`
+fn consolidate(path: &Path) -> Result<(), Box<dyn Error>> {
+    let mut metadata = serde_json::Map::new();
+    for key in [".zgroup", ".zattrs"] {
+        metadata.insert(String::from(key), read_json(&path.join(key))?);
+    }
+    for name in ARRAYS {
+        for key in [".zarray", ".zattrs"] {
+            metadata.insert(
+                format!("{}/{}", name, key),
+                read_json(&path.join(name).join(key))?,
+            );
+        }
+    }
+    write_json(
+        &path.join(".zmetadata"),
+        &json!({ "zarr_consolidated_format": 1, "metadata": metadata }),
+    )
+}
+
+/// Append `grid`, which was sampled from `scan`, as the next time slice of
+/// the store at `path`, creating the store if it doesn't exist yet. Every
+/// grid in a store must have the same shape and come from the same
+/// station. The coordinates are taken from the first grid.
+#[allow(clippy::ptr_arg)]
+pub fn append_zarr(scan: &PrecipRate, grid: &GridData, path: &Path) -> Result<(), Box<dyn Error>> {
+    let (rows, columns) = (grid.len(), grid.first().map_or(0, |row| row.len()));
+    if rows == 0 || columns == 0 || grid.iter().any(|row| row.len() != columns) {
+        return Err("Grid must be a non-empty rectangle".into());
+    }
+    let array_path = path.join("precip_rate").join(".zarray");
+    if !array_path.exists() {
+        create(scan, grid, path)?;
+    }
+
+    let station = scan.station_code.to_uppercase();
+    let stored_station = read_json(&path.join(".zattrs"))?["station"].clone();
+    if stored_station != station.as_str() {
+        return Err(format!(
+            "Zarr store at {} holds scans from {}, not {}",
+            path.display(),
+            stored_station,
+            station
+        )
+        .into());
+    }
+    let mut metadata = read_json(&array_path)?;
+    let shape: Vec<usize> = serde_json::from_value(metadata["shape"].clone())?;
+    if shape[1..] != [rows, columns] {
+        return Err(format!(
+            "Zarr store at {} holds {} by {} grids, not {} by {}",
+            path.display(),
+            shape[1],
+            shape[2],
+            rows,
+            columns
+        )
+        .into());
+    }
+
+    let index = shape[0];
+    let rates: Vec<u8> = grid
+        .iter()
+        .flatten()
+        .flat_map(|(_, rate)| rate.to_le_bytes())
+        .collect();
+    write_chunk(
+        &path.join("precip_rate").join(format!("{}.0.0", index)),
+        &rates,
+    )?;
+    write_chunk(
+        &path.join("time").join(index.to_string()),
+        &scan.capture_time.timestamp().to_le_bytes(),
+    )?;
+
+    // only bump the shapes once the chunks are safely written
+    metadata["shape"][0] = (index + 1).into();
+    write_json(&array_path, &metadata)?;
+    let time_path = path.join("time").join(".zarray");
+    let mut time = read_json(&time_path)?;
+    time["shape"][0] = (index + 1).into();
+    write_json(&time_path, &time)?;
+    consolidate(path)
+}
+
+#[test]
+fn test_append_zarr() {
+    let path = std::env::temp_dir().join(format!("threecast-test-{}.zarr", std::process::id()));
+    let _ = std::fs::remove_dir_all(&path);
+    let mut scan = crate::coverage::scan_with_azimuths(&[0.5]);
+    let grid: GridData = vec![
+        vec![([440000, -700000], 0.), ([440000, -695000], 0.5)],
+        vec![([435000, -700000], f32::NAN), ([435000, -695000], 0.25)],
+    ];
+    append_zarr(&scan, &grid, &path).unwrap();
+    scan.capture_time += chrono::Duration::minutes(5);
+    let mut later = grid.clone();
+    later[0][0].1 = 1.;
+    append_zarr(&scan, &later, &path).unwrap();
+
+    let shape = |name: &str| read_json(&path.join(name).join(".zarray")).unwrap()["shape"].clone();
+    assert_eq!(shape("precip_rate"), json!([2, 2, 2]));
+    assert_eq!(shape("time"), json!([2]));
+    assert_eq!(shape("lat"), json!([2, 2]));
+    let consolidated = read_json(&path.join(".zmetadata")).unwrap();
+    assert_eq!(
+        consolidated["metadata"]["precip_rate/.zarray"]["shape"],
+        json!([2, 2, 2])
+    );
+
+    let chunk = |name: &str| {
+        let mut data = Vec::new();
+        std::io::Read::read_to_end(
+            &mut flate2::read::ZlibDecoder::new(std::fs::File::open(path.join(name)).unwrap()),
+            &mut data,
+        )
+        .unwrap();
+        data
+    };
+    let rates: Vec<f32> = chunk("precip_rate/1.0.0")
+        .chunks(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect();
+    assert_eq!(rates[..2], [1., 0.5]);
+    assert!(rates[2].is_nan());
+    assert_eq!(chunk("time/1"), 300i64.to_le_bytes());
+    let latitudes: Vec<u8> = [44f32, 44., 43.5, 43.5]
+        .iter()
+        .flat_map(|l| l.to_le_bytes())
+        .collect();
+    assert_eq!(chunk("lat/0.0"), latitudes);
+
+    assert!(append_zarr(&scan, &grid[..1].to_vec(), &path).is_err());
+    scan.station_code = String::from("kbox");
+    assert!(append_zarr(&scan, &grid, &path).is_err());
+    assert_eq!(shape("time"), json!([2]));
+    std::fs::remove_dir_all(&path).unwrap();
+}
@@ -0,0 +1,226 @@
+//! Per-zone statistics against boundaries such as counties or catchments
+
+use std::error::Error;
+
+use crate::filter::BinFilter;
+use crate::output::BinRef;
+use crate::parse::{coord_as_i64, PrecipRate};
+use crate::watch::contains;
+
+/// A named area made of one or more polygons, each an outer ring with
+/// optional holes
+#[derive(Debug, Clone)]
+pub struct Zone {
+    pub name: String,
+    polygons: Vec<Vec<Vec<[i64; 2]>>>,
+    /// `[south, west, north, east]`, to skip most bins cheaply
+    bbox: [i64; 4],
+    /// The GeoJSON feature the zone came from, if any
+    pub feature: Option<serde_json::Value>,
+}
+
+impl Zone {
+    /// A zone from polygons of (latitude, longitude) rings in degrees. The
+    /// first ring of each polygon is its outline and the rest are holes.
+    pub fn new(name: &str, polygons: &[Vec<Vec<(f32, f32)>>]) -> Self {
+        let polygons: Vec<Vec<Vec<[i64; 2]>>> = polygons
+            .iter()
+            .map(|rings| {
+                rings
+                    .iter()
+                    .map(|ring| {
+                        ring.iter()
+                            .map(|v| [coord_as_i64(v.0), coord_as_i64(v.1)])
+                            .collect()
+                    })
+                    .collect()
+            })
+            .collect();
+        let mut bbox = [i64::MAX, i64::MAX, i64::MIN, i64::MIN];
+        for vertex in polygons.iter().filter_map(|p| p.first()).flatten() {
+            bbox = [
+                bbox[0].min(vertex[0]),
+                bbox[1].min(vertex[1]),
+                bbox[2].max(vertex[0]),
+                bbox[3].max(vertex[1]),
+            ];
+        }
+        Zone {
+            name: String::from(name),
+            polygons,
+            bbox,
+            feature: None,
+        }
+    }
+
+    /// True if the point is inside the zone, not counting holes
+    pub fn contains(&self, latitude: f32, longitude: f32) -> bool {
+        let point = [coord_as_i64(latitude), coord_as_i64(longitude)];
+        if point[0] < self.bbox[0]
+            || point[1] < self.bbox[1]
+            || point[0] > self.bbox[2]
+            || point[1] > self.bbox[3]
+        {
+            return false;
+        }
+        self.polygons.iter().any(|rings| match rings.split_first() {
+            Some((outline, holes)) => {
+                contains(outline, point) && !holes.iter().any(|hole| contains(hole, point))
+            }
+            None => false,
+        })
+    }
+}
+
+/// Zones keep the bins whose centers are inside them
+impl BinFilter for Zone {
+    fn keep(&self, bin: &BinRef) -> bool {
+        let (latitude, longitude) = bin.center();
+        self.contains(latitude, longitude)
+    }
+}
+
+/// Read (latitude, longitude) rings from GeoJSON polygon coordinates, which
+/// are in (longitude, latitude) order
+fn polygon_rings(coordinates: &serde_json::Value) -> Option<Vec<Vec<(f32, f32)>>> {
+    coordinates
+        .as_array()?
+        .iter()
+        .map(|ring| {
+            ring.as_array()?
+                .iter()
+                .map(|position| {
+                    Some((
+                        position.get(1)?.as_f64()? as f32,
+                        position.get(0)?.as_f64()? as f32,
+                    ))
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Read the Polygon and MultiPolygon features of a GeoJSON
+/// FeatureCollection as zones, named by the `name_property` property, or by
+/// their position in the file if it's missing. Other geometries are
+/// skipped.
+pub fn zones_from_geojson(geojson: &str, name_property: &str) -> Result<Vec<Zone>, Box<dyn Error>> {
+    let collection: serde_json::Value = serde_json::from_str(geojson)?;
+    let features = match collection["features"].as_array() {
+        Some(f) => f,
+        None => return Err("Zones must be a GeoJSON FeatureCollection".into()),
+    };
+    let mut zones = Vec::new();
+    for (idx, feature) in features.iter().enumerate() {
+        let geometry = &feature["geometry"];
+        let polygons = match geometry["type"].as_str() {
+            Some("Polygon") => vec![polygon_rings(&geometry["coordinates"])],
+            Some("MultiPolygon") => match geometry["coordinates"].as_array() {
+                Some(p) => p.iter().map(polygon_rings).collect(),
+                None => vec![None],
+            },
+            _ => continue,
+        };
+        let polygons: Vec<Vec<Vec<(f32, f32)>>> = match polygons.into_iter().collect() {
+            Some(p) => p,
+            None => return Err(format!("Zone feature {} has malformed coordinates", idx).into()),
+        };
+        let name = match &feature["properties"][name_property] {
+            serde_json::Value::String(s) => s.clone(),
+            serde_json::Value::Null => idx.to_string(),
+            other => other.to_string(),
+        };
+        let mut zone = Zone::new(&name, &polygons);
+        zone.feature = Some(feature.clone());
+        zones.push(zone);
+    }
+    Ok(zones)
+}
+
+/// Statistics of the bins in one zone
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct ZoneStats {
+    pub zone: String,
+    /// Number of bins with data whose centers are in the zone
+    pub bins: usize,
+    /// Mean rate in in/hr, weighted by bin area, or `NaN` without any bins
+    pub mean: f32,
+    /// Highest rate in in/hr
+    pub max: f32,
+    /// Fraction of the zone's area with a rate above the threshold
+    pub fraction_above: f32,
+}
+
+impl PrecipRate {
+    /// Compute [`ZoneStats`] for each zone, counting rates above `threshold`
+    /// in/hr for `fraction_above`. Bins without data are left out.
+    pub fn zonal_stats(&self, zones: &[Zone], threshold: f32) -> Vec<ZoneStats> {
+        let mut sums = vec![(0usize, 0f32, 0f32, f32::NAN, 0f32); zones.len()];
+        for bin in self.bins().filter(|bin| !bin.is_missing()) {
+            let (latitude, longitude) = bin.center();
+            for (zone, sum) in zones.iter().zip(sums.iter_mut()) {
+                if zone.contains(latitude, longitude) {
+                    let (rate, area) = (bin.rate(), bin.area());
+                    sum.0 += 1;
+                    sum.1 += area;
+                    sum.2 += rate * area;
+                    sum.3 = sum.3.max(rate);
+                    if rate > threshold {
+                        sum.4 += area;
+                    }
+                }
+            }
+        }
+        zones
+            .iter()
+            .zip(sums)
+            .map(|(zone, (bins, area, weighted, max, above))| ZoneStats {
+                zone: zone.name.clone(),
+                bins,
+                mean: weighted / area,
+                max,
+                fraction_above: above / area,
+            })
+            .collect()
+    }
+}
+
+#[test]
+fn test_zonal_stats() {
+    let azimuths: Vec<f32> = (0..360).map(|a| a as f32 + 0.5).collect();
+    let mut scan = crate::coverage::scan_with_azimuths(&azimuths);
+    (scan.bin_size, scan.range_to_first_bin) = (10., 10.);
+    for radial in scan.radials.iter_mut().take(180) {
+        radial.precip_rates = vec![0.5];
+    }
+    // a box over the eastern half of the ring of bins, with a hole in it
+    // that nothing falls inside, and a box that misses the scan entirely
+    let (lat, lon) = (scan.latitude, scan.longitude);
+    let geojson = serde_json::json!({
+        "type": "FeatureCollection",
+        "features": [
+            {
+                "type": "Feature",
+                "properties": {"name": "east"},
+                "geometry": {"type": "Polygon", "coordinates": [
+                    [[lon, lat - 1.], [lon + 1., lat - 1.], [lon + 1., lat + 1.], [lon, lat + 1.], [lon, lat - 1.]],
+                    [[lon + 0.9, lat], [lon + 0.95, lat], [lon + 0.95, lat + 0.05], [lon + 0.9, lat]],
+                ]},
+            },
+            {
+                "type": "Feature",
+                "properties": {},
+                "geometry": {"type": "MultiPolygon", "coordinates": [
+                    [[[0., 0.], [1., 0.], [1., 1.], [0., 0.]]],
+                ]},
+            },
+        ],
+    });
+    let zones = zones_from_geojson(&geojson.to_string(), "name").unwrap();
+    assert_eq!(zones[1].name, "1");
+    let stats = scan.zonal_stats(&zones, 0.1);
+    assert_eq!((stats[0].bins, stats[0].max), (180, 0.5));
+    assert!((stats[0].mean - 0.5).abs() < 1e-6 && stats[0].fraction_above == 1.);
+    assert_eq!(stats[1].bins, 0);
+    assert!(stats[1].mean.is_nan());
+}